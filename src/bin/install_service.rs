@@ -7,12 +7,17 @@ mod shared;
 
 use anyhow::Error;
 use anyhow::{Context as _, bail};
+use serde::{Deserialize, Serialize};
 use sha2::{Digest as _, Sha256};
+#[cfg(target_os = "linux")]
+use shared::init::{InitService, InitSystem, select_init_system};
+#[cfg(target_os = "linux")]
+use shared::requested_service_mode;
 #[cfg(any(target_os = "linux", target_os = "macos"))]
 use shared::run_command;
-#[cfg(all(target_os = "macos", not(feature = "development-channel")))]
+#[cfg(target_os = "macos")]
 use shared::uninstall_old_service;
-use shared::{enter_repair_gate, run_maintenance_if_requested};
+use shared::{enter_repair_gate, run_maintenance_if_requested, select_service_paths};
 use std::fs::{File, OpenOptions};
 use std::io::Read as _;
 #[cfg(unix)]
@@ -64,13 +69,17 @@ fn remove_ordinary_file_if_exists(path: &Path) -> Result<(), Error> {
     std::fs::remove_file(path).with_context(|| format!("failed to remove {path:?}"))
 }
 
+fn staged_binary_path(target: &Path) -> PathBuf {
+    target.with_extension(if cfg!(windows) { "exe.next" } else { "next" })
+}
+
 fn stage_service_binary(source: &Path, target: &Path) -> Result<PathBuf, Error> {
     let parent = target
         .parent()
         .context("protected service target has no parent")?;
     std::fs::create_dir_all(parent)
         .with_context(|| format!("failed to create protected service directory {parent:?}"))?;
-    let staged = target.with_extension(if cfg!(windows) { "exe.next" } else { "next" });
+    let staged = staged_binary_path(target);
     remove_ordinary_file_if_exists(&staged)?;
 
     let mut source_file = File::open(source)
@@ -149,7 +158,327 @@ fn publish_staged_binary(staged: &Path, target: &Path) -> Result<(), Error> {
     }
 }
 
-fn wait_for_service_ready() -> Result<(), Error> {
+type StepAction<'a> = Box<dyn FnOnce() -> Result<(), Error> + 'a>;
+
+struct InstallStep<'a> {
+    description: String,
+    apply: StepAction<'a>,
+    undo: Option<StepAction<'a>>,
+}
+
+/// An install as an ordered list of steps. When one fails, the steps before it that changed the
+/// machine are undone in reverse order, returning it to the previous installation.
+#[derive(Default)]
+struct InstallPlan<'a> {
+    steps: Vec<InstallStep<'a>>,
+}
+
+impl<'a> InstallPlan<'a> {
+    fn step(
+        &mut self,
+        description: impl Into<String>,
+        apply: impl FnOnce() -> Result<(), Error> + 'a,
+    ) {
+        self.steps.push(InstallStep {
+            description: description.into(),
+            apply: Box::new(apply),
+            undo: None,
+        });
+    }
+
+    fn reversible(
+        &mut self,
+        description: impl Into<String>,
+        apply: impl FnOnce() -> Result<(), Error> + 'a,
+        undo: impl FnOnce() -> Result<(), Error> + 'a,
+    ) {
+        self.steps.push(InstallStep {
+            description: description.into(),
+            apply: Box::new(apply),
+            undo: Some(Box::new(undo)),
+        });
+    }
+
+    fn describe(&self) -> String {
+        self.steps
+            .iter()
+            .enumerate()
+            .map(|(index, step)| {
+                let undo = if step.undo.is_some() {
+                    " (undone on failure)"
+                } else {
+                    ""
+                };
+                format!("{:>3}. {}{undo}\n", index + 1, step.description)
+            })
+            .collect()
+    }
+
+    fn execute(self, debug: bool) -> Result<(), Error> {
+        let mut completed = Vec::new();
+        for step in self.steps {
+            if debug {
+                println!("Step: {}", step.description);
+            }
+            if let Err(error) = (step.apply)() {
+                let rolled_back = rollback(completed);
+                return Err(error.context(format!(
+                    "{} failed; {}",
+                    step.description,
+                    if rolled_back {
+                        "the previous installation was restored"
+                    } else {
+                        "rollback was incomplete, see the errors above"
+                    }
+                )));
+            }
+            if let Some(undo) = step.undo {
+                completed.push((step.description, undo));
+            }
+        }
+        Ok(())
+    }
+}
+
+fn rollback(completed: Vec<(String, StepAction<'_>)>) -> bool {
+    let mut restored = true;
+    for (description, undo) in completed.into_iter().rev() {
+        if let Err(error) = undo() {
+            eprintln!("Failed to undo \"{description}\": {error:#}");
+            restored = false;
+        }
+    }
+    restored
+}
+
+fn dry_run_requested() -> bool {
+    std::env::args().any(|argument| argument == "--dry-run")
+}
+
+/// A dry run only prints the plan; nothing on the machine changes.
+fn run_install_plan(plan: InstallPlan<'_>, dry_run: bool, debug: bool) -> Result<(), Error> {
+    if dry_run {
+        print!("{}", plan.describe());
+        return Ok(());
+    }
+    plan.execute(debug)
+}
+
+/// What `install_dir/previous` holds, so `--rollback` can tell an intact copy from a damaged one.
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct SavedInstallManifest {
+    /// Build version the replaced service reported; absent when it was not running.
+    version: Option<String>,
+    /// Build version of the install that replaced it.
+    replaced_by: String,
+    files: Vec<SavedFile>,
+}
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct SavedFile {
+    path: PathBuf,
+    sha256: String,
+}
+
+const SAVED_INSTALL_MANIFEST: &str = "manifest.json";
+const PREVIOUS_INSTALL_DIR: &str = "previous";
+/// Where an install saves the files it replaces until the new service has answered.
+const STAGED_PREVIOUS_INSTALL_DIR: &str = "previous.next";
+
+/// Copies of installed files kept before they are replaced: the previous installation, staged
+/// beside `install_dir/previous` until the install succeeds, or the current one while
+/// `--rollback` swaps it out.
+struct PreviousInstall {
+    dir: PathBuf,
+    files: Vec<PathBuf>,
+}
+
+impl PreviousInstall {
+    fn find(install_dir: &Path, installed: &[&Path]) -> Self {
+        Self::in_dir(install_dir.join(STAGED_PREVIOUS_INSTALL_DIR), installed)
+    }
+
+    fn in_dir(dir: PathBuf, installed: &[&Path]) -> Self {
+        Self {
+            dir,
+            files: installed
+                .iter()
+                .filter(|path| std::fs::symlink_metadata(path).is_ok_and(|meta| meta.is_file()))
+                .map(|path| path.to_path_buf())
+                .collect(),
+        }
+    }
+
+    /// Loads the previous installation, refusing copies whose hashes no longer match.
+    fn load(install_dir: &Path) -> Result<(Self, SavedInstallManifest), Error> {
+        let dir = install_dir.join(PREVIOUS_INSTALL_DIR);
+        let manifest_path = dir.join(SAVED_INSTALL_MANIFEST);
+        let manifest: SavedInstallManifest = serde_json::from_slice(
+            &std::fs::read(&manifest_path)
+                .with_context(|| format!("no previous installation saved in {dir:?}"))?,
+        )
+        .with_context(|| format!("failed to parse {manifest_path:?}"))?;
+        let previous = Self {
+            dir,
+            files: manifest
+                .files
+                .iter()
+                .map(|file| file.path.clone())
+                .collect(),
+        };
+        for file in &manifest.files {
+            let backup = previous.backup_path(&file.path);
+            if hex(&sha256(&backup)?) != file.sha256 {
+                bail!(
+                    "saved copy {backup:?} of {:?} does not match its recorded hash",
+                    file.path
+                );
+            }
+        }
+        Ok((previous, manifest))
+    }
+
+    fn is_empty(&self) -> bool {
+        self.files.is_empty()
+    }
+
+    fn contains(&self, path: &Path) -> bool {
+        self.files.iter().any(|file| file == path)
+    }
+
+    fn paths(&self) -> Vec<&Path> {
+        self.files.iter().map(PathBuf::as_path).collect()
+    }
+
+    /// Copies are numbered by their place in the manifest, since installed files may share a
+    /// name: the production OpenRC script and the binary are both `clash-verge-service`.
+    fn backup_path(&self, path: &Path) -> PathBuf {
+        let index = self
+            .files
+            .iter()
+            .position(|file| file == path)
+            .unwrap_or(self.files.len());
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        self.dir
+            .join(format!("{index}-{}", Path::new(name).display()))
+    }
+
+    /// The manifest goes first and is written last, so a partial save never looks intact.
+    fn save(&self, version: Option<String>) -> Result<(), Error> {
+        self.discard()?;
+        std::fs::create_dir_all(&self.dir)
+            .with_context(|| format!("failed to create backup directory {:?}", self.dir))?;
+        let manifest_path = self.dir.join(SAVED_INSTALL_MANIFEST);
+        remove_ordinary_file_if_exists(&manifest_path)?;
+        let mut files = Vec::with_capacity(self.files.len());
+        for file in &self.files {
+            let backup = self.backup_path(file);
+            std::fs::copy(file, &backup)
+                .with_context(|| format!("failed to back up {file:?} to {backup:?}"))?;
+            files.push(SavedFile {
+                path: file.clone(),
+                sha256: hex(&sha256(&backup)?),
+            });
+        }
+        let manifest = SavedInstallManifest {
+            version,
+            replaced_by: clash_verge_service_ipc::VERSION.to_owned(),
+            files,
+        };
+        let pending = manifest_path.with_extension("json.next");
+        std::fs::write(&pending, serde_json::to_vec_pretty(&manifest)?)
+            .with_context(|| format!("failed to write {pending:?}"))?;
+        std::fs::rename(&pending, &manifest_path)
+            .with_context(|| format!("failed to publish {manifest_path:?}"))
+    }
+
+    /// Drops copies that were never kept.
+    fn discard(&self) -> Result<(), Error> {
+        match std::fs::remove_dir_all(&self.dir) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(error).with_context(|| format!("failed to remove {:?}", self.dir))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Makes staged copies the previous installation `--rollback` returns to. Runs once the new
+    /// service has answered, so a failed install leaves the earlier copies in place.
+    fn keep(&self) -> Result<(), Error> {
+        let kept = self.dir.with_file_name(PREVIOUS_INSTALL_DIR);
+        match std::fs::remove_dir_all(&kept) {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                return Err(error).with_context(|| format!("failed to remove {kept:?}"));
+            }
+            _ => {}
+        }
+        std::fs::rename(&self.dir, &kept)
+            .with_context(|| format!("failed to move {:?} to {kept:?}", self.dir))
+    }
+
+    /// Puts a replaced file back, or removes it when the install created it.
+    fn restore(&self, path: &Path) -> Result<(), Error> {
+        if !self.contains(path) {
+            return remove_ordinary_file_if_exists(path);
+        }
+        let backup = self.backup_path(path);
+        let restoring = path.with_extension("restore");
+        remove_ordinary_file_if_exists(&restoring)?;
+        std::fs::copy(&backup, &restoring)
+            .with_context(|| format!("failed to copy {backup:?} to {restoring:?}"))?;
+        std::fs::rename(&restoring, path)
+            .with_context(|| format!("failed to restore {path:?} from {backup:?}"))
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn rollback_requested() -> bool {
+    std::env::args().any(|argument| argument == "--rollback")
+}
+
+/// Adds the steps that swap the current files for the saved ones. The current files are set
+/// aside first, so a rollback that fails part-way can return to them.
+fn plan_file_rollback<'a>(
+    plan: &mut InstallPlan<'a>,
+    paths: &'a clash_verge_service_ipc::ServicePaths,
+    saved: &'a PreviousInstall,
+    manifest: &SavedInstallManifest,
+    current: &'a PreviousInstall,
+    current_version: Option<String>,
+) {
+    plan.step(
+        format!("Set the current files aside in {:?}", current.dir),
+        move || current.save(current_version),
+    );
+    for file in &saved.files {
+        plan.reversible(
+            format!(
+                "Restore {file:?} from version {}",
+                manifest.version.as_deref().unwrap_or("unknown")
+            ),
+            move || saved.restore(file),
+            move || current.restore(file),
+        );
+    }
+    // A restored binary that predates the handshake never replaces the published key, and
+    // clients that find one refuse the legacy probe.
+    plan.step("Withdraw the published handshake key", move || {
+        clash_verge_service_ipc::withdraw_handshake_key(paths)
+    });
+}
+
+fn probe_ipc_config() -> clash_verge_service_ipc::IpcConfig {
+    clash_verge_service_ipc::IpcConfig {
+        default_timeout: Duration::from_millis(250),
+        max_retries: 1,
+        retry_delay: Duration::from_millis(25),
+    }
+}
+
+fn wait_for_service_ready(paths: &clash_verge_service_ipc::ServicePaths) -> Result<(), Error> {
     const READY_TIMEOUT: Duration = Duration::from_secs(20);
     const READY_INTERVAL: Duration = Duration::from_millis(250);
 
@@ -158,16 +487,11 @@ fn wait_for_service_ready() -> Result<(), Error> {
         .build()
         .context("failed to create service readiness runtime")?;
     runtime.block_on(async {
-        clash_verge_service_ipc::set_config(Some(clash_verge_service_ipc::IpcConfig {
-            default_timeout: Duration::from_millis(250),
-            max_retries: 1,
-            retry_delay: Duration::from_millis(25),
-        }))
-        .await;
+        clash_verge_service_ipc::set_config(Some(probe_ipc_config())).await;
 
         let deadline = Instant::now() + READY_TIMEOUT;
         let result = loop {
-            if let Ok(response) = clash_verge_service_ipc::get_version().await
+            if let Ok(response) = clash_verge_service_ipc::get_version_at(paths).await
                 && response.code == 0
                 && response.data.is_some_and(|info| {
                     info.supports_client(
@@ -191,10 +515,30 @@ fn wait_for_service_ready() -> Result<(), Error> {
     })
 }
 
+/// What the running service reports about itself, or `None` when nothing answers.
+fn running_service_info(
+    paths: &clash_verge_service_ipc::ServicePaths,
+) -> Option<clash_verge_service_ipc::ProtocolInfo> {
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .ok()?;
+    runtime.block_on(async {
+        clash_verge_service_ipc::set_config(Some(probe_ipc_config())).await;
+        let info = clash_verge_service_ipc::get_version_at(paths)
+            .await
+            .ok()
+            .filter(|response| response.code == 0)
+            .and_then(|response| response.data);
+        clash_verge_service_ipc::set_config(None).await;
+        info
+    })
+}
+
 // Only launchd code needs the concrete target; tests exercise the plan classifier instead.
 #[cfg(target_os = "macos")]
-fn launchd_service_target() -> String {
-    format!("system/{}", clash_verge_service_ipc::MACOS_SERVICE_ID)
+fn launchd_service_target(channel: &clash_verge_service_ipc::ChannelIdentity) -> String {
+    format!("system/{}", channel.macos_service_id)
 }
 
 #[cfg(any(target_os = "macos", test))]
@@ -223,13 +567,17 @@ fn classify_launchd_service_probe(
 }
 
 #[cfg(target_os = "macos")]
-fn probe_launchd_service(debug: bool) -> Result<LaunchdInstallPlan, Error> {
+fn probe_launchd_service(
+    channel: &clash_verge_service_ipc::ChannelIdentity,
+    debug: bool,
+) -> Result<LaunchdInstallPlan, Error> {
+    let target = launchd_service_target(channel);
     if debug {
-        println!("Executing: launchctl print {}", launchd_service_target());
+        println!("Executing: launchctl print {target}");
     }
 
     let output = std::process::Command::new("launchctl")
-        .args(["print", &launchd_service_target()])
+        .args(["print", &target])
         .output()
         .map_err(|e| anyhow::anyhow!("Failed to probe launchd service: {}", e))?;
     let diagnostic = format!(
@@ -272,131 +620,520 @@ fn resolve_service_group_name() -> Result<String, Error> {
     bail!("unable to resolve the invoking user's service group; use sudo or pkexec")
 }
 
+#[cfg(target_os = "linux")]
+struct ServiceGroup {
+    name: String,
+    gid: u32,
+}
+
+#[cfg(target_os = "linux")]
+fn resolve_service_group() -> Result<ServiceGroup, Error> {
+    let name = resolve_service_group_name()?;
+    let group = nix::unistd::Group::from_name(&name)?
+        .with_context(|| format!("service group {name:?} disappeared"))?;
+    Ok(ServiceGroup {
+        name,
+        gid: group.gid.as_raw(),
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn render_service_definition(
+    service: &InitService,
+    target: &Path,
+    channel: &clash_verge_service_ipc::ChannelIdentity,
+) -> Result<String, Error> {
+    if service.user {
+        return Ok(format!(
+            include_str!("../../resources/systemd_user_unit.tmpl"),
+            exec_start = format!("{} --channel {}", target.to_string_lossy(), channel.id),
+            runtime_directory = channel.service_slug,
+        ));
+    }
+    Ok(render_system_definition(
+        service.init,
+        target,
+        channel,
+        &resolve_service_group()?,
+    ))
+}
+
+#[cfg(target_os = "linux")]
+fn render_system_definition(
+    init: InitSystem,
+    target: &Path,
+    channel: &clash_verge_service_ipc::ChannelIdentity,
+    group: &ServiceGroup,
+) -> String {
+    let exec_start = format!("{} --channel {}", target.to_string_lossy(), channel.id);
+    match init {
+        InitSystem::Systemd => format!(
+            include_str!("../../resources/systemd_service_unit.tmpl"),
+            exec_start = exec_start,
+            group = group.name,
+            runtime_directory = channel.service_slug,
+        ),
+        InitSystem::OpenRc => format!(
+            include_str!("../../resources/openrc_service.tmpl"),
+            service_binary = target.to_string_lossy(),
+            channel = channel.id,
+            group = group.name,
+        ),
+        InitSystem::Runit => format!(
+            include_str!("../../resources/runit_run.tmpl"),
+            exec_start = exec_start,
+            group = group.name,
+        ),
+        InitSystem::S6 => format!(
+            include_str!("../../resources/s6_run.tmpl"),
+            exec_start = exec_start,
+            gid = group.gid,
+        ),
+    }
+}
+
+/// Operations only the installer performs; the uninstaller shares the rest from `shared::init`.
+#[cfg(target_os = "linux")]
+impl InitService {
+    /// Everything but a systemd unit is run directly and must be executable.
+    pub(crate) fn definition_is_executable(&self) -> bool {
+        self.init != InitSystem::Systemd
+    }
+
+    /// Registers the service to start at boot.
+    pub(crate) fn enable(&self) -> Result<(), Error> {
+        match self.init {
+            InitSystem::Systemd => self.systemctl(&["enable", &self.unit_name()]),
+            InitSystem::OpenRc => {
+                if self.in_default_runlevel() {
+                    return Ok(());
+                }
+                run_command("rc-update", &["add", self.name, "default"], self.debug)
+            }
+            InitSystem::Runit | InitSystem::S6 => {
+                let links = self.scan_links();
+                anyhow::ensure!(
+                    !links.is_empty(),
+                    "no {} scan directory found; is its supervisor running?",
+                    self.init.name()
+                );
+                let service_dir = self.service_dir();
+                for link in &links {
+                    if std::fs::read_link(link).is_ok_and(|target| target == service_dir) {
+                        continue;
+                    }
+                    std::os::unix::fs::symlink(&service_dir, link).with_context(|| {
+                        format!("failed to link {service_dir:?} into scan directory as {link:?}")
+                    })?;
+                }
+                if self.init == InitSystem::S6 {
+                    self.rescan_s6(&links[0], "-a")?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    pub(crate) fn start(&self) -> Result<(), Error> {
+        match self.init {
+            InitSystem::Systemd => self.systemctl(&["start", &self.unit_name()]),
+            InitSystem::OpenRc => run_command("rc-service", &[self.name, "start"], self.debug),
+            InitSystem::Runit | InitSystem::S6 => self.control("up"),
+        }
+    }
+
+    pub(crate) fn restart(&self) -> Result<(), Error> {
+        match self.init {
+            InitSystem::Systemd => self.systemctl(&["restart", &self.unit_name()]),
+            InitSystem::OpenRc => run_command("rc-service", &[self.name, "restart"], self.debug),
+            InitSystem::Runit | InitSystem::S6 => self.control("restart"),
+        }
+    }
+
+    /// Whether `rc-update show default` already lists the service.
+    fn in_default_runlevel(&self) -> bool {
+        std::process::Command::new("rc-update")
+            .args(["show", "default"])
+            .output()
+            .is_ok_and(|output| {
+                String::from_utf8_lossy(&output.stdout)
+                    .lines()
+                    .any(|line| line.split('|').next().map(str::trim) == Some(self.name))
+            })
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn write_service_definition(path: &Path, content: &str, executable: bool) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt as _;
+
+    let parent = path
+        .parent()
+        .context("service definition path has no parent")?;
+    std::fs::create_dir_all(parent)
+        .with_context(|| format!("failed to create service definition directory {parent:?}"))?;
+    let mut file = File::create(path)
+        .with_context(|| format!("failed to create service definition {path:?}"))?;
+    file.write_all(content.as_bytes())
+        .with_context(|| format!("failed to write service definition {path:?}"))?;
+    if executable {
+        file.set_permissions(std::fs::Permissions::from_mode(0o755))
+            .with_context(|| format!("failed to make service definition {path:?} executable"))?;
+    }
+    file.sync_all()
+        .with_context(|| format!("failed to sync service definition {path:?}"))
+}
+
 #[cfg(target_os = "macos")]
 fn main() -> Result<(), Error> {
+    let paths = select_service_paths(clash_verge_service_ipc::ServiceMode::System)?;
+    let channel = paths.channel();
     if run_maintenance_if_requested()? {
         return Ok(());
     }
-    let _gate = enter_repair_gate()?;
+    let dry_run = dry_run_requested();
+    let _gate = if dry_run {
+        None
+    } else {
+        Some(enter_repair_gate()?)
+    };
     let debug = std::env::args().any(|arg| arg == "--debug");
-    let launchd_install_plan = probe_launchd_service(debug)?;
-    let service_binary_path = bundled_service_binary()?;
+    let launchd_install_plan = probe_launchd_service(&channel, debug)?;
+    let install_dir = paths.install_dir();
 
-    let bundle_path = PathBuf::from("/Library/PrivilegedHelperTools").join(format!(
-        "{}.bundle",
-        clash_verge_service_ipc::MACOS_SERVICE_ID
-    ));
+    let bundle_path = PathBuf::from("/Library/PrivilegedHelperTools")
+        .join(format!("{}.bundle", channel.macos_service_id));
     let contents_path = bundle_path.join("Contents");
     let macos_path = contents_path.join("MacOS");
-
-    std::fs::create_dir_all(&macos_path)
-        .map_err(|e| anyhow::anyhow!("Failed to create bundle directories: {}", e))?;
-
     let target_binary_path = macos_path.join("clash-verge-service");
-    let staged = stage_service_binary(&service_binary_path, &target_binary_path)?;
-
+    let staged = staged_binary_path(&target_binary_path);
     let info_plist_path = contents_path.join("Info.plist");
-
     let plist_dir = PathBuf::from("/Library/LaunchDaemons");
-    if !plist_dir.exists() {
-        std::fs::create_dir(&plist_dir)
-            .map_err(|e| anyhow::anyhow!("Failed to create plist directory: {}", e))?;
+    let plist_file = plist_dir.join(format!("{}.plist", channel.macos_service_id));
+    let plist_path = plist_file.to_string_lossy().into_owned();
+    let launchd_target = launchd_service_target(&channel);
+    let running_version = running_service_info(&paths).map(|info| info.build_version);
+
+    if rollback_requested() {
+        let (saved, manifest) = PreviousInstall::load(&install_dir)?;
+        let current = PreviousInstall::in_dir(install_dir.join("replaced"), &saved.paths());
+        let mut plan = InstallPlan::default();
+        if launchd_install_plan == LaunchdInstallPlan::Bootout {
+            plan.reversible(
+                format!("Unload {launchd_target}"),
+                || run_command("launchctl", &["bootout", "system", &plist_path], debug),
+                || run_command("launchctl", &["bootstrap", "system", &plist_path], debug),
+            );
+        }
+        plan_file_rollback(
+            &mut plan,
+            &paths,
+            &saved,
+            &manifest,
+            &current,
+            running_version,
+        );
+        plan.reversible(
+            format!("Load {launchd_target}"),
+            || {
+                run_command("launchctl", &["enable", &launchd_target], debug)?;
+                run_command("launchctl", &["bootstrap", "system", &plist_path], debug)
+            },
+            || run_command("launchctl", &["bootout", "system", &plist_path], debug),
+        );
+        plan.step("Start the helper", || {
+            run_command("launchctl", &["start", channel.macos_service_id], debug)
+        });
+        plan.step(
+            "Wait for the service to answer with a compatible protocol",
+            || wait_for_service_ready(&paths),
+        );
+        return run_install_plan(plan, dry_run, debug);
     }
 
-    let plist_file = plist_dir.join(format!(
-        "{}.plist",
-        clash_verge_service_ipc::MACOS_SERVICE_ID
-    ));
-
+    let service_binary_path = bundled_service_binary()?;
     let launchd_plist_content = format!(
         include_str!("../../resources/launchd.plist.tmpl"),
         group_name = resolve_service_group_name()?,
-        service_id = clash_verge_service_ipc::MACOS_SERVICE_ID,
-        app_bundle_id = clash_verge_service_ipc::MACOS_APP_BUNDLE_ID,
+        service_id = channel.macos_service_id,
+        app_bundle_id = channel.macos_app_bundle_id,
         service_binary = target_binary_path.to_string_lossy(),
+        channel = channel.id,
     );
     let info_plist_content = format!(
         include_str!("../../resources/info.plist.tmpl"),
-        display_name = clash_verge_service_ipc::SERVICE_DISPLAY_NAME,
-        service_id = clash_verge_service_ipc::MACOS_SERVICE_ID,
+        display_name = channel.service_display_name,
+        service_id = channel.macos_service_id,
     );
-    let plist_path = plist_file.to_string_lossy().into_owned();
     let target_path = target_binary_path.to_string_lossy().into_owned();
     let bundle_path_string = bundle_path.to_string_lossy().into_owned();
+    let previous = PreviousInstall::find(
+        &install_dir,
+        &[&target_binary_path, &info_plist_path, &plist_file],
+    );
 
+    let mut plan = InstallPlan::default();
+    plan.step(
+        format!("Prepare the install directory {install_dir:?}"),
+        || clash_verge_service_ipc::prepare_service_install_directory(&paths).map(drop),
+    );
+    let create_bundle = || {
+        std::fs::create_dir_all(&macos_path)
+            .map_err(|e| anyhow::anyhow!("Failed to create bundle directories: {}", e))?;
+        std::fs::create_dir_all(&plist_dir)
+            .map_err(|e| anyhow::anyhow!("Failed to create plist directory: {}", e))
+    };
+    if bundle_path.exists() {
+        plan.step(
+            format!("Create the helper bundle {bundle_path:?}"),
+            create_bundle,
+        );
+    } else {
+        plan.reversible(
+            format!("Create the helper bundle {bundle_path:?}"),
+            create_bundle,
+            || {
+                std::fs::remove_dir_all(&bundle_path)
+                    .with_context(|| format!("failed to remove bundle {bundle_path:?}"))
+            },
+        );
+    }
+    plan.reversible(
+        format!("Stage {service_binary_path:?} as {staged:?}"),
+        || stage_service_binary(&service_binary_path, &target_binary_path).map(drop),
+        || remove_ordinary_file_if_exists(&staged),
+    );
+    if !previous.is_empty() {
+        plan.reversible(
+            format!("Back up the installed files to {:?}", previous.dir),
+            || previous.save(running_version),
+            || previous.discard(),
+        );
+    }
     if launchd_install_plan == LaunchdInstallPlan::Bootout {
-        run_command("launchctl", &["bootout", "system", &plist_path], debug)?;
-    }
-    publish_staged_binary(&staged, &target_binary_path)?;
-    std::fs::write(&info_plist_path, info_plist_content)
-        .with_context(|| format!("failed to write Info.plist {info_plist_path:?}"))?;
-    File::create(&plist_file)
-        .and_then(|mut file| file.write_all(launchd_plist_content.as_bytes()))
-        .map_err(|e| anyhow::anyhow!("Failed to write plist file: {}", e))?;
-
-    run_command("chmod", &["644", &plist_path], debug)?;
-    run_command("chown", &["root:wheel", &plist_path], debug)?;
-
-    run_command("chmod", &["544", &target_path], debug)?;
-    run_command("chown", &["root:wheel", &target_path], debug)?;
-
-    run_command("chmod", &["755", &bundle_path_string], debug)?;
-    run_command("chown", &["-R", "root:wheel", &bundle_path_string], debug)?;
-
-    let launchd_target = launchd_service_target();
-    run_command("launchctl", &["enable", &launchd_target], debug)?;
-    run_command("launchctl", &["bootstrap", "system", &plist_path], debug)?;
-    run_command(
-        "launchctl",
-        &["start", clash_verge_service_ipc::MACOS_SERVICE_ID],
-        debug,
-    )?;
-    wait_for_service_ready()?;
-    #[cfg(not(feature = "development-channel"))]
-    let _ = uninstall_old_service();
+        plan.reversible(
+            format!("Unload {launchd_target}"),
+            || run_command("launchctl", &["bootout", "system", &plist_path], debug),
+            || run_command("launchctl", &["bootstrap", "system", &plist_path], debug),
+        );
+    }
+    plan.reversible(
+        format!("Publish the service binary at {target_binary_path:?}"),
+        || publish_staged_binary(&staged, &target_binary_path),
+        || previous.restore(&target_binary_path),
+    );
+    plan.reversible(
+        format!("Write {info_plist_path:?}"),
+        || {
+            std::fs::write(&info_plist_path, &info_plist_content)
+                .with_context(|| format!("failed to write Info.plist {info_plist_path:?}"))
+        },
+        || previous.restore(&info_plist_path),
+    );
+    plan.reversible(
+        format!("Write the launchd job {plist_file:?}"),
+        || {
+            File::create(&plist_file)
+                .and_then(|mut file| file.write_all(launchd_plist_content.as_bytes()))
+                .map_err(|e| anyhow::anyhow!("Failed to write plist file: {}", e))
+        },
+        || previous.restore(&plist_file),
+    );
+    plan.step("Hand the job and bundle to root:wheel", || {
+        run_command("chmod", &["644", &plist_path], debug)?;
+        run_command("chown", &["root:wheel", &plist_path], debug)?;
 
-    Ok(())
+        run_command("chmod", &["544", &target_path], debug)?;
+        run_command("chown", &["root:wheel", &target_path], debug)?;
+
+        run_command("chmod", &["755", &bundle_path_string], debug)?;
+        run_command("chown", &["-R", "root:wheel", &bundle_path_string], debug)
+    });
+    plan.reversible(
+        format!("Load {launchd_target}"),
+        || {
+            run_command("launchctl", &["enable", &launchd_target], debug)?;
+            run_command("launchctl", &["bootstrap", "system", &plist_path], debug)
+        },
+        || run_command("launchctl", &["bootout", "system", &plist_path], debug),
+    );
+    plan.step("Start the helper", || {
+        run_command("launchctl", &["start", channel.macos_service_id], debug)
+    });
+    plan.step(
+        "Wait for the service to answer with a compatible protocol",
+        || wait_for_service_ready(&paths),
+    );
+    if !previous.is_empty() {
+        plan.step("Keep the replaced files for --rollback", || previous.keep());
+    }
+    if channel.is_production() {
+        plan.step("Remove the helper that predates channels", || {
+            let _ = uninstall_old_service();
+            Ok(())
+        });
+    }
+    run_install_plan(plan, dry_run, debug)
 }
 
 #[cfg(target_os = "linux")]
 fn main() -> Result<(), Error> {
+    let mode = requested_service_mode()?;
+    let user = mode == clash_verge_service_ipc::ServiceMode::User;
+    let paths = select_service_paths(mode)?;
+    let channel = paths.channel();
     if run_maintenance_if_requested()? {
         return Ok(());
     }
-    let _gate = enter_repair_gate()?;
+    let dry_run = dry_run_requested();
+    let _gate = if dry_run {
+        None
+    } else {
+        Some(enter_repair_gate()?)
+    };
     let debug = std::env::args().any(|arg| arg == "--debug");
+    let install_dir = paths.install_dir();
+    let service = InitService::new(select_init_system()?, &channel, user, debug)?;
+    let init = service.init.name();
+    let running = running_service_info(&paths);
+    let running_version = running.as_ref().map(|info| info.build_version.clone());
+
+    if rollback_requested() {
+        let (saved, manifest) = PreviousInstall::load(&install_dir)?;
+        let current = PreviousInstall::in_dir(install_dir.join("replaced"), &saved.paths());
+        let mut plan = InstallPlan::default();
+        plan.reversible(
+            format!("Stop the {init} service"),
+            || {
+                let _ = service.stop();
+                Ok(())
+            },
+            || {
+                service.reload()?;
+                service.restart()
+            },
+        );
+        plan_file_rollback(
+            &mut plan,
+            &paths,
+            &saved,
+            &manifest,
+            &current,
+            running_version,
+        );
+        plan.step(format!("Reload {init}"), || service.reload());
+        plan.reversible("Start the service", || service.start(), || service.stop());
+        plan.step(
+            "Wait for the service to answer with a compatible protocol",
+            || wait_for_service_ready(&paths),
+        );
+        return run_install_plan(plan, dry_run, debug);
+    }
+
     let source = bundled_service_binary()?;
-    let install_dir = clash_verge_service_ipc::prepare_service_install_directory()?;
     let target = install_dir.join("clash-verge-service");
-    let staged = stage_service_binary(&source, &target)?;
-    let unit_name = format!("{}.service", clash_verge_service_ipc::SERVICE_SLUG);
-    let unit_path = PathBuf::from("/etc/systemd/system").join(&unit_name);
-
-    let _ = run_command("systemctl", &["stop", &unit_name], debug);
-    publish_staged_binary(&staged, &target)?;
-
-    let unit_file_content = format!(
-        include_str!("../../resources/systemd_service_unit.tmpl"),
-        exec_start = target.to_string_lossy(),
-        group = resolve_service_group_name()?,
-        runtime_directory = clash_verge_service_ipc::SERVICE_SLUG,
+    let staged = staged_binary_path(&target);
+    let definition_path = service.definition_path()?;
+    let definition = render_service_definition(&service, &target, &channel)?;
+    let previous = PreviousInstall::find(&install_dir, &[&target, &definition_path]);
+    let upgrade = previous.contains(&definition_path);
+
+    // A handoff-capable service is restarted in place so the proxy keeps running; older ones
+    // are stopped before the binary changes under them.
+    let handoff = running
+        .as_ref()
+        .is_some_and(|info| info.supports(clash_verge_service_ipc::ServiceCapability::CoreHandoff));
+
+    let mut plan = InstallPlan::default();
+    plan.step(
+        format!("Prepare the install directory {install_dir:?}"),
+        || clash_verge_service_ipc::prepare_service_install_directory(&paths).map(drop),
     );
-
-    let mut unit_file = File::create(&unit_path)
-        .with_context(|| format!("failed to create systemd unit {unit_path:?}"))?;
-    unit_file
-        .write_all(unit_file_content.as_bytes())
-        .with_context(|| format!("failed to write systemd unit {unit_path:?}"))?;
-    unit_file
-        .sync_all()
-        .with_context(|| format!("failed to sync systemd unit {unit_path:?}"))?;
-
-    run_command("systemctl", &["daemon-reload"], debug)?;
-    run_command("systemctl", &["enable", &unit_name], debug)?;
-    run_command("systemctl", &["start", &unit_name], debug)?;
-    wait_for_service_ready()?;
-
-    Ok(())
+    plan.reversible(
+        format!("Stage {source:?} as {staged:?}"),
+        || stage_service_binary(&source, &target).map(drop),
+        || remove_ordinary_file_if_exists(&staged),
+    );
+    if !previous.is_empty() {
+        plan.reversible(
+            format!("Back up the installed files to {:?}", previous.dir),
+            || previous.save(running_version),
+            || previous.discard(),
+        );
+    }
+    if upgrade && handoff {
+        plan.reversible(
+            "Leave the running service up to hand its core over",
+            || Ok(()),
+            || service.restart(),
+        );
+    } else if upgrade {
+        plan.reversible(
+            format!("Stop the {init} service"),
+            || {
+                let _ = service.stop();
+                Ok(())
+            },
+            || service.restart(),
+        );
+    }
+    plan.reversible(
+        format!("Publish the service binary at {target:?}"),
+        || publish_staged_binary(&staged, &target),
+        || previous.restore(&target),
+    );
+    plan.reversible(
+        format!("Write the {init} service definition {definition_path:?}"),
+        || {
+            write_service_definition(
+                &definition_path,
+                &definition,
+                service.definition_is_executable(),
+            )
+        },
+        || {
+            if upgrade {
+                previous.restore(&definition_path)?;
+            } else {
+                service.remove_definition()?;
+            }
+            service.reload()
+        },
+    );
+    plan.step(format!("Reload {init}"), || service.reload());
+    if upgrade {
+        plan.step("Enable the service at boot", || service.enable());
+    } else {
+        plan.reversible(
+            "Enable the service at boot",
+            || service.enable(),
+            || service.disable(),
+        );
+    }
+    if handoff {
+        plan.reversible(
+            "Restart the service, handing the running core over",
+            || {
+                clash_verge_service_ipc::request_core_handoff()?;
+                if let Err(error) = service.restart() {
+                    clash_verge_service_ipc::cancel_core_handoff();
+                    return Err(error);
+                }
+                Ok(())
+            },
+            || service.stop(),
+        );
+    } else {
+        plan.reversible("Start the service", || service.start(), || service.stop());
+    }
+    plan.step(
+        "Wait for the service to answer with a compatible protocol",
+        || wait_for_service_ready(&paths),
+    );
+    if !previous.is_empty() {
+        plan.step("Keep the replaced files for --rollback", || previous.keep());
+    }
+    run_install_plan(plan, dry_run, debug)
 }
 
 #[cfg(windows)]
@@ -409,33 +1146,45 @@ fn main() -> anyhow::Result<()> {
         },
         service_manager::{ServiceManager, ServiceManagerAccess},
     };
+    use std::cell::RefCell;
     use std::ffi::{OsStr, OsString};
     use std::{thread, time::Duration};
 
+    const ERROR_SERVICE_DOES_NOT_EXIST: i32 = 1060;
+    const ERROR_SERVICE_NOT_ACTIVE: i32 = 1062;
+
+    let paths = select_service_paths(clash_verge_service_ipc::ServiceMode::System)?;
+    let channel = paths.channel();
     if run_maintenance_if_requested()? {
         return Ok(());
     }
-    let _gate = enter_repair_gate()?;
-    let source = bundled_service_binary()?;
-    let install_dir = clash_verge_service_ipc::prepare_service_install_directory()?;
+    let dry_run = dry_run_requested();
+    let _gate = if dry_run {
+        None
+    } else {
+        Some(enter_repair_gate()?)
+    };
+    let debug = std::env::args().any(|arg| arg == "--debug");
+    let install_dir = paths.install_dir();
     let target = install_dir.join("clash-verge-service.exe");
-    let staged = stage_service_binary(&source, &target)?;
+    let staged = staged_binary_path(&target);
+    let running_version = running_service_info(&paths).map(|info| info.build_version);
 
     let manager_access = ServiceManagerAccess::CONNECT | ServiceManagerAccess::CREATE_SERVICE;
     let service_manager = ServiceManager::local_computer(None::<&str>, manager_access)?;
-    let start_type = if cfg!(feature = "development-channel") {
-        ServiceStartType::OnDemand
-    } else {
+    let start_type = if channel.is_production() {
         ServiceStartType::AutoStart
+    } else {
+        ServiceStartType::OnDemand
     };
     let service_info = ServiceInfo {
-        name: OsString::from(clash_verge_service_ipc::WINDOWS_SERVICE_NAME),
-        display_name: OsString::from(clash_verge_service_ipc::SERVICE_DISPLAY_NAME),
+        name: OsString::from(channel.windows_service_name),
+        display_name: OsString::from(channel.service_display_name),
         service_type: ServiceType::OWN_PROCESS,
         start_type,
         error_control: ServiceErrorControl::Normal,
         executable_path: target.clone(),
-        launch_arguments: vec![],
+        launch_arguments: vec![OsString::from("--channel"), OsString::from(channel.id)],
         dependencies: vec![],
         account_name: None,
         account_password: None,
@@ -444,56 +1193,150 @@ fn main() -> anyhow::Result<()> {
     let service_access = ServiceAccess::QUERY_STATUS
         | ServiceAccess::START
         | ServiceAccess::STOP
-        | ServiceAccess::CHANGE_CONFIG;
-    match service_manager.open_service(
-        clash_verge_service_ipc::WINDOWS_SERVICE_NAME,
-        service_access,
-    ) {
-        Ok(service) => {
-            const ERROR_SERVICE_NOT_ACTIVE: i32 = 1062;
-            let status = service.query_status()?;
-            if status.current_state != ServiceState::Stopped {
-                if let Err(error) = service.stop()
-                    && !matches!(
-                        &error,
-                        WindowsServiceError::Winapi(error)
-                            if error.raw_os_error() == Some(ERROR_SERVICE_NOT_ACTIVE)
-                    )
-                {
-                    return Err(error.into());
-                }
-                for _ in 0..200 {
-                    if service.query_status()?.current_state == ServiceState::Stopped {
-                        break;
-                    }
-                    thread::sleep(Duration::from_millis(100));
-                }
-                if service.query_status()?.current_state != ServiceState::Stopped {
-                    bail!("timed out waiting for service to stop before replacement");
-                }
+        | ServiceAccess::CHANGE_CONFIG
+        | ServiceAccess::DELETE;
+    let service = RefCell::new(
+        match service_manager.open_service(channel.windows_service_name, service_access) {
+            Ok(service) => Some(service),
+            Err(WindowsServiceError::Winapi(error))
+                if error.raw_os_error() == Some(ERROR_SERVICE_DOES_NOT_EXIST) =>
+            {
+                None
             }
+            Err(error) => return Err(error.into()),
+        },
+    );
+    let upgrade = service.borrow().is_some();
 
-            publish_staged_binary(&staged, &target)?;
-            service.change_config(&service_info)?;
-            configure_windows_service_recovery(&service)?;
-            service.start(&Vec::<&OsStr>::new())?;
-            wait_for_service_ready()?;
+    let stop_service = || -> Result<(), Error> {
+        let service = service.borrow();
+        let Some(service) = service.as_ref() else {
+            return Ok(());
+        };
+        if service.query_status()?.current_state == ServiceState::Stopped {
             return Ok(());
         }
-        Err(WindowsServiceError::Winapi(error)) if error.raw_os_error() == Some(1060) => {}
-        Err(error) => return Err(error.into()),
-    }
-
-    publish_staged_binary(&staged, &target)?;
-    let start_access = ServiceAccess::CHANGE_CONFIG | ServiceAccess::START;
-    let service = service_manager.create_service(&service_info, start_access)?;
+        if let Err(error) = service.stop()
+            && !matches!(
+                &error,
+                WindowsServiceError::Winapi(error)
+                    if error.raw_os_error() == Some(ERROR_SERVICE_NOT_ACTIVE)
+            )
+        {
+            return Err(error.into());
+        }
+        for _ in 0..200 {
+            if service.query_status()?.current_state == ServiceState::Stopped {
+                return Ok(());
+            }
+            thread::sleep(Duration::from_millis(100));
+        }
+        bail!("timed out waiting for service to stop");
+    };
+    let start_service = || -> Result<(), Error> {
+        service
+            .borrow()
+            .as_ref()
+            .context("service is not installed")?
+            .start(&Vec::<&OsStr>::new())?;
+        Ok(())
+    };
 
-    service.set_description("Clash Verge Service helps to launch Clash Core")?;
-    configure_windows_service_recovery(&service)?;
-    service.start(&Vec::<&OsStr>::new())?;
-    wait_for_service_ready()?;
+    if rollback_requested() {
+        anyhow::ensure!(upgrade, "the service is not installed");
+        let (saved, manifest) = PreviousInstall::load(&install_dir)?;
+        let current = PreviousInstall::in_dir(install_dir.join("replaced"), &saved.paths());
+        let mut plan = InstallPlan::default();
+        plan.reversible("Stop the service", &stop_service, &start_service);
+        plan_file_rollback(
+            &mut plan,
+            &paths,
+            &saved,
+            &manifest,
+            &current,
+            running_version,
+        );
+        plan.reversible("Start the service", &start_service, &stop_service);
+        plan.step(
+            "Wait for the service to answer with a compatible protocol",
+            || wait_for_service_ready(&paths),
+        );
+        return run_install_plan(plan, dry_run, debug);
+    }
 
-    Ok(())
+    let source = bundled_service_binary()?;
+    let previous = PreviousInstall::find(&install_dir, &[&target]);
+    let mut plan = InstallPlan::default();
+    plan.step(
+        format!("Prepare the install directory {install_dir:?}"),
+        || clash_verge_service_ipc::prepare_service_install_directory(&paths).map(drop),
+    );
+    plan.reversible(
+        format!("Stage {source:?} as {staged:?}"),
+        || stage_service_binary(&source, &target).map(drop),
+        || remove_ordinary_file_if_exists(&staged),
+    );
+    if !previous.is_empty() {
+        plan.reversible(
+            format!("Back up the installed binary to {:?}", previous.dir),
+            || previous.save(running_version),
+            || previous.discard(),
+        );
+    }
+    if upgrade {
+        plan.reversible("Stop the service", &stop_service, &start_service);
+    }
+    plan.reversible(
+        format!("Publish the service binary at {target:?}"),
+        || publish_staged_binary(&staged, &target),
+        || previous.restore(&target),
+    );
+    if upgrade {
+        // The binary path is unchanged and older binaries ignore `--channel`, so the restored
+        // binary runs under the new configuration too.
+        plan.step("Update the service configuration", || {
+            service
+                .borrow()
+                .as_ref()
+                .context("service is not installed")?
+                .change_config(&service_info)?;
+            Ok(())
+        });
+    } else {
+        plan.reversible(
+            "Create the service",
+            || {
+                let created = service_manager.create_service(&service_info, service_access)?;
+                created.set_description("Clash Verge Service helps to launch Clash Core")?;
+                *service.borrow_mut() = Some(created);
+                Ok(())
+            },
+            || {
+                if let Some(created) = service.borrow_mut().take() {
+                    created.delete()?;
+                }
+                Ok(())
+            },
+        );
+    }
+    plan.step("Configure restart on failure", || {
+        configure_windows_service_recovery(
+            service
+                .borrow()
+                .as_ref()
+                .context("service is not installed")?,
+        )?;
+        Ok(())
+    });
+    plan.reversible("Start the service", &start_service, &stop_service);
+    plan.step(
+        "Wait for the service to answer with a compatible protocol",
+        || wait_for_service_ready(&paths),
+    );
+    if !previous.is_empty() {
+        plan.step("Keep the replaced files for --rollback", || previous.keep());
+    }
+    run_install_plan(plan, dry_run, debug)
 }
 
 #[cfg(windows)]
@@ -559,4 +1402,243 @@ mod tests {
 
         assert!(result.is_err());
     }
+
+    #[test]
+    fn failed_step_undoes_completed_steps_in_reverse() {
+        let log = std::cell::RefCell::new(Vec::new());
+        let record = |entry: &'static str| -> Result<(), Error> {
+            log.borrow_mut().push(entry);
+            Ok(())
+        };
+        let mut plan = InstallPlan::default();
+        plan.reversible("stage", || record("stage"), || record("unstage"));
+        plan.step("back up", || record("back up"));
+        plan.reversible("publish", || record("publish"), || record("restore"));
+        plan.step("probe", || Err(anyhow::anyhow!("service did not answer")));
+        plan.reversible("never reached", || record("applied"), || record("undone"));
+
+        let error = plan.execute(false).unwrap_err();
+
+        assert!(format!("{error:#}").contains("probe failed"), "{error:#}");
+        assert_eq!(
+            *log.borrow(),
+            ["stage", "back up", "publish", "restore", "unstage"]
+        );
+    }
+
+    #[test]
+    fn saved_installation_is_verified_before_rollback() -> anyhow::Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("service-install-previous-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root)?;
+        let binary = root.join("clash-verge-service");
+        let unit = root.join("clash-verge-service.service");
+        std::fs::write(&binary, b"old binary")?;
+        std::fs::write(&unit, b"old unit")?;
+
+        let previous = PreviousInstall::find(&root, &[&binary, &unit, &root.join("missing")]);
+        previous.save(Some("2.6.3".to_owned()))?;
+        previous.keep()?;
+        std::fs::write(&binary, b"new binary")?;
+
+        let (saved, manifest) = PreviousInstall::load(&root)?;
+        assert_eq!(manifest.version.as_deref(), Some("2.6.3"));
+        assert_eq!(saved.files, [binary.clone(), unit.clone()]);
+        saved.restore(&binary)?;
+        assert_eq!(std::fs::read(&binary)?, b"old binary");
+
+        std::fs::write(saved.backup_path(&unit), b"tampered")?;
+        assert!(PreviousInstall::load(&root).is_err());
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn installed_files_sharing_a_name_keep_separate_copies() -> anyhow::Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("service-install-collide-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("init.d"))?;
+        std::fs::create_dir_all(root.join("bin"))?;
+        let script = root.join("init.d/clash-verge-service");
+        let binary = root.join("bin/clash-verge-service");
+        std::fs::write(&binary, b"old binary")?;
+        std::fs::write(&script, b"old script")?;
+
+        let previous = PreviousInstall::find(&root, &[&binary, &script]);
+        previous.save(None)?;
+        previous.keep()?;
+        std::fs::write(&binary, b"new binary")?;
+        std::fs::write(&script, b"new script")?;
+
+        let (saved, _) = PreviousInstall::load(&root)?;
+        saved.restore(&binary)?;
+        saved.restore(&script)?;
+        assert_eq!(std::fs::read(&binary)?, b"old binary");
+        assert_eq!(std::fs::read(&script)?, b"old script");
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn a_failed_install_keeps_the_earlier_saved_installation() -> anyhow::Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("service-install-staged-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(&root)?;
+        let binary = root.join("clash-verge-service");
+        std::fs::write(&binary, b"first binary")?;
+        let first = PreviousInstall::find(&root, &[&binary]);
+        first.save(Some("1".to_owned()))?;
+        first.keep()?;
+
+        std::fs::write(&binary, b"second binary")?;
+        let mut plan = InstallPlan::default();
+        let second = PreviousInstall::find(&root, &[&binary]);
+        plan.reversible(
+            "Back up the installed files",
+            || second.save(Some("2".to_owned())),
+            || second.discard(),
+        );
+        plan.step("Wait for the service", || {
+            Err(anyhow::anyhow!("service did not answer"))
+        });
+        plan.step("Keep the replaced files", || second.keep());
+        assert!(plan.execute(false).is_err());
+
+        let (saved, manifest) = PreviousInstall::load(&root)?;
+        assert_eq!(manifest.version.as_deref(), Some("1"));
+        saved.restore(&binary)?;
+        assert_eq!(std::fs::read(&binary)?, b"first binary");
+        assert!(!root.join(STAGED_PREVIOUS_INSTALL_DIR).exists());
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn rollback_withdraws_the_published_handshake_key() -> anyhow::Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("service-install-rollback-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let paths = clash_verge_service_ipc::rooted_service_paths(
+            &clash_verge_service_ipc::CHANNEL_IDENTITY,
+            clash_verge_service_ipc::ServiceMode::System,
+            &root,
+        );
+        std::fs::create_dir_all(paths.runtime_dir())?;
+        std::fs::write(paths.handshake_public_key_path(), b"published key")?;
+        let install_dir = root.join("install");
+        std::fs::create_dir_all(&install_dir)?;
+        let binary = install_dir.join("clash-verge-service");
+        std::fs::write(&binary, b"binary without handshake")?;
+        let previous = PreviousInstall::find(&install_dir, &[&binary]);
+        previous.save(Some("2.6.3".to_owned()))?;
+        previous.keep()?;
+        std::fs::write(&binary, b"binary with handshake")?;
+
+        let (saved, manifest) = PreviousInstall::load(&install_dir)?;
+        let current = PreviousInstall::in_dir(install_dir.join("replaced"), &saved.paths());
+        let mut plan = InstallPlan::default();
+        plan_file_rollback(
+            &mut plan,
+            &paths,
+            &saved,
+            &manifest,
+            &current,
+            Some("2.7.0".to_owned()),
+        );
+        plan.execute(false)?;
+
+        assert_eq!(std::fs::read(&binary)?, b"binary without handshake");
+        assert!(!paths.handshake_public_key_path().exists());
+        std::fs::remove_dir_all(&root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn dry_run_lists_steps_without_running_them() {
+        let mut plan = InstallPlan::default();
+        plan.reversible(
+            "Stage the binary",
+            || panic!("dry run applied a step"),
+            || Ok(()),
+        );
+        plan.step("Probe the service", || panic!("dry run applied a step"));
+
+        assert_eq!(
+            plan.describe(),
+            "  1. Stage the binary (undone on failure)\n  2. Probe the service\n"
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn every_init_backend_runs_the_installed_binary_for_its_channel() {
+        let target = Path::new("/usr/lib/clash-verge-service/clash-verge-service");
+        let channel = clash_verge_service_ipc::DEVELOPMENT_CHANNEL;
+        let group = ServiceGroup {
+            name: "wheel".to_owned(),
+            gid: 10,
+        };
+
+        for init in [
+            InitSystem::Systemd,
+            InitSystem::OpenRc,
+            InitSystem::Runit,
+            InitSystem::S6,
+        ] {
+            let definition = render_system_definition(init, target, &channel, &group);
+            assert!(
+                definition.contains(target.to_str().unwrap()),
+                "{init:?}: {definition}"
+            );
+            assert!(
+                definition.contains("--channel development"),
+                "{init:?}: {definition}"
+            );
+            if init != InitSystem::Systemd {
+                assert!(definition.starts_with("#!/"), "{init:?}: {definition}");
+            }
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn supervised_backends_run_the_service_as_root_in_its_group() -> anyhow::Result<()> {
+        let target = Path::new("/usr/lib/clash-verge-service/clash-verge-service");
+        let channel = clash_verge_service_ipc::PRODUCTION_CHANNEL;
+        let group = ServiceGroup {
+            name: "wheel".to_owned(),
+            gid: 10,
+        };
+        let render = |init| render_system_definition(init, target, &channel, &group);
+
+        let openrc = render(InitSystem::OpenRc);
+        assert!(openrc.starts_with("#!/sbin/openrc-run\n"), "{openrc}");
+        assert!(openrc.contains(&format!("command=\"{}\"", target.display())));
+        assert!(openrc.contains("command_args=\"--channel production\""));
+        assert!(openrc.contains("command_user=\"root:wheel\""));
+        assert!(openrc.contains("depend() {\n") && openrc.trim_end().ends_with('}'));
+
+        let exec_start = format!("{} --channel production", target.display());
+        let runit = render(InitSystem::Runit);
+        assert!(
+            runit.contains(&format!("exec chpst -u root:wheel {exec_start}\n")),
+            "{runit}"
+        );
+        let s6 = render(InitSystem::S6);
+        assert!(
+            s6.contains(&format!("exec s6-setuidgid 0:10 {exec_start}\n")),
+            "{s6}"
+        );
+
+        for init in [InitSystem::OpenRc, InitSystem::Runit, InitSystem::S6] {
+            let service = InitService::new(init, &channel, false, false)?;
+            assert!(service.definition_is_executable(), "{init:?}");
+        }
+        let systemd = InitService::new(InitSystem::Systemd, &channel, false, false)?;
+        assert!(!systemd.definition_is_executable());
+        Ok(())
+    }
 }
@@ -1,9 +1,11 @@
 //! Cross-platform IPC daemon, run standalone or as a Windows service.
+//! `clash-verge-service doctor [--json] [--fix]` inspects the installation instead of serving it.
 
-use anyhow::Result;
+use anyhow::{Context as _, Result};
 use clash_verge_service_ipc::{
-    acquire_service_owner, reconcile_service_startup, restore_desired_state,
-    run_ipc_supervisor_until_shutdown,
+    CHANNEL_IDENTITY, ChannelIdentity, REPAIR_IN_PROGRESS_EXIT_CODE, ServiceMode, ServicePaths,
+    acquire_service_owner, diagnose_service, reconcile_service_startup, restore_desired_state,
+    rooted_service_paths, run_ipc_supervisor_until_shutdown, service_paths_for, set_service_paths,
 };
 use tracing::{Level, info, warn};
 use tracing_subscriber::FmtSubscriber;
@@ -27,8 +29,13 @@ use {
 #[tokio::main(flavor = "current_thread")]
 async fn main() -> Result<()> {
     set_secure_process_umask();
+    if doctor_requested() {
+        select_service_layout()?;
+        return run_doctor().await;
+    }
     init_logger();
-    run_standalone().await
+    let paths = select_service_layout()?;
+    run_standalone(&paths).await
 }
 
 #[cfg(unix)]
@@ -41,16 +48,16 @@ fn set_secure_process_umask() {
 /// Runs as a Windows service when possible, otherwise standalone.
 #[cfg(windows)]
 fn main() -> Result<()> {
+    if doctor_requested() {
+        select_service_layout()?;
+        return tokio::runtime::Runtime::new()?.block_on(run_doctor());
+    }
     init_logger();
-    if service_dispatcher::start(
-        clash_verge_service_ipc::WINDOWS_SERVICE_NAME,
-        ffi_service_main,
-    )
-    .is_err()
-    {
+    let paths = select_service_layout()?;
+    if service_dispatcher::start(paths.channel().windows_service_name, ffi_service_main).is_err() {
         info!("Not running as a service, starting in standalone mode.");
         let rt = tokio::runtime::Runtime::new()?;
-        rt.block_on(run_standalone())?;
+        rt.block_on(run_standalone(&paths))?;
     }
     Ok(())
 }
@@ -80,10 +87,9 @@ fn run_service() -> platform_lib::Result<()> {
         }
     };
 
-    let status_handle = service_control_handler::register(
-        clash_verge_service_ipc::WINDOWS_SERVICE_NAME,
-        event_handler,
-    )?;
+    let paths = clash_verge_service_ipc::service_paths();
+    let status_handle =
+        service_control_handler::register(paths.channel().windows_service_name, event_handler)?;
 
     status_handle.set_service_status(ServiceStatus {
         service_type: ServiceType::OWN_PROCESS,
@@ -124,7 +130,7 @@ fn run_service() -> platform_lib::Result<()> {
             ),
         }
 
-        let result = run_ipc_supervisor_until_shutdown(async {
+        let result = run_ipc_supervisor_until_shutdown(&paths, async {
             let _ = shutdown_rx.recv().await;
         })
         .await;
@@ -155,6 +161,92 @@ fn run_service() -> platform_lib::Result<()> {
     Ok(())
 }
 
+/// Applies `--channel <id>`, `--user` and `--service-root <dir>` before anything resolves a
+/// service path, and selects the paths they describe.
+fn select_service_layout() -> Result<ServicePaths> {
+    let mut channel = CHANNEL_IDENTITY;
+    let mut mode = ServiceMode::System;
+    let mut root = None;
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        let (flag, inline_value) = match argument.split_once('=') {
+            Some((flag, value)) => (flag.to_owned(), Some(value.to_owned())),
+            None => (argument, None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| arguments.next())
+                .unwrap_or_default()
+        };
+        match flag.as_str() {
+            "--channel" => {
+                let id = value();
+                channel = ChannelIdentity::from_id(&id)
+                    .with_context(|| format!("unknown or invalid channel {id:?}"))?;
+            }
+            "--user" => {
+                anyhow::ensure!(
+                    cfg!(target_os = "linux"),
+                    "rootless mode is only supported under systemd --user on Linux"
+                );
+                mode = ServiceMode::User;
+                info!("Running rootless; only the current user will be served");
+            }
+            "--service-root" => {
+                let directory = std::path::PathBuf::from(value());
+                anyhow::ensure!(
+                    directory.is_absolute() && directory.is_dir(),
+                    "--service-root must name an existing absolute directory: {directory:?}"
+                );
+                root = Some(directory);
+            }
+            _ => {}
+        }
+    }
+    let paths = match &root {
+        Some(root) => rooted_service_paths(&channel, mode, root),
+        None => service_paths_for(&channel, mode),
+    };
+    set_service_paths(paths.clone());
+    info!(
+        "Service channel {}, IPC path {:?}",
+        channel.id,
+        paths.ipc_path()
+    );
+    Ok(paths)
+}
+
+fn doctor_requested() -> bool {
+    std::env::args()
+        .nth(1)
+        .is_some_and(|argument| argument == "doctor")
+}
+
+/// Prints the doctor report and exits non-zero while problems remain. The logger stays off so
+/// the JSON report is the only thing on stdout.
+async fn run_doctor() -> Result<()> {
+    let json = std::env::args().any(|argument| argument == "--json");
+    let fix = std::env::args().any(|argument| argument == "--fix");
+
+    let mut report = diagnose_service().await;
+    if fix && !report.repair()? {
+        eprintln!(
+            "Another install or repair is in progress; run the doctor again once it finishes."
+        );
+        std::process::exit(REPAIR_IN_PROGRESS_EXIT_CODE);
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        println!("{report}");
+    }
+    if report.has_problems() {
+        std::process::exit(1);
+    }
+    Ok(())
+}
+
 fn init_logger() {
     let subscriber = FmtSubscriber::builder()
         .with_max_level(Level::INFO)
@@ -164,7 +256,7 @@ fn init_logger() {
     let _ = tracing::subscriber::set_global_default(subscriber);
 }
 
-async fn run_standalone() -> Result<()> {
+async fn run_standalone(paths: &ServicePaths) -> Result<()> {
     let pid = std::process::id();
     info!("Clash Verge Service - Standalone Mode");
     info!("Current process PID: {}", pid);
@@ -188,7 +280,7 @@ async fn run_standalone() -> Result<()> {
         ),
     }
 
-    run_ipc_supervisor_until_shutdown(shutdown_signal()).await?;
+    run_ipc_supervisor_until_shutdown(paths, shutdown_signal()).await?;
 
     info!("Service shutdown complete.");
     Ok(())
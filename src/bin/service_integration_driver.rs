@@ -5,7 +5,8 @@ use anyhow::Context as _;
 use clash_verge_service_ipc::test_owner_credentials;
 use clash_verge_service_ipc::{
     IpcConfig, MIN_REQUIRED_SERVICE_REVISION, OwnerSessionProof, ProtocolVersion, RuntimeBundle,
-    StartClashRequest, get_status, get_version, set_config, start_clash, stop_clash,
+    ServicePaths, StartClashRequest, get_status_at, get_version_at, service_paths, set_config,
+    start_clash_at, stop_clash_at,
 };
 #[cfg(not(feature = "test"))]
 use clash_verge_service_ipc::{OwnerCredentials, OwnerIdentity};
@@ -24,12 +25,13 @@ async fn main() -> anyhow::Result<()> {
         std::process::exit(1);
     }
 
+    let paths = service_paths();
     match args[1].as_str() {
-        "probe" => probe_protocol().await?,
-        "ready" => wait_protocol_ready().await?,
-        "ping" => wait_ipc_ready().await?,
-        "start" => start_flow().await?,
-        "stop" => stop_flow().await?,
+        "probe" => probe_protocol(&paths).await?,
+        "ready" => wait_protocol_ready(&paths).await?,
+        "ping" => wait_ipc_ready(&paths).await?,
+        "start" => start_flow(&paths).await?,
+        "stop" => stop_flow(&paths).await?,
         _ => {
             eprintln!("usage: service-integration-driver <probe|ready|ping|start|stop>");
             std::process::exit(1);
@@ -39,7 +41,7 @@ async fn main() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn probe_protocol() -> anyhow::Result<()> {
+async fn probe_protocol(paths: &ServicePaths) -> anyhow::Result<()> {
     set_config(Some(IpcConfig {
         default_timeout: Duration::from_millis(250),
         max_retries: 1,
@@ -47,7 +49,7 @@ async fn probe_protocol() -> anyhow::Result<()> {
     }))
     .await;
     let result = async {
-        let response = get_version().await?;
+        let response = get_version_at(paths).await?;
         let info = response
             .data
             .ok_or_else(|| anyhow::anyhow!("service omitted protocol information"))?;
@@ -63,7 +65,7 @@ async fn probe_protocol() -> anyhow::Result<()> {
     result
 }
 
-async fn wait_protocol_ready() -> anyhow::Result<()> {
+async fn wait_protocol_ready(paths: &ServicePaths) -> anyhow::Result<()> {
     set_config(Some(IpcConfig {
         default_timeout: Duration::from_millis(250),
         max_retries: 1,
@@ -75,7 +77,7 @@ async fn wait_protocol_ready() -> anyhow::Result<()> {
         let deadline = Instant::now() + IPC_READY_TIMEOUT;
         let mut last_error = None;
         while Instant::now() < deadline {
-            match probe_protocol().await {
+            match probe_protocol(paths).await {
                 Ok(()) => return Ok(()),
                 Err(error) => last_error = Some(error),
             }
@@ -94,20 +96,22 @@ async fn wait_protocol_ready() -> anyhow::Result<()> {
     result
 }
 
-async fn start_flow() -> anyhow::Result<()> {
-    wait_ipc_ready().await?;
+async fn start_flow(paths: &ServicePaths) -> anyhow::Result<()> {
+    wait_ipc_ready(paths).await?;
     let config = RuntimeBundle {
         yaml: "mode: rule\n".to_string(),
         assets: vec![],
         remote_providers: Vec::new(),
         core_path: mock_binary_path()?,
     };
-    let response = start_clash(
+    let response = start_clash_at(
+        paths,
         &owner_credentials()?,
         &StartClashRequest {
             runtime: config,
             proposed_session_token: session_token()?,
             macos_proxy: None,
+            lease: None,
         },
     )
     .await?;
@@ -127,8 +131,8 @@ async fn start_flow() -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn stop_flow() -> anyhow::Result<()> {
-    let response = stop_clash(&owner_credentials()?, &session_proof()?).await?;
+async fn stop_flow(paths: &ServicePaths) -> anyhow::Result<()> {
+    let response = stop_clash_at(paths, &owner_credentials()?, &session_proof()?).await?;
     if response.code != 0 {
         anyhow::bail!(
             "service rejected Stop: {} ({})",
@@ -155,7 +159,7 @@ fn session_proof() -> anyhow::Result<OwnerSessionProof> {
     })
 }
 
-async fn wait_ipc_ready() -> anyhow::Result<()> {
+async fn wait_ipc_ready(paths: &ServicePaths) -> anyhow::Result<()> {
     set_config(Some(IpcConfig {
         default_timeout: Duration::from_millis(250),
         max_retries: 1,
@@ -166,7 +170,7 @@ async fn wait_ipc_ready() -> anyhow::Result<()> {
     let result: anyhow::Result<()> = async {
         let deadline = Instant::now() + IPC_READY_TIMEOUT;
         while Instant::now() < deadline {
-            if let Ok(response) = get_status(&owner_credentials()?).await
+            if let Ok(response) = get_status_at(paths, &owner_credentials()?).await
                 && response.code == 0
                 && response.data.is_some()
             {
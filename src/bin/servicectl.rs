@@ -0,0 +1,647 @@
+//! Admin command line for a running service, built on the client module.
+//! It authenticates as the user running it with that user's app data root, so it sees and
+//! changes exactly what the user's app would.
+
+use anyhow::{Context as _, bail};
+use clash_verge_service_ipc::{
+    AuditQuery, AuditRecord, CHANNEL_IDENTITY, ChannelIdentity, EvictOwnerRequest,
+    MIN_REQUIRED_SERVICE_REVISION, ObserverStatusSnapshot, OwnerCredentials, OwnerSessionProof,
+    ProtocolVersion, ProxyApplyOutcome, Response, RuntimeBundle, SESSION_TOKEN_HEX_LEN,
+    ServiceErrorCode, ServiceMode, ServicePaths, ServiceStatusSnapshot, StageRuntimeOutcome,
+    WriterConfig, current_owner_credentials, evict_owner_at, get_clash_log_snapshot_at,
+    get_clash_logs_at, get_status_at, get_version_at, observe_status_at, query_audit_log_at,
+    service_paths_for, stage_runtime_at, stop_clash_at, update_writer_at,
+};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+const USAGE: &str = "\
+usage: clash-verge-servicectl [--channel <id>] [--user] [--app-data-dir <dir>] [--json] <command>
+
+commands:
+  status                      show the service and core state
+  observe                     show who owns the service and whether its core runs, for
+                              members of the observer policy
+  version                     show the service build and protocol
+  logs [--follow]             print the core log, or keep printing new lines
+  stop                        stop the core
+  evict <owner-key> [--purge] stop another owner's core, clear its proxy and release it, and
+                              with --purge also remove its state; root or the admin policy only
+  audit [--since <unix-secs>] [--until <unix-secs>] [--owner <owner-key>]
+                              print audited requests, oldest first; root or the admin policy only
+  stage <bundle.json>         stage a runtime bundle into the running core
+  writer --directory <dir> --max-log-size <bytes> --max-log-files <count>
+                              set where and how the core log is written
+  session import <file|->     keep the app's session proof ({\"generation\", \"token\"})
+  session show                show the kept session generation
+  session clear               forget the kept session proof";
+
+/// Kept in the app data root, which only its owner can reach.
+const SESSION_PROOF_FILE_NAME: &str = ".clash-verge-servicectl-session.json";
+const FOLLOW_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Invocation {
+    channel: Option<String>,
+    user: bool,
+    app_data_dir: Option<PathBuf>,
+    json: bool,
+    command: Vec<String>,
+}
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> anyhow::Result<()> {
+    let invocation = match parse_invocation(std::env::args().skip(1)) {
+        Ok(invocation) if !invocation.command.is_empty() => invocation,
+        Ok(_) => usage_error("a command is required"),
+        Err(error) => usage_error(&format!("{error:#}")),
+    };
+    let channel = match &invocation.channel {
+        Some(id) => ChannelIdentity::from_id(id)
+            .with_context(|| format!("unknown or invalid channel {id:?}"))?,
+        None => CHANNEL_IDENTITY,
+    };
+    let mode = if invocation.user {
+        anyhow::ensure!(
+            cfg!(target_os = "linux"),
+            "rootless services only exist on Linux"
+        );
+        ServiceMode::User
+    } else {
+        ServiceMode::System
+    };
+    let paths = service_paths_for(&channel, mode);
+    let app_data_dir = match &invocation.app_data_dir {
+        Some(directory) => directory.clone(),
+        None => default_app_data_dir(&channel)?,
+    };
+
+    let command: Vec<&str> = invocation.command.iter().map(String::as_str).collect();
+    match command.as_slice() {
+        ["status"] => show_status(&paths, &app_data_dir, invocation.json).await,
+        ["observe"] => show_observed_status(&paths, &app_data_dir, invocation.json).await,
+        ["version"] => show_version(&paths, invocation.json).await,
+        ["logs"] => print_log_snapshot(&paths, &app_data_dir).await,
+        ["logs", "--follow"] => follow_logs(&paths, &app_data_dir).await,
+        ["stop"] => stop_core(&paths, &app_data_dir).await,
+        ["evict", owner_key] => evict(&paths, &app_data_dir, owner_key, false).await,
+        ["evict", owner_key, "--purge"] => evict(&paths, &app_data_dir, owner_key, true).await,
+        ["audit", options @ ..] => {
+            show_audit_log(&paths, &app_data_dir, options, invocation.json).await
+        }
+        ["stage", bundle] => stage_bundle(&paths, &app_data_dir, Path::new(bundle)).await,
+        ["writer", options @ ..] => set_writer(&paths, &app_data_dir, options).await,
+        ["session", "import", source] => import_session(&app_data_dir, source),
+        ["session", "show"] => {
+            let proof = load_session_proof(&app_data_dir)?;
+            println!("session generation {}", proof.generation);
+            Ok(())
+        }
+        ["session", "clear"] => clear_session(&app_data_dir),
+        _ => usage_error(&format!("unknown command {:?}", command.join(" "))),
+    }
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{message}\n\n{USAGE}");
+    std::process::exit(2);
+}
+
+/// Global options may appear anywhere; everything else belongs to the command.
+fn parse_invocation(arguments: impl IntoIterator<Item = String>) -> anyhow::Result<Invocation> {
+    let mut invocation = Invocation::default();
+    let mut arguments = arguments.into_iter();
+    while let Some(argument) = arguments.next() {
+        let (flag, inline_value) = match argument.split_once('=') {
+            Some((flag, value)) => (flag.to_owned(), Some(value.to_owned())),
+            None => (argument.clone(), None),
+        };
+        let mut value = || {
+            inline_value
+                .clone()
+                .or_else(|| arguments.next())
+                .with_context(|| format!("{flag} needs a value"))
+        };
+        match flag.as_str() {
+            "--channel" => invocation.channel = Some(value()?),
+            "--app-data-dir" => invocation.app_data_dir = Some(PathBuf::from(value()?)),
+            "--user" => invocation.user = true,
+            "--json" => invocation.json = true,
+            _ => invocation.command.push(argument),
+        }
+    }
+    Ok(invocation)
+}
+
+/// The app keeps its data under its bundle identifier, which is the same on every platform.
+fn default_app_data_dir(channel: &ChannelIdentity) -> anyhow::Result<PathBuf> {
+    let home = || {
+        std::env::var_os("HOME")
+            .map(PathBuf::from)
+            .context("HOME is not set; pass --app-data-dir")
+    };
+    #[cfg(target_os = "macos")]
+    let base = home()?.join("Library").join("Application Support");
+    #[cfg(all(unix, not(target_os = "macos")))]
+    let base = match std::env::var_os("XDG_DATA_HOME").map(PathBuf::from) {
+        Some(path) if path.is_absolute() => path,
+        _ => home()?.join(".local").join("share"),
+    };
+    #[cfg(windows)]
+    let base = {
+        let _ = home;
+        std::env::var_os("APPDATA")
+            .map(PathBuf::from)
+            .context("APPDATA is not set; pass --app-data-dir")?
+    };
+    Ok(base.join(channel.macos_app_bundle_id))
+}
+
+fn credentials(app_data_dir: &Path) -> anyhow::Result<OwnerCredentials> {
+    current_owner_credentials(app_data_dir)
+        .with_context(|| format!("failed to load owner credentials for {app_data_dir:?}"))
+}
+
+/// Returns the payload of a successful response, turning refusals into errors.
+fn accepted<T>(action: &str, response: Response<T>) -> anyhow::Result<Option<T>> {
+    if response.code == ServiceErrorCode::StaleOwnerSession as u16 {
+        bail!(
+            "service rejected {action}: {} ({}); import the app's current session with \
+             `clash-verge-servicectl session import`",
+            response.message,
+            response.code
+        );
+    }
+    if response.code != 0 {
+        bail!(
+            "service rejected {action}: {} ({})",
+            response.message,
+            response.code
+        );
+    }
+    Ok(response.data)
+}
+
+async fn show_status(paths: &ServicePaths, app_data_dir: &Path, json: bool) -> anyhow::Result<()> {
+    let response = get_status_at(paths, &credentials(app_data_dir)?).await?;
+    let status = accepted("Status", response)?.context("service omitted its status")?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+    } else {
+        print!("{}", describe_status(&status));
+    }
+    Ok(())
+}
+
+async fn show_observed_status(
+    paths: &ServicePaths,
+    app_data_dir: &Path,
+    json: bool,
+) -> anyhow::Result<()> {
+    let response = observe_status_at(paths, &credentials(app_data_dir)?).await?;
+    let status = accepted("ObserveStatus", response)?.context("service omitted its status")?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&status)?);
+    } else {
+        print!("{}", describe_observed_status(&status));
+    }
+    Ok(())
+}
+
+fn describe_observed_status(status: &ObserverStatusSnapshot) -> String {
+    let mut lines = vec![format!("service: {:?}", status.service_state)];
+    lines.push(match status.owner_uid {
+        Some(uid) => format!("owner: uid {uid}"),
+        None => "owner: none".to_owned(),
+    });
+    lines.push(match (status.core_running, status.core_uptime_secs) {
+        (true, Some(uptime)) => format!("core: running for {uptime}s"),
+        (true, None) => "core: running".to_owned(),
+        (false, _) => "core: stopped".to_owned(),
+    });
+    lines.push(format!("restarts: {}", status.restart_count));
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+fn describe_status(status: &ServiceStatusSnapshot) -> String {
+    let mut lines = vec![format!("service: {:?}", status.service_state)];
+    lines.push(match (status.core_pid, status.active_generation) {
+        (Some(pid), generation) => format!(
+            "core: running, pid {pid}, generation {}{}",
+            generation.map_or_else(|| "-".to_owned(), |generation| generation.to_string()),
+            if status.core_adopted {
+                " (adopted)"
+            } else {
+                ""
+            }
+        ),
+        (None, _) => "core: stopped".to_owned(),
+    });
+    lines.push(format!(
+        "desired: core {} (generation {})",
+        if status.desired_core_should_be_running {
+            "running"
+        } else {
+            "stopped"
+        },
+        status.desired_generation
+    ));
+    if let Some(outcome) = &status.proxy_outcome {
+        lines.push(match outcome {
+            ProxyApplyOutcome::NotRequested => "proxy: not requested".to_owned(),
+            ProxyApplyOutcome::Applied => "proxy: applied".to_owned(),
+            ProxyApplyOutcome::DirectFallback { message } => {
+                format!("proxy: direct fallback ({message})")
+            }
+        });
+    }
+    if !status.sessions.is_empty() {
+        lines.push(format!("sessions: {}", status.sessions.join(", ")));
+    }
+    if let Some(lease) = &status.session_lease {
+        lines.push(format!(
+            "session lease: {} at unix time {} ({}s ttl, then {:?})",
+            if lease.expired { "expired" } else { "expires" },
+            lease.expires_at,
+            lease.ttl_secs,
+            lease.on_expiry
+        ));
+    }
+    lines.push(format!("restarts: {}", status.restart_count));
+    if let Some(reason) = &status.last_core_exit_reason {
+        lines.push(format!("last exit: {reason}"));
+    }
+    if let Some(report) = &status.last_crash_report {
+        lines.push(format!("last crash report: {report}"));
+    }
+    if let Some(resources) = &status.core_resources {
+        lines.push(format!(
+            "resources: {} bytes resident, {} ms cpu time",
+            resources.rss_bytes, resources.cpu_time_ms
+        ));
+    }
+    lines.iter().map(|line| format!("{line}\n")).collect()
+}
+
+async fn show_version(paths: &ServicePaths, json: bool) -> anyhow::Result<()> {
+    let info = accepted("GetVersion", get_version_at(paths).await?)?
+        .context("service omitted its protocol information")?;
+    if json {
+        println!("{}", serde_json::to_string_pretty(&info)?);
+        return Ok(());
+    }
+    let client = ProtocolVersion::current();
+    let compatible = info.supports_client(client, MIN_REQUIRED_SERVICE_REVISION);
+    println!("service build {}", info.build_version);
+    println!(
+        "protocol {}.{} (this client {}.{}, {})",
+        info.protocol.epoch,
+        info.protocol.revision,
+        client.epoch,
+        client.revision,
+        if compatible {
+            "compatible"
+        } else {
+            "incompatible"
+        }
+    );
+    println!("mode: {:?}", info.mode);
+    Ok(())
+}
+
+async fn print_log_snapshot(paths: &ServicePaths, app_data_dir: &Path) -> anyhow::Result<()> {
+    let response = get_clash_log_snapshot_at(paths, &credentials(app_data_dir)?).await?;
+    print!(
+        "{}",
+        accepted("GetClashLogSnapshot", response)?.unwrap_or_default()
+    );
+    Ok(())
+}
+
+/// Polls the service's recent-line buffer until interrupted.
+async fn follow_logs(paths: &ServicePaths, app_data_dir: &Path) -> anyhow::Result<()> {
+    let credentials = credentials(app_data_dir)?;
+    let mut previous = Vec::new();
+    loop {
+        let lines = accepted(
+            "GetClashLogs",
+            get_clash_logs_at(paths, &credentials).await?,
+        )?
+        .unwrap_or_default();
+        for line in unseen_log_lines(&previous, &lines) {
+            println!("{line}");
+        }
+        previous = lines;
+        tokio::time::sleep(FOLLOW_INTERVAL).await;
+    }
+}
+
+/// The buffer drops its oldest lines as new ones arrive, so the lines already printed are the
+/// longest tail of `previous` that `current` starts with.
+fn unseen_log_lines<'a, T: PartialEq>(previous: &[T], current: &'a [T]) -> &'a [T] {
+    let longest = previous.len().min(current.len());
+    (1..=longest)
+        .rev()
+        .find(|overlap| previous[previous.len() - overlap..] == current[..*overlap])
+        .map_or(current, |overlap| &current[overlap..])
+}
+
+async fn stop_core(paths: &ServicePaths, app_data_dir: &Path) -> anyhow::Result<()> {
+    let proof = load_session_proof(app_data_dir)?;
+    let response = stop_clash_at(paths, &credentials(app_data_dir)?, &proof).await?;
+    accepted("StopClash", response)?;
+    println!("core stopped");
+    Ok(())
+}
+
+async fn evict(
+    paths: &ServicePaths,
+    app_data_dir: &Path,
+    owner_key: &str,
+    purge_state: bool,
+) -> anyhow::Result<()> {
+    let request = EvictOwnerRequest {
+        owner_key: owner_key.to_owned(),
+        purge_state,
+    };
+    let response = evict_owner_at(paths, &credentials(app_data_dir)?, &request).await?;
+    let message = response.message.clone();
+    accepted("EvictOwner", response)?;
+    println!("{message}");
+    Ok(())
+}
+
+async fn show_audit_log(
+    paths: &ServicePaths,
+    app_data_dir: &Path,
+    options: &[&str],
+    json: bool,
+) -> anyhow::Result<()> {
+    let mut query = AuditQuery::default();
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let mut value = || {
+            options
+                .next()
+                .copied()
+                .with_context(|| format!("{option} needs a value"))
+        };
+        match *option {
+            "--since" => {
+                query.since = Some(value()?.parse().context("--since takes Unix seconds")?)
+            }
+            "--until" => {
+                query.until = Some(value()?.parse().context("--until takes Unix seconds")?)
+            }
+            "--owner" => query.owner_key = Some(value()?.to_owned()),
+            other => usage_error(&format!("unknown audit option {other:?}")),
+        }
+    }
+
+    let response = query_audit_log_at(paths, &credentials(app_data_dir)?, &query).await?;
+    let records = accepted("QueryAuditLog", response)?.unwrap_or_default();
+    if json {
+        println!("{}", serde_json::to_string_pretty(&records)?);
+    } else {
+        for record in &records {
+            println!("{}", describe_audit_record(record));
+        }
+    }
+    Ok(())
+}
+
+fn describe_audit_record(record: &AuditRecord) -> String {
+    let optional = |value: Option<&str>| value.unwrap_or("-").to_owned();
+    let mut line = format!(
+        "{} {} caller {} owner {} code {}",
+        record.at,
+        record.route,
+        optional(record.caller.as_deref()),
+        optional(record.owner_key.as_deref()),
+        record
+            .code
+            .map_or_else(|| "-".to_owned(), |code| code.to_string()),
+    );
+    if let Some(generation) = record.generation {
+        line.push_str(&format!(" generation {generation}"));
+    }
+    if let Some(peer_uid) = record.peer_uid {
+        line.push_str(&format!(" uid {peer_uid}"));
+    }
+    if let Some(peer_pid) = record.peer_pid {
+        line.push_str(&format!(" pid {peer_pid}"));
+    }
+    if let Some(proxy_mode) = &record.proxy_mode {
+        line.push_str(&format!(" proxy {proxy_mode}"));
+    }
+    if let Some(bundle_hash) = &record.bundle_hash {
+        line.push_str(&format!(
+            " bundle {}",
+            &bundle_hash[..bundle_hash.len().min(12)]
+        ));
+    }
+    if let Some(message) = &record.message {
+        line.push_str(&format!(": {message}"));
+    }
+    line
+}
+
+async fn stage_bundle(
+    paths: &ServicePaths,
+    app_data_dir: &Path,
+    bundle: &Path,
+) -> anyhow::Result<()> {
+    let content =
+        std::fs::read(bundle).with_context(|| format!("failed to read bundle {bundle:?}"))?;
+    let bundle: RuntimeBundle = serde_json::from_slice(&content)
+        .with_context(|| format!("{bundle:?} is not a runtime bundle"))?;
+    let info = accepted("GetVersion", get_version_at(paths).await?)?
+        .context("service omitted its protocol information")?;
+    anyhow::ensure!(
+        info.supports_runtime_staging(),
+        "service {} cannot stage a runtime into a running core",
+        info.build_version
+    );
+
+    let proof = load_session_proof(app_data_dir)?;
+    let response = stage_runtime_at(paths, &credentials(app_data_dir)?, &proof, &bundle).await?;
+    match accepted("StageRuntime", response)?.context("service omitted the staging outcome")? {
+        StageRuntimeOutcome::Staged { config_path } => {
+            println!("staged; load {config_path} into the core to apply it");
+        }
+        StageRuntimeOutcome::RestartRequired { reason } => {
+            bail!("the core must be restarted to apply this bundle: {reason:?}");
+        }
+    }
+    Ok(())
+}
+
+async fn set_writer(
+    paths: &ServicePaths,
+    app_data_dir: &Path,
+    options: &[&str],
+) -> anyhow::Result<()> {
+    let (mut directory, mut max_log_size, mut max_log_files) = (None, None, None);
+    let mut options = options.iter();
+    while let Some(option) = options.next() {
+        let mut value = || {
+            options
+                .next()
+                .copied()
+                .with_context(|| format!("{option} needs a value"))
+        };
+        match *option {
+            "--directory" => directory = Some(value()?.to_owned()),
+            "--max-log-size" => {
+                max_log_size = Some(value()?.parse().context("--max-log-size takes bytes")?);
+            }
+            "--max-log-files" => {
+                max_log_files = Some(value()?.parse().context("--max-log-files takes a count")?);
+            }
+            other => usage_error(&format!("unknown writer option {other:?}")),
+        }
+    }
+    let config = WriterConfig {
+        directory: directory.context("writer needs --directory")?,
+        max_log_size: max_log_size.context("writer needs --max-log-size")?,
+        max_log_files: max_log_files.context("writer needs --max-log-files")?,
+    };
+
+    let proof = load_session_proof(app_data_dir)?;
+    let response = update_writer_at(paths, &credentials(app_data_dir)?, &proof, &config).await?;
+    accepted("UpdateWriter", response)?;
+    println!("log writer updated");
+    Ok(())
+}
+
+fn session_proof_path(app_data_dir: &Path) -> PathBuf {
+    app_data_dir.join(SESSION_PROOF_FILE_NAME)
+}
+
+fn load_session_proof(app_data_dir: &Path) -> anyhow::Result<OwnerSessionProof> {
+    let path = session_proof_path(app_data_dir);
+    let content = match std::fs::read(&path) {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => bail!(
+            "no session proof is kept; import the app's session with \
+             `clash-verge-servicectl session import <file|->`"
+        ),
+        Err(error) => return Err(error).with_context(|| format!("failed to read {path:?}")),
+    };
+    serde_json::from_slice(&content).with_context(|| format!("{path:?} is not a session proof"))
+}
+
+/// Reads the proof from a file or, with `-`, from stdin, so the token never shows up in the
+/// process list.
+fn import_session(app_data_dir: &Path, source: &str) -> anyhow::Result<()> {
+    let content = if source == "-" {
+        let mut content = Vec::new();
+        std::io::Read::read_to_end(&mut std::io::stdin(), &mut content)
+            .context("failed to read the session proof from stdin")?;
+        content
+    } else {
+        std::fs::read(source).with_context(|| format!("failed to read {source:?}"))?
+    };
+    let proof: OwnerSessionProof =
+        serde_json::from_slice(&content).context("input is not a session proof")?;
+    anyhow::ensure!(
+        is_session_token(&proof.token),
+        "session token must be {SESSION_TOKEN_HEX_LEN} lowercase hexadecimal characters"
+    );
+
+    let path = session_proof_path(app_data_dir);
+    let temporary = path.with_extension("json.tmp");
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options
+        .open(&temporary)
+        .with_context(|| format!("failed to create {temporary:?}"))?;
+    std::io::Write::write_all(&mut file, &serde_json::to_vec(&proof)?)?;
+    file.sync_all()?;
+    drop(file);
+    std::fs::rename(&temporary, &path).with_context(|| format!("failed to replace {path:?}"))?;
+    println!("kept session generation {}", proof.generation);
+    Ok(())
+}
+
+fn clear_session(app_data_dir: &Path) -> anyhow::Result<()> {
+    let path = session_proof_path(app_data_dir);
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error).with_context(|| format!("failed to remove {path:?}")),
+    }
+}
+
+fn is_session_token(token: &str) -> bool {
+    token.len() == SESSION_TOKEN_HEX_LEN
+        && token
+            .bytes()
+            .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        Invocation, describe_audit_record, is_session_token, parse_invocation, unseen_log_lines,
+    };
+    use std::path::PathBuf;
+
+    #[test]
+    fn global_options_are_accepted_around_the_command() -> anyhow::Result<()> {
+        let arguments = [
+            "logs",
+            "--channel=qa",
+            "--follow",
+            "--app-data-dir",
+            "/data",
+        ];
+        let invocation = parse_invocation(arguments.map(str::to_owned))?;
+        assert_eq!(
+            invocation,
+            Invocation {
+                channel: Some("qa".to_owned()),
+                app_data_dir: Some(PathBuf::from("/data")),
+                command: vec!["logs".to_owned(), "--follow".to_owned()],
+                ..Invocation::default()
+            }
+        );
+        assert!(parse_invocation(["status", "--channel"].map(str::to_owned)).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn followed_logs_print_only_lines_past_the_previous_poll() {
+        assert_eq!(unseen_log_lines(&[] as &[&str], &["a", "b"]), ["a", "b"]);
+        assert_eq!(unseen_log_lines(&["a", "b"], &["a", "b", "c"]), ["c"]);
+        assert_eq!(unseen_log_lines(&["a", "b", "c"], &["b", "c", "d"]), ["d"]);
+        assert_eq!(unseen_log_lines(&["a", "b"], &["a", "b"]), [] as [&str; 0]);
+        assert_eq!(unseen_log_lines(&["a"], &["x", "y"]), ["x", "y"]);
+    }
+
+    #[test]
+    fn audit_records_name_the_connecting_process() -> anyhow::Result<()> {
+        let record = serde_json::from_str(
+            r#"{"at":7,"route":"/clash/stop","peer_uid":1000,"caller":"u1000","owner_key":"u1000","session":null,"generation":3,"code":0,"message":null,"bundle_hash":null,"proxy_mode":null}"#,
+        )?;
+        assert_eq!(
+            describe_audit_record(&record),
+            "7 /clash/stop caller u1000 owner u1000 code 0 generation 3 uid 1000"
+        );
+        let record = clash_verge_service_ipc::AuditRecord {
+            peer_pid: Some(4242),
+            ..record
+        };
+        assert!(describe_audit_record(&record).ends_with("uid 1000 pid 4242"));
+        Ok(())
+    }
+
+    #[test]
+    fn only_lowercase_hex_session_tokens_are_kept() {
+        assert!(is_session_token(&"0f".repeat(32)));
+        assert!(!is_session_token(&"0F".repeat(32)));
+        assert!(!is_session_token(&"0f".repeat(31)));
+    }
+}
@@ -0,0 +1,341 @@
+//! Init-system backends for the Linux installer and uninstaller.
+//! systemd is the common case; OpenRC, runit and s6 cover distributions that do not ship it.
+
+use super::run_command;
+use anyhow::{Context as _, Error};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum InitSystem {
+    Systemd,
+    OpenRc,
+    Runit,
+    S6,
+}
+
+impl InitSystem {
+    const ALL: [Self; 4] = [Self::Systemd, Self::OpenRc, Self::Runit, Self::S6];
+
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Self::Systemd => "systemd",
+            Self::OpenRc => "openrc",
+            Self::Runit => "runit",
+            Self::S6 => "s6",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|init| init.name() == name)
+    }
+}
+
+/// Picks the init system named by `--init <name>`, or else the one this machine booted with.
+pub(crate) fn select_init_system() -> Result<InitSystem, Error> {
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        let name = match argument.strip_prefix("--init=") {
+            Some(name) => name.to_owned(),
+            None if argument == "--init" => arguments.next().unwrap_or_default(),
+            None => continue,
+        };
+        return InitSystem::from_name(&name).ok_or_else(|| {
+            anyhow::anyhow!(
+                "unsupported init system {name:?}; expected systemd, openrc, runit or s6"
+            )
+        });
+    }
+    let pid1 = std::fs::read_to_string("/proc/1/comm").unwrap_or_default();
+    detect_init_system(pid1.trim(), |path| Path::new(path).exists())
+        .context("could not detect the init system; pass --init systemd|openrc|runit|s6")
+}
+
+/// systemd and OpenRC leave the same markers `sd_booted` and `rc-status` check; runit and s6 are
+/// recognised by the name of PID 1, since OpenRC itself may run under either supervisor.
+fn detect_init_system(pid1: &str, exists: impl Fn(&str) -> bool) -> Option<InitSystem> {
+    if exists("/run/systemd/system") {
+        return Some(InitSystem::Systemd);
+    }
+    match pid1 {
+        "runit" => Some(InitSystem::Runit),
+        "s6-svscan" => Some(InitSystem::S6),
+        _ if exists("/run/openrc/softlevel") => Some(InitSystem::OpenRc),
+        _ => None,
+    }
+}
+
+/// Scan directories runsvdir watches, in the order distributions use them.
+const RUNIT_SCAN_DIRS: [&str; 4] = [
+    "/var/service",
+    "/run/runit/service",
+    "/etc/service",
+    "/service",
+];
+/// Scan directories s6-svscan watches; s6-linux-init rebuilds `/run/service` from its run image.
+const S6_SCAN_DIRS: [&str; 2] = ["/run/service", "/service"];
+const S6_BOOT_SCAN_DIR: &str = "/etc/s6-linux-init/current/run-image/service";
+
+/// One channel's service as registered with an init system. Operations only the installer needs
+/// live beside it in the installer.
+pub(crate) struct InitService {
+    pub(crate) init: InitSystem,
+    pub(crate) name: &'static str,
+    pub(crate) user: bool,
+    pub(crate) debug: bool,
+}
+
+impl InitService {
+    pub(crate) fn new(
+        init: InitSystem,
+        channel: &clash_verge_service_ipc::ChannelIdentity,
+        user: bool,
+        debug: bool,
+    ) -> Result<Self, Error> {
+        anyhow::ensure!(
+            !user || init == InitSystem::Systemd,
+            "rootless services need systemd --user, not {}",
+            init.name()
+        );
+        Ok(Self {
+            init,
+            name: channel.service_slug,
+            user,
+            debug,
+        })
+    }
+
+    /// The file the installer writes: a unit, an OpenRC script, or a supervision `run` script.
+    pub(crate) fn definition_path(&self) -> Result<PathBuf, Error> {
+        Ok(match self.init {
+            InitSystem::Systemd => self.unit_dir()?.join(self.unit_name()),
+            InitSystem::OpenRc => Path::new("/etc/init.d").join(self.name),
+            InitSystem::Runit | InitSystem::S6 => self.service_dir().join("run"),
+        })
+    }
+
+    pub(crate) fn remove_definition(&self) -> Result<(), Error> {
+        let (path, removed) = match self.init {
+            InitSystem::Systemd | InitSystem::OpenRc => {
+                let path = self.definition_path()?;
+                let removed = std::fs::remove_file(&path);
+                (path, removed)
+            }
+            InitSystem::Runit | InitSystem::S6 => {
+                let path = self.service_dir();
+                let removed = std::fs::remove_dir_all(&path);
+                (path, removed)
+            }
+        };
+        match removed {
+            Err(error) if error.kind() != std::io::ErrorKind::NotFound => {
+                Err(error).with_context(|| format!("failed to remove service definition {path:?}"))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Makes the init system pick up a changed definition.
+    pub(crate) fn reload(&self) -> Result<(), Error> {
+        match self.init {
+            InitSystem::Systemd => self.systemctl(&["daemon-reload"]),
+            // OpenRC reads the script on every call; supervisors reread `run` on each start.
+            InitSystem::OpenRc | InitSystem::Runit | InitSystem::S6 => Ok(()),
+        }
+    }
+
+    pub(crate) fn disable(&self) -> Result<(), Error> {
+        match self.init {
+            InitSystem::Systemd => self.systemctl(&["disable", &self.unit_name()]),
+            InitSystem::OpenRc => {
+                run_command("rc-update", &["del", self.name, "default"], self.debug)
+            }
+            InitSystem::Runit | InitSystem::S6 => {
+                let links = self.scan_links();
+                for link in &links {
+                    if std::fs::symlink_metadata(link).is_ok_and(|meta| meta.is_symlink()) {
+                        std::fs::remove_file(link)
+                            .with_context(|| format!("failed to remove scan link {link:?}"))?;
+                    }
+                }
+                // Without its link nothing restarts the supervisor once it exits.
+                match (self.init, links.first()) {
+                    (InitSystem::Runit, _) => {
+                        let _ = self.control("exit");
+                        Ok(())
+                    }
+                    (InitSystem::S6, Some(scan_dir_link)) => self.rescan_s6(scan_dir_link, "-an"),
+                    _ => Ok(()),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn stop(&self) -> Result<(), Error> {
+        match self.init {
+            InitSystem::Systemd => self.systemctl(&["stop", &self.unit_name()]),
+            InitSystem::OpenRc => run_command("rc-service", &[self.name, "stop"], self.debug),
+            InitSystem::Runit | InitSystem::S6 => self.control("down"),
+        }
+    }
+
+    pub(crate) fn unit_name(&self) -> String {
+        format!("{}.service", self.name)
+    }
+
+    fn unit_dir(&self) -> Result<PathBuf, Error> {
+        if self.user {
+            user_unit_dir()
+        } else {
+            Ok(PathBuf::from("/etc/systemd/system"))
+        }
+    }
+
+    pub(crate) fn service_dir(&self) -> PathBuf {
+        match self.init {
+            InitSystem::S6 => Path::new("/etc/s6/sv").join(self.name),
+            _ => Path::new("/etc/sv").join(self.name),
+        }
+    }
+
+    /// Links that put the service directory under supervision; the first is the live one.
+    pub(crate) fn scan_links(&self) -> Vec<PathBuf> {
+        let live = match self.init {
+            InitSystem::Runit => &RUNIT_SCAN_DIRS[..],
+            InitSystem::S6 => &S6_SCAN_DIRS[..],
+            InitSystem::Systemd | InitSystem::OpenRc => return Vec::new(),
+        }
+        .iter()
+        .map(Path::new)
+        .find(|dir| dir.is_dir());
+        let boot = (self.init == InitSystem::S6)
+            .then_some(Path::new(S6_BOOT_SCAN_DIR))
+            .filter(|dir| dir.is_dir());
+        live.into_iter()
+            .chain(boot)
+            .map(|dir| dir.join(self.name))
+            .collect()
+    }
+
+    /// Sends an `sv` command, or its `s6-svc` equivalent, to the service's supervisor.
+    pub(crate) fn control(&self, command: &str) -> Result<(), Error> {
+        // A directory linked a moment ago is picked up on the supervisor's next scan.
+        const SUPERVISOR_TIMEOUT: Duration = Duration::from_secs(10);
+
+        let service_dir = self.service_dir();
+        let (program, fifo) = match self.init {
+            InitSystem::Runit => ("sv", "supervise/ok"),
+            _ => ("s6-svc", "supervise/control"),
+        };
+        if command == "up" {
+            let deadline = Instant::now() + SUPERVISOR_TIMEOUT;
+            while !service_dir.join(fifo).exists() && Instant::now() < deadline {
+                std::thread::sleep(Duration::from_millis(250));
+            }
+        }
+        let command = match (self.init, command) {
+            (InitSystem::Runit, command) => command,
+            (_, "up") => "-u",
+            (_, "down") => "-d",
+            (_, "restart") => "-r",
+            (_, command) => anyhow::bail!("s6-svc has no {command:?} command"),
+        };
+        run_command(
+            program,
+            &[command, &service_dir.to_string_lossy()],
+            self.debug,
+        )
+    }
+
+    pub(crate) fn rescan_s6(&self, link: &Path, flags: &str) -> Result<(), Error> {
+        let scan_dir = link
+            .parent()
+            .context("s6 scan link has no parent")?
+            .to_string_lossy();
+        run_command("s6-svscanctl", &[flags, &scan_dir], self.debug)
+    }
+
+    pub(crate) fn systemctl(&self, args: &[&str]) -> Result<(), Error> {
+        if self.user {
+            let args: Vec<&str> = std::iter::once("--user")
+                .chain(args.iter().copied())
+                .collect();
+            run_command("systemctl", &args, self.debug)
+        } else {
+            run_command("systemctl", args, self.debug)
+        }
+    }
+}
+
+/// Where `systemd --user` looks for units the user installed.
+fn user_unit_dir() -> Result<PathBuf, Error> {
+    let config_home = std::env::var_os("XDG_CONFIG_HOME")
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+        .or_else(|| {
+            std::env::var_os("HOME")
+                .map(PathBuf::from)
+                .filter(|path| path.is_absolute())
+                .map(|home| home.join(".config"))
+        })
+        .ok_or_else(|| anyhow::anyhow!("neither XDG_CONFIG_HOME nor HOME is set"))?;
+    Ok(config_home.join("systemd/user"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InitService, InitSystem, detect_init_system};
+    use std::path::Path;
+
+    #[test]
+    fn init_system_is_detected_from_boot_markers_and_pid1() {
+        let systemd = |path: &str| path == "/run/systemd/system";
+        let openrc = |path: &str| path == "/run/openrc/softlevel";
+        let nothing = |_: &str| false;
+
+        assert_eq!(
+            detect_init_system("systemd", systemd),
+            Some(InitSystem::Systemd)
+        );
+        assert_eq!(detect_init_system("init", openrc), Some(InitSystem::OpenRc));
+        assert_eq!(detect_init_system("runit", openrc), Some(InitSystem::Runit));
+        assert_eq!(
+            detect_init_system("s6-svscan", nothing),
+            Some(InitSystem::S6)
+        );
+        assert_eq!(detect_init_system("init", nothing), None);
+    }
+
+    #[test]
+    fn each_backend_writes_its_definition_where_its_init_system_reads_it() -> anyhow::Result<()> {
+        let channel = clash_verge_service_ipc::DEVELOPMENT_CHANNEL;
+        let path = |init| InitService::new(init, &channel, false, false)?.definition_path();
+
+        assert_eq!(
+            path(InitSystem::Systemd)?,
+            Path::new("/etc/systemd/system/clash-verge-service-dev.service")
+        );
+        assert_eq!(
+            path(InitSystem::OpenRc)?,
+            Path::new("/etc/init.d/clash-verge-service-dev")
+        );
+        assert_eq!(
+            path(InitSystem::Runit)?,
+            Path::new("/etc/sv/clash-verge-service-dev/run")
+        );
+        assert_eq!(
+            path(InitSystem::S6)?,
+            Path::new("/etc/s6/sv/clash-verge-service-dev/run")
+        );
+        assert!(InitService::new(InitSystem::OpenRc, &channel, true, false).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn init_override_accepts_every_backend_name() {
+        for init in InitSystem::ALL {
+            assert_eq!(InitSystem::from_name(init.name()), Some(init));
+        }
+        assert_eq!(InitSystem::from_name("upstart"), None);
+    }
+}
@@ -2,6 +2,9 @@
 
 use anyhow::Error;
 
+#[cfg(target_os = "linux")]
+pub(crate) mod init;
+
 pub(crate) fn enter_repair_gate() -> Result<clash_verge_service_ipc::ServiceRepairGate, Error> {
     match clash_verge_service_ipc::acquire_service_repair_gate()? {
         Some(gate) => Ok(gate),
@@ -12,6 +15,39 @@ pub(crate) fn enter_repair_gate() -> Result<clash_verge_service_ipc::ServiceRepa
     }
 }
 
+/// Resolves the `mode` service of the channel named by `--channel <id>`, or of the compiled-in
+/// one, and selects its paths for the library calls this process makes.
+pub(crate) fn select_service_paths(
+    mode: clash_verge_service_ipc::ServiceMode,
+) -> Result<clash_verge_service_ipc::ServicePaths, Error> {
+    let mut channel = clash_verge_service_ipc::CHANNEL_IDENTITY;
+    let mut arguments = std::env::args().skip(1);
+    while let Some(argument) = arguments.next() {
+        let id = match argument.strip_prefix("--channel=") {
+            Some(id) => id.to_owned(),
+            None if argument == "--channel" => arguments.next().unwrap_or_default(),
+            None => continue,
+        };
+        channel = clash_verge_service_ipc::ChannelIdentity::from_id(&id)
+            .ok_or_else(|| anyhow::anyhow!("unknown or invalid channel {id:?}"))?;
+    }
+    let paths = clash_verge_service_ipc::service_paths_for(&channel, mode);
+    clash_verge_service_ipc::set_service_paths(paths.clone());
+    Ok(paths)
+}
+
+/// The rootless layout when `--user` is passed, otherwise the system service.
+#[cfg(target_os = "linux")]
+pub(crate) fn requested_service_mode() -> Result<clash_verge_service_ipc::ServiceMode, Error> {
+    if !std::env::args().any(|argument| argument == "--user") {
+        return Ok(clash_verge_service_ipc::ServiceMode::System);
+    }
+    if nix::unistd::geteuid().is_root() {
+        anyhow::bail!("a rootless service must be managed by the user it serves, not root");
+    }
+    Ok(clash_verge_service_ipc::ServiceMode::User)
+}
+
 pub(crate) fn run_maintenance_if_requested() -> Result<bool, Error> {
     if !std::env::args().any(|argument| argument == "--cleanup-stale-owners") {
         return Ok(false);
@@ -21,7 +57,8 @@ pub(crate) fn run_maintenance_if_requested() -> Result<bool, Error> {
     Ok(true)
 }
 
-#[cfg(all(target_os = "macos", not(feature = "development-channel")))]
+/// Removes the helper that predates channels; it only ever served production.
+#[cfg(target_os = "macos")]
 pub fn uninstall_old_service() -> Result<(), Error> {
     use std::path::Path;
 
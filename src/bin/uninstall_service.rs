@@ -6,11 +6,15 @@ fn main() {
 mod shared;
 
 use anyhow::Error;
-#[cfg(any(target_os = "linux", target_os = "macos"))]
+#[cfg(target_os = "linux")]
+use shared::init::{InitService, select_init_system};
+#[cfg(target_os = "linux")]
+use shared::requested_service_mode;
+#[cfg(target_os = "macos")]
 use shared::run_command;
-#[cfg(all(target_os = "macos", not(feature = "development-channel")))]
+#[cfg(target_os = "macos")]
 use shared::uninstall_old_service;
-use shared::{enter_repair_gate, run_maintenance_if_requested};
+use shared::{enter_repair_gate, run_maintenance_if_requested, select_service_paths};
 
 #[cfg(any(windows, test))]
 fn poll_until<T>(
@@ -35,23 +39,20 @@ fn main() -> Result<(), Error> {
     use std::env;
     use std::path::Path;
 
+    let paths = select_service_paths(clash_verge_service_ipc::ServiceMode::System)?;
+    let channel = paths.channel();
     if run_maintenance_if_requested()? {
         return Ok(());
     }
     let _gate = enter_repair_gate()?;
     let debug = env::args().any(|arg| arg == "--debug");
 
-    #[cfg(not(feature = "development-channel"))]
-    let _ = uninstall_old_service();
-    let bundle_path = format!(
-        "/Library/PrivilegedHelperTools/{}.bundle",
-        clash_verge_service_ipc::MACOS_SERVICE_ID
-    );
-    let plist_file = format!(
-        "/Library/LaunchDaemons/{}.plist",
-        clash_verge_service_ipc::MACOS_SERVICE_ID
-    );
-    let service_id = clash_verge_service_ipc::MACOS_SERVICE_ID;
+    if channel.is_production() {
+        let _ = uninstall_old_service();
+    }
+    let service_id = channel.macos_service_id;
+    let bundle_path = format!("/Library/PrivilegedHelperTools/{service_id}.bundle");
+    let plist_file = format!("/Library/LaunchDaemons/{service_id}.plist");
 
     let _ = run_command("launchctl", &["stop", service_id], debug);
     let _ = run_command(
@@ -60,6 +61,7 @@ fn main() -> Result<(), Error> {
         debug,
     );
     let _ = run_command("launchctl", &["bootout", "system", &plist_file], debug);
+    clash_verge_service_ipc::withdraw_handshake_key(&paths)?;
 
     if Path::new(&plist_file).exists() {
         std::fs::remove_file(&plist_file)
@@ -78,33 +80,24 @@ fn main() -> Result<(), Error> {
 fn main() -> Result<(), Error> {
     use std::env;
 
+    let mode = requested_service_mode()?;
+    let user = mode == clash_verge_service_ipc::ServiceMode::User;
+    let paths = select_service_paths(mode)?;
+    let channel = paths.channel();
     if run_maintenance_if_requested()? {
         return Ok(());
     }
     let _gate = enter_repair_gate()?;
     let debug = env::args().any(|arg| arg == "--debug");
-    let service_name = clash_verge_service_ipc::SERVICE_SLUG;
-
-    let _ = run_command(
-        "systemctl",
-        &["stop", &format!("{}.service", service_name)],
-        debug,
-    );
-    let _ = run_command(
-        "systemctl",
-        &["disable", &format!("{}.service", service_name)],
-        debug,
-    );
-
-    let unit_file = format!("/etc/systemd/system/{}.service", service_name);
-    if std::path::Path::new(&unit_file).exists() {
-        std::fs::remove_file(&unit_file)
-            .map_err(|e| anyhow::anyhow!("Failed to remove service file: {}", e))?;
-    }
-
-    let _ = run_command("systemctl", &["daemon-reload"], debug);
-    let target =
-        clash_verge_service_ipc::prepare_service_install_directory()?.join("clash-verge-service");
+    let service = InitService::new(select_init_system()?, &channel, user, debug)?;
+
+    let _ = service.stop();
+    let _ = service.disable();
+    service.remove_definition()?;
+    let _ = service.reload();
+    clash_verge_service_ipc::withdraw_handshake_key(&paths)?;
+    let target = clash_verge_service_ipc::prepare_service_install_directory(&paths)?
+        .join("clash-verge-service");
     if target.exists() {
         std::fs::remove_file(&target).map_err(|error| {
             anyhow::anyhow!("Failed to remove service binary {target:?}: {error}")
@@ -132,6 +125,8 @@ fn main() -> anyhow::Result<()> {
         matches!(error, WindowsServiceError::Winapi(error) if error.raw_os_error() == Some(code))
     }
 
+    let paths = select_service_paths(clash_verge_service_ipc::ServiceMode::System)?;
+    let channel = paths.channel();
     if run_maintenance_if_requested()? {
         return Ok(());
     }
@@ -140,10 +135,7 @@ fn main() -> anyhow::Result<()> {
     let service_manager = ServiceManager::local_computer(None::<&str>, manager_access)?;
 
     let service_access = ServiceAccess::QUERY_STATUS | ServiceAccess::STOP | ServiceAccess::DELETE;
-    let service = service_manager.open_service(
-        clash_verge_service_ipc::WINDOWS_SERVICE_NAME,
-        service_access,
-    )?;
+    let service = service_manager.open_service(channel.windows_service_name, service_access)?;
 
     let service_status = service.query_status()?;
     if service_status.current_state != ServiceState::Stopped {
@@ -163,14 +155,14 @@ fn main() -> anyhow::Result<()> {
         )?;
     }
 
+    clash_verge_service_ipc::withdraw_handshake_key(&paths)?;
     service.delete()?;
     drop(service);
     poll_until(
         POLL_ATTEMPTS,
-        || match service_manager.open_service(
-            clash_verge_service_ipc::WINDOWS_SERVICE_NAME,
-            ServiceAccess::QUERY_STATUS,
-        ) {
+        || match service_manager
+            .open_service(channel.windows_service_name, ServiceAccess::QUERY_STATUS)
+        {
             Ok(service) => {
                 drop(service);
                 Ok(None)
@@ -181,7 +173,7 @@ fn main() -> anyhow::Result<()> {
         || thread::sleep(POLL_INTERVAL),
         "timed out waiting for service deletion",
     )?;
-    let target = clash_verge_service_ipc::prepare_service_install_directory()?
+    let target = clash_verge_service_ipc::prepare_service_install_directory(&paths)?
         .join("clash-verge-service.exe");
     if target.exists() {
         std::fs::remove_file(&target).map_err(|error| {
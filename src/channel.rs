@@ -1,3 +1,6 @@
+use std::collections::HashMap;
+use std::sync::{Mutex, OnceLock};
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ChannelIdentity {
     pub id: &'static str,
@@ -8,8 +11,7 @@ pub struct ChannelIdentity {
     pub macos_service_id: &'static str,
 }
 
-#[cfg(not(feature = "development-channel"))]
-pub const CHANNEL_IDENTITY: ChannelIdentity = ChannelIdentity {
+pub const PRODUCTION_CHANNEL: ChannelIdentity = ChannelIdentity {
     id: "production",
     service_slug: "clash-verge-service",
     windows_service_name: "clash_verge_service",
@@ -18,8 +20,7 @@ pub const CHANNEL_IDENTITY: ChannelIdentity = ChannelIdentity {
     macos_service_id: "io.github.clash-verge-rev.clash-verge-rev.service",
 };
 
-#[cfg(feature = "development-channel")]
-pub const CHANNEL_IDENTITY: ChannelIdentity = ChannelIdentity {
+pub const DEVELOPMENT_CHANNEL: ChannelIdentity = ChannelIdentity {
     id: "development",
     service_slug: "clash-verge-service-dev",
     windows_service_name: "clash_verge_service_dev",
@@ -28,15 +29,78 @@ pub const CHANNEL_IDENTITY: ChannelIdentity = ChannelIdentity {
     macos_service_id: "io.github.clash-verge-rev.clash-verge-rev.dev.service",
 };
 
+/// The channel a process uses unless it is handed another one.
+#[cfg(not(feature = "development-channel"))]
+pub const CHANNEL_IDENTITY: ChannelIdentity = PRODUCTION_CHANNEL;
+
+#[cfg(feature = "development-channel")]
+pub const CHANNEL_IDENTITY: ChannelIdentity = DEVELOPMENT_CHANNEL;
+
 pub const SERVICE_SLUG: &str = CHANNEL_IDENTITY.service_slug;
 pub const WINDOWS_SERVICE_NAME: &str = CHANNEL_IDENTITY.windows_service_name;
 pub const SERVICE_DISPLAY_NAME: &str = CHANNEL_IDENTITY.service_display_name;
 pub const MACOS_APP_BUNDLE_ID: &str = CHANNEL_IDENTITY.macos_app_bundle_id;
 pub const MACOS_SERVICE_ID: &str = CHANNEL_IDENTITY.macos_service_id;
 
+const MAX_CUSTOM_CHANNEL_LEN: usize = 32;
+
+/// Custom channels looked up so far, keyed by id.
+static CUSTOM_CHANNELS: OnceLock<Mutex<HashMap<String, ChannelIdentity>>> = OnceLock::new();
+
+impl ChannelIdentity {
+    /// Looks a channel up by id. Ids other than the built-in ones name custom channels, whose
+    /// service names are derived from the id so they never collide with a built-in service.
+    pub fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "production" => Some(PRODUCTION_CHANNEL),
+            "development" => Some(DEVELOPMENT_CHANNEL),
+            custom => Self::custom(custom),
+        }
+    }
+
+    fn custom(id: &str) -> Option<Self> {
+        let valid = !id.is_empty()
+            && id.len() <= MAX_CUSTOM_CHANNEL_LEN
+            && id
+                .bytes()
+                .all(|byte| matches!(byte, b'a'..=b'z' | b'0'..=b'9' | b'-'))
+            && !id.starts_with('-')
+            && id != "dev";
+        if !valid {
+            return None;
+        }
+        let mut channels = CUSTOM_CHANNELS
+            .get_or_init(Default::default)
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        if let Some(channel) = channels.get(id) {
+            return Some(*channel);
+        }
+        // Names are leaked once per id and reused by every later lookup, so they stay bounded by
+        // the channels a process actually names.
+        let leak = |value: String| -> &'static str { Box::leak(value.into_boxed_str()) };
+        let channel = Self {
+            id: leak(id.to_owned()),
+            service_slug: leak(format!("clash-verge-service-{id}")),
+            windows_service_name: leak(format!("clash_verge_service_{}", id.replace('-', "_"))),
+            service_display_name: leak(format!("Clash Verge Service ({id})")),
+            macos_app_bundle_id: leak(format!("io.github.clash-verge-rev.clash-verge-rev.{id}")),
+            macos_service_id: leak(format!(
+                "io.github.clash-verge-rev.clash-verge-rev.{id}.service"
+            )),
+        };
+        channels.insert(id.to_owned(), channel);
+        Some(channel)
+    }
+
+    pub fn is_production(&self) -> bool {
+        self.id == PRODUCTION_CHANNEL.id
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::CHANNEL_IDENTITY;
+    use super::{CHANNEL_IDENTITY, ChannelIdentity, DEVELOPMENT_CHANNEL, PRODUCTION_CHANNEL};
 
     #[test]
     fn compiled_channel_has_a_self_consistent_identity() {
@@ -57,4 +121,35 @@ mod tests {
                 .starts_with(CHANNEL_IDENTITY.macos_app_bundle_id)
         );
     }
+
+    #[test]
+    fn registry_resolves_built_in_and_custom_channels() {
+        assert_eq!(
+            ChannelIdentity::from_id("production"),
+            Some(PRODUCTION_CHANNEL)
+        );
+        assert_eq!(
+            ChannelIdentity::from_id("development"),
+            Some(DEVELOPMENT_CHANNEL)
+        );
+
+        let qa = ChannelIdentity::from_id("qa-2").expect("custom channel");
+        assert_eq!(qa.service_slug, "clash-verge-service-qa-2");
+        assert_eq!(qa.windows_service_name, "clash_verge_service_qa_2");
+        assert!(qa.macos_service_id.starts_with(qa.macos_app_bundle_id));
+        assert!(!qa.is_production());
+
+        for invalid in ["", "-x", "QA", "qa/../x", "dev", &"x".repeat(33)] {
+            assert!(ChannelIdentity::from_id(invalid).is_none(), "{invalid:?}");
+        }
+    }
+
+    #[test]
+    fn a_custom_channel_is_built_once_per_id() {
+        let first = ChannelIdentity::from_id("qa-3").expect("custom channel");
+        let second = ChannelIdentity::from_id("qa-3").expect("custom channel");
+
+        assert_eq!(first, second);
+        assert!(std::ptr::eq(first.service_slug, second.service_slug));
+    }
 }
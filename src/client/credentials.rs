@@ -0,0 +1,124 @@
+use crate::{OwnerCredentials, OwnerIdentity};
+use anyhow::Result;
+use std::path::Path;
+
+/// Credentials of the user running this process for the app data root `app_data_dir`.
+/// On Windows the root must already hold the owner token the app wrote there; this never
+/// creates one.
+pub fn current_owner_credentials(app_data_dir: &Path) -> Result<OwnerCredentials> {
+    #[cfg(unix)]
+    let (identity, token) = (
+        OwnerIdentity::Unix {
+            uid: unsafe { platform_lib::geteuid() },
+            gid: unsafe { platform_lib::getegid() },
+        },
+        None,
+    );
+    #[cfg(windows)]
+    let (identity, token) = (
+        OwnerIdentity::Windows {
+            sid: windows::current_sid()?,
+        },
+        Some(windows::read_owner_token(app_data_dir)?),
+    );
+
+    Ok(OwnerCredentials {
+        identity,
+        app_data_dir: app_data_dir.to_string_lossy().into_owned(),
+        token,
+    })
+}
+
+#[cfg(windows)]
+mod windows {
+    use crate::OWNER_TOKEN_FILE_NAME;
+    use anyhow::{Context as _, Result};
+    use std::ffi::c_void;
+    use std::fmt::Write as _;
+    use std::path::Path;
+    use windows_sys::Win32::Foundation::{CloseHandle, LocalFree};
+    use windows_sys::Win32::Security::Authorization::ConvertSidToStringSidW;
+    use windows_sys::Win32::Security::{GetTokenInformation, TOKEN_QUERY, TOKEN_USER, TokenUser};
+    use windows_sys::Win32::System::Threading::{GetCurrentProcess, OpenProcessToken};
+
+    const TOKEN_BYTES: usize = 32;
+
+    /// Hex-encodes the raw token the app keeps in its data root, as the service expects it.
+    pub(super) fn read_owner_token(app_data_dir: &Path) -> Result<String> {
+        let path = app_data_dir.join(OWNER_TOKEN_FILE_NAME);
+        let token =
+            std::fs::read(&path).with_context(|| format!("failed to read owner token {path:?}"))?;
+        anyhow::ensure!(
+            token.len() == TOKEN_BYTES,
+            "owner token {path:?} must hold {TOKEN_BYTES} bytes"
+        );
+        let mut encoded = String::with_capacity(TOKEN_BYTES * 2);
+        for byte in token {
+            let _ = write!(encoded, "{byte:02x}");
+        }
+        Ok(encoded)
+    }
+
+    pub(super) fn current_sid() -> Result<String> {
+        let mut token = std::ptr::null_mut();
+        if unsafe { OpenProcessToken(GetCurrentProcess(), TOKEN_QUERY, &mut token) } == 0 {
+            return Err(std::io::Error::last_os_error()).context("failed to open process token");
+        }
+        let token = OwnedHandle(token);
+
+        let mut required = 0_u32;
+        unsafe { GetTokenInformation(token.0, TokenUser, std::ptr::null_mut(), 0, &mut required) };
+        if required == 0 {
+            return Err(std::io::Error::last_os_error())
+                .context("failed to size process SID buffer");
+        }
+        let words = (required as usize).div_ceil(std::mem::size_of::<usize>());
+        let mut buffer = vec![0_usize; words];
+        if unsafe {
+            GetTokenInformation(
+                token.0,
+                TokenUser,
+                buffer.as_mut_ptr().cast(),
+                required,
+                &mut required,
+            )
+        } == 0
+        {
+            return Err(std::io::Error::last_os_error()).context("failed to read process SID");
+        }
+        let token_user = unsafe { &*buffer.as_ptr().cast::<TOKEN_USER>() };
+
+        let mut value = std::ptr::null_mut();
+        if unsafe { ConvertSidToStringSidW(token_user.User.Sid, &mut value) } == 0
+            || value.is_null()
+        {
+            return Err(std::io::Error::last_os_error()).context("failed to format process SID");
+        }
+        let value = LocalWideString(value);
+        let length = (0..)
+            .take_while(|index| unsafe { *value.0.add(*index) } != 0)
+            .count();
+        String::from_utf16(unsafe { std::slice::from_raw_parts(value.0, length) })
+            .context("process SID is not valid UTF-16")
+    }
+
+    struct OwnedHandle(*mut c_void);
+
+    impl Drop for OwnedHandle {
+        fn drop(&mut self) {
+            if !self.0.is_null() {
+                unsafe { CloseHandle(self.0) };
+            }
+        }
+    }
+
+    struct LocalWideString(*mut u16);
+
+    impl Drop for LocalWideString {
+        fn drop(&mut self) {
+            if !self.0.is_null() {
+                unsafe { LocalFree(self.0.cast()) };
+            }
+        }
+    }
+}
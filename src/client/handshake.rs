@@ -0,0 +1,183 @@
+//! Client half of the connection handshake: the service must sign a fresh nonce with the key it
+//! published before this client sends it any credentials.
+
+use crate::core::structure::{JsonConvert as _, Response, decode_hex, handshake_transcript};
+use crate::{
+    HandshakeProof, HandshakeRequest, IPC_AUTH_EXPECT, IpcCommand, ProtocolVersion, ServicePaths,
+};
+use anyhow::{Context as _, Result, anyhow, ensure};
+use ed25519_dalek::{Signature, VerifyingKey};
+use kode_bridge::IpcHttpClient;
+use log::debug;
+use std::io::Read as _;
+use std::path::Path;
+
+use super::IPC_AUTH_HEADER_KEY;
+
+/// Checks that the service installed at `paths` answers on `client`. Services that predate the
+/// handshake publish no key and get the legacy magic probe instead, returning `None`. Once a key
+/// is published nothing else is accepted, so an impostor cannot downgrade the check.
+pub(super) async fn verify_service(
+    client: &IpcHttpClient,
+    paths: &ServicePaths,
+) -> Result<Option<HandshakeProof>> {
+    let Some(public_key) = load_published_key(&paths.handshake_public_key_path())? else {
+        debug!("Service publishes no handshake key; probing it the legacy way");
+        client
+            .get(IpcCommand::Magic.as_ref())
+            .header(IPC_AUTH_HEADER_KEY, IPC_AUTH_EXPECT)
+            .send()
+            .await?;
+        return Ok(None);
+    };
+
+    let nonce = new_nonce()?;
+    let request = HandshakeRequest {
+        nonce: nonce.clone(),
+        protocol: ProtocolVersion::current(),
+    };
+    let response = client
+        .get(IpcCommand::Handshake.as_ref())
+        .json_body(&request.to_json_value()?)
+        .send()
+        .await?
+        .json::<Response<HandshakeProof>>()
+        .context("service does not answer the handshake")?;
+    let proof = response
+        .data
+        .ok_or_else(|| anyhow!("service refused the handshake: {}", response.message))?;
+    verify_proof(&public_key, paths.channel().service_slug, &nonce, &proof)?;
+    Ok(Some(proof))
+}
+
+fn verify_proof(
+    public_key: &VerifyingKey,
+    service_slug: &str,
+    nonce: &str,
+    proof: &HandshakeProof,
+) -> Result<()> {
+    let signature = decode_hex(&proof.signature)
+        .map(|bytes| Signature::from_bytes(&bytes))
+        .context("handshake signature is malformed")?;
+    let transcript = handshake_transcript(service_slug, nonce, &proof.info, proof.negotiated);
+    public_key
+        .verify_strict(&transcript, &signature)
+        .map_err(|_| anyhow!("service failed to prove it is the installed service"))?;
+    ensure!(
+        proof.info.negotiate(ProtocolVersion::current()) == Some(proof.negotiated),
+        "service negotiated protocol {} inconsistently",
+        proof.negotiated.header_value()
+    );
+    Ok(())
+}
+
+/// Reads the key the service published. On Unix it must belong to root or to the user running
+/// this client, the only accounts able to serve the socket, and nobody else may write it. On
+/// Windows it sits in a directory only SYSTEM and administrators can write.
+fn load_published_key(path: &Path) -> Result<Option<VerifyingKey>> {
+    let mut file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to open handshake key {path:?}"));
+        }
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt as _;
+
+        let metadata = file.metadata()?;
+        let euid = unsafe { platform_lib::geteuid() };
+        ensure!(
+            metadata.is_file()
+                && (metadata.uid() == 0 || metadata.uid() == euid)
+                && metadata.mode() & 0o022 == 0,
+            "handshake key {path:?} has an untrusted owner or mode"
+        );
+    }
+    let mut content = String::new();
+    file.read_to_string(&mut content)
+        .with_context(|| format!("failed to read handshake key {path:?}"))?;
+    let public_key = decode_hex(content.trim())
+        .and_then(|bytes| VerifyingKey::from_bytes(&bytes).ok())
+        .with_context(|| format!("handshake key {path:?} is malformed"))?;
+    Ok(Some(public_key))
+}
+
+fn new_nonce() -> Result<String> {
+    let mut nonce = [0_u8; crate::HANDSHAKE_NONCE_HEX_LEN / 2];
+    getrandom::fill(&mut nonce).map_err(|error| anyhow!("failed to generate a nonce: {error}"))?;
+    Ok(nonce.iter().map(|byte| format!("{byte:02x}")).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{load_published_key, new_nonce, verify_proof};
+    use crate::core::structure::handshake_transcript;
+    use crate::{HandshakeProof, ProtocolInfo, ProtocolVersion};
+    use ed25519_dalek::{Signer as _, SigningKey};
+
+    fn signed_proof(key: &SigningKey, nonce: &str, negotiated: ProtocolVersion) -> HandshakeProof {
+        let info = ProtocolInfo::current();
+        let signature = key.sign(&handshake_transcript("service", nonce, &info, negotiated));
+        HandshakeProof {
+            info,
+            negotiated,
+            signature: signature
+                .to_bytes()
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn only_the_published_key_signing_this_nonce_is_accepted() -> anyhow::Result<()> {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let nonce = new_nonce()?;
+        let current = ProtocolVersion::current();
+        let proof = signed_proof(&key, &nonce, current);
+
+        assert!(verify_proof(&key.verifying_key(), "service", &nonce, &proof).is_ok());
+        assert!(verify_proof(&key.verifying_key(), "service", &new_nonce()?, &proof).is_err());
+        let impostor = SigningKey::from_bytes(&[8; 32]);
+        assert!(verify_proof(&impostor.verifying_key(), "service", &nonce, &proof).is_err());
+
+        let mut downgraded = proof.clone();
+        downgraded.negotiated.revision -= 1;
+        assert!(verify_proof(&key.verifying_key(), "service", &nonce, &downgraded).is_err());
+        let lowered = ProtocolVersion {
+            revision: current.revision - 1,
+            ..current
+        };
+        let inconsistent = signed_proof(&key, &nonce, lowered);
+        assert!(verify_proof(&key.verifying_key(), "service", &nonce, &inconsistent).is_err());
+        Ok(())
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn a_key_others_can_write_is_not_trusted() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let dir = std::env::temp_dir().join(format!("client-handshake-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        let path = dir.join("handshake.pub");
+        assert!(load_published_key(&path)?.is_none());
+
+        let key = SigningKey::from_bytes(&[7; 32]).verifying_key();
+        let encoded: String = key
+            .as_bytes()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect();
+        std::fs::write(&path, format!("{encoded}\n"))?;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644))?;
+        assert_eq!(load_published_key(&path)?, Some(key));
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o666))?;
+        assert!(load_published_key(&path).is_err());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
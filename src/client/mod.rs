@@ -10,15 +10,23 @@ use log::{debug, warn};
 use once_cell::sync::Lazy;
 use tokio::sync::RwLock;
 
+mod credentials;
+mod handshake;
 #[cfg(all(windows, any(not(feature = "test"), test)))]
 mod windows_identity;
 
+pub use credentials::current_owner_credentials;
+
 use crate::{
-    AuthenticatedRequest, AuthenticatedSessionRequest, IPC_AUTH_EXPECT, IPC_PATH, IpcCommand,
-    MIN_REQUIRED_SERVICE_REVISION, MacosProxyConfig, OwnerCredentials, OwnerSessionProof,
-    ProtocolInfo, ProtocolVersion, ProxyApplyOutcome, RuntimeBundle, ServiceStatusSnapshot,
-    StageRuntimeOutcome, StartClashRequest, StartClashResult, WriterConfig,
+    AuditQuery, AuditRecord, AuthenticatedRequest, AuthenticatedSessionRequest, CrashReport,
+    CrashReportInfo, EvictOwnerRequest, EvictOwnerResult, IPC_AUTH_EXPECT, IpcCommand,
+    MIN_REQUIRED_SERVICE_REVISION, MacosProxyConfig, MintScopedSessionRequest,
+    ObserverStatusSnapshot, OpenSessionRequest, OwnerCredentials, OwnerSessionHandle,
+    OwnerSessionProof, ProtocolInfo, ProtocolVersion, ProxyApplyOutcome, RuntimeBundle,
+    ServicePaths, ServiceStatusSnapshot, SessionLeaseStatus, StageRuntimeOutcome,
+    StartClashRequest, StartClashResult, WriterConfig,
     core::structure::{JsonConvert, Response},
+    service_paths,
 };
 
 static CLIENT_CONFIG: Lazy<Arc<RwLock<Option<IpcConfig>>>> =
@@ -47,6 +55,7 @@ enum Verb {
 /// Sends a versioned, authenticated request using the route's required envelope.
 /// `session` selects owner-only or active-session authentication.
 async fn protected_call<P, R>(
+    paths: &ServicePaths,
     verb: Verb,
     command: IpcCommand,
     credentials: &OwnerCredentials,
@@ -58,7 +67,7 @@ where
     P: serde::Serialize + for<'de> serde::Deserialize<'de>,
     R: for<'de> serde::Deserialize<'de>,
 {
-    let client = connect().await?;
+    let client = connect_at(paths).await?;
     let body = match session {
         None => AuthenticatedRequest {
             credentials: credentials.clone(),
@@ -113,20 +122,31 @@ pub async fn set_config(config: Option<IpcConfig>) {
     *guard = config;
 }
 
+/// Connects to the selected service once it has proven it is that service. The calls below
+/// use the selected paths too; their `_at` variants reach the service installed at `paths`.
 pub async fn connect() -> Result<IpcHttpClient> {
-    debug!("Connecting to IPC at {}", IPC_PATH);
+    connect_at(&service_paths()).await
+}
+
+/// Connects to the service installed at `paths` once it has proven it is that service.
+pub async fn connect_at(paths: &ServicePaths) -> Result<IpcHttpClient> {
+    let ipc_path = paths.ipc_path();
+    debug!("Connecting to IPC at {}", ipc_path.display());
 
     #[cfg(unix)]
     {
-        if let Err(err) = Path::metadata(IPC_PATH.as_ref()) {
+        if let Err(err) = Path::metadata(ipc_path) {
             return Err(anyhow!("IPC path unavailable: {err}"));
         }
     }
 
+    #[cfg(all(windows, not(feature = "test")))]
+    windows_identity::expect_service(paths.channel().windows_service_name);
+
     let c = { CLIENT_CONFIG.read().await.clone() }.unwrap_or_default();
     debug!("Using config: {:?}", c);
     let client = kode_bridge::IpcHttpClient::with_config(
-        IPC_PATH,
+        ipc_path,
         ClientConfig {
             default_timeout: c.default_timeout,
             max_retries: c.max_retries,
@@ -143,25 +163,30 @@ pub async fn connect() -> Result<IpcHttpClient> {
         },
     )?;
 
-    if let Err(e) = client
-        .get(IpcCommand::Magic.as_ref())
-        .header(IPC_AUTH_HEADER_KEY, IPC_AUTH_EXPECT)
-        .send()
-        .await
-    {
-        warn!("Failed to connect to IPC server: {}", e);
-        return Err(anyhow::anyhow!("Failed to connect to IPC server: {}", e));
+    if let Err(e) = handshake::verify_service(&client, paths).await {
+        warn!("Failed to connect to IPC server: {:#}", e);
+        return Err(anyhow::anyhow!("Failed to connect to IPC server: {:#}", e));
     }
 
     Ok(client)
 }
 
 pub fn is_ipc_path_exists() -> bool {
-    Path::new(IPC_PATH).exists()
+    is_ipc_path_exists_at(&service_paths())
+}
+
+/// Like [`is_ipc_path_exists`], against the service installed at `paths`.
+pub fn is_ipc_path_exists_at(paths: &ServicePaths) -> bool {
+    paths.ipc_path().exists()
 }
 
 pub async fn get_version() -> Result<Response<ProtocolInfo>> {
-    let client = connect().await?;
+    get_version_at(&service_paths()).await
+}
+
+/// Like [`get_version`], against the service installed at `paths`.
+pub async fn get_version_at(paths: &ServicePaths) -> Result<Response<ProtocolInfo>> {
+    let client = connect_at(paths).await?;
     let response = client
         .get(IpcCommand::GetVersion.as_ref())
         .header(IPC_AUTH_HEADER_KEY, IPC_AUTH_EXPECT)
@@ -172,12 +197,59 @@ pub async fn get_version() -> Result<Response<ProtocolInfo>> {
 }
 
 pub async fn get_status(credentials: &OwnerCredentials) -> Result<Response<ServiceStatusSnapshot>> {
-    protected_call(Verb::Get, IpcCommand::Status, credentials, None, (), None).await
+    get_status_at(&service_paths(), credentials).await
+}
+
+/// Like [`get_status`], against the service installed at `paths`.
+pub async fn get_status_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+) -> Result<Response<ServiceStatusSnapshot>> {
+    protected_call(
+        paths,
+        Verb::Get,
+        IpcCommand::Status,
+        credentials,
+        None,
+        (),
+        None,
+    )
+    .await
+}
+
+/// Reads the redacted status as an observer; the caller need not own anything, but the service's
+/// observer policy must admit it.
+pub async fn observe_status(
+    credentials: &OwnerCredentials,
+) -> Result<Response<ObserverStatusSnapshot>> {
+    observe_status_at(&service_paths(), credentials).await
+}
+
+/// Like [`observe_status`], against the service installed at `paths`.
+pub async fn observe_status_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+) -> Result<Response<ObserverStatusSnapshot>> {
+    protected_call(
+        paths,
+        Verb::Get,
+        IpcCommand::ObserveStatus,
+        credentials,
+        None,
+        (),
+        None,
+    )
+    .await
 }
 
 pub async fn is_reinstall_service_needed() -> bool {
-    is_ipc_path_exists()
-        && match get_version().await {
+    is_reinstall_service_needed_at(&service_paths()).await
+}
+
+/// Like [`is_reinstall_service_needed`], against the service installed at `paths`.
+pub async fn is_reinstall_service_needed_at(paths: &ServicePaths) -> bool {
+    is_ipc_path_exists_at(paths)
+        && match get_version_at(paths).await {
             Ok(resp) => resp.data.is_none_or(|info| {
                 !info.supports_client(ProtocolVersion::current(), MIN_REQUIRED_SERVICE_REVISION)
             }),
@@ -188,8 +260,18 @@ pub async fn is_reinstall_service_needed() -> bool {
 pub async fn start_clash(
     credentials: &OwnerCredentials,
     body: &StartClashRequest,
+) -> Result<Response<StartClashResult>> {
+    start_clash_at(&service_paths(), credentials, body).await
+}
+
+/// Like [`start_clash`], against the service installed at `paths`.
+pub async fn start_clash_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    body: &StartClashRequest,
 ) -> Result<Response<StartClashResult>> {
     protected_call(
+        paths,
         Verb::Post,
         IpcCommand::StartClash,
         credentials,
@@ -202,8 +284,17 @@ pub async fn start_clash(
 
 pub async fn get_clash_logs(
     credentials: &OwnerCredentials,
+) -> Result<Response<Vec<CompactString>>> {
+    get_clash_logs_at(&service_paths(), credentials).await
+}
+
+/// Like [`get_clash_logs`], against the service installed at `paths`.
+pub async fn get_clash_logs_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
 ) -> Result<Response<Vec<CompactString>>> {
     protected_call(
+        paths,
         Verb::Get,
         IpcCommand::GetClashLogs,
         credentials,
@@ -215,7 +306,16 @@ pub async fn get_clash_logs(
 }
 
 pub async fn get_clash_log_snapshot(credentials: &OwnerCredentials) -> Result<Response<String>> {
+    get_clash_log_snapshot_at(&service_paths(), credentials).await
+}
+
+/// Like [`get_clash_log_snapshot`], against the service installed at `paths`.
+pub async fn get_clash_log_snapshot_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+) -> Result<Response<String>> {
     protected_call(
+        paths,
         Verb::Get,
         IpcCommand::GetClashLogSnapshot,
         credentials,
@@ -226,11 +326,107 @@ pub async fn get_clash_log_snapshot(credentials: &OwnerCredentials) -> Result<Re
     .await
 }
 
+/// Lists the caller's core crash reports, newest first.
+/// Call only when [`ProtocolInfo::supports`] accepts [`crate::ServiceCapability::CrashReports`].
+pub async fn list_crash_reports(
+    credentials: &OwnerCredentials,
+) -> Result<Response<Vec<CrashReportInfo>>> {
+    list_crash_reports_at(&service_paths(), credentials).await
+}
+
+/// Like [`list_crash_reports`], against the service installed at `paths`.
+pub async fn list_crash_reports_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+) -> Result<Response<Vec<CrashReportInfo>>> {
+    protected_call(
+        paths,
+        Verb::Get,
+        IpcCommand::ListCrashReports,
+        credentials,
+        None,
+        (),
+        None,
+    )
+    .await
+}
+
+/// Reads one crash report named by [`list_crash_reports`].
+/// Call only when [`ProtocolInfo::supports`] accepts [`crate::ServiceCapability::CrashReports`].
+pub async fn get_crash_report(
+    credentials: &OwnerCredentials,
+    name: &str,
+) -> Result<Response<CrashReport>> {
+    get_crash_report_at(&service_paths(), credentials, name).await
+}
+
+/// Like [`get_crash_report`], against the service installed at `paths`.
+pub async fn get_crash_report_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    name: &str,
+) -> Result<Response<CrashReport>> {
+    protected_call(
+        paths,
+        Verb::Get,
+        IpcCommand::GetCrashReport,
+        credentials,
+        None,
+        name.to_owned(),
+        None,
+    )
+    .await
+}
+
+/// Returns the service's counters in the Prometheus text exposition format, exactly as a scraper
+/// receives them. Call only when [`ProtocolInfo::supports`] accepts
+/// [`crate::ServiceCapability::Metrics`].
+pub async fn get_metrics(credentials: &OwnerCredentials) -> Result<String> {
+    get_metrics_at(&service_paths(), credentials).await
+}
+
+/// Like [`get_metrics`], against the service installed at `paths`.
+pub async fn get_metrics_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+) -> Result<String> {
+    let client = connect_at(paths).await?;
+    let body = AuthenticatedRequest {
+        credentials: credentials.clone(),
+        payload: (),
+    }
+    .to_json_value()?;
+    let response = protected(client.get(IpcCommand::Metrics.as_ref()))
+        .json_body(&body)
+        .send()
+        .await?;
+    if response.is_success() {
+        return Ok(response.text()?);
+    }
+    // Refusals still arrive as the usual envelope.
+    let refusal = response.json::<Response<()>>()?;
+    Err(anyhow::anyhow!(
+        "service refused to render metrics ({}): {}",
+        refusal.code,
+        refusal.message
+    ))
+}
+
 pub async fn stop_clash(
     credentials: &OwnerCredentials,
     session: &OwnerSessionProof,
+) -> Result<Response<()>> {
+    stop_clash_at(&service_paths(), credentials, session).await
+}
+
+/// Like [`stop_clash`], against the service installed at `paths`.
+pub async fn stop_clash_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
 ) -> Result<Response<()>> {
     protected_call(
+        paths,
         Verb::Delete,
         IpcCommand::StopClash,
         credentials,
@@ -241,6 +437,61 @@ pub async fn stop_clash(
     .await
 }
 
+/// Evicts another owner as root or a member of the service's admin policy. Call only when
+/// [`ProtocolInfo::supports`] accepts [`crate::ServiceCapability::OwnerEviction`]. The policy
+/// names Unix groups, so Windows callers, administrators included, are always refused.
+pub async fn evict_owner(
+    credentials: &OwnerCredentials,
+    body: &EvictOwnerRequest,
+) -> Result<Response<EvictOwnerResult>> {
+    evict_owner_at(&service_paths(), credentials, body).await
+}
+
+/// Like [`evict_owner`], against the service installed at `paths`.
+pub async fn evict_owner_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    body: &EvictOwnerRequest,
+) -> Result<Response<EvictOwnerResult>> {
+    protected_call(
+        paths,
+        Verb::Delete,
+        IpcCommand::EvictOwner,
+        credentials,
+        None,
+        body.clone(),
+        Some(LIFECYCLE_TIMEOUT),
+    )
+    .await
+}
+
+/// Reads the newest audit records matching `query`, oldest first, as root or an admin.
+/// Call only when [`ProtocolInfo::supports`] accepts [`crate::ServiceCapability::AuditLog`].
+pub async fn query_audit_log(
+    credentials: &OwnerCredentials,
+    query: &AuditQuery,
+) -> Result<Response<Vec<AuditRecord>>> {
+    query_audit_log_at(&service_paths(), credentials, query).await
+}
+
+/// Like [`query_audit_log`], against the service installed at `paths`.
+pub async fn query_audit_log_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    query: &AuditQuery,
+) -> Result<Response<Vec<AuditRecord>>> {
+    protected_call(
+        paths,
+        Verb::Get,
+        IpcCommand::QueryAuditLog,
+        credentials,
+        None,
+        query.clone(),
+        None,
+    )
+    .await
+}
+
 /// Stages `body` into the running core's generation without restarting it.
 /// Call only when [`ProtocolInfo::supports_runtime_staging`] is true; `RestartRequired` is a
 /// successful response that asks the caller to fall back to stop and start.
@@ -248,8 +499,19 @@ pub async fn stage_runtime(
     credentials: &OwnerCredentials,
     session: &OwnerSessionProof,
     body: &RuntimeBundle,
+) -> Result<Response<StageRuntimeOutcome>> {
+    stage_runtime_at(&service_paths(), credentials, session, body).await
+}
+
+/// Like [`stage_runtime`], against the service installed at `paths`.
+pub async fn stage_runtime_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+    body: &RuntimeBundle,
 ) -> Result<Response<StageRuntimeOutcome>> {
     protected_call(
+        paths,
         Verb::Put,
         IpcCommand::StageRuntime,
         credentials,
@@ -264,8 +526,19 @@ pub async fn update_writer(
     credentials: &OwnerCredentials,
     session: &OwnerSessionProof,
     body: &WriterConfig,
+) -> Result<Response<()>> {
+    update_writer_at(&service_paths(), credentials, session, body).await
+}
+
+/// Like [`update_writer`], against the service installed at `paths`.
+pub async fn update_writer_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+    body: &WriterConfig,
 ) -> Result<Response<()>> {
     protected_call(
+        paths,
         Verb::Put,
         IpcCommand::UpdateWriter,
         credentials,
@@ -280,8 +553,19 @@ pub async fn set_system_proxy(
     credentials: &OwnerCredentials,
     session: &OwnerSessionProof,
     body: &MacosProxyConfig,
+) -> Result<Response<ProxyApplyOutcome>> {
+    set_system_proxy_at(&service_paths(), credentials, session, body).await
+}
+
+/// Like [`set_system_proxy`], against the service installed at `paths`.
+pub async fn set_system_proxy_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+    body: &MacosProxyConfig,
 ) -> Result<Response<ProxyApplyOutcome>> {
     protected_call(
+        paths,
         Verb::Put,
         IpcCommand::SetSystemProxy,
         credentials,
@@ -291,3 +575,200 @@ pub async fn set_system_proxy(
     )
     .await
 }
+
+/// Opens another named session for the active owner through the full `session`, without
+/// disturbing its other sessions. Call only when [`ProtocolInfo::supports`] accepts
+/// [`crate::ServiceCapability::NamedSessions`].
+///
+/// A helper that holds no session yet gets its first one from the process that started the
+/// core: that process opens a session under the helper's name and hands the proposed token and
+/// the returned generation to the helper. Calling [`start_clash`] instead would replace every
+/// session of the owner. The primary session cannot be reopened here.
+pub async fn open_session(
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+    body: &OpenSessionRequest,
+) -> Result<Response<OwnerSessionHandle>> {
+    open_session_at(&service_paths(), credentials, session, body).await
+}
+
+/// Like [`open_session`], against the service installed at `paths`.
+pub async fn open_session_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+    body: &OpenSessionRequest,
+) -> Result<Response<OwnerSessionHandle>> {
+    protected_call(
+        paths,
+        Verb::Post,
+        IpcCommand::OpenSession,
+        credentials,
+        Some(session),
+        body.clone(),
+        None,
+    )
+    .await
+}
+
+/// Revokes the active owner's session called `name`; its other sessions stay valid. The primary
+/// session is refused, since owner credentials do not prove it; it ends with [`stop_clash`].
+pub async fn revoke_session(credentials: &OwnerCredentials, name: &str) -> Result<Response<()>> {
+    revoke_session_at(&service_paths(), credentials, name).await
+}
+
+/// Like [`revoke_session`], against the service installed at `paths`.
+pub async fn revoke_session_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    name: &str,
+) -> Result<Response<()>> {
+    protected_call(
+        paths,
+        Verb::Delete,
+        IpcCommand::RevokeSession,
+        credentials,
+        None,
+        name.to_owned(),
+        None,
+    )
+    .await
+}
+
+/// Mints a session limited to `body.scopes` through the full `session`, for handing to a tool
+/// that needs only those rights. Call only when [`ProtocolInfo::supports`] accepts
+/// [`crate::ServiceCapability::ScopedSessions`], like the other scoped-session calls below.
+pub async fn mint_scoped_session(
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+    body: &MintScopedSessionRequest,
+) -> Result<Response<OwnerSessionHandle>> {
+    mint_scoped_session_at(&service_paths(), credentials, session, body).await
+}
+
+/// Like [`mint_scoped_session`], against the service installed at `paths`.
+pub async fn mint_scoped_session_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+    body: &MintScopedSessionRequest,
+) -> Result<Response<OwnerSessionHandle>> {
+    protected_call(
+        paths,
+        Verb::Post,
+        IpcCommand::MintScopedSession,
+        credentials,
+        Some(session),
+        body.clone(),
+        None,
+    )
+    .await
+}
+
+/// Revokes the scoped session called `name` through the full `session`.
+pub async fn revoke_scoped_session(
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+    name: &str,
+) -> Result<Response<()>> {
+    revoke_scoped_session_at(&service_paths(), credentials, session, name).await
+}
+
+/// Like [`revoke_scoped_session`], against the service installed at `paths`.
+pub async fn revoke_scoped_session_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+    name: &str,
+) -> Result<Response<()>> {
+    protected_call(
+        paths,
+        Verb::Delete,
+        IpcCommand::RevokeScopedSession,
+        credentials,
+        Some(session),
+        name.to_owned(),
+        None,
+    )
+    .await
+}
+
+/// Reads status as `session`, which must hold the `read_status` scope.
+pub async fn get_session_status(
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+) -> Result<Response<ServiceStatusSnapshot>> {
+    get_session_status_at(&service_paths(), credentials, session).await
+}
+
+/// Like [`get_session_status`], against the service installed at `paths`.
+pub async fn get_session_status_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+) -> Result<Response<ServiceStatusSnapshot>> {
+    protected_call(
+        paths,
+        Verb::Get,
+        IpcCommand::Status,
+        credentials,
+        Some(session),
+        (),
+        None,
+    )
+    .await
+}
+
+/// Reads the core logs as `session`, which must hold the `read_logs` scope.
+pub async fn get_session_clash_logs(
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+) -> Result<Response<Vec<CompactString>>> {
+    get_session_clash_logs_at(&service_paths(), credentials, session).await
+}
+
+/// Like [`get_session_clash_logs`], against the service installed at `paths`.
+pub async fn get_session_clash_logs_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+) -> Result<Response<Vec<CompactString>>> {
+    protected_call(
+        paths,
+        Verb::Get,
+        IpcCommand::GetClashLogs,
+        credentials,
+        Some(session),
+        (),
+        None,
+    )
+    .await
+}
+
+/// Renews the lease `StartClash` opened; any full session of the owner may renew it. Data is
+/// `None` when the owner started without a lease.
+/// Call only when [`ProtocolInfo::supports`] accepts [`crate::ServiceCapability::SessionLeases`].
+pub async fn renew_session_lease(
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+) -> Result<Response<Option<SessionLeaseStatus>>> {
+    renew_session_lease_at(&service_paths(), credentials, session).await
+}
+
+/// Like [`renew_session_lease`], against the service installed at `paths`.
+pub async fn renew_session_lease_at(
+    paths: &ServicePaths,
+    credentials: &OwnerCredentials,
+    session: &OwnerSessionProof,
+) -> Result<Response<Option<SessionLeaseStatus>>> {
+    protected_call(
+        paths,
+        Verb::Put,
+        IpcCommand::RenewSessionLease,
+        credentials,
+        Some(session),
+        (),
+        None,
+    )
+    .await
+}
@@ -1,12 +1,19 @@
 #[cfg(not(feature = "test"))]
-use anyhow::{Context as _, Result, bail};
+use anyhow::{Context as _, Result, anyhow, bail};
 use std::ffi::OsStr;
+#[cfg(not(feature = "test"))]
+use std::sync::Mutex;
 
 #[cfg(not(feature = "test"))]
 use platform_lib::service::ServiceAccess;
 #[cfg(not(feature = "test"))]
 use platform_lib::service_manager::{ServiceManager, ServiceManagerAccess};
 
+/// Services this process has connected to. The pipe verifier gets no context, so it accepts the
+/// process of any of them; the handshake then proves which channel's service answered.
+#[cfg(not(feature = "test"))]
+static EXPECTED_SERVICES: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
 fn is_local_system_account(account: &OsStr) -> bool {
     matches!(
         account.to_string_lossy().to_ascii_lowercase().as_str(),
@@ -24,9 +31,35 @@ fn trusted_service_identity(
         && account.is_some_and(is_local_system_account)
 }
 
+/// Lets the verifier accept pipes served by `service_name`.
+#[cfg(not(feature = "test"))]
+pub(super) fn expect_service(service_name: &'static str) {
+    let mut services = EXPECTED_SERVICES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if !services.contains(&service_name) {
+        services.push(service_name);
+    }
+}
+
 #[cfg(not(feature = "test"))]
 fn verify_registered_service_process_id_inner(pipe_process_id: u32) -> Result<()> {
-    let service_name = crate::WINDOWS_SERVICE_NAME;
+    let services = EXPECTED_SERVICES
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    let mut last_error = None;
+    for service_name in services {
+        match verify_service_process_id(service_name, pipe_process_id) {
+            Ok(()) => return Ok(()),
+            Err(error) => last_error = Some(error),
+        }
+    }
+    Err(last_error.unwrap_or_else(|| anyhow!("no Windows service is expected to serve this pipe")))
+}
+
+#[cfg(not(feature = "test"))]
+fn verify_service_process_id(service_name: &str, pipe_process_id: u32) -> Result<()> {
     let manager = ServiceManager::local_computer(None::<&str>, ServiceManagerAccess::CONNECT)
         .context("failed to connect to the Windows Service Control Manager")?;
     let service = manager
@@ -0,0 +1,166 @@
+//! Who besides the active owner may use the service. Observers read a redacted status; admins may
+//! also evict owners. Both roles are granted by policy files in the root-only persistent state
+//! directory that name Unix groups, and a caller is admitted through its kernel-verified primary
+//! group or a supplementary membership. Root is always an admin. Without a policy file a role has
+//! no members. Windows callers never hold either role, members of Administrators included: the
+//! policies name Unix groups and the pipe transport reports no token to check membership against.
+//! Group lookups go through NSS, which may ask a directory service, so they run on the blocking
+//! pool rather than on the runtime serving other clients.
+
+use crate::OwnerIdentity;
+use crate::core::auth::{AuthenticatedOwner, ServiceError};
+use crate::core::paths::service_paths;
+use crate::core::schema::{self, DocumentSchema};
+use anyhow::{Context as _, Result};
+use serde::Deserialize;
+use std::path::Path;
+use tracing::warn;
+
+#[derive(Debug, Default, Deserialize)]
+pub(super) struct GroupPolicy {
+    /// Names of the Unix groups whose members hold the role.
+    groups: Vec<String>,
+}
+
+impl GroupPolicy {
+    fn admits(&self, identity: &OwnerIdentity) -> bool {
+        #[cfg(unix)]
+        if let OwnerIdentity::Unix { uid, gid } = identity {
+            use nix::unistd::{Group, Uid, User};
+
+            let user_name = User::from_uid(Uid::from_raw(*uid))
+                .ok()
+                .flatten()
+                .map(|user| user.name);
+            return self.groups.iter().any(|name| match Group::from_name(name) {
+                Ok(Some(group)) => {
+                    group.gid.as_raw() == *gid
+                        || user_name
+                            .as_ref()
+                            .is_some_and(|user_name| group.mem.contains(user_name))
+                }
+                _ => false,
+            });
+        }
+        let _ = identity;
+        false
+    }
+}
+
+async fn load_group_policy(schema: &DocumentSchema, path: &Path) -> Result<GroupPolicy> {
+    match tokio::fs::read(path).await {
+        Ok(content) => schema::decode(schema, &content)
+            .map(|decoded| decoded.value)
+            .with_context(|| format!("failed to load {path:?}")),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(GroupPolicy::default()),
+        Err(error) => Err(error).with_context(|| format!("failed to read {path:?}")),
+    }
+}
+
+/// Whether the policy at `path` admits `caller`. A policy that cannot be read admits nobody.
+async fn policy_admits(schema: &DocumentSchema, path: &Path, caller: &AuthenticatedOwner) -> bool {
+    let policy = match load_group_policy(schema, path).await {
+        Ok(policy) => policy,
+        Err(error) => {
+            warn!("Ignoring the {}: {error:#}", schema.name());
+            return false;
+        }
+    };
+    let identity = caller.identity.clone();
+    match tokio::task::spawn_blocking(move || policy.admits(&identity)).await {
+        Ok(admitted) => admitted,
+        Err(error) => {
+            warn!("Failed to check the {}: {error}", schema.name());
+            false
+        }
+    }
+}
+
+fn is_root(identity: &OwnerIdentity) -> bool {
+    matches!(identity, OwnerIdentity::Unix { uid: 0, .. })
+}
+
+async fn is_admin(caller: &AuthenticatedOwner) -> bool {
+    is_root(&caller.identity)
+        || policy_admits(
+            &schema::ADMIN_POLICY,
+            &service_paths().admin_policy_path(),
+            caller,
+        )
+        .await
+}
+
+/// Admits members of the observer policy, and admins, who may see at least as much.
+pub(super) async fn authorize_observer(caller: &AuthenticatedOwner) -> Result<(), ServiceError> {
+    let observer = policy_admits(
+        &schema::OBSERVER_POLICY,
+        &service_paths().observer_policy_path(),
+        caller,
+    )
+    .await;
+    if observer || is_admin(caller).await {
+        Ok(())
+    } else {
+        Err(ServiceError::observer_denied())
+    }
+}
+
+pub(super) async fn authorize_admin(caller: &AuthenticatedOwner) -> Result<(), ServiceError> {
+    if is_admin(caller).await {
+        Ok(())
+    } else {
+        Err(ServiceError::admin_denied())
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::{GroupPolicy, is_root};
+    use crate::OwnerIdentity;
+    use crate::core::schema::{self, ADMIN_POLICY, OBSERVER_POLICY};
+    use nix::unistd::{Gid, Group};
+
+    fn policy(groups: &[&str]) -> GroupPolicy {
+        GroupPolicy {
+            groups: groups.iter().map(|group| (*group).to_owned()).collect(),
+        }
+    }
+
+    #[test]
+    fn members_of_a_listed_group_are_admitted() -> anyhow::Result<()> {
+        let gid = unsafe { platform_lib::getegid() };
+        let Some(group) = Group::from_gid(Gid::from_raw(gid))? else {
+            return Ok(());
+        };
+        let caller = OwnerIdentity::Unix {
+            uid: unsafe { platform_lib::geteuid() },
+            gid,
+        };
+
+        assert!(policy(&[group.name.as_str()]).admits(&caller));
+        assert!(!policy(&["clash-verge-no-such-group"]).admits(&caller));
+        assert!(!GroupPolicy::default().admits(&caller));
+        assert!(
+            !policy(&[group.name.as_str()]).admits(&OwnerIdentity::Windows {
+                sid: "S-1-5-21-1".to_owned(),
+            })
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn policies_are_hand_written_group_lists() -> anyhow::Result<()> {
+        for schema in [&OBSERVER_POLICY, &ADMIN_POLICY] {
+            let decoded = schema::decode::<GroupPolicy>(schema, br#"{"groups": ["wheel"]}"#)?;
+            assert_eq!(decoded.value.groups, ["wheel"]);
+            assert!(schema::decode::<GroupPolicy>(schema, br#"{"group": "wheel"}"#).is_err());
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn only_uid_zero_is_implicitly_an_admin() {
+        assert!(is_root(&OwnerIdentity::Unix { uid: 0, gid: 0 }));
+        assert!(!is_root(&OwnerIdentity::Unix { uid: 501, gid: 0 }));
+    }
+}
@@ -0,0 +1,400 @@
+//! Resuming supervision of a core that outlived the service process.
+//! A Go core dies of SIGPIPE on its next log line once nobody can read its stdout or stderr, so
+//! the core is started holding the read ends of its own output pipes at fixed descriptors. The
+//! pipes then survive the service, and a new instance reopens them through procfs.
+
+use crate::core::desired::{DesiredState, load_active_owner, load_owner_desired_state};
+use crate::core::process::{ProcessIdentity, process_identity};
+use crate::core::runtime::{CoreOutputFds, CoreRuntimeRecord, is_core_socket_reachable};
+use crate::{ClashConfig, OwnerIdentity};
+use anyhow::{Context as _, Result};
+use std::os::fd::{AsRawFd as _, FromRawFd as _, OwnedFd, RawFd};
+use std::time::Duration;
+use tokio::io::Interest;
+use tokio::io::unix::AsyncFd;
+use tokio::net::unix::pipe;
+use tracing::{debug, error, info, warn};
+
+/// Where the core keeps the read ends of its stdout and stderr pipes.
+pub(super) const HANDOFF_OUTPUT_FDS: CoreOutputFds = CoreOutputFds {
+    stdout: 3,
+    stderr: 4,
+};
+/// Lets the core keep logging for a while when no service is reading; the default is 64 KiB.
+const PIPE_CAPACITY: i32 = 1 << 20;
+/// Scratch descriptors used while moving the read ends into place must stay clear of the targets.
+const SCRATCH_FD_FLOOR: RawFd = 10;
+const HEALTH_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+const MAX_FAILED_HEALTH_PROBES: u32 = 3;
+const KILL_TIMEOUT: Duration = Duration::from_secs(5);
+
+pub(super) struct AdoptionCandidate {
+    pub(super) config: ClashConfig,
+    pub(super) owner: OwnerIdentity,
+}
+
+/// Returns the configuration to adopt `record` under, or `None` if the active owner no longer
+/// wants that exact core running.
+pub(super) async fn adoption_candidate(
+    record: &CoreRuntimeRecord,
+) -> Result<Option<AdoptionCandidate>> {
+    let Some(active_owner) = load_active_owner().await? else {
+        info!(
+            "No active owner; previous core {} will not be adopted",
+            record.pid
+        );
+        return Ok(None);
+    };
+    let state = load_owner_desired_state(&active_owner.owner_key).await?;
+    match adoption_config(record, state) {
+        Ok(config) => Ok(Some(AdoptionCandidate {
+            config,
+            owner: active_owner.identity,
+        })),
+        Err(reason) => {
+            info!("Previous core {} will not be adopted: {reason}", record.pid);
+            Ok(None)
+        }
+    }
+}
+
+fn adoption_config(
+    record: &CoreRuntimeRecord,
+    state: DesiredState,
+) -> std::result::Result<ClashConfig, &'static str> {
+    if record.output_fds.is_none() {
+        return Err("it was started without reattachable output");
+    }
+    if !state.core_should_be_running {
+        return Err("the desired state expects the core to be stopped");
+    }
+    let Some(config) = state.last_clash_config else {
+        return Err("the desired state has no core configuration");
+    };
+    if config.core_config.core_ipc_path != record.ipc_path {
+        return Err("its IPC path differs from the desired configuration");
+    }
+    let desired_executable = std::fs::canonicalize(&config.core_config.core_path)
+        .map(|path| path.to_string_lossy().into_owned());
+    if desired_executable.ok().as_deref() != Some(record.identity.executable.as_str()) {
+        return Err("its binary differs from the desired configuration");
+    }
+    Ok(config)
+}
+
+pub(super) struct CoreOutputPipes {
+    pub(super) stdout_write: OwnedFd,
+    pub(super) stderr_write: OwnedFd,
+    pub(super) stdout: pipe::Receiver,
+    pub(super) stderr: pipe::Receiver,
+}
+
+pub(super) fn create_output_pipes() -> Result<CoreOutputPipes> {
+    let (stdout_read, stdout_write) = create_pipe()?;
+    let (stderr_read, stderr_write) = create_pipe()?;
+    Ok(CoreOutputPipes {
+        stdout_write,
+        stderr_write,
+        stdout: pipe::Receiver::from_owned_fd(stdout_read)?,
+        stderr: pipe::Receiver::from_owned_fd(stderr_read)?,
+    })
+}
+
+fn create_pipe() -> Result<(OwnedFd, OwnedFd)> {
+    let mut fds = [0; 2];
+    if unsafe { platform_lib::pipe2(fds.as_mut_ptr(), platform_lib::O_CLOEXEC) } != 0 {
+        return Err(std::io::Error::last_os_error()).context("failed to create core output pipe");
+    }
+    let (read, write) = unsafe { (OwnedFd::from_raw_fd(fds[0]), OwnedFd::from_raw_fd(fds[1])) };
+    if unsafe { platform_lib::fcntl(write.as_raw_fd(), platform_lib::F_SETPIPE_SZ, PIPE_CAPACITY) }
+        < 0
+    {
+        debug!(
+            "Failed to enlarge core output pipe: {}",
+            std::io::Error::last_os_error()
+        );
+    }
+    Ok((read, write))
+}
+
+/// Places the pipe read ends at [`HANDOFF_OUTPUT_FDS`] without close-on-exec.
+/// Processes the core spawns inherit them too; holding a read end only keeps the pipe open, which
+/// is what the handoff needs anyway. Runs between fork and exec, so it only makes
+/// async-signal-safe calls.
+pub(super) fn expose_output_read_ends(
+    stdout_read: RawFd,
+    stderr_read: RawFd,
+) -> std::io::Result<()> {
+    // Copy both sources out of the way first: a source sitting at 3 or 4 would otherwise be
+    // replaced by the first dup2 before the second one reads it.
+    let mut scratch = [0; 2];
+    for (slot, source) in scratch.iter_mut().zip([stdout_read, stderr_read]) {
+        *slot =
+            unsafe { platform_lib::fcntl(source, platform_lib::F_DUPFD_CLOEXEC, SCRATCH_FD_FLOOR) };
+        if *slot < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    for (source, target) in scratch
+        .into_iter()
+        .zip([HANDOFF_OUTPUT_FDS.stdout, HANDOFF_OUTPUT_FDS.stderr])
+    {
+        if unsafe { platform_lib::dup2(source, target) } < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Opens new readers on the output pipes of core `pid`. The readers are close-on-exec, so cores
+/// this service spawns later never inherit another core's output.
+pub(super) fn reopen_core_output(
+    pid: u32,
+    fds: CoreOutputFds,
+) -> Result<(pipe::Receiver, pipe::Receiver)> {
+    let reopen = |held: i32, written: i32| -> Result<pipe::Receiver> {
+        use std::os::unix::fs::{MetadataExt as _, OpenOptionsExt as _};
+
+        let path = format!("/proc/{pid}/fd/{held}");
+        let file = std::fs::OpenOptions::new()
+            .read(true)
+            .custom_flags(platform_lib::O_NONBLOCK)
+            .open(&path)
+            .with_context(|| format!("failed to reopen core output {path}"))?;
+        set_close_on_exec(file.as_raw_fd())
+            .with_context(|| format!("failed to make core output {path} close-on-exec"))?;
+        let written_inode = std::fs::metadata(format!("/proc/{pid}/fd/{written}"))?.ino();
+        anyhow::ensure!(
+            file.metadata()?.ino() == written_inode,
+            "{path} is not the pipe behind descriptor {written} of core {pid}"
+        );
+        pipe::Receiver::from_file(file).with_context(|| format!("{path} is not a readable pipe"))
+    };
+    Ok((reopen(fds.stdout, 1)?, reopen(fds.stderr, 2)?))
+}
+
+fn set_close_on_exec(fd: RawFd) -> std::io::Result<()> {
+    let flags = unsafe { platform_lib::fcntl(fd, platform_lib::F_GETFD) };
+    if flags < 0
+        || unsafe {
+            platform_lib::fcntl(fd, platform_lib::F_SETFD, flags | platform_lib::FD_CLOEXEC)
+        } < 0
+    {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Wall-clock start time of `identity`, derived from its start tick and the boot time.
+pub(super) fn started_at_unix_secs(identity: &ProcessIdentity) -> Option<u64> {
+    let stat = std::fs::read_to_string("/proc/stat").ok()?;
+    let boot_time = stat
+        .lines()
+        .find_map(|line| line.strip_prefix("btime "))?
+        .trim()
+        .parse::<u64>()
+        .ok()?;
+    let ticks_per_second = unsafe { platform_lib::sysconf(platform_lib::_SC_CLK_TCK) };
+    (ticks_per_second > 0).then(|| boot_time + identity.started_at / ticks_per_second as u64)
+}
+
+/// A core this service did not spawn, pinned by a pidfd so signals cannot reach a reused PID.
+pub(super) struct AdoptedCore {
+    pid: u32,
+    ipc_path: String,
+    pidfd: AsyncFd<OwnedFd>,
+}
+
+impl AdoptedCore {
+    pub(super) fn open(record: &CoreRuntimeRecord) -> Result<Self> {
+        let raw = unsafe {
+            platform_lib::syscall(
+                platform_lib::SYS_pidfd_open,
+                record.pid as platform_lib::pid_t,
+                0,
+            )
+        };
+        if raw < 0 {
+            return Err(std::io::Error::last_os_error())
+                .with_context(|| format!("failed to open pidfd for core {}", record.pid));
+        }
+        let pidfd = unsafe { OwnedFd::from_raw_fd(raw as RawFd) };
+        // Checked only now that the pidfd pins whichever process the PID named.
+        anyhow::ensure!(
+            process_identity(record.pid)?.as_ref() == Some(&record.identity),
+            "core {} changed identity before it could be adopted",
+            record.pid
+        );
+        Ok(Self {
+            pid: record.pid,
+            ipc_path: record.ipc_path.clone(),
+            pidfd: AsyncFd::with_interest(pidfd, Interest::READABLE)?,
+        })
+    }
+
+    pub(super) fn pid(&self) -> u32 {
+        self.pid
+    }
+
+    /// Resolves once the core exits. A core whose socket stops answering is killed first so
+    /// the watchdog can replace it.
+    pub(super) async fn wait(&self) {
+        let mut probes = tokio::time::interval(HEALTH_PROBE_INTERVAL);
+        probes.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        let mut failed_probes = 0u32;
+        loop {
+            tokio::select! {
+                _ = self.pidfd.readable() => return,
+                _ = probes.tick() => {
+                    if is_core_socket_reachable(&self.ipc_path).await {
+                        failed_probes = 0;
+                        continue;
+                    }
+                    failed_probes += 1;
+                    warn!(
+                        "Adopted core {} did not answer on {} ({failed_probes}/{MAX_FAILED_HEALTH_PROBES})",
+                        self.pid, self.ipc_path
+                    );
+                    if failed_probes >= MAX_FAILED_HEALTH_PROBES {
+                        error!("Adopted core {} is unresponsive; killing it", self.pid);
+                        if let Err(error) = self.kill() {
+                            warn!("Failed to kill unresponsive adopted core {}: {error}", self.pid);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    pub(super) fn kill(&self) -> std::io::Result<()> {
+        let result = unsafe {
+            platform_lib::syscall(
+                platform_lib::SYS_pidfd_send_signal,
+                self.pidfd.as_raw_fd(),
+                platform_lib::SIGKILL,
+                std::ptr::null::<platform_lib::siginfo_t>(),
+                0,
+            )
+        };
+        if result != 0 {
+            let error = std::io::Error::last_os_error();
+            if error.raw_os_error() != Some(platform_lib::ESRCH) {
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    pub(super) async fn kill_and_wait(&self) -> Result<()> {
+        self.kill()?;
+        tokio::time::timeout(KILL_TIMEOUT, self.pidfd.readable())
+            .await
+            .with_context(|| format!("adopted core {} did not exit after SIGKILL", self.pid))??;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        HANDOFF_OUTPUT_FDS, adoption_config, create_output_pipes, expose_output_read_ends,
+        reopen_core_output,
+    };
+    use crate::core::desired::DesiredState;
+    use crate::core::process::ProcessIdentity;
+    use crate::core::runtime::CoreRuntimeRecord;
+    use crate::{ClashConfig, CoreConfig};
+    use std::os::fd::AsRawFd as _;
+    use tokio::io::AsyncBufReadExt as _;
+
+    fn record(executable: &str) -> CoreRuntimeRecord {
+        CoreRuntimeRecord {
+            pid: 1,
+            ipc_path: "/run/test/mihomo.sock".to_owned(),
+            identity: ProcessIdentity {
+                executable: executable.to_owned(),
+                started_at: 1,
+            },
+            output_fds: Some(HANDOFF_OUTPUT_FDS),
+        }
+    }
+
+    fn desired(core_path: &str) -> DesiredState {
+        DesiredState {
+            core_should_be_running: true,
+            last_clash_config: Some(ClashConfig {
+                core_config: CoreConfig {
+                    core_path: core_path.to_owned(),
+                    core_ipc_path: "/run/test/mihomo.sock".to_owned(),
+                    ..CoreConfig::default()
+                },
+                ..ClashConfig::default()
+            }),
+            ..DesiredState::default()
+        }
+    }
+
+    #[test]
+    fn adopts_only_the_core_the_owner_still_wants() {
+        let executable = std::env::current_exe()
+            .unwrap()
+            .canonicalize()
+            .unwrap()
+            .to_string_lossy()
+            .into_owned();
+
+        assert!(adoption_config(&record(&executable), desired(&executable)).is_ok());
+
+        let stopped = DesiredState {
+            core_should_be_running: false,
+            ..desired(&executable)
+        };
+        assert!(adoption_config(&record(&executable), stopped).is_err());
+        assert!(adoption_config(&record("/usr/bin/other-core"), desired(&executable)).is_err());
+
+        let mut moved_socket = desired(&executable);
+        moved_socket
+            .last_clash_config
+            .as_mut()
+            .unwrap()
+            .core_config
+            .core_ipc_path = "/run/test/other.sock".to_owned();
+        assert!(adoption_config(&record(&executable), moved_socket).is_err());
+
+        let legacy = CoreRuntimeRecord {
+            output_fds: None,
+            ..record(&executable)
+        };
+        assert!(adoption_config(&legacy, desired(&executable)).is_err());
+    }
+
+    #[tokio::test]
+    async fn core_output_survives_its_first_reader() -> anyhow::Result<()> {
+        let pipes = create_output_pipes()?;
+        let (stdout_read, stderr_read) = (pipes.stdout.as_raw_fd(), pipes.stderr.as_raw_fd());
+        let mut child = unsafe {
+            tokio::process::Command::new("sh")
+                .args(["-c", "sleep 1; echo adopted"])
+                .stdout(std::process::Stdio::from(pipes.stdout_write))
+                .stderr(std::process::Stdio::from(pipes.stderr_write))
+                .pre_exec(move || expose_output_read_ends(stdout_read, stderr_read))
+                .spawn()?
+        };
+        drop((pipes.stdout, pipes.stderr));
+
+        let pid = child.id().expect("child is running");
+        let (stdout, stderr) = reopen_core_output(pid, HANDOFF_OUTPUT_FDS)?;
+        for reader in [stdout.as_raw_fd(), stderr.as_raw_fd()] {
+            let flags = unsafe { platform_lib::fcntl(reader, platform_lib::F_GETFD) };
+            assert!(flags >= 0 && flags & platform_lib::FD_CLOEXEC != 0);
+        }
+        let line = tokio::io::BufReader::new(stdout)
+            .lines()
+            .next_line()
+            .await?;
+
+        assert_eq!(line.as_deref(), Some("adopted"));
+        assert!(child.wait().await?.success());
+        Ok(())
+    }
+}
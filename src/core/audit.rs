@@ -0,0 +1,338 @@
+//! Durable record of requests to state-changing routes. Records are appended as JSON lines to
+//! `audit.jsonl` in the persistent state directory, which rotates to `audit.jsonl.1` and onwards
+//! once it outgrows `MAX_AUDIT_LOG_BYTES`; nothing is ever rewritten in place. A record that
+//! cannot be written is logged instead, so a full disk never blocks an admin from stopping a
+//! runaway core.
+
+use crate::core::paths::service_paths;
+use crate::{AuditQuery, AuditRecord, IpcCommand, RuntimeBundle};
+use anyhow::{Context as _, Result};
+use once_cell::sync::Lazy;
+use sha2::{Digest as _, Sha256};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tokio::io::{AsyncReadExt as _, AsyncSeekExt as _, AsyncWriteExt as _};
+use tokio::sync::Mutex;
+use tracing::warn;
+
+const MAX_AUDIT_LOG_BYTES: u64 = 1024 * 1024;
+/// Rotated files kept besides the live one.
+const MAX_ROTATED_AUDIT_LOGS: usize = 4;
+/// Newest matching records a query returns, which keeps responses well below the IPC limit.
+const MAX_QUERY_RECORDS: usize = 2000;
+
+/// Serializes appends against rotation and queries.
+static AUDIT_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
+
+/// What a handler learned about its request, gathered while it runs and audited when it ends.
+#[derive(Debug, Default)]
+pub(super) struct AuditDetails {
+    pub peer_uid: Option<u32>,
+    pub peer_pid: Option<u32>,
+    pub caller: Option<String>,
+    pub owner_key: Option<String>,
+    pub session: Option<String>,
+    pub generation: Option<u64>,
+    pub message: Option<String>,
+    pub bundle_hash: Option<String>,
+    pub proxy_mode: Option<String>,
+}
+
+impl AuditDetails {
+    pub(super) fn into_record(self, command: &IpcCommand, code: Option<u16>) -> AuditRecord {
+        AuditRecord {
+            at: unix_timestamp_secs(),
+            route: command.as_ref().to_owned(),
+            peer_uid: self.peer_uid,
+            peer_pid: self.peer_pid,
+            caller: self.caller,
+            owner_key: self.owner_key,
+            session: self.session,
+            generation: self.generation,
+            code,
+            message: self.message,
+            bundle_hash: self.bundle_hash,
+            proxy_mode: self.proxy_mode,
+        }
+    }
+}
+
+/// State-changing routes are always audited, and so is any other request a session
+/// authenticated, which records the session behind every read. Heartbeats are left out: they only
+/// move an in-memory deadline and arrive every few seconds.
+pub(super) fn is_audited(command: &IpcCommand, details: &AuditDetails) -> bool {
+    if matches!(command, IpcCommand::RenewSessionLease) {
+        return false;
+    }
+    details.session.is_some()
+        || matches!(
+            command,
+            IpcCommand::StartClash
+                | IpcCommand::StopClash
+                | IpcCommand::StageRuntime
+                | IpcCommand::SetSystemProxy
+                | IpcCommand::UpdateWriter
+                | IpcCommand::OpenSession
+                | IpcCommand::RevokeSession
+                | IpcCommand::MintScopedSession
+                | IpcCommand::RevokeScopedSession
+                | IpcCommand::EvictOwner
+        )
+}
+
+/// Pid of the process behind a connection. kode-bridge takes the peer credentials from
+/// `SO_PEERCRED` and `getpeereid` but keeps only the uid and gid, and it never exposes the pipe
+/// handle `GetNamedPipeClientProcessId` needs, so no platform reports one through it yet. Records
+/// keep the field so a transport that does report it fills it here.
+pub(super) fn peer_pid(_client: &kode_bridge::ipc_http_server::ClientInfo) -> Option<u32> {
+    None
+}
+
+pub(super) fn bundle_hash(bundle: &RuntimeBundle) -> String {
+    let encoded = serde_json::to_vec(bundle).unwrap_or_default();
+    Sha256::digest(&encoded)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+pub(super) async fn append_audit_record(record: &AuditRecord) {
+    let _guard = AUDIT_LOCK.lock().await;
+    let path = service_paths().audit_log_path();
+    if let Err(error) = append_audit_record_to(&path, record, MAX_AUDIT_LOG_BYTES).await {
+        warn!("Failed to audit {record:?}: {error:#}");
+    }
+}
+
+/// Returns the newest records matching `query`, oldest first.
+pub(super) async fn query_audit_log(query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+    let _guard = AUDIT_LOCK.lock().await;
+    query_audit_log_at(&service_paths().audit_log_path(), query).await
+}
+
+async fn append_audit_record_to(path: &Path, record: &AuditRecord, max_bytes: u64) -> Result<()> {
+    let mut line = serde_json::to_vec(record)?;
+    line.push(b'\n');
+    if ends_mid_record(path).await {
+        line.insert(0, b'\n');
+    }
+    if tokio::fs::metadata(path)
+        .await
+        .is_ok_and(|metadata| metadata.len() + line.len() as u64 > max_bytes)
+    {
+        rotate(path).await?;
+    }
+    let mut options = tokio::fs::OpenOptions::new();
+    options.append(true).create(true);
+    #[cfg(unix)]
+    options.mode(0o600);
+    let mut file = options
+        .open(path)
+        .await
+        .with_context(|| format!("failed to open audit log {path:?}"))?;
+    file.write_all(&line)
+        .await
+        .with_context(|| format!("failed to append to audit log {path:?}"))?;
+    file.sync_data()
+        .await
+        .with_context(|| format!("failed to sync audit log {path:?}"))
+}
+
+/// Whether the file ends inside a record, as a crash during an append leaves it.
+async fn ends_mid_record(path: &Path) -> bool {
+    let Ok(mut file) = tokio::fs::File::open(path).await else {
+        return false;
+    };
+    let mut last = [0_u8];
+    file.seek(std::io::SeekFrom::End(-1)).await.is_ok()
+        && file.read_exact(&mut last).await.is_ok()
+        && last[0] != b'\n'
+}
+
+/// Shifts every rotated file one place older, dropping the oldest, and starts a new live file.
+async fn rotate(path: &Path) -> Result<()> {
+    for index in (1..=MAX_ROTATED_AUDIT_LOGS).rev() {
+        let from = if index == 1 {
+            path.to_path_buf()
+        } else {
+            rotated_path(path, index - 1)
+        };
+        match tokio::fs::rename(&from, rotated_path(path, index)).await {
+            Ok(()) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to rotate audit log {from:?}"));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn rotated_path(path: &Path, index: usize) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{file_name}.{index}"))
+}
+
+/// The rotated files beside the live audit log at `path`, oldest first, then the live one.
+pub(super) fn audit_log_files(path: &Path) -> impl Iterator<Item = PathBuf> + '_ {
+    (1..=MAX_ROTATED_AUDIT_LOGS)
+        .rev()
+        .map(|index| rotated_path(path, index))
+        .chain([path.to_path_buf()])
+}
+
+async fn query_audit_log_at(path: &Path, query: &AuditQuery) -> Result<Vec<AuditRecord>> {
+    let mut records = Vec::new();
+    for file in audit_log_files(path) {
+        let content = match tokio::fs::read_to_string(&file).await {
+            Ok(content) => content,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(error) => {
+                return Err(error).with_context(|| format!("failed to read audit log {file:?}"));
+            }
+        };
+        // A record torn by a crash mid-append is skipped rather than failing the query.
+        records.extend(
+            content
+                .lines()
+                .filter_map(|line| serde_json::from_str::<AuditRecord>(line).ok())
+                .filter(|record| matches(query, record)),
+        );
+    }
+    let excess = records.len().saturating_sub(MAX_QUERY_RECORDS);
+    records.drain(..excess);
+    Ok(records)
+}
+
+fn matches(query: &AuditQuery, record: &AuditRecord) -> bool {
+    query.since.is_none_or(|since| record.at >= since)
+        && query.until.is_none_or(|until| record.at <= until)
+        && query.owner_key.as_ref().is_none_or(|owner_key| {
+            record.caller.as_ref() == Some(owner_key)
+                || record.owner_key.as_ref() == Some(owner_key)
+        })
+}
+
+fn unix_timestamp_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        AuditDetails, MAX_ROTATED_AUDIT_LOGS, append_audit_record_to, is_audited,
+        query_audit_log_at, rotated_path,
+    };
+    use crate::{AuditQuery, AuditRecord, IpcCommand};
+    use std::path::{Path, PathBuf};
+
+    fn audit_dir(name: &str) -> anyhow::Result<PathBuf> {
+        let dir = std::env::temp_dir().join(format!("service-audit-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    fn record(at: u64, caller: &str, owner_key: &str) -> AuditRecord {
+        let details = AuditDetails {
+            caller: Some(caller.to_owned()),
+            owner_key: Some(owner_key.to_owned()),
+            ..AuditDetails::default()
+        };
+        AuditRecord {
+            at,
+            ..details.into_record(&IpcCommand::EvictOwner, Some(0))
+        }
+    }
+
+    async fn query(path: &Path, query: AuditQuery) -> anyhow::Result<Vec<u64>> {
+        Ok(query_audit_log_at(path, &query)
+            .await?
+            .iter()
+            .map(|record| record.at)
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn records_are_selected_by_time_and_owner() -> anyhow::Result<()> {
+        let dir = audit_dir("query")?;
+        let path = dir.join("audit.jsonl");
+        for record in [
+            record(10, "501", "501"),
+            record(20, "0", "501"),
+            record(30, "502", "502"),
+        ] {
+            append_audit_record_to(&path, &record, u64::MAX).await?;
+        }
+
+        assert_eq!(query(&path, AuditQuery::default()).await?, [10, 20, 30]);
+        let owner = AuditQuery {
+            owner_key: Some("501".to_owned()),
+            ..AuditQuery::default()
+        };
+        assert_eq!(query(&path, owner).await?, [10, 20]);
+        let admin = AuditQuery {
+            owner_key: Some("0".to_owned()),
+            ..AuditQuery::default()
+        };
+        assert_eq!(query(&path, admin).await?, [20]);
+        let window = AuditQuery {
+            since: Some(15),
+            until: Some(30),
+            owner_key: None,
+        };
+        assert_eq!(query(&path, window).await?, [20, 30]);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn reads_are_audited_once_a_session_authenticated_them() {
+        let anonymous = AuditDetails::default();
+        let session = AuditDetails {
+            session: Some("gui".to_owned()),
+            ..AuditDetails::default()
+        };
+
+        assert!(is_audited(&IpcCommand::StopClash, &anonymous));
+        assert!(!is_audited(&IpcCommand::Status, &anonymous));
+        assert!(is_audited(&IpcCommand::Status, &session));
+        assert!(!is_audited(&IpcCommand::RenewSessionLease, &session));
+    }
+
+    #[tokio::test]
+    async fn rotation_keeps_a_bounded_number_of_files() -> anyhow::Result<()> {
+        let dir = audit_dir("rotation")?;
+        let path = dir.join("audit.jsonl");
+        let records = MAX_ROTATED_AUDIT_LOGS as u64 + 3;
+        for at in 0..records {
+            append_audit_record_to(&path, &record(at, "501", "501"), 1).await?;
+        }
+
+        assert!(rotated_path(&path, MAX_ROTATED_AUDIT_LOGS).exists());
+        assert!(!rotated_path(&path, MAX_ROTATED_AUDIT_LOGS + 1).exists());
+        let kept = query(&path, AuditQuery::default()).await?;
+        assert_eq!(kept, (2..records).collect::<Vec<_>>());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn a_torn_record_is_skipped_and_later_records_are_kept() -> anyhow::Result<()> {
+        let dir = audit_dir("torn")?;
+        let path = dir.join("audit.jsonl");
+        append_audit_record_to(&path, &record(1, "501", "501"), u64::MAX).await?;
+        std::fs::write(
+            &path,
+            format!("{}{{\"at\":", std::fs::read_to_string(&path)?),
+        )?;
+        append_audit_record_to(&path, &record(2, "501", "501"), u64::MAX).await?;
+
+        assert_eq!(query(&path, AuditQuery::default()).await?, [1, 2]);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+}
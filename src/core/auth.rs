@@ -2,6 +2,7 @@
 use crate::owner_key;
 use crate::{
     IPC_AUTH_EXPECT, OwnerCredentials, OwnerIdentity, SESSION_TOKEN_HEX_LEN, ServiceErrorCode,
+    SessionScope,
 };
 use kode_bridge::errors::KodeBridgeError;
 use kode_bridge::ipc_http_server::RequestContext;
@@ -69,6 +70,32 @@ impl ServiceError {
     pub(crate) fn proxy_apply_failed(message: impl Into<String>) -> Self {
         Self::new(ServiceErrorCode::ProxyApplyFailed, message)
     }
+
+    pub(crate) fn invalid_owner_session(message: impl Into<String>) -> Self {
+        Self::new(ServiceErrorCode::InvalidOwnerSession, message)
+    }
+
+    pub(crate) fn session_scope_denied(required: Option<SessionScope>) -> Self {
+        let message = match required {
+            Some(scope) => format!("owner session lacks the {} scope", scope.as_ref()),
+            None => "a scoped session cannot perform this request".to_owned(),
+        };
+        Self::new(ServiceErrorCode::SessionScopeDenied, message)
+    }
+
+    pub(crate) fn observer_denied() -> Self {
+        Self::new(
+            ServiceErrorCode::ObserverDenied,
+            "caller is not admitted by the observer policy",
+        )
+    }
+
+    pub(crate) fn admin_denied() -> Self {
+        Self::new(
+            ServiceErrorCode::AdminDenied,
+            "caller is neither root nor admitted by the admin policy",
+        )
+    }
 }
 
 impl fmt::Display for ServiceError {
@@ -169,6 +196,13 @@ pub fn authenticate_owner(
                 "application data root is not an owner-controlled directory",
             ));
         }
+        if crate::service_paths().mode() == crate::ServiceMode::User
+            && uid != unsafe { platform_lib::geteuid() }
+        {
+            return Err(ServiceError::unauthorized(
+                "a rootless service only serves the user running it",
+            ));
+        }
 
         Ok(AuthenticatedOwner {
             key: owner_key(&credentials.identity),
@@ -7,12 +7,19 @@ pub enum IpcCommand {
     GetVersion,
     #[strum(serialize = "/status")]
     Status,
+    #[strum(serialize = "/status/observe")]
+    ObserveStatus,
     #[strum(serialize = "/clash/logs")]
     GetClashLogs,
 
     #[strum(serialize = "/clash/log-snapshot")]
     GetClashLogSnapshot,
 
+    #[strum(serialize = "/clash/crash-reports")]
+    ListCrashReports,
+    #[strum(serialize = "/clash/crash-report")]
+    GetCrashReport,
+
     #[strum(serialize = "/clash/start")]
     StartClash,
     #[strum(serialize = "/clash/stop")]
@@ -21,8 +28,26 @@ pub enum IpcCommand {
     StageRuntime,
     #[strum(serialize = "/system-proxy")]
     SetSystemProxy,
+    #[strum(serialize = "/session/heartbeat")]
+    RenewSessionLease,
+    #[strum(serialize = "/session/open")]
+    OpenSession,
+    #[strum(serialize = "/session/revoke")]
+    RevokeSession,
+    #[strum(serialize = "/session/scoped/mint")]
+    MintScopedSession,
+    #[strum(serialize = "/session/scoped/revoke")]
+    RevokeScopedSession,
+    #[strum(serialize = "/admin/evict")]
+    EvictOwner,
+    #[strum(serialize = "/admin/audit")]
+    QueryAuditLog,
     #[strum(serialize = "/writer")]
     UpdateWriter,
+    #[strum(serialize = "/handshake")]
+    Handshake,
     #[strum(serialize = "/magic")]
     Magic,
+    #[strum(serialize = "/metrics")]
+    Metrics,
 }
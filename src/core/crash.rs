@@ -0,0 +1,211 @@
+//! Extracts Go runtime panic traces from core stderr into standalone crash reports.
+//! The rotating `service` log still receives every line; a report only duplicates the final
+//! trace so it can be attached to a bug report without the surrounding log noise.
+
+use crate::core::structure::{CrashReport, CrashReportInfo};
+use anyhow::{Context as _, Result, bail};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const CRASH_DIRECTORY_NAME: &str = "crashes";
+const CRASH_REPORT_PREFIX: &str = "core-";
+const CRASH_REPORT_SUFFIX: &str = ".log";
+/// Go prints every goroutine on a fatal error, so a busy core can produce megabytes of trace.
+const MAX_CAPTURED_BYTES: usize = 256 * 1024;
+const MAX_CRASH_REPORTS: usize = 16;
+
+/// Lines with which the Go runtime opens an unrecovered panic or fatal runtime error.
+const TRACE_MARKERS: [&str; 2] = ["panic: ", "fatal error: "];
+
+/// Accumulates stderr from the first trace marker until the stream closes.
+/// The Go runtime writes nothing but the trace after the marker, so EOF ends the block.
+#[derive(Debug, Default)]
+pub(super) struct PanicCapture {
+    trace: Option<String>,
+    truncated: bool,
+}
+
+impl PanicCapture {
+    pub(super) fn observe(&mut self, line: &str) {
+        if self.trace.is_none() && TRACE_MARKERS.iter().any(|marker| line.starts_with(marker)) {
+            self.trace = Some(String::new());
+        }
+        let Some(trace) = self.trace.as_mut() else {
+            return;
+        };
+        if trace.len() + line.len() + 1 > MAX_CAPTURED_BYTES {
+            self.truncated = true;
+            return;
+        }
+        trace.push_str(line);
+        trace.push('\n');
+    }
+
+    pub(super) fn finish(self) -> Option<String> {
+        let mut trace = self.trace?;
+        if self.truncated {
+            trace.push_str("... trace truncated by clash-verge-service ...\n");
+        }
+        Some(trace)
+    }
+}
+
+pub(super) fn crash_directory(logs_dir: &Path) -> PathBuf {
+    logs_dir.join(CRASH_DIRECTORY_NAME)
+}
+
+/// Writes `trace` as a new report and prunes the oldest ones; returns the report name.
+pub(super) async fn write_crash_report(
+    logs_dir: &Path,
+    pid: Option<u32>,
+    trace: &str,
+) -> Result<String> {
+    let directory = crash_directory(logs_dir);
+    tokio::fs::create_dir_all(&directory)
+        .await
+        .with_context(|| format!("failed to create crash directory {directory:?}"))?;
+    crate::core::platform_security::ensure_private_service_directory(&directory)?;
+
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_millis())
+        .unwrap_or_default();
+    let name = format!(
+        "{CRASH_REPORT_PREFIX}{timestamp}-{}{CRASH_REPORT_SUFFIX}",
+        pid.unwrap_or_default()
+    );
+    let path = directory.join(&name);
+    tokio::fs::write(&path, trace)
+        .await
+        .with_context(|| format!("failed to write crash report {path:?}"))?;
+    crate::core::platform_security::secure_private_service_file_if_exists(&path)?;
+
+    let mut reports = list_crash_reports(logs_dir).await?;
+    if reports.len() > MAX_CRASH_REPORTS {
+        for stale in reports.split_off(MAX_CRASH_REPORTS) {
+            if let Err(error) = tokio::fs::remove_file(directory.join(&stale.name)).await {
+                tracing::warn!("Failed to prune crash report {}: {error}", stale.name);
+            }
+        }
+    }
+    Ok(name)
+}
+
+/// Lists reports newest first. A missing directory means the core has never crashed.
+pub(super) async fn list_crash_reports(logs_dir: &Path) -> Result<Vec<CrashReportInfo>> {
+    let directory = crash_directory(logs_dir);
+    let mut entries = match tokio::fs::read_dir(&directory).await {
+        Ok(entries) => entries,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {directory:?}"));
+        }
+    };
+
+    let mut reports = Vec::new();
+    while let Some(entry) = entries.next_entry().await? {
+        let Some(name) = entry.file_name().to_str().map(str::to_owned) else {
+            continue;
+        };
+        if !is_crash_report_name(&name) {
+            continue;
+        }
+        let metadata = entry.metadata().await?;
+        if !metadata.is_file() {
+            continue;
+        }
+        let created_at = metadata
+            .modified()
+            .ok()
+            .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default();
+        reports.push(CrashReportInfo {
+            name,
+            size: metadata.len(),
+            created_at,
+        });
+    }
+    reports.sort_by(|left, right| right.name.cmp(&left.name));
+    Ok(reports)
+}
+
+pub(super) async fn read_crash_report(logs_dir: &Path, name: &str) -> Result<CrashReport> {
+    if !is_crash_report_name(name) {
+        bail!("invalid crash report name {name:?}");
+    }
+    let path = crash_directory(logs_dir).join(name);
+    let content = tokio::fs::read(&path)
+        .await
+        .with_context(|| format!("failed to read crash report {path:?}"))?;
+    Ok(CrashReport {
+        name: name.to_owned(),
+        content: String::from_utf8_lossy(&content).into_owned(),
+    })
+}
+
+/// Accepts only names produced by `write_crash_report`, which also rules out path traversal.
+fn is_crash_report_name(name: &str) -> bool {
+    name.strip_prefix(CRASH_REPORT_PREFIX)
+        .and_then(|rest| rest.strip_suffix(CRASH_REPORT_SUFFIX))
+        .and_then(|stem| stem.split_once('-'))
+        .is_some_and(|(timestamp, pid)| {
+            !timestamp.is_empty()
+                && !pid.is_empty()
+                && timestamp.bytes().all(|byte| byte.is_ascii_digit())
+                && pid.bytes().all(|byte| byte.is_ascii_digit())
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PanicCapture, is_crash_report_name};
+
+    #[test]
+    fn ordinary_stderr_is_not_captured() {
+        let mut capture = PanicCapture::default();
+        capture.observe("level=error msg=\"dial tcp: i/o timeout\"");
+        capture.observe("runtime: a note that is not a trace marker");
+
+        assert_eq!(capture.finish(), None);
+    }
+
+    #[test]
+    fn panic_block_is_captured_until_the_stream_ends() {
+        let mut capture = PanicCapture::default();
+        capture.observe("level=warning msg=\"before\"");
+        capture.observe("panic: runtime error: invalid memory address or nil pointer dereference");
+        capture.observe("[signal SIGSEGV: segmentation violation code=0x1 addr=0x0 pc=0x0]");
+        capture.observe("");
+        capture.observe("goroutine 1 [running]:");
+
+        let trace = capture.finish().expect("panic should be captured");
+        assert!(trace.starts_with("panic: runtime error"));
+        assert!(trace.ends_with("goroutine 1 [running]:\n"));
+        assert!(!trace.contains("before"));
+    }
+
+    #[test]
+    fn fatal_error_is_captured_and_oversized_traces_are_truncated() {
+        let mut capture = PanicCapture::default();
+        capture.observe("fatal error: concurrent map writes");
+        let frame = "x".repeat(1024);
+        for _ in 0..512 {
+            capture.observe(&frame);
+        }
+
+        let trace = capture.finish().expect("fatal error should be captured");
+        assert!(trace.starts_with("fatal error: concurrent map writes\n"));
+        assert!(trace.len() <= super::MAX_CAPTURED_BYTES + 64);
+        assert!(trace.ends_with("truncated by clash-verge-service ...\n"));
+    }
+
+    #[test]
+    fn report_names_reject_traversal_and_foreign_files() {
+        assert!(is_crash_report_name("core-1700000000000-4242.log"));
+        assert!(!is_crash_report_name("../core-1-2.log"));
+        assert!(!is_crash_report_name("core-1-2.log/../../owner.json"));
+        assert!(!is_crash_report_name("service_latest.log"));
+        assert!(!is_crash_report_name("core--2.log"));
+    }
+}
@@ -1,9 +1,14 @@
-use crate::core::auth::{AuthenticatedOwner, hash_session_token};
+use crate::core::auth::{AuthenticatedOwner, ServiceError, hash_session_token};
 use crate::core::logger::set_or_update_writer;
 use crate::core::manager::CORE_MANAGER;
 use crate::core::paths::service_paths;
+use crate::core::proxy::apply_service_proxy_or_direct;
+use crate::core::schema::{self, DocumentSchema};
 use crate::core::state::set_core_lifecycle_state;
-use crate::{ClashConfig, OwnerIdentity, ServiceLifecycleState, WriterConfig};
+use crate::{
+    ClashConfig, MacosProxyConfig, OwnerIdentity, PRIMARY_SESSION_NAME, ProxyApplyOutcome,
+    ServiceLifecycleState, SessionLease, SessionScope, WriterConfig, is_valid_session_name,
+};
 use anyhow::{Context, Result};
 use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
@@ -15,12 +20,17 @@ use tracing::{info, warn};
 
 static DESIRED_STATE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 static STATE_FILE_SEQUENCE: AtomicU64 = AtomicU64::new(0);
+const MAX_OWNER_SESSIONS: usize = 8;
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct DesiredState {
     pub core_should_be_running: bool,
     pub last_clash_config: Option<ClashConfig>,
     pub last_writer_config: Option<WriterConfig>,
+    /// System proxy the owner last asked for; reapplied once the core is restored.
+    pub last_proxy_config: Option<MacosProxyConfig>,
+    /// Result of the last attempt to apply `last_proxy_config`, `None` before the first one.
+    pub last_proxy_outcome: Option<ProxyApplyOutcome>,
     pub generation: u64,
     pub updated_at: u64,
 }
@@ -30,10 +40,33 @@ pub struct ActiveOwnerState {
     pub owner_key: String,
     pub identity: OwnerIdentity,
     pub app_data_root: String,
-    #[serde(default)]
     pub generation: u64,
-    #[serde(default)]
-    pub session_token_hash: String,
+    /// Sessions open for `generation`; `StartClash` replaces them all with a new primary one.
+    pub sessions: Vec<OwnerSessionRecord>,
+    /// Lease terms the owner started with; any of its sessions renews it, and only its expiry
+    /// clock lives in memory.
+    pub lease: Option<SessionLease>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct OwnerSessionRecord {
+    pub name: String,
+    pub token_hash: String,
+    pub opened_at: u64,
+    /// Rights of a scoped session; `None` for a full session.
+    pub scopes: Option<Vec<SessionScope>>,
+}
+
+impl OwnerSessionRecord {
+    /// Whether this session may perform a request needing `required`, where `None` asks for a
+    /// full session.
+    pub fn allows(&self, required: Option<SessionScope>) -> bool {
+        match (&self.scopes, required) {
+            (None, _) => true,
+            (Some(scopes), Some(required)) => scopes.contains(&required),
+            (Some(_), None) => false,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -44,13 +77,14 @@ impl From<&AuthenticatedOwner> for ActiveOwnerState {
             identity: owner.identity.clone(),
             app_data_root: owner.app_data_root.to_string_lossy().into_owned(),
             generation: 0,
-            session_token_hash: String::new(),
+            sessions: Vec::new(),
+            lease: None,
         }
     }
 }
 
 #[derive(Debug, Default, Serialize, Deserialize)]
-struct OwnerGenerationState {
+pub(super) struct OwnerGenerationState {
     generation: u64,
 }
 
@@ -64,11 +98,14 @@ pub async fn load_owner_desired_state(owner_key: &str) -> Result<DesiredState> {
 pub async fn persist_owner_core_started(
     owner: &AuthenticatedOwner,
     config: &ClashConfig,
+    proxy: Option<&MacosProxyConfig>,
 ) -> Result<DesiredState> {
     update_owner_desired_state(&owner.key, |state| {
         state.core_should_be_running = true;
         state.last_clash_config = Some(config.clone());
         state.last_writer_config = Some(config.log_config.clone());
+        state.last_proxy_config = proxy.cloned();
+        state.last_proxy_outcome = None;
     })
     .await
 }
@@ -80,6 +117,36 @@ pub async fn persist_owner_core_stopped(owner: &AuthenticatedOwner) -> Result<De
 pub async fn persist_owner_core_stopped_by_key(owner_key: &str) -> Result<DesiredState> {
     update_owner_desired_state(owner_key, |state| {
         state.core_should_be_running = false;
+        state.last_proxy_config = None;
+        state.last_proxy_outcome = None;
+    })
+    .await
+}
+
+pub(super) async fn persist_owner_proxy(
+    owner_key: &str,
+    config: Option<&MacosProxyConfig>,
+    outcome: &ProxyApplyOutcome,
+) -> Result<DesiredState> {
+    update_owner_desired_state(owner_key, |state| {
+        state.last_proxy_config = config.cloned();
+        state.last_proxy_outcome = Some(outcome.clone());
+    })
+    .await
+}
+
+/// Puts an owner's core and proxy intent back to what it was before an interrupted transition.
+pub(super) async fn restore_owner_core_intent(
+    owner_key: &str,
+    core_should_be_running: bool,
+    proxy: Option<&MacosProxyConfig>,
+) -> Result<DesiredState> {
+    update_owner_desired_state(owner_key, |state| {
+        state.core_should_be_running = core_should_be_running;
+        if state.last_proxy_config.as_ref() != proxy {
+            state.last_proxy_config = proxy.cloned();
+            state.last_proxy_outcome = None;
+        }
     })
     .await
 }
@@ -101,9 +168,7 @@ pub async fn load_active_owner() -> Result<Option<ActiveOwnerState>> {
     let path = service_paths().active_owner_path();
     secure_state_file_if_exists(&path)?;
     match tokio::fs::read(&path).await {
-        Ok(content) => serde_json::from_slice(&content)
-            .map(Some)
-            .with_context(|| format!("failed to parse active owner {path:?}")),
+        Ok(content) => schema::decode_file(&schema::ACTIVE_OWNER, &path, &content).map(Some),
         Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
         Err(error) => Err(error).with_context(|| format!("failed to read active owner {path:?}")),
     }
@@ -113,33 +178,193 @@ pub async fn load_active_owner() -> Result<Option<ActiveOwnerState>> {
 pub async fn persist_active_owner(owner: &AuthenticatedOwner) -> Result<ActiveOwnerState> {
     let _guard = DESIRED_STATE_LOCK.lock().await;
     let state = ActiveOwnerState::from(owner);
-    write_json_atomic(&service_paths().active_owner_path(), &state).await?;
+    write_document_atomic(
+        &schema::ACTIVE_OWNER,
+        &service_paths().active_owner_path(),
+        &state,
+    )
+    .await?;
     Ok(state)
 }
 
 pub async fn commit_active_owner_session(
     owner: &AuthenticatedOwner,
     session_token: &str,
+    lease: Option<&SessionLease>,
 ) -> Result<ActiveOwnerState> {
     let session_token_hash = hash_session_token(session_token)?;
     let _guard = DESIRED_STATE_LOCK.lock().await;
     let paths = service_paths();
     let generation_path = paths.owner_generation_path();
-    let mut generation_state: OwnerGenerationState = read_json_or_default(&generation_path).await?;
+    let mut generation_state: OwnerGenerationState =
+        read_document_or_default(&schema::OWNER_GENERATION, &generation_path).await?;
     generation_state.generation = generation_state.generation.saturating_add(1);
-    write_json_atomic(&generation_path, &generation_state).await?;
+    write_document_atomic(
+        &schema::OWNER_GENERATION,
+        &generation_path,
+        &generation_state,
+    )
+    .await?;
 
     let state = ActiveOwnerState {
         owner_key: owner.key.clone(),
         identity: owner.identity.clone(),
         app_data_root: owner.app_data_root.to_string_lossy().into_owned(),
         generation: generation_state.generation,
-        session_token_hash,
+        sessions: vec![OwnerSessionRecord {
+            name: PRIMARY_SESSION_NAME.to_owned(),
+            token_hash: session_token_hash,
+            opened_at: unix_timestamp_secs(),
+            scopes: None,
+        }],
+        lease: lease.copied(),
     };
-    write_json_atomic(&paths.active_owner_path(), &state).await?;
+    write_document_atomic(&schema::ACTIVE_OWNER, &paths.active_owner_path(), &state).await?;
+    Ok(state)
+}
+
+/// Opens `name` for the active owner's current generation, replacing that name's token when it
+/// is already open. `scopes` limits a scoped session, which never replaces a full one. Rejected
+/// requests fail with a [`ServiceError`].
+pub(super) async fn open_owner_session(
+    owner: &AuthenticatedOwner,
+    name: &str,
+    session_token: &str,
+    mut scopes: Option<Vec<SessionScope>>,
+) -> Result<ActiveOwnerState> {
+    if !is_valid_session_name(name) {
+        return Err(
+            ServiceError::invalid_owner_session(format!("invalid session name {name:?}")).into(),
+        );
+    }
+    if let Some(scopes) = scopes.as_mut() {
+        if scopes.is_empty() {
+            return Err(ServiceError::invalid_owner_session(
+                "a scoped session needs at least one scope",
+            )
+            .into());
+        }
+        scopes.sort_unstable();
+        scopes.dedup();
+    }
+    let token_hash = hash_session_token(session_token)
+        .map_err(|error| ServiceError::invalid_owner_session(error.to_string()))?;
+    update_active_owner_sessions(owner, |sessions| {
+        if scopes.is_some()
+            && sessions
+                .iter()
+                .any(|session| session.name == name && session.scopes.is_none())
+        {
+            return Err(ServiceError::invalid_owner_session(format!(
+                "session {name:?} is a full session"
+            )));
+        }
+        if sessions
+            .iter()
+            .any(|session| session.token_hash == token_hash)
+        {
+            return Err(ServiceError::invalid_owner_session(
+                "the proposed token already belongs to an open session",
+            ));
+        }
+        sessions.retain(|session| session.name != name);
+        if sessions.len() >= MAX_OWNER_SESSIONS {
+            return Err(ServiceError::invalid_owner_session(format!(
+                "an owner can hold at most {MAX_OWNER_SESSIONS} sessions"
+            )));
+        }
+        sessions.push(OwnerSessionRecord {
+            name: name.to_owned(),
+            token_hash,
+            opened_at: unix_timestamp_secs(),
+            scopes,
+        });
+        Ok(())
+    })
+    .await
+}
+
+/// Closes one session and leaves the owner's other sessions and its core untouched. With
+/// `scoped_only`, a full session of that name is left open and reported as missing.
+pub(super) async fn revoke_owner_session(
+    owner: &AuthenticatedOwner,
+    name: &str,
+    scoped_only: bool,
+) -> Result<ActiveOwnerState> {
+    update_active_owner_sessions(owner, |sessions| {
+        let open = sessions.len();
+        sessions
+            .retain(|session| session.name != name || (scoped_only && session.scopes.is_none()));
+        if sessions.len() == open {
+            return Err(ServiceError::invalid_owner_session(format!(
+                "no open session is named {name:?}"
+            )));
+        }
+        Ok(())
+    })
+    .await
+}
+
+async fn update_active_owner_sessions(
+    owner: &AuthenticatedOwner,
+    update: impl FnOnce(&mut Vec<OwnerSessionRecord>) -> std::result::Result<(), ServiceError>,
+) -> Result<ActiveOwnerState> {
+    let _guard = DESIRED_STATE_LOCK.lock().await;
+    let mut state = load_active_owner()
+        .await?
+        .filter(|active| active.owner_key == owner.key)
+        .ok_or_else(ServiceError::not_active)?;
+    update(&mut state.sessions)?;
+    write_document_atomic(
+        &schema::ACTIVE_OWNER,
+        &service_paths().active_owner_path(),
+        &state,
+    )
+    .await?;
     Ok(state)
 }
 
+/// Writes back the exact owner record, session included, that an interrupted transition replaced.
+pub(super) async fn restore_active_owner(previous: Option<&ActiveOwnerState>) -> Result<()> {
+    let Some(previous) = previous else {
+        return clear_active_owner().await;
+    };
+    let _guard = DESIRED_STATE_LOCK.lock().await;
+    write_document_atomic(
+        &schema::ACTIVE_OWNER,
+        &service_paths().active_owner_path(),
+        previous,
+    )
+    .await
+}
+
+/// Owner keys are decimal uids or SHA-256 hex digests, so a valid key is a single path component.
+pub(super) fn is_owner_key(key: &str) -> bool {
+    !key.is_empty() && key.len() <= 64 && key.bytes().all(|byte| byte.is_ascii_alphanumeric())
+}
+
+/// Removes the desired state, runtime and logs kept for an owner that is not active. Returns
+/// whether there was anything to remove.
+pub(super) async fn purge_owner_state(owner_key: &str) -> Result<bool> {
+    anyhow::ensure!(is_owner_key(owner_key), "invalid owner key {owner_key:?}");
+    let _guard = DESIRED_STATE_LOCK.lock().await;
+    if load_active_owner()
+        .await?
+        .is_some_and(|active| active.owner_key == owner_key)
+    {
+        anyhow::bail!("owner {owner_key} is active; evict it before purging its state");
+    }
+    let root = service_paths()
+        .for_owner_key(owner_key)
+        .root()
+        .to_path_buf();
+    match tokio::fs::remove_dir_all(&root).await {
+        Ok(()) => Ok(true),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(false),
+        Err(error) => Err(error).with_context(|| format!("failed to purge owner state {root:?}")),
+    }
+}
+
 pub async fn clear_active_owner() -> Result<()> {
     let _guard = DESIRED_STATE_LOCK.lock().await;
     let path = service_paths().active_owner_path();
@@ -175,6 +400,12 @@ pub async fn restore_desired_state() -> Result<()> {
         return Ok(());
     };
 
+    if let Some((pid, _)) = CORE_MANAGER.lock().await.running_core_config().await {
+        info!("Core {pid} was adopted during startup reconciliation; skipping restore");
+        restore_owner_proxy(&active_owner.owner_key, state.last_proxy_config.as_ref()).await;
+        return Ok(());
+    }
+
     info!(
         "Restoring core from desired state generation {}",
         state.generation
@@ -204,9 +435,35 @@ pub async fn restore_desired_state() -> Result<()> {
         set_core_lifecycle_state(ServiceLifecycleState::Fatal);
         return Err(error);
     }
+    restore_owner_proxy(&active_owner.owner_key, state.last_proxy_config.as_ref()).await;
     Ok(())
 }
 
+/// Reapplies the owner's proxy once its core runs again. A failure keeps the core running and
+/// only leaves the outcome unknown.
+async fn restore_owner_proxy(owner_key: &str, config: Option<&MacosProxyConfig>) {
+    let Some(config) = config else {
+        return;
+    };
+    let outcome = match apply_service_proxy_or_direct(Some(config)).await {
+        Ok(outcome) => {
+            info!("Restored system proxy: {outcome:?}");
+            Some(outcome)
+        }
+        Err(error) => {
+            warn!("Failed to restore system proxy; it stays unchanged: {error:#}");
+            None
+        }
+    };
+    if let Err(error) = update_owner_desired_state(owner_key, |state| {
+        state.last_proxy_outcome = outcome;
+    })
+    .await
+    {
+        warn!("Failed to record the restored proxy outcome: {error:#}");
+    }
+}
+
 /// Returns whether the error chain contains an I/O `NotFound`.
 fn is_not_found_error(error: &anyhow::Error) -> bool {
     error.chain().any(|cause| {
@@ -272,7 +529,7 @@ async fn update_owner_desired_state(
     update(&mut state);
     state.generation = state.generation.saturating_add(1);
     state.updated_at = unix_timestamp_secs();
-    if let Err(error) = write_json_atomic(&path, &state).await {
+    if let Err(error) = write_document_atomic(&schema::DESIRED_STATE, &path, &state).await {
         warn!(
             "Failed to persist owner desired state {:?}; continuing with live core state: {error:#}",
             path
@@ -305,16 +562,33 @@ async fn read_owner_desired_state_resilient(path: &std::path::Path) -> DesiredSt
         }
     };
 
-    match serde_json::from_slice(&content) {
-        Ok(state) => state,
+    match schema::decode(&schema::DESIRED_STATE, &content) {
+        Ok(decoded) => {
+            if let Some(version) = decoded.migrated_from
+                && let Err(error) = schema::backup_before_migration(path, &content, version)
+            {
+                warn!(
+                    "Failed to back up owner desired state {:?} before migrating it: {error:#}",
+                    path
+                );
+            }
+            decoded.value
+        }
+        Err(error) if schema::is_newer_schema(&error) => {
+            warn!(
+                "Ignoring owner desired state {:?} without replacing it: {error:#}",
+                path
+            );
+            DesiredState::default()
+        }
         Err(parse_error) => {
             match quarantine_corrupt_owner_desired_state(path).await {
                 Ok(backup) => warn!(
-                    "Quarantined corrupt owner desired state {:?} at {:?}: {}",
+                    "Quarantined corrupt owner desired state {:?} at {:?}: {:#}",
                     path, backup, parse_error
                 ),
                 Err(quarantine_error) => warn!(
-                    "Owner desired state {:?} is corrupt and could not be quarantined: {}; quarantine failed: {quarantine_error:#}",
+                    "Owner desired state {:?} is corrupt and could not be quarantined: {:#}; quarantine failed: {quarantine_error:#}",
                     path, parse_error
                 ),
             }
@@ -333,20 +607,26 @@ async fn quarantine_corrupt_owner_desired_state(
     Ok(backup)
 }
 
-async fn read_json_or_default<T>(path: &std::path::Path) -> Result<T>
+pub(super) async fn read_document_or_default<T>(
+    schema: &DocumentSchema,
+    path: &std::path::Path,
+) -> Result<T>
 where
     T: for<'de> Deserialize<'de> + Default,
 {
     secure_state_file_if_exists(path)?;
     match tokio::fs::read(path).await {
-        Ok(content) => serde_json::from_slice(&content)
-            .with_context(|| format!("failed to parse state {path:?}")),
+        Ok(content) => schema::decode_file(schema, path, &content),
         Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(T::default()),
         Err(error) => Err(error).with_context(|| format!("failed to read state {path:?}")),
     }
 }
 
-async fn write_json_atomic<T>(path: &std::path::Path, value: &T) -> Result<()>
+pub(super) async fn write_document_atomic<T>(
+    schema: &DocumentSchema,
+    path: &std::path::Path,
+    value: &T,
+) -> Result<()>
 where
     T: Serialize,
 {
@@ -354,9 +634,10 @@ where
     if let Some(parent) = path.parent() {
         crate::core::platform_security::ensure_private_service_directory(parent)?;
     }
+    schema::ensure_replaceable(schema, path).await?;
 
     let temp_path = sibling_state_path(path, "tmp");
-    let json = serde_json::to_vec_pretty(value)?;
+    let json = schema::encode(schema, value)?;
     let result = async {
         let mut file = tokio::fs::OpenOptions::new()
             .write(true)
@@ -395,7 +676,7 @@ where
     result
 }
 
-fn sibling_state_path(path: &std::path::Path, role: &str) -> std::path::PathBuf {
+pub(super) fn sibling_state_path(path: &std::path::Path, role: &str) -> std::path::PathBuf {
     let sequence = STATE_FILE_SEQUENCE.fetch_add(1, Ordering::Relaxed);
     let timestamp = SystemTime::now()
         .duration_since(UNIX_EPOCH)
@@ -423,12 +704,13 @@ fn unix_timestamp_secs() -> u64 {
 #[cfg(test)]
 mod owner_tests {
     use super::{
-        backup_legacy_state_file, clear_active_owner, commit_active_owner_session,
+        backup_legacy_state_file, clear_active_owner, commit_active_owner_session, is_owner_key,
         load_active_owner, load_owner_desired_state, persist_active_owner,
-        persist_owner_core_started, persist_owner_core_stopped, write_json_atomic,
+        persist_owner_core_started, persist_owner_core_stopped, persist_owner_proxy,
+        purge_owner_state,
     };
     use crate::core::auth::AuthenticatedOwner;
-    use crate::{ClashConfig, CoreConfig, OwnerIdentity};
+    use crate::{ClashConfig, CoreConfig, MacosProxyConfig, OwnerIdentity, ProxyApplyOutcome};
     use serial_test::serial;
 
     fn test_owner(uid: u32) -> AuthenticatedOwner {
@@ -451,7 +733,7 @@ mod owner_tests {
             log_config: Default::default(),
         };
 
-        persist_owner_core_started(&owner_a, &config).await?;
+        persist_owner_core_started(&owner_a, &config, None).await?;
         persist_owner_core_stopped(&owner_b).await?;
 
         assert!(
@@ -467,6 +749,50 @@ mod owner_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn proxy_intent_is_kept_until_the_core_stops() -> anyhow::Result<()> {
+        let owner = test_owner(90_010);
+        let proxy = MacosProxyConfig::Pac {
+            url: "http://127.0.0.1:7890/commands/pac".to_owned(),
+        };
+
+        let started =
+            persist_owner_core_started(&owner, &ClashConfig::default(), Some(&proxy)).await?;
+        assert_eq!(started.last_proxy_config.as_ref(), Some(&proxy));
+        assert_eq!(started.last_proxy_outcome, None);
+
+        persist_owner_proxy(&owner.key, Some(&proxy), &ProxyApplyOutcome::Applied).await?;
+        let loaded = load_owner_desired_state(&owner.key).await?;
+        assert_eq!(loaded.last_proxy_config, Some(proxy));
+        assert_eq!(loaded.last_proxy_outcome, Some(ProxyApplyOutcome::Applied));
+
+        let stopped = persist_owner_core_stopped(&owner).await?;
+        assert_eq!(stopped.last_proxy_config, None);
+        assert_eq!(stopped.last_proxy_outcome, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn only_inactive_owners_can_be_purged() -> anyhow::Result<()> {
+        let active = test_owner(90_020);
+        let inactive = test_owner(90_021);
+        persist_owner_core_stopped(&active).await?;
+        persist_owner_core_stopped(&inactive).await?;
+        persist_active_owner(&active).await?;
+
+        assert!(purge_owner_state(&active.key).await.is_err());
+        assert!(purge_owner_state(&inactive.key).await?);
+        assert!(!purge_owner_state(&inactive.key).await?);
+        assert_eq!(load_owner_desired_state(&inactive.key).await?.generation, 0);
+        assert!(purge_owner_state("../90021").await.is_err());
+        assert!(is_owner_key(&"ab".repeat(32)));
+
+        clear_active_owner().await?;
+        assert!(purge_owner_state(&active.key).await?);
+        Ok(())
+    }
+
     #[tokio::test]
     #[serial]
     async fn active_owner_can_be_atomically_replaced_and_cleared() -> anyhow::Result<()> {
@@ -502,9 +828,9 @@ mod owner_tests {
         clear_active_owner().await?;
         let owner = test_owner(90_005);
         let first_token = "33".repeat(32);
-        let first = commit_active_owner_session(&owner, &first_token).await?;
+        let first = commit_active_owner_session(&owner, &first_token, None).await?;
 
-        assert_ne!(first.session_token_hash, first_token);
+        assert_ne!(first.sessions[0].token_hash, first_token);
         assert!(
             !tokio::fs::read_to_string(crate::service_paths().active_owner_path())
                 .await?
@@ -512,7 +838,7 @@ mod owner_tests {
         );
 
         clear_active_owner().await?;
-        let second = commit_active_owner_session(&owner, &"44".repeat(32)).await?;
+        let second = commit_active_owner_session(&owner, &"44".repeat(32), None).await?;
 
         assert!(second.generation > first.generation);
         clear_active_owner().await?;
@@ -529,14 +855,24 @@ mod owner_tests {
             "identity": owner.identity,
             "app_data_root": owner.app_data_root.to_string_lossy(),
         });
-        write_json_atomic(&crate::service_paths().active_owner_path(), &legacy_state).await?;
+        let path = crate::service_paths().active_owner_path();
+        let backup = crate::core::schema::pre_migration_backup_path(&path, 0);
+        let _ = tokio::fs::remove_file(&backup).await;
+        crate::core::paths::ensure_persistent_state_layout()?;
+        tokio::fs::write(&path, serde_json::to_vec(&legacy_state)?).await?;
 
         let active = load_active_owner()
             .await?
             .expect("legacy owner should load");
 
         assert_eq!(active.generation, 0);
-        assert!(active.session_token_hash.is_empty());
+        assert!(active.sessions.is_empty());
+        assert_eq!(active.lease, None);
+        assert_eq!(
+            tokio::fs::read(&backup).await?,
+            serde_json::to_vec(&legacy_state)?
+        );
+        tokio::fs::remove_file(&backup).await?;
         clear_active_owner().await?;
         Ok(())
     }
@@ -548,7 +884,11 @@ mod owner_tests {
         let owner = test_owner(90_007);
 
         for invalid in ["55".repeat(31), "AA".repeat(32), "gg".repeat(32)] {
-            assert!(commit_active_owner_session(&owner, &invalid).await.is_err());
+            assert!(
+                commit_active_owner_session(&owner, &invalid, None)
+                    .await
+                    .is_err()
+            );
         }
         assert!(load_active_owner().await?.is_none());
         Ok(())
@@ -568,7 +908,7 @@ mod owner_tests {
         let damaged = b"\0damaged-json";
         std::fs::write(&path, damaged)?;
 
-        let state = persist_owner_core_started(&owner, &ClashConfig::default()).await?;
+        let state = persist_owner_core_started(&owner, &ClashConfig::default(), None).await?;
 
         assert!(state.core_should_be_running);
         assert!(
@@ -636,7 +976,7 @@ mod owner_tests {
         let _ = std::fs::remove_dir_all(&owner_root);
         std::fs::create_dir_all(owner_root.join("desired-state.json"))?;
 
-        let state = persist_owner_core_started(&owner, &ClashConfig::default()).await?;
+        let state = persist_owner_core_started(&owner, &ClashConfig::default(), None).await?;
 
         assert!(state.core_should_be_running);
         assert!(owner_root.join("desired-state.json").is_dir());
@@ -0,0 +1,1121 @@
+//! Offline diagnosis of the service's on-disk state.
+//! `clash-verge-service doctor` inspects every path from `ServicePaths` and `OwnerPaths` without
+//! starting the service. Repairs run under the service repair gate, and anything that deletes or
+//! moves a file also takes the owner lock, so it cannot race a service that is starting up.
+
+use crate::core::access::GroupPolicy;
+use crate::core::audit::audit_log_files;
+use crate::core::desired::{
+    ActiveOwnerState, DesiredState, OwnerGenerationState, sibling_state_path,
+};
+use crate::core::handoff::is_fresh_request;
+use crate::core::handshake::public_key_for;
+use crate::core::journal::OwnerTransitionRecord;
+use crate::core::maintenance::{StoppedServiceGuard, acquire_stopped_service_guard};
+use crate::core::owner::read_owner_pid;
+use crate::core::paths::{ServicePaths, service_paths};
+use crate::core::process::{is_process_alive, process_identity};
+use crate::core::repair::acquire_service_repair_gate;
+use crate::core::runtime::{CoreExitRecord, read_core_runtime_record};
+use crate::core::schema::{self, DocumentSchema};
+use crate::{OwnerIdentity, ServiceMode};
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::fs::{File, Metadata};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DoctorSeverity {
+    Ok,
+    /// Harmless on its own, or something the next service start cleans up.
+    Warning,
+    /// Keeps the service from starting, serving clients or recovering the core.
+    Problem,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DoctorFinding {
+    pub check: &'static str,
+    pub path: PathBuf,
+    pub severity: DoctorSeverity,
+    pub detail: String,
+    /// Whether `--fix` repaired the finding.
+    pub fixed: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix_error: Option<String>,
+    #[serde(skip)]
+    repair: Option<Repair>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct DoctorReport {
+    pub channel: &'static str,
+    pub mode: ServiceMode,
+    /// Whether a service instance holds the owner lock.
+    pub service_running: bool,
+    pub findings: Vec<DoctorFinding>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Repair {
+    #[cfg(unix)]
+    SecureDirectory {
+        runtime: bool,
+    },
+    #[cfg(unix)]
+    SecureFile {
+        public: bool,
+    },
+    Remove,
+    Quarantine,
+}
+
+/// Inspects the selected installation. Nothing is changed; see [`DoctorReport::repair`].
+pub async fn diagnose_service() -> DoctorReport {
+    let paths = service_paths();
+    let mut findings = Vec::new();
+
+    let service_running = inspect_locks(&paths, &mut findings);
+    inspect_directory(&mut findings, paths.runtime_dir(), true);
+    inspect_directory(&mut findings, paths.persistent_state_dir(), false);
+    inspect_directory(
+        &mut findings,
+        &paths.persistent_state_dir().join("users"),
+        false,
+    );
+    inspect_directory(&mut findings, &paths.install_dir(), false);
+    inspect_state_file::<ActiveOwnerState>(
+        &mut findings,
+        &schema::ACTIVE_OWNER,
+        &paths.active_owner_path(),
+        DoctorSeverity::Problem,
+    );
+    inspect_state_file::<OwnerGenerationState>(
+        &mut findings,
+        &schema::OWNER_GENERATION,
+        &paths.owner_generation_path(),
+        DoctorSeverity::Problem,
+    );
+    // Startup reconciliation stays blocked while an interrupted transition cannot be read.
+    inspect_state_file::<OwnerTransitionRecord>(
+        &mut findings,
+        &schema::OWNER_TRANSITION,
+        &paths.owner_transition_path(),
+        DoctorSeverity::Problem,
+    );
+    inspect_state_file::<CoreExitRecord>(
+        &mut findings,
+        &schema::CORE_EXIT,
+        &paths.core_exit_path(),
+        DoctorSeverity::Warning,
+    );
+    // A policy that does not parse admits nobody, which locks out its role and nothing else.
+    inspect_state_file::<GroupPolicy>(
+        &mut findings,
+        &schema::OBSERVER_POLICY,
+        &paths.observer_policy_path(),
+        DoctorSeverity::Warning,
+    );
+    inspect_state_file::<GroupPolicy>(
+        &mut findings,
+        &schema::ADMIN_POLICY,
+        &paths.admin_policy_path(),
+        DoctorSeverity::Warning,
+    );
+    inspect_audit_log(&paths, &mut findings);
+    inspect_handshake_key(&paths, service_running, &mut findings);
+    inspect_handoff_request(&paths, service_running, &mut findings);
+    inspect_owner_states(&paths, &mut findings);
+    inspect_core_runtime(&paths, service_running, &mut findings).await;
+    inspect_leftovers(&mut findings, paths.runtime_dir());
+    inspect_leftovers(&mut findings, paths.persistent_state_dir());
+
+    DoctorReport {
+        channel: paths.channel().id,
+        mode: paths.mode(),
+        service_running,
+        findings,
+    }
+}
+
+impl DoctorReport {
+    /// Whether any problem is left unrepaired.
+    pub fn has_problems(&self) -> bool {
+        self.findings
+            .iter()
+            .any(|finding| finding.severity == DoctorSeverity::Problem && !finding.fixed)
+    }
+
+    /// Applies the repairs the diagnosis found. Returns `false` without touching anything when
+    /// an install, uninstall or another repair holds the repair gate.
+    pub fn repair(&mut self) -> Result<bool> {
+        let Some(_gate) = acquire_service_repair_gate()? else {
+            return Ok(false);
+        };
+        let mut stopped: Option<Result<StoppedServiceGuard, String>> = None;
+        for finding in &mut self.findings {
+            let Some(repair) = finding.repair.take() else {
+                continue;
+            };
+            let result = if repair.needs_stopped_service() {
+                match stopped.get_or_insert_with(|| {
+                    acquire_stopped_service_guard().map_err(|error| format!("{error:#}"))
+                }) {
+                    Ok(_) => repair.apply(&finding.path),
+                    Err(error) => Err(anyhow::anyhow!("{error}")),
+                }
+            } else {
+                repair.apply(&finding.path)
+            };
+            match result {
+                Ok(()) => finding.fixed = true,
+                Err(error) => finding.fix_error = Some(format!("{error:#}")),
+            }
+        }
+        Ok(true)
+    }
+}
+
+impl fmt::Display for DoctorReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mode = match self.mode {
+            ServiceMode::System => "system",
+            ServiceMode::User => "user",
+        };
+        let state = if self.service_running {
+            "running"
+        } else {
+            "stopped"
+        };
+        writeln!(f, "Channel {} ({mode} service), {state}", self.channel)?;
+        for finding in &self.findings {
+            let status = match (finding.severity, finding.fixed) {
+                (_, true) => "fixed",
+                (DoctorSeverity::Ok, false) => "ok",
+                (DoctorSeverity::Warning, false) => "warning",
+                (DoctorSeverity::Problem, false) => "problem",
+            };
+            writeln!(
+                f,
+                "[{status:>7}] {}: {}",
+                finding.check,
+                finding.path.display()
+            )?;
+            writeln!(f, "          {}", finding.detail)?;
+            if let Some(error) = &finding.fix_error {
+                writeln!(f, "          fix failed: {error}")?;
+            }
+        }
+        let open = |severity| {
+            self.findings
+                .iter()
+                .filter(|finding| finding.severity == severity && !finding.fixed)
+                .count()
+        };
+        write!(
+            f,
+            "{} problem(s), {} warning(s)",
+            open(DoctorSeverity::Problem),
+            open(DoctorSeverity::Warning)
+        )
+    }
+}
+
+impl DoctorFinding {
+    fn new(
+        check: &'static str,
+        path: impl Into<PathBuf>,
+        severity: DoctorSeverity,
+        detail: impl Into<String>,
+    ) -> Self {
+        Self {
+            check,
+            path: path.into(),
+            severity,
+            detail: detail.into(),
+            fixed: false,
+            fix_error: None,
+            repair: None,
+        }
+    }
+
+    fn repaired_by(mut self, repair: Repair) -> Self {
+        self.repair = Some(repair);
+        self
+    }
+}
+
+impl Repair {
+    /// Deleting or moving files races a starting service, so those repairs hold the owner lock.
+    fn needs_stopped_service(self) -> bool {
+        matches!(self, Self::Remove | Self::Quarantine)
+    }
+
+    fn apply(self, path: &Path) -> Result<()> {
+        match self {
+            #[cfg(unix)]
+            Self::SecureDirectory { runtime: true } => {
+                crate::core::unix_security::ensure_runtime_directory(path)
+            }
+            #[cfg(unix)]
+            Self::SecureDirectory { runtime: false } => {
+                crate::core::unix_security::ensure_private_service_directory(path)
+            }
+            #[cfg(unix)]
+            Self::SecureFile { public: false } => {
+                crate::core::unix_security::secure_private_service_file_if_exists(path)
+            }
+            #[cfg(unix)]
+            Self::SecureFile { public: true } => {
+                crate::core::unix_security::secure_public_service_file_if_exists(path)
+            }
+            Self::Remove => {
+                std::fs::remove_file(path).with_context(|| format!("failed to remove {path:?}"))
+            }
+            Self::Quarantine => {
+                let backup = sibling_state_path(path, "corrupt");
+                std::fs::rename(path, &backup)
+                    .with_context(|| format!("failed to quarantine {path:?} at {backup:?}"))
+            }
+        }
+    }
+}
+
+/// Reports the repair gate and the owner lock, and returns whether a service holds the latter.
+fn inspect_locks(paths: &ServicePaths, findings: &mut Vec<DoctorFinding>) -> bool {
+    let gate = paths.install_dir().join(".repair.lock");
+    match lock_is_held(&gate) {
+        Ok(true) => findings.push(DoctorFinding::new(
+            "repair_gate",
+            gate,
+            DoctorSeverity::Warning,
+            "an install, uninstall or repair is in progress",
+        )),
+        Ok(false) => {}
+        Err(error) => findings.push(DoctorFinding::new(
+            "repair_gate",
+            gate,
+            DoctorSeverity::Problem,
+            format!("{error:#}"),
+        )),
+    }
+
+    let lock = paths.owner_lock_path();
+    let running = match lock_is_held(lock) {
+        Ok(held) => held,
+        Err(error) => {
+            // Assume the worst so nothing below offers to remove a live service's files.
+            findings.push(DoctorFinding::new(
+                "owner_lock",
+                lock,
+                DoctorSeverity::Problem,
+                format!("{error:#}"),
+            ));
+            return true;
+        }
+    };
+    let pid = read_owner_pid(paths);
+    if !running {
+        findings.push(DoctorFinding::new(
+            "owner_lock",
+            lock,
+            DoctorSeverity::Ok,
+            "not held; the service is stopped",
+        ));
+        if let Some(pid) = pid
+            && paths.pid_file_path().exists()
+        {
+            findings.push(
+                DoctorFinding::new(
+                    "pid_file",
+                    paths.pid_file_path(),
+                    DoctorSeverity::Warning,
+                    format!("left behind by service pid {pid}, which no longer holds the lock"),
+                )
+                .repaired_by(Repair::Remove),
+            );
+        }
+        #[cfg(unix)]
+        if std::fs::symlink_metadata(paths.ipc_path()).is_ok() {
+            findings.push(
+                DoctorFinding::new(
+                    "control_socket",
+                    paths.ipc_path(),
+                    DoctorSeverity::Warning,
+                    "left behind by a service that is no longer running",
+                )
+                .repaired_by(Repair::Remove),
+            );
+        }
+        return false;
+    }
+
+    findings.push(match pid {
+        Some(pid) if is_process_alive(pid) => DoctorFinding::new(
+            "owner_lock",
+            lock,
+            DoctorSeverity::Ok,
+            format!("held by service pid {pid}"),
+        ),
+        Some(pid) => DoctorFinding::new(
+            "owner_lock",
+            lock,
+            DoctorSeverity::Warning,
+            format!("held, but the recorded service pid {pid} is not running"),
+        ),
+        None => DoctorFinding::new(
+            "owner_lock",
+            lock,
+            DoctorSeverity::Warning,
+            "held by a service that did not record its pid",
+        ),
+    });
+    #[cfg(unix)]
+    if std::fs::symlink_metadata(paths.ipc_path()).is_err() {
+        findings.push(DoctorFinding::new(
+            "control_socket",
+            paths.ipc_path(),
+            DoctorSeverity::Problem,
+            "missing while the service runs; clients cannot connect until it restarts",
+        ));
+    }
+    true
+}
+
+/// Probes a lock without creating its file. A missing file is a lock nobody holds.
+fn lock_is_held(path: &Path) -> Result<bool> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+        Err(error) => return Err(error).with_context(|| format!("failed to open lock {path:?}")),
+    };
+
+    #[cfg(unix)]
+    {
+        use std::os::fd::AsRawFd as _;
+
+        let status = unsafe {
+            platform_lib::flock(
+                file.as_raw_fd(),
+                platform_lib::LOCK_SH | platform_lib::LOCK_NB,
+            )
+        };
+        if status == 0 {
+            return Ok(false);
+        }
+        let error = std::io::Error::last_os_error();
+        if matches!(
+            error.kind(),
+            std::io::ErrorKind::WouldBlock | std::io::ErrorKind::PermissionDenied
+        ) {
+            return Ok(true);
+        }
+        Err(error).with_context(|| format!("failed to probe lock {path:?}"))
+    }
+
+    #[cfg(windows)]
+    {
+        use std::os::windows::io::AsRawHandle as _;
+        use windows_sys::Win32::Foundation::{ERROR_LOCK_VIOLATION, GetLastError};
+        use windows_sys::Win32::Storage::FileSystem::{LockFile, UnlockFile};
+
+        let handle = file.as_raw_handle();
+        if unsafe { LockFile(handle, 0, 0, u32::MAX, u32::MAX) } != 0 {
+            unsafe { UnlockFile(handle, 0, 0, u32::MAX, u32::MAX) };
+            return Ok(false);
+        }
+        if unsafe { GetLastError() } == ERROR_LOCK_VIOLATION {
+            return Ok(true);
+        }
+        Err(std::io::Error::last_os_error())
+            .with_context(|| format!("failed to probe lock {path:?}"))
+    }
+}
+
+/// Checks a service directory against the owner and mode rules `unix_security` enforces.
+fn inspect_directory(findings: &mut Vec<DoctorFinding>, path: &Path, runtime: bool) {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
+            findings.push(DoctorFinding::new(
+                "directory",
+                path,
+                DoctorSeverity::Ok,
+                "not created yet",
+            ));
+            return;
+        }
+        Err(error) => {
+            findings.push(DoctorFinding::new(
+                "directory",
+                path,
+                DoctorSeverity::Problem,
+                format!("cannot be inspected: {error}"),
+            ));
+            return;
+        }
+    };
+    if !metadata.is_dir() {
+        findings.push(DoctorFinding::new(
+            "directory",
+            path,
+            DoctorSeverity::Problem,
+            format!("expected a directory, found {}", file_kind(&metadata)),
+        ));
+        return;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt as _;
+
+        let expected_uid = crate::core::unix_security::expected_owner_uid();
+        let expected_mode = if runtime {
+            crate::core::unix_security::runtime_directory_mode()
+        } else {
+            0o700
+        };
+        let mode = (metadata.mode() & 0o7777) as platform_lib::mode_t;
+        let finding = if metadata.uid() != expected_uid {
+            DoctorFinding::new(
+                "directory",
+                path,
+                DoctorSeverity::Problem,
+                format!(
+                    "owned by uid {}, expected uid {expected_uid}; the service refuses to use it",
+                    metadata.uid()
+                ),
+            )
+        } else if mode != expected_mode {
+            DoctorFinding::new(
+                "directory",
+                path,
+                DoctorSeverity::Problem,
+                format!("mode {mode:04o}, expected {expected_mode:04o}"),
+            )
+            .repaired_by(Repair::SecureDirectory { runtime })
+        } else {
+            DoctorFinding::new(
+                "directory",
+                path,
+                DoctorSeverity::Ok,
+                format!("owner uid {expected_uid}, mode {mode:04o}"),
+            )
+        };
+        findings.push(finding);
+    }
+
+    #[cfg(windows)]
+    {
+        let _ = runtime;
+        findings.push(DoctorFinding::new(
+            "directory",
+            path,
+            DoctorSeverity::Ok,
+            "present",
+        ));
+    }
+}
+
+/// Checks that a private state file is owned and protected like the service writes it, and that
+/// it still parses. `corrupt` is how bad an unparseable file is for this kind of state.
+/// Checks that `path` is a regular file owned by the service, readable only by it unless
+/// `public`. Returns whether the file exists and its content is worth inspecting.
+fn inspect_service_file(
+    findings: &mut Vec<DoctorFinding>,
+    check: &'static str,
+    path: &Path,
+    public: bool,
+) -> bool {
+    let metadata = match std::fs::symlink_metadata(path) {
+        Ok(metadata) => metadata,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return false,
+        Err(error) => {
+            findings.push(DoctorFinding::new(
+                check,
+                path,
+                DoctorSeverity::Problem,
+                format!("cannot be inspected: {error}"),
+            ));
+            return false;
+        }
+    };
+    if !metadata.is_file() {
+        findings.push(DoctorFinding::new(
+            check,
+            path,
+            DoctorSeverity::Problem,
+            format!("expected a regular file, found {}", file_kind(&metadata)),
+        ));
+        return false;
+    }
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt as _;
+
+        let expected_uid = crate::core::unix_security::expected_owner_uid();
+        let expected_mode = if public { 0o644 } else { 0o600 };
+        let mode = metadata.mode() & 0o7777;
+        if metadata.uid() != expected_uid {
+            findings.push(DoctorFinding::new(
+                check,
+                path,
+                DoctorSeverity::Problem,
+                format!(
+                    "owned by uid {}, expected uid {expected_uid}; the service refuses to use it",
+                    metadata.uid()
+                ),
+            ));
+            return false;
+        }
+        if mode != expected_mode {
+            findings.push(
+                DoctorFinding::new(
+                    check,
+                    path,
+                    DoctorSeverity::Problem,
+                    format!("mode {mode:04o}, expected {expected_mode:04o}"),
+                )
+                .repaired_by(Repair::SecureFile { public }),
+            );
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = public;
+    true
+}
+
+fn inspect_state_file<T: DeserializeOwned>(
+    findings: &mut Vec<DoctorFinding>,
+    schema: &DocumentSchema,
+    path: &Path,
+    corrupt: DoctorSeverity,
+) {
+    let findings_before = findings.len();
+    if !inspect_service_file(findings, "state_file", path, false) {
+        return;
+    }
+
+    let mut detail = "parses".to_owned();
+    match std::fs::read(path) {
+        Ok(content) => match schema::decode::<T>(schema, &content) {
+            Ok(decoded) => {
+                if let Some(version) = decoded.migrated_from {
+                    detail = format!(
+                        "parses; schema version {version} is upgraded to {} on the next load",
+                        schema.current_version()
+                    );
+                }
+            }
+            // Moving it aside would lose what the newer service wrote; a downgrade needs a person.
+            Err(error) if schema::is_newer_schema(&error) => findings.push(DoctorFinding::new(
+                "state_file",
+                path,
+                DoctorSeverity::Problem,
+                format!("{error:#}"),
+            )),
+            Err(error) => findings.push(
+                DoctorFinding::new(
+                    "state_file",
+                    path,
+                    corrupt,
+                    format!("does not parse: {error:#}"),
+                )
+                .repaired_by(Repair::Quarantine),
+            ),
+        },
+        Err(error) => findings.push(DoctorFinding::new(
+            "state_file",
+            path,
+            DoctorSeverity::Problem,
+            format!("cannot be read: {error}"),
+        )),
+    }
+    if findings.len() == findings_before {
+        findings.push(DoctorFinding::new(
+            "state_file",
+            path,
+            DoctorSeverity::Ok,
+            detail,
+        ));
+    }
+}
+
+/// The audit log is only appended to, so a torn record is reported but left for queries to
+/// skip.
+fn inspect_audit_log(paths: &ServicePaths, findings: &mut Vec<DoctorFinding>) {
+    for path in audit_log_files(&paths.audit_log_path()) {
+        let findings_before = findings.len();
+        if !inspect_service_file(findings, "audit_log", &path, false) {
+            continue;
+        }
+        let finding = match std::fs::read_to_string(&path) {
+            Ok(content) => {
+                let lines = content.lines().count();
+                let torn = content
+                    .lines()
+                    .filter(|line| serde_json::from_str::<crate::AuditRecord>(line).is_err())
+                    .count();
+                if torn == 0 {
+                    DoctorFinding::new(
+                        "audit_log",
+                        &path,
+                        DoctorSeverity::Ok,
+                        format!("{lines} record(s)"),
+                    )
+                } else {
+                    DoctorFinding::new(
+                        "audit_log",
+                        &path,
+                        DoctorSeverity::Warning,
+                        format!("{torn} of {lines} line(s) are not records; queries skip them"),
+                    )
+                }
+            }
+            Err(error) => DoctorFinding::new(
+                "audit_log",
+                &path,
+                DoctorSeverity::Problem,
+                format!("cannot be read: {error}"),
+            ),
+        };
+        if finding.severity != DoctorSeverity::Ok || findings.len() == findings_before {
+            findings.push(finding);
+        }
+    }
+}
+
+/// Checks the private handshake key and the public half published beside the control socket,
+/// which a client compares the service's proof against.
+fn inspect_handshake_key(
+    paths: &ServicePaths,
+    service_running: bool,
+    findings: &mut Vec<DoctorFinding>,
+) {
+    let key_path = paths.handshake_key_path();
+    let mut expected_public_key = None;
+    let findings_before = findings.len();
+    if inspect_service_file(findings, "handshake_key", &key_path, false) {
+        match std::fs::read_to_string(&key_path) {
+            Ok(key) => match public_key_for(&key) {
+                Some(public_key) => expected_public_key = Some(public_key),
+                None => findings.push(
+                    DoctorFinding::new(
+                        "handshake_key",
+                        &key_path,
+                        DoctorSeverity::Warning,
+                        "malformed; the next start replaces it with a new key",
+                    )
+                    .repaired_by(Repair::Remove),
+                ),
+            },
+            Err(error) => findings.push(DoctorFinding::new(
+                "handshake_key",
+                &key_path,
+                DoctorSeverity::Problem,
+                format!("cannot be read: {error}"),
+            )),
+        }
+        if findings.len() == findings_before {
+            findings.push(DoctorFinding::new(
+                "handshake_key",
+                &key_path,
+                DoctorSeverity::Ok,
+                "parses",
+            ));
+        }
+    }
+
+    let public_path = paths.handshake_public_key_path();
+    let findings_before = findings.len();
+    if !inspect_service_file(findings, "handshake_public_key", &public_path, true) {
+        if service_running && findings.len() == findings_before {
+            findings.push(DoctorFinding::new(
+                "handshake_public_key",
+                &public_path,
+                DoctorSeverity::Warning,
+                "not published; clients cannot verify the running service",
+            ));
+        }
+        return;
+    }
+    if !service_running {
+        findings.push(
+            DoctorFinding::new(
+                "handshake_public_key",
+                &public_path,
+                DoctorSeverity::Warning,
+                "left published by a service that did not stop cleanly",
+            )
+            .repaired_by(Repair::Remove),
+        );
+        return;
+    }
+    match std::fs::read_to_string(&public_path) {
+        Ok(published) => {
+            if expected_public_key.is_some_and(|expected| published.trim() != expected) {
+                findings.push(DoctorFinding::new(
+                    "handshake_public_key",
+                    &public_path,
+                    DoctorSeverity::Problem,
+                    "does not match the handshake key; clients refuse the running service",
+                ));
+            }
+        }
+        Err(error) => findings.push(DoctorFinding::new(
+            "handshake_public_key",
+            &public_path,
+            DoctorSeverity::Problem,
+            format!("cannot be read: {error}"),
+        )),
+    }
+    if findings.len() == findings_before {
+        findings.push(DoctorFinding::new(
+            "handshake_public_key",
+            &public_path,
+            DoctorSeverity::Ok,
+            "matches the handshake key",
+        ));
+    }
+}
+
+/// A handoff request is consumed by the next shutdown or discarded by the next start.
+fn inspect_handoff_request(
+    paths: &ServicePaths,
+    service_running: bool,
+    findings: &mut Vec<DoctorFinding>,
+) {
+    let path = paths.core_handoff_path();
+    let findings_before = findings.len();
+    if !inspect_service_file(findings, "core_handoff", &path, false) {
+        return;
+    }
+    if !service_running {
+        findings.push(
+            DoctorFinding::new(
+                "core_handoff",
+                &path,
+                DoctorSeverity::Warning,
+                "left by an install that never restarted the service; the next start discards it",
+            )
+            .repaired_by(Repair::Remove),
+        );
+        return;
+    }
+    let finding = match std::fs::read(&path).map(|content| is_fresh_request(&content)) {
+        Ok(Ok(true)) => DoctorFinding::new(
+            "core_handoff",
+            &path,
+            DoctorSeverity::Ok,
+            "the service leaves its core running on its next shutdown",
+        ),
+        Ok(Ok(false)) => DoctorFinding::new(
+            "core_handoff",
+            &path,
+            DoctorSeverity::Warning,
+            "expired; the next shutdown stops the core as usual",
+        ),
+        Ok(Err(error)) => DoctorFinding::new(
+            "core_handoff",
+            &path,
+            DoctorSeverity::Warning,
+            format!("does not parse: {error}; the next shutdown stops the core as usual"),
+        ),
+        Err(error) => DoctorFinding::new(
+            "core_handoff",
+            &path,
+            DoctorSeverity::Problem,
+            format!("cannot be read: {error}"),
+        ),
+    };
+    if finding.severity != DoctorSeverity::Ok || findings.len() == findings_before {
+        findings.push(finding);
+    }
+}
+
+fn inspect_owner_states(paths: &ServicePaths, findings: &mut Vec<DoctorFinding>) {
+    // An unreadable users directory is already reported by its directory check.
+    let Ok(entries) = std::fs::read_dir(paths.persistent_state_dir().join("users")) else {
+        return;
+    };
+    let mut keys: Vec<String> = entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str().map(str::to_owned))
+        .collect();
+    keys.sort();
+
+    for key in keys {
+        let owner = paths.for_owner_key(&key);
+        let before = findings.len();
+        inspect_directory(findings, owner.root(), false);
+        let usable = findings[before..]
+            .iter()
+            .all(|finding| finding.severity != DoctorSeverity::Problem || finding.repair.is_some());
+        if !usable {
+            continue;
+        }
+        // The service quarantines a corrupt desired state itself and starts without the hint.
+        inspect_state_file::<DesiredState>(
+            findings,
+            &schema::DESIRED_STATE,
+            &owner.desired_state_path(),
+            DoctorSeverity::Warning,
+        );
+        inspect_state_file::<OwnerIdentity>(
+            findings,
+            &schema::OWNER_IDENTITY,
+            &owner.root().join("owner.json"),
+            DoctorSeverity::Problem,
+        );
+        inspect_leftovers(findings, owner.root());
+    }
+}
+
+/// Compares the core runtime record with the process it names, the way startup reconciliation
+/// will, without stopping or adopting anything.
+async fn inspect_core_runtime(
+    paths: &ServicePaths,
+    service_running: bool,
+    findings: &mut Vec<DoctorFinding>,
+) {
+    let path = paths.core_runtime_path();
+    let record = match read_core_runtime_record().await {
+        Ok(Some(record)) => record,
+        Ok(None) => return,
+        Err(error) => {
+            findings.push(
+                DoctorFinding::new(
+                    "core_runtime",
+                    path,
+                    DoctorSeverity::Problem,
+                    format!("{error:#}; startup reconciliation fails until it is removed"),
+                )
+                .repaired_by(Repair::Remove),
+            );
+            return;
+        }
+    };
+    let identity = match process_identity(record.pid) {
+        Ok(identity) => identity,
+        Err(error) => {
+            findings.push(DoctorFinding::new(
+                "core_runtime",
+                path,
+                DoctorSeverity::Problem,
+                format!("cannot inspect core pid {}: {error:#}", record.pid),
+            ));
+            return;
+        }
+    };
+
+    let pid = record.pid;
+    let finding = match (identity.as_ref() == Some(&record.identity), service_running) {
+        (true, true) => DoctorFinding::new(
+            "core_runtime",
+            path,
+            DoctorSeverity::Ok,
+            format!("core pid {pid} is running"),
+        ),
+        (true, false) => DoctorFinding::new(
+            "core_runtime",
+            path,
+            DoctorSeverity::Warning,
+            format!("core pid {pid} outlived the service; the next start adopts or stops it"),
+        ),
+        (false, true) => DoctorFinding::new(
+            "core_runtime",
+            path,
+            DoctorSeverity::Warning,
+            format!("names core pid {pid}, which is no longer the recorded process"),
+        ),
+        (false, false) => {
+            #[cfg(unix)]
+            if !crate::core::runtime::is_core_socket_reachable(&record.ipc_path).await
+                && std::fs::symlink_metadata(&record.ipc_path).is_ok()
+            {
+                findings.push(
+                    DoctorFinding::new(
+                        "core_socket",
+                        &record.ipc_path,
+                        DoctorSeverity::Warning,
+                        format!("left behind by core pid {pid}, which is no longer running"),
+                    )
+                    .repaired_by(Repair::Remove),
+                );
+            }
+            DoctorFinding::new(
+                "core_runtime",
+                path,
+                DoctorSeverity::Warning,
+                format!("stale record of core pid {pid}, which is no longer running"),
+            )
+            .repaired_by(Repair::Remove)
+        }
+    };
+    findings.push(finding);
+}
+
+fn inspect_leftovers(findings: &mut Vec<DoctorFinding>, directory: &Path) {
+    let Ok(entries) = std::fs::read_dir(directory) else {
+        return;
+    };
+    let mut leftovers: Vec<(PathBuf, &'static str)> = entries
+        .flatten()
+        .filter_map(|entry| {
+            let kind = leftover_kind(entry.file_name().to_str()?)?;
+            Some((entry.path(), kind))
+        })
+        .collect();
+    leftovers.sort();
+    for (path, kind) in leftovers {
+        findings.push(
+            DoctorFinding::new("leftover", path, DoctorSeverity::Warning, kind)
+                .repaired_by(Repair::Remove),
+        );
+    }
+}
+
+/// Recognizes the sibling names `desired` and the runtime record use for quarantined and
+/// half-written state.
+fn leftover_kind(file_name: &str) -> Option<&'static str> {
+    if file_name.contains(".corrupt-") {
+        Some("quarantined copy of a corrupt state file")
+    } else if file_name.contains(".tmp-") || file_name.ends_with(".tmp") {
+        Some("temporary file from an interrupted write")
+    } else {
+        None
+    }
+}
+
+fn file_kind(metadata: &Metadata) -> &'static str {
+    let file_type = metadata.file_type();
+    if file_type.is_symlink() {
+        "a symbolic link"
+    } else if file_type.is_dir() {
+        "a directory"
+    } else if file_type.is_file() {
+        "a regular file"
+    } else {
+        "a special file"
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DoctorFinding, DoctorReport, DoctorSeverity, Repair, leftover_kind};
+    #[cfg(unix)]
+    use super::{inspect_handoff_request, inspect_handshake_key};
+    use crate::ServiceMode;
+
+    #[test]
+    fn leftovers_match_quarantine_and_temporary_names_only() {
+        assert!(leftover_kind("desired-state.json.corrupt-12-345-0").is_some());
+        assert!(leftover_kind("desired-state.json.tmp-12-345-1").is_some());
+        assert!(leftover_kind("clash-verge-service.core.json.tmp").is_some());
+        assert!(leftover_kind("owner.json.tmp").is_some());
+        assert!(leftover_kind("desired-state.json").is_none());
+        assert!(leftover_kind("clash-verge-service.owner.lock").is_none());
+    }
+
+    #[test]
+    fn fixed_problems_no_longer_fail_the_report() {
+        let mut report = DoctorReport {
+            channel: "production",
+            mode: ServiceMode::System,
+            service_running: false,
+            findings: vec![
+                DoctorFinding::new("leftover", "/state/x.tmp", DoctorSeverity::Warning, "left")
+                    .repaired_by(Repair::Remove),
+                DoctorFinding::new(
+                    "state_file",
+                    "/state/a.json",
+                    DoctorSeverity::Problem,
+                    "bad",
+                )
+                .repaired_by(Repair::Quarantine),
+            ],
+        };
+        assert!(report.has_problems());
+
+        report.findings[1].fixed = true;
+        assert!(!report.has_problems());
+        let rendered = report.to_string();
+        assert!(rendered.contains("[  fixed] state_file: /state/a.json"));
+        assert!(rendered.ends_with("0 problem(s), 1 warning(s)"));
+    }
+
+    /// A stopped service should have withdrawn its public key, and a handoff request only means
+    /// something to a running one.
+    #[cfg(unix)]
+    #[test]
+    fn published_keys_and_handoff_requests_are_judged_by_whether_the_service_runs()
+    -> anyhow::Result<()> {
+        use anyhow::Context as _;
+        use std::fs::Permissions;
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let root = std::env::temp_dir().join(format!("service-doctor-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        let paths = crate::core::paths::rooted_service_paths(
+            &crate::CHANNEL_IDENTITY,
+            ServiceMode::System,
+            &root,
+        );
+        std::fs::create_dir_all(paths.runtime_dir())?;
+        std::fs::create_dir_all(paths.persistent_state_dir())?;
+        let key = "11".repeat(32);
+        let public_key = super::public_key_for(&key).context("the test key is malformed")?;
+        for (path, content, mode) in [
+            (paths.handshake_key_path(), key, 0o600),
+            (paths.handshake_public_key_path(), public_key, 0o644),
+            (
+                paths.core_handoff_path(),
+                r#"{"requested_at":0}"#.to_owned(),
+                0o600,
+            ),
+        ] {
+            std::fs::write(&path, content)?;
+            std::fs::set_permissions(&path, Permissions::from_mode(mode))?;
+        }
+        let judge = |service_running| {
+            let mut findings = Vec::new();
+            inspect_handshake_key(&paths, service_running, &mut findings);
+            inspect_handoff_request(&paths, service_running, &mut findings);
+            findings
+                .into_iter()
+                .map(|finding| (finding.check, finding.severity, finding.repair))
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            judge(false),
+            [
+                ("handshake_key", DoctorSeverity::Ok, None),
+                (
+                    "handshake_public_key",
+                    DoctorSeverity::Warning,
+                    Some(Repair::Remove)
+                ),
+                (
+                    "core_handoff",
+                    DoctorSeverity::Warning,
+                    Some(Repair::Remove)
+                ),
+            ]
+        );
+        assert_eq!(
+            judge(true),
+            [
+                ("handshake_key", DoctorSeverity::Ok, None),
+                ("handshake_public_key", DoctorSeverity::Ok, None),
+                ("core_handoff", DoctorSeverity::Warning, None),
+            ]
+        );
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+//! Core handoff across a service upgrade.
+//! The installer leaves a request in the root-owned runtime directory before restarting the unit;
+//! the outgoing service then exits without stopping its core, and the replacement adopts it from
+//! the runtime record during startup reconciliation.
+
+use crate::core::desired::sibling_state_path;
+use crate::core::paths::service_paths;
+use crate::core::platform_security;
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::io::Write as _;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// A request older than this belongs to an install that never restarted the service.
+const HANDOFF_REQUEST_TTL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Serialize, Deserialize)]
+struct HandoffRequest {
+    requested_at: u64,
+}
+
+fn unix_timestamp_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Asks the running service to leave its core running on its next shutdown.
+/// Only root can write the request, since it lives in the service runtime directory. The request
+/// is secured in a temporary sibling before it is moved into place, so the service never reads a
+/// partial request or one with loose permissions.
+pub fn request_core_handoff() -> Result<()> {
+    anyhow::ensure!(
+        cfg!(target_os = "linux"),
+        "core handoff is only supported on Linux"
+    );
+    let path = service_paths().core_handoff_path();
+    let temporary = sibling_state_path(&path, "tmp");
+    let request = serde_json::to_vec(&HandoffRequest {
+        requested_at: unix_timestamp_secs(),
+    })?;
+    let result = (|| {
+        let mut file = std::fs::OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&temporary)
+            .with_context(|| format!("failed to create core handoff request {temporary:?}"))?;
+        file.write_all(&request)
+            .and_then(|()| file.sync_all())
+            .with_context(|| format!("failed to write core handoff request {temporary:?}"))?;
+        drop(file);
+        platform_security::secure_private_service_file_if_exists(&temporary)?;
+        std::fs::rename(&temporary, &path)
+            .with_context(|| format!("failed to publish core handoff request {path:?}"))
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&temporary);
+    }
+    result
+}
+
+/// Withdraws a request whose restart did not happen.
+pub fn cancel_core_handoff() {
+    let _ = std::fs::remove_file(service_paths().core_handoff_path());
+}
+
+/// Consumes a pending request and reports whether it is still fresh enough to honour.
+pub(super) async fn take_core_handoff_request() -> bool {
+    let path = service_paths().core_handoff_path();
+    let content = match tokio::fs::read(&path).await {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return false,
+        Err(error) => {
+            warn!("Failed to read core handoff request {path:?}: {error}");
+            return false;
+        }
+    };
+    let _ = tokio::fs::remove_file(&path).await;
+    if !cfg!(target_os = "linux") {
+        return false;
+    }
+    match is_fresh_request(&content) {
+        Ok(true) => {
+            info!("Core handoff requested; leaving the core running for the next service");
+            true
+        }
+        Ok(false) => {
+            warn!("Ignoring expired core handoff request");
+            false
+        }
+        Err(error) => {
+            warn!("Ignoring malformed core handoff request: {error}");
+            false
+        }
+    }
+}
+
+/// Whether the stored request would still be honoured now.
+pub(super) fn is_fresh_request(content: &[u8]) -> Result<bool, serde_json::Error> {
+    let request = serde_json::from_slice::<HandoffRequest>(content)?;
+    Ok(is_fresh(&request, unix_timestamp_secs()))
+}
+
+fn is_fresh(request: &HandoffRequest, now: u64) -> bool {
+    now.saturating_sub(request.requested_at) <= HANDOFF_REQUEST_TTL.as_secs()
+        && request.requested_at <= now
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HANDOFF_REQUEST_TTL, HandoffRequest, is_fresh};
+    #[cfg(target_os = "linux")]
+    use super::{request_core_handoff, take_core_handoff_request};
+    #[cfg(target_os = "linux")]
+    use serial_test::serial;
+
+    #[test]
+    fn only_recent_requests_are_honoured() {
+        let now = 1_000_000;
+        let request = |requested_at| HandoffRequest { requested_at };
+
+        assert!(is_fresh(&request(now), now));
+        assert!(is_fresh(&request(now - HANDOFF_REQUEST_TTL.as_secs()), now));
+        assert!(!is_fresh(
+            &request(now - HANDOFF_REQUEST_TTL.as_secs() - 1),
+            now
+        ));
+        assert!(!is_fresh(&request(now + 5), now));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[tokio::test]
+    #[serial]
+    async fn a_request_is_private_complete_and_consumed_once() -> anyhow::Result<()> {
+        use std::os::unix::fs::PermissionsExt as _;
+
+        let path = crate::core::paths::service_paths().core_handoff_path();
+        std::fs::create_dir_all(path.parent().expect("handoff path has a parent"))?;
+        request_core_handoff()?;
+
+        assert_eq!(std::fs::metadata(&path)?.permissions().mode() & 0o077, 0);
+        let siblings = std::fs::read_dir(path.parent().unwrap())?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry.file_name().to_string_lossy().starts_with(&format!(
+                    "{}.tmp",
+                    path.file_name().unwrap().to_string_lossy()
+                ))
+            })
+            .count();
+        assert_eq!(siblings, 0, "no temporary request may remain");
+        assert!(take_core_handoff_request().await);
+        assert!(!path.exists());
+        assert!(!take_core_handoff_request().await);
+        Ok(())
+    }
+}
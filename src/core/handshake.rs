@@ -0,0 +1,253 @@
+//! Lets the service prove it is the installed one before a client trusts it with credentials.
+//! The first start creates an Ed25519 key in the private persistent state directory and every
+//! start publishes its public half next to the control socket, where only the service can write.
+//! Stopping withdraws it again, so whatever binary serves these paths next is judged on its own.
+//! `/handshake` signs the client's nonce together with the negotiated protocol; the client checks
+//! that signature against the published key.
+
+use crate::core::desired::sibling_state_path;
+use crate::core::paths::{ServicePaths, service_paths};
+use crate::core::platform_security;
+use crate::core::structure::{decode_hex, handshake_transcript};
+use crate::{HANDSHAKE_NONCE_HEX_LEN, HandshakeProof, ProtocolInfo, ProtocolVersion};
+use anyhow::{Context as _, Result, anyhow};
+use ed25519_dalek::{Signer as _, SigningKey};
+use std::path::Path;
+use std::sync::RwLock;
+use tracing::warn;
+
+/// Replaced on every start, since a later server in this process may serve other paths.
+static HANDSHAKE_KEY: RwLock<Option<SigningKey>> = RwLock::new(None);
+
+/// Loads or creates the handshake key and publishes its public half. Called before the control
+/// socket is bound, so no client reaches a service whose key is missing.
+pub(crate) async fn prepare_handshake_key() -> Result<()> {
+    let paths = service_paths();
+    crate::core::paths::ensure_persistent_state_layout()?;
+    #[cfg(unix)]
+    platform_security::ensure_runtime_directory(paths.runtime_dir())?;
+    #[cfg(windows)]
+    platform_security::ensure_private_service_directory(paths.runtime_dir())?;
+    let key = load_or_create_key(&paths.handshake_key_path()).await?;
+    publish_public_key(&key, &paths.handshake_public_key_path()).await?;
+    *HANDSHAKE_KEY
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(key);
+    Ok(())
+}
+
+/// Removes the published public key when the service stops, so a binary that predates the
+/// handshake and takes over these paths is still reached through the legacy probe.
+pub(super) async fn withdraw_published_key() -> Result<()> {
+    let path = service_paths().handshake_public_key_path();
+    match tokio::fs::remove_file(&path).await {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error).with_context(|| format!("failed to withdraw {path:?}")),
+    }
+}
+
+/// Removes the public key a stopped service left behind. Installers call it after they stop the
+/// service; the private key stays, so the next start publishes the same key again.
+pub fn withdraw_handshake_key(paths: &ServicePaths) -> Result<()> {
+    let path = paths.handshake_public_key_path();
+    match std::fs::remove_file(&path) {
+        Ok(()) => Ok(()),
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(error) => Err(error).with_context(|| format!("failed to withdraw {path:?}")),
+    }
+}
+
+pub(super) fn is_handshake_nonce(nonce: &str) -> bool {
+    nonce.len() == HANDSHAKE_NONCE_HEX_LEN
+        && nonce
+            .bytes()
+            .all(|byte| matches!(byte, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Answers a handshake, or `None` while the key is not loaded yet.
+pub(super) fn sign_handshake(
+    nonce: &str,
+    info: ProtocolInfo,
+    negotiated: ProtocolVersion,
+) -> Option<HandshakeProof> {
+    let key = HANDSHAKE_KEY
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()?;
+    Some(proof(
+        &key,
+        service_paths().channel().service_slug,
+        nonce,
+        info,
+        negotiated,
+    ))
+}
+
+fn proof(
+    key: &SigningKey,
+    service_slug: &str,
+    nonce: &str,
+    info: ProtocolInfo,
+    negotiated: ProtocolVersion,
+) -> HandshakeProof {
+    let transcript = handshake_transcript(service_slug, nonce, &info, negotiated);
+    HandshakeProof {
+        signature: hex(&key.sign(&transcript).to_bytes()),
+        info,
+        negotiated,
+    }
+}
+
+/// A key that cannot be read back is replaced: clients read the published half on every
+/// connection, so a new key only costs the proofs that were in flight.
+async fn load_or_create_key(path: &Path) -> Result<SigningKey> {
+    match tokio::fs::read_to_string(path).await {
+        Ok(content) => {
+            if let Some(seed) = decode_hex(content.trim()) {
+                platform_security::secure_private_service_file_if_exists(path)?;
+                return Ok(SigningKey::from_bytes(&seed));
+            }
+            warn!("Replacing the malformed handshake key {path:?}");
+        }
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read handshake key {path:?}"));
+        }
+    }
+    let mut seed = [0_u8; ed25519_dalek::SECRET_KEY_LENGTH];
+    getrandom::fill(&mut seed).map_err(|error| anyhow!("failed to generate a key: {error}"))?;
+    replace_key_file(
+        path,
+        &hex(&seed),
+        platform_security::secure_private_service_file_if_exists,
+    )
+    .await?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+async fn publish_public_key(key: &SigningKey, path: &Path) -> Result<()> {
+    let public_key = hex(key.verifying_key().as_bytes());
+    if tokio::fs::read_to_string(path)
+        .await
+        .is_ok_and(|published| published.trim() == public_key)
+    {
+        return platform_security::secure_public_service_file_if_exists(path);
+    }
+    replace_key_file(
+        path,
+        &public_key,
+        platform_security::secure_public_service_file_if_exists,
+    )
+    .await
+}
+
+/// Secures the key in a temporary sibling before moving it into place, so readers only ever see
+/// a complete key with its final permissions.
+async fn replace_key_file(
+    path: &Path,
+    content: &str,
+    secure: impl Fn(&Path) -> Result<()>,
+) -> Result<()> {
+    let temp_path = sibling_state_path(path, "tmp");
+    let result = async {
+        tokio::fs::write(&temp_path, format!("{content}\n"))
+            .await
+            .with_context(|| format!("failed to write key temp file {temp_path:?}"))?;
+        secure(&temp_path)?;
+        crate::core::atomic_file::replace(&temp_path, path)
+            .await
+            .with_context(|| format!("failed to move key into {path:?}"))
+    }
+    .await;
+    if result.is_err() {
+        let _ = tokio::fs::remove_file(&temp_path).await;
+    }
+    result
+}
+
+/// The public key a service holding the stored `key` publishes, or `None` when it is malformed.
+pub(super) fn public_key_for(key: &str) -> Option<String> {
+    let seed = decode_hex(key.trim())?;
+    Some(hex(SigningKey::from_bytes(&seed)
+        .verifying_key()
+        .as_bytes()))
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_handshake_nonce, load_or_create_key, proof, publish_public_key};
+    use crate::core::structure::{decode_hex, handshake_transcript};
+    use crate::{ProtocolInfo, ProtocolVersion};
+    use ed25519_dalek::{Signature, VerifyingKey};
+    use std::path::PathBuf;
+
+    fn key_dir(name: &str) -> anyhow::Result<PathBuf> {
+        let dir =
+            std::env::temp_dir().join(format!("service-handshake-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir)?;
+        Ok(dir)
+    }
+
+    #[tokio::test]
+    async fn the_key_survives_restarts_and_a_malformed_one_is_replaced() -> anyhow::Result<()> {
+        let dir = key_dir("persist")?;
+        let path = dir.join("handshake.key");
+        let created = load_or_create_key(&path).await?;
+        assert_eq!(load_or_create_key(&path).await?, created);
+
+        std::fs::write(&path, "not a key")?;
+        let replaced = load_or_create_key(&path).await?;
+        assert_ne!(replaced, created);
+        assert_eq!(load_or_create_key(&path).await?, replaced);
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn the_published_key_verifies_only_the_signed_transcript() -> anyhow::Result<()> {
+        let dir = key_dir("publish")?;
+        let key = load_or_create_key(&dir.join("handshake.key")).await?;
+        let public_path = dir.join("handshake.pub");
+        publish_public_key(&key, &public_path).await?;
+        let published = decode_hex(std::fs::read_to_string(&public_path)?.trim())
+            .expect("published key should be hex");
+        let public_key = VerifyingKey::from_bytes(&published)?;
+
+        let nonce = "ab".repeat(32);
+        let info = ProtocolInfo::current();
+        let negotiated = ProtocolVersion::current();
+        let proof = proof(&key, "service", &nonce, info.clone(), negotiated);
+        let signature =
+            Signature::from_bytes(&decode_hex(&proof.signature).expect("signature should be hex"));
+        let verify = |slug: &str, nonce: &str, negotiated: ProtocolVersion| {
+            public_key.verify_strict(
+                &handshake_transcript(slug, nonce, &info, negotiated),
+                &signature,
+            )
+        };
+
+        assert!(verify("service", &nonce, negotiated).is_ok());
+        assert!(verify("other-service", &nonce, negotiated).is_err());
+        assert!(verify("service", &"cd".repeat(32), negotiated).is_err());
+        let downgraded = ProtocolVersion {
+            revision: negotiated.revision - 1,
+            ..negotiated
+        };
+        assert!(verify("service", &nonce, downgraded).is_err());
+        std::fs::remove_dir_all(dir)?;
+        Ok(())
+    }
+
+    #[test]
+    fn nonces_are_lowercase_hex_of_a_fixed_length() {
+        assert!(is_handshake_nonce(&"0f".repeat(32)));
+        assert!(!is_handshake_nonce(&"0F".repeat(32)));
+        assert!(!is_handshake_nonce(&"0f".repeat(31)));
+    }
+}
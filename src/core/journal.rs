@@ -0,0 +1,182 @@
+//! Intent journal for owner transitions.
+//! `StartClash` clears the old proxy, stops the old core, starts the new one, commits the new
+//! owner and applies its proxy, and each step persists on its own. The journal is written before
+//! the first step, so a service that dies between steps can tell at startup what it was doing.
+//! Recovery keys off one commit point: once `active-owner.json` holds the new session, the switch
+//! is completed; before that, the owner records from before the transition are put back.
+
+use crate::MacosProxyConfig;
+use crate::core::auth::{AuthenticatedOwner, hash_session_token};
+use crate::core::desired::{
+    ActiveOwnerState, DesiredState, load_active_owner, load_owner_desired_state,
+    restore_active_owner, restore_owner_core_intent, write_document_atomic,
+};
+use crate::core::paths::service_paths;
+use crate::core::schema;
+use anyhow::{Context as _, Result};
+use serde::{Deserialize, Serialize};
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::{info, warn};
+
+/// Last step of an owner transition known to have completed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub(super) enum OwnerTransitionStep {
+    Started,
+    PreviousProxyCleared,
+    PreviousCoreStopped,
+    NewCoreStarted,
+    NewOwnerCommitted,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(super) struct OwnerTransitionRecord {
+    pub previous_owner: Option<ActiveOwnerState>,
+    pub previous_core_should_be_running: bool,
+    pub previous_proxy_config: Option<MacosProxyConfig>,
+    pub next_owner_key: String,
+    pub next_session_token_hash: String,
+    pub next_core_should_be_running: bool,
+    pub next_proxy_config: Option<MacosProxyConfig>,
+    pub step: OwnerTransitionStep,
+    pub started_at: u64,
+}
+
+/// An open journal; the transition updates it after each step and closes it when it returns.
+pub(super) struct OwnerTransitionJournal {
+    record: OwnerTransitionRecord,
+}
+
+impl OwnerTransitionJournal {
+    pub(super) async fn begin(
+        previous_owner: Option<&ActiveOwnerState>,
+        next_owner: &AuthenticatedOwner,
+        next_session_token: &str,
+    ) -> Result<Self> {
+        let path = service_paths().owner_transition_path();
+        // Callers hold the owner lifecycle lock and startup recovery runs before the socket is
+        // served, so a journal still here belongs to a transition that returned but failed to
+        // close it. Its records already match what the transition left, so it is only cleared.
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => warn!("Cleared the unclosed journal of a finished owner transition {path:?}"),
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => {
+                return Err(error).with_context(|| {
+                    format!("failed to clear the unclosed owner transition journal {path:?}")
+                });
+            }
+        }
+        let previous = match previous_owner {
+            Some(previous) => load_owner_desired_state(&previous.owner_key).await?,
+            None => DesiredState::default(),
+        };
+        let next = load_owner_desired_state(&next_owner.key).await?;
+        let record = OwnerTransitionRecord {
+            previous_owner: previous_owner.cloned(),
+            previous_core_should_be_running: previous.core_should_be_running,
+            previous_proxy_config: previous.last_proxy_config,
+            next_owner_key: next_owner.key.clone(),
+            next_session_token_hash: hash_session_token(next_session_token)?,
+            next_core_should_be_running: next.core_should_be_running,
+            next_proxy_config: next.last_proxy_config,
+            step: OwnerTransitionStep::Started,
+            started_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+        };
+        write_document_atomic(&schema::OWNER_TRANSITION, &path, &record)
+            .await
+            .context("failed to write the owner transition journal")?;
+        Ok(Self { record })
+    }
+
+    /// Recovery decides from the commit point, so a step that fails to record only makes the
+    /// journal less precise for diagnosis.
+    pub(super) async fn record(&mut self, step: OwnerTransitionStep) {
+        self.record.step = step;
+        let path = service_paths().owner_transition_path();
+        if let Err(error) =
+            write_document_atomic(&schema::OWNER_TRANSITION, &path, &self.record).await
+        {
+            warn!("Failed to record owner transition step {step:?}: {error:#}");
+        }
+    }
+
+    /// The transition returned, successfully or after compensating in process. A journal that
+    /// cannot be removed here is cleared by the next `begin`.
+    pub(super) async fn close(self) {
+        let path = service_paths().owner_transition_path();
+        match tokio::fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => {}
+            Err(error) => warn!(
+                "Failed to remove owner transition journal {:?}; the next transition will clear it: {error}",
+                path
+            ),
+        }
+    }
+}
+
+/// What startup recovery did with a journal left by an interrupted transition.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum OwnerTransitionRecovery {
+    Completed,
+    RolledBack,
+}
+
+pub(super) async fn recover_owner_transition() -> Result<Option<OwnerTransitionRecovery>> {
+    let path = service_paths().owner_transition_path();
+    let content = match tokio::fs::read(&path).await {
+        Ok(content) => content,
+        Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to read {path:?}"));
+        }
+    };
+    let record: OwnerTransitionRecord =
+        schema::decode_file(&schema::OWNER_TRANSITION, &path, &content)?;
+    let active = load_active_owner().await?;
+    let committed = active.as_ref().is_some_and(|active| {
+        active.owner_key == record.next_owner_key
+            && active
+                .sessions
+                .iter()
+                .any(|session| session.token_hash == record.next_session_token_hash)
+    });
+
+    let recovery = if committed {
+        // Only the proxy was left to apply; restoring desired state reapplies the recorded one.
+        info!(
+            "Completing the interrupted transition to owner {} after step {:?}",
+            record.next_owner_key, record.step
+        );
+        OwnerTransitionRecovery::Completed
+    } else {
+        warn!(
+            "Rolling back the interrupted transition to owner {} after step {:?}",
+            record.next_owner_key, record.step
+        );
+        // Restore the next owner first so the previous owner's intent wins when they are the same.
+        restore_owner_core_intent(
+            &record.next_owner_key,
+            record.next_core_should_be_running,
+            record.next_proxy_config.as_ref(),
+        )
+        .await?;
+        if let Some(previous) = record.previous_owner.as_ref() {
+            restore_owner_core_intent(
+                &previous.owner_key,
+                record.previous_core_should_be_running,
+                record.previous_proxy_config.as_ref(),
+            )
+            .await?;
+        }
+        restore_active_owner(record.previous_owner.as_ref()).await?;
+        OwnerTransitionRecovery::RolledBack
+    };
+    tokio::fs::remove_file(&path)
+        .await
+        .with_context(|| format!("failed to remove recovered owner transition {path:?}"))?;
+    Ok(Some(recovery))
+}
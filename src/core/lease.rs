@@ -0,0 +1,247 @@
+//! Expiry clock for the active session's lease.
+//! The lease terms are persisted with the active owner, but renewals only move an in-memory
+//! deadline so a heartbeat never touches disk. A restarted service therefore gives the owner one
+//! full `ttl_secs` to reconnect before the expiry policy applies.
+
+use crate::core::desired::{ActiveOwnerState, load_active_owner};
+use crate::{LeaseExpiryPolicy, SessionLease, SessionLeaseStatus};
+use anyhow::bail;
+use once_cell::sync::Lazy;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tracing::{info, warn};
+
+const MIN_LEASE_TTL_SECS: u64 = 5;
+const MAX_LEASE_TTL_SECS: u64 = 24 * 60 * 60;
+const LEASE_CHECK_INTERVAL: Duration = Duration::from_secs(1);
+
+struct LeaseClock {
+    owner_key: String,
+    generation: u64,
+    lease: SessionLease,
+    deadline: Instant,
+    expires_at: u64,
+    expired: bool,
+}
+
+impl LeaseClock {
+    fn start(owner_key: &str, generation: u64, lease: SessionLease, now: Instant) -> Self {
+        let mut clock = Self {
+            owner_key: owner_key.to_owned(),
+            generation,
+            lease,
+            deadline: now,
+            expires_at: 0,
+            expired: false,
+        };
+        clock.renew(now);
+        clock
+    }
+
+    fn renew(&mut self, now: Instant) {
+        let ttl = Duration::from_secs(self.lease.ttl_secs);
+        self.deadline = now + ttl;
+        self.expires_at = unix_now().saturating_add(self.lease.ttl_secs);
+        self.expired = false;
+    }
+
+    fn belongs_to(&self, active: &ActiveOwnerState) -> bool {
+        self.owner_key == active.owner_key && self.generation == active.generation
+    }
+
+    fn status(&self) -> SessionLeaseStatus {
+        SessionLeaseStatus {
+            ttl_secs: self.lease.ttl_secs,
+            on_expiry: self.lease.on_expiry,
+            expires_at: self.expires_at,
+            expired: self.expired,
+        }
+    }
+}
+
+static LEASE_CLOCK: Lazy<Mutex<Option<LeaseClock>>> = Lazy::new(|| Mutex::new(None));
+
+/// A lease that ran out and still has to have its policy applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ExpiredLease {
+    pub owner_key: String,
+    pub generation: u64,
+    pub on_expiry: LeaseExpiryPolicy,
+}
+
+pub(super) fn validate_lease(lease: &SessionLease) -> anyhow::Result<()> {
+    if !(MIN_LEASE_TTL_SECS..=MAX_LEASE_TTL_SECS).contains(&lease.ttl_secs) {
+        bail!("lease ttl_secs must be between {MIN_LEASE_TTL_SECS} and {MAX_LEASE_TTL_SECS}");
+    }
+    Ok(())
+}
+
+/// Starts the clock for a newly active session, replacing any earlier session's clock.
+pub(super) fn arm_lease(active: &ActiveOwnerState) {
+    *lock_clock() = active.lease.map(|lease| {
+        LeaseClock::start(&active.owner_key, active.generation, lease, Instant::now())
+    });
+}
+
+pub(super) fn disarm_lease() {
+    lock_clock().take();
+}
+
+/// Pushes the deadline out by one `ttl_secs`. An expired lease starts again, since the owner is
+/// evidently back; with `stop_core` there is no session left to renew.
+pub(super) fn renew_lease(active: &ActiveOwnerState) -> Option<SessionLeaseStatus> {
+    let mut clock = lock_clock();
+    let clock = clock.as_mut().filter(|clock| clock.belongs_to(active))?;
+    clock.renew(Instant::now());
+    Some(clock.status())
+}
+
+pub(super) fn lease_status(active: &ActiveOwnerState) -> Option<SessionLeaseStatus> {
+    lock_clock()
+        .as_ref()
+        .filter(|clock| clock.belongs_to(active))
+        .map(LeaseClock::status)
+}
+
+/// Marks a lease whose deadline passed as expired and hands it out once.
+fn take_expired_lease(now: Instant) -> Option<ExpiredLease> {
+    let mut clock = lock_clock();
+    let clock = clock
+        .as_mut()
+        .filter(|clock| !clock.expired && clock.deadline <= now)?;
+    clock.expired = true;
+    Some(ExpiredLease {
+        owner_key: clock.owner_key.clone(),
+        generation: clock.generation,
+        on_expiry: clock.lease.on_expiry,
+    })
+}
+
+/// Whether `expired` is still the current, unrenewed lease once the lifecycle lock is held.
+pub(super) fn is_still_expired(expired: &ExpiredLease) -> bool {
+    lock_clock().as_ref().is_some_and(|clock| {
+        clock.expired
+            && clock.owner_key == expired.owner_key
+            && clock.generation == expired.generation
+    })
+}
+
+/// Arms the lease of the session that survived a restart and applies expiry policies until the
+/// service shuts down.
+pub(super) async fn spawn_lease_watcher() -> JoinHandle<()> {
+    match load_active_owner().await {
+        Ok(Some(active)) => {
+            if active.lease.is_some() {
+                info!("Rearming the session lease of owner {}", active.owner_key);
+            }
+            arm_lease(&active);
+        }
+        Ok(None) => disarm_lease(),
+        Err(error) => warn!("Failed to load the active owner's session lease: {error:#}"),
+    }
+    tokio::spawn(async {
+        let mut interval = tokio::time::interval(LEASE_CHECK_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        loop {
+            interval.tick().await;
+            if let Some(expired) = take_expired_lease(Instant::now()) {
+                crate::core::server::expire_owner_lease(expired).await;
+            }
+        }
+    })
+}
+
+fn lock_clock() -> std::sync::MutexGuard<'static, Option<LeaseClock>> {
+    LEASE_CLOCK.lock().unwrap()
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ExpiredLease, arm_lease, disarm_lease, is_still_expired, lease_status, renew_lease,
+        take_expired_lease, validate_lease,
+    };
+    use crate::core::desired::ActiveOwnerState;
+    use crate::{LeaseExpiryPolicy, OwnerIdentity, SessionLease};
+    use serial_test::serial;
+    use std::time::{Duration, Instant};
+
+    fn active(generation: u64, lease: Option<SessionLease>) -> ActiveOwnerState {
+        ActiveOwnerState {
+            owner_key: "92001".to_owned(),
+            identity: OwnerIdentity::Unix {
+                uid: 92_001,
+                gid: 20,
+            },
+            app_data_root: std::env::temp_dir().to_string_lossy().into_owned(),
+            generation,
+            sessions: Vec::new(),
+            lease,
+        }
+    }
+
+    const LEASE: SessionLease = SessionLease {
+        ttl_secs: 30,
+        on_expiry: LeaseExpiryPolicy::ClearProxy,
+    };
+
+    #[test]
+    fn lease_ttl_is_bounded() {
+        assert!(validate_lease(&LEASE).is_ok());
+        for ttl_secs in [0, 4, 24 * 60 * 60 + 1] {
+            assert!(validate_lease(&SessionLease { ttl_secs, ..LEASE }).is_err());
+        }
+    }
+
+    #[test]
+    #[serial]
+    fn expired_lease_is_handed_out_once_and_revived_by_renewal() {
+        let session = active(7, Some(LEASE));
+        arm_lease(&session);
+        let now = Instant::now();
+        assert_eq!(take_expired_lease(now), None);
+
+        let later = now + Duration::from_secs(31);
+        let expired = take_expired_lease(later).expect("the lease should expire");
+        assert_eq!(
+            expired,
+            ExpiredLease {
+                owner_key: "92001".to_owned(),
+                generation: 7,
+                on_expiry: LeaseExpiryPolicy::ClearProxy,
+            }
+        );
+        assert_eq!(take_expired_lease(later), None);
+        assert!(is_still_expired(&expired));
+        assert!(lease_status(&session).is_some_and(|status| status.expired));
+
+        let renewed = renew_lease(&session).expect("the session still holds the lease");
+        assert!(!renewed.expired);
+        assert!(!is_still_expired(&expired));
+        disarm_lease();
+    }
+
+    #[test]
+    #[serial]
+    fn leases_follow_the_session_that_started_them() {
+        arm_lease(&active(3, Some(LEASE)));
+        assert_eq!(lease_status(&active(4, Some(LEASE))), None);
+        assert_eq!(renew_lease(&active(4, Some(LEASE))), None);
+
+        arm_lease(&active(4, None));
+        assert_eq!(lease_status(&active(3, Some(LEASE))), None);
+        assert_eq!(
+            take_expired_lease(Instant::now() + Duration::from_secs(60)),
+            None
+        );
+        disarm_lease();
+    }
+}
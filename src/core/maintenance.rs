@@ -1,5 +1,6 @@
 use crate::core::desired::ActiveOwnerState;
 use crate::core::paths::service_paths;
+use crate::core::schema;
 use crate::{OwnerIdentity, owner_key};
 use anyhow::{Context as _, Result};
 use std::fs::{File, OpenOptions};
@@ -56,26 +57,30 @@ pub(crate) async fn persist_owner_identity(
 ) -> Result<()> {
     let path = owner_root.join("owner.json");
     let temporary = owner_root.join("owner.json.tmp");
-    tokio::fs::write(&temporary, serde_json::to_vec_pretty(identity)?)
-        .await
-        .with_context(|| format!("failed to write owner identity {temporary:?}"))?;
+    schema::ensure_replaceable(&schema::OWNER_IDENTITY, &path).await?;
+    tokio::fs::write(
+        &temporary,
+        schema::encode(&schema::OWNER_IDENTITY, identity)?,
+    )
+    .await
+    .with_context(|| format!("failed to write owner identity {temporary:?}"))?;
     crate::core::atomic_file::replace(&temporary, &path)
         .await
         .with_context(|| format!("failed to activate owner identity {path:?}"))?;
     Ok(())
 }
 
-struct StoppedServiceGuard {
+pub(super) struct StoppedServiceGuard {
     _file: File,
 }
 
-fn acquire_stopped_service_guard() -> Result<StoppedServiceGuard> {
+pub(super) fn acquire_stopped_service_guard() -> Result<StoppedServiceGuard> {
     let paths = service_paths();
     #[cfg(unix)]
     {
         use std::os::fd::AsRawFd as _;
 
-        crate::core::unix_security::ensure_service_directory(paths.runtime_dir(), 0o755)?;
+        crate::core::unix_security::ensure_runtime_directory(paths.runtime_dir())?;
         let file = OpenOptions::new()
             .read(true)
             .write(true)
@@ -89,9 +94,8 @@ fn acquire_stopped_service_guard() -> Result<StoppedServiceGuard> {
             )
         } != 0
         {
-            return Err(std::io::Error::last_os_error()).context(
-                "service owner lock is held; stop the service before stale-owner maintenance",
-            );
+            return Err(std::io::Error::last_os_error())
+                .context("service owner lock is held; stop the service before maintenance");
         }
         Ok(StoppedServiceGuard { _file: file })
     }
@@ -109,9 +113,8 @@ fn acquire_stopped_service_guard() -> Result<StoppedServiceGuard> {
             .truncate(false)
             .open(paths.owner_lock_path())?;
         if unsafe { LockFile(file.as_raw_handle(), 0, 0, u32::MAX, u32::MAX) } == 0 {
-            return Err(std::io::Error::last_os_error()).context(
-                "service owner lock is held; stop the service before stale-owner maintenance",
-            );
+            return Err(std::io::Error::last_os_error())
+                .context("service owner lock is held; stop the service before maintenance");
         }
         Ok(StoppedServiceGuard { _file: file })
     }
@@ -120,9 +123,7 @@ fn acquire_stopped_service_guard() -> Result<StoppedServiceGuard> {
 fn read_active_owner() -> Result<Option<ActiveOwnerState>> {
     let path = service_paths().active_owner_path();
     match std::fs::read(&path) {
-        Ok(content) => serde_json::from_slice(&content)
-            .map(Some)
-            .with_context(|| format!("failed to parse {path:?}")),
+        Ok(content) => schema::decode_file(&schema::ACTIVE_OWNER, &path, &content).map(Some),
         Err(error) if error.kind() == std::io::ErrorKind::NotFound => Ok(None),
         Err(error) => Err(error).with_context(|| format!("failed to read {path:?}")),
     }
@@ -141,7 +142,8 @@ fn owner_identity_for_directory(
     #[cfg(windows)]
     let identity: OwnerIdentity = match std::fs::read(path.join("owner.json")) {
         Ok(content) => {
-            serde_json::from_slice(&content).context("failed to parse Windows owner metadata")?
+            schema::decode_file(&schema::OWNER_IDENTITY, &path.join("owner.json"), &content)
+                .context("failed to parse Windows owner metadata")?
         }
         Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
         Err(error) => return Err(error.into()),
@@ -1,12 +1,23 @@
 use crate::core::ClashConfig;
+#[cfg(target_os = "linux")]
+use crate::core::adoption::{
+    AdoptedCore, HANDOFF_OUTPUT_FDS, create_output_pipes, expose_output_read_ends,
+    reopen_core_output, started_at_unix_secs,
+};
+use crate::core::crash::{PanicCapture, write_crash_report};
 use crate::core::logger::{get_writer, set_or_update_writer};
+use crate::core::metrics::{LogStream, METRICS};
+use crate::core::paths::service_paths;
 use crate::core::process::process_identity;
 use crate::core::reconcile::ensure_startup_reconciled;
+use crate::core::resources::{ResourceHistory, sample_core_resources, spawn_resource_sampler};
 use crate::core::runtime::{
-    CoreRuntimeRecord, remove_core_runtime_record, write_core_runtime_record,
+    CoreExitRecord, CoreRuntimeRecord, CoreSupervisionState, read_core_exit_record,
+    read_core_runtime_record, remove_core_runtime_record, write_core_exit_record,
+    write_core_runtime_record,
 };
 use crate::core::state::set_core_lifecycle_state;
-use crate::core::structure::ServiceLifecycleState;
+use crate::core::structure::{CoreResourceSample, ServiceLifecycleState};
 use crate::{OwnerIdentity, WriterConfig};
 use anyhow::{Context as _, Result, anyhow};
 use clash_verge_logger::AsyncLogger;
@@ -14,15 +25,14 @@ use compact_str::CompactString;
 use flexi_logger::writers::LogWriter;
 use flexi_logger::{DeferredNow, Record};
 use once_cell::sync::Lazy;
-use std::process::Stdio;
-#[cfg(feature = "test")]
+use std::process::{ExitStatus, Stdio};
 use std::sync::Mutex as StdMutex;
 use std::sync::{
     Arc,
-    atomic::{AtomicU32, AtomicU64, Ordering},
+    atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering},
 };
 use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
-use tokio::io::AsyncBufReadExt;
+use tokio::io::{AsyncBufReadExt, AsyncRead};
 use tokio::{io::BufReader, process::Command};
 use tokio::{
     process::Child,
@@ -61,39 +71,186 @@ impl CoreExitInfo {
     }
 }
 
+/// How the watchdog should let go of the core when asked to shut down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum WatchdogExit {
+    Stop,
+    /// Leave the core running for the next service instance to adopt.
+    Detach,
+}
+
+/// How long an exited core's output readers may take to flush before the watchdog moves on.
+const OUTPUT_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
+
+enum SupervisedCore {
+    Spawned(Child),
+    /// Left running by a previous service instance; see [`CoreManager::adopt_core`].
+    #[cfg(target_os = "linux")]
+    Adopted(AdoptedCore),
+}
+
 pub struct ChildGuard {
-    child: Option<Child>,
+    core: Option<SupervisedCore>,
     readers: Vec<JoinHandle<()>>,
+    crash_report: Arc<StdMutex<Option<String>>>,
 }
 
 impl ChildGuard {
+    fn new(core: SupervisedCore) -> Self {
+        Self {
+            core: Some(core),
+            readers: Vec::new(),
+            crash_report: Arc::new(StdMutex::new(None)),
+        }
+    }
+
+    #[cfg(not(target_os = "linux"))]
     fn inner(&mut self) -> Option<&mut Child> {
-        self.child.as_mut()
+        match self.core.as_mut() {
+            Some(SupervisedCore::Spawned(child)) => Some(child),
+            None => None,
+        }
     }
 
     fn id(&self) -> Option<u32> {
-        self.child.as_ref().and_then(Child::id)
+        match self.core.as_ref()? {
+            SupervisedCore::Spawned(child) => child.id(),
+            #[cfg(target_os = "linux")]
+            SupervisedCore::Adopted(core) => Some(core.pid()),
+        }
     }
 
-    fn take(mut self) -> Option<Child> {
-        self.child.take()
+    fn take(mut self) -> Option<SupervisedCore> {
+        self.core.take()
     }
 
-    async fn kill_now(&mut self) -> Result<()> {
+    /// Waits for the core to exit. An adopted core is not our child, so its status is unknown.
+    async fn wait(&mut self) -> std::io::Result<Option<ExitStatus>> {
+        match self.core.as_mut() {
+            Some(SupervisedCore::Spawned(child)) => child.wait().await.map(Some),
+            #[cfg(target_os = "linux")]
+            Some(SupervisedCore::Adopted(core)) => {
+                core.wait().await;
+                Ok(None)
+            }
+            None => Err(std::io::Error::other("no core process is supervised")),
+        }
+    }
+
+    /// Forwards the core's output to the log writer and the in-memory buffer, saving a panic
+    /// trace found on stderr as a crash report under `owner`'s logs.
+    fn attach_output(
+        &mut self,
+        stdout: impl AsyncRead + Unpin + Send + 'static,
+        stderr: impl AsyncRead + Unpin + Send + 'static,
+        owner: &OwnerIdentity,
+    ) {
+        let child_pid = self.id();
+        let logs_dir = service_paths().for_owner(owner).logs_dir();
+        let crash_report = Arc::clone(&self.crash_report);
+
+        let stdout_handle = tokio::spawn(async move {
+            let mut stdout_reader = BufReader::new(stdout).lines();
+            while let Ok(Some(line)) = stdout_reader.next_line().await {
+                METRICS.record_core_log_line(LogStream::Stdout);
+                let message = CompactString::from(line.as_str());
+                {
+                    if let Some(shared_writer) = get_writer() {
+                        let w = shared_writer.lock().await;
+                        let mut now = DeferredNow::default();
+                        let arg = format_args!("{}", line);
+                        let record = Record::builder()
+                            .args(arg)
+                            .level(log::Level::Info)
+                            .target("service")
+                            .build();
+                        let _ = w.write(&mut now, &record);
+                    }
+                }
+                LOGGER_MANAGER.append_log(message).await;
+            }
+        });
+
+        let stderr_handle = tokio::spawn(async move {
+            let mut stderr_reader = BufReader::new(stderr).lines();
+            let mut panic_capture = PanicCapture::default();
+            while let Ok(Some(line)) = stderr_reader.next_line().await {
+                panic_capture.observe(&line);
+                METRICS.record_core_log_line(LogStream::Stderr);
+                let message = CompactString::from(line.as_str());
+                {
+                    if let Some(shared_writer) = get_writer() {
+                        let w = shared_writer.lock().await;
+                        let mut now = DeferredNow::default();
+                        let arg = format_args!("{}", line);
+                        let record = Record::builder()
+                            .args(arg)
+                            .level(log::Level::Error)
+                            .target("service")
+                            .build();
+                        let _ = w.write(&mut now, &record);
+                    }
+                }
+                LOGGER_MANAGER.append_log(message).await;
+            }
+            if let Some(trace) = panic_capture.finish() {
+                match write_crash_report(&logs_dir, child_pid, &trace).await {
+                    Ok(name) => *crash_report.lock().unwrap() = Some(name),
+                    Err(error) => warn!("Failed to save core crash report: {error:#}"),
+                }
+            }
+        });
+
+        self.readers.push(stdout_handle);
+        self.readers.push(stderr_handle);
+    }
+
+    /// Releases the core without stopping it. The core keeps its own pipe read ends, so
+    /// dropping ours does not break its output.
+    fn detach(mut self) {
         for reader in self.readers.drain(..) {
             reader.abort();
         }
+        self.core.take();
+    }
 
-        if let Some(child) = self.child.as_mut() {
-            let child_id = child.id();
-            child
-                .kill()
+    /// Lets the readers of an exited child reach EOF and returns the crash report they wrote.
+    async fn drain_output(&mut self) -> Option<String> {
+        for mut reader in self.readers.drain(..) {
+            if tokio::time::timeout(OUTPUT_DRAIN_TIMEOUT, &mut reader)
                 .await
-                .with_context(|| format!("failed to kill child {child_id:?}"))?;
-            self.child.take();
-            info!("Successfully killed child ({:?})", child_id);
-        } else {
-            info!("No running core process found");
+                .is_err()
+            {
+                warn!("Core output reader did not finish after exit");
+                reader.abort();
+            }
+        }
+        self.crash_report.lock().unwrap().take()
+    }
+
+    async fn kill_now(&mut self) -> Result<()> {
+        for reader in self.readers.drain(..) {
+            reader.abort();
+        }
+
+        match self.core.as_mut() {
+            Some(SupervisedCore::Spawned(child)) => {
+                let child_id = child.id();
+                child
+                    .kill()
+                    .await
+                    .with_context(|| format!("failed to kill child {child_id:?}"))?;
+                self.core.take();
+                info!("Successfully killed child ({:?})", child_id);
+            }
+            #[cfg(target_os = "linux")]
+            Some(SupervisedCore::Adopted(core)) => {
+                let pid = core.pid();
+                core.kill_and_wait().await?;
+                self.core.take();
+                info!("Successfully killed adopted core ({pid})");
+            }
+            None => info!("No running core process found"),
         }
         Ok(())
     }
@@ -104,16 +261,22 @@ impl Drop for ChildGuard {
         for reader in self.readers.drain(..) {
             reader.abort();
         }
-        if let Some(mut child) = self.child.take() {
-            tokio::spawn(async move {
-                if let Err(e) = child.kill().await {
-                    warn!("Failed to kill child ({:?}): {e}", child.id());
-                } else {
-                    info!("Successfully killed child ({:?})", child.id());
-                }
-            });
-        } else {
-            info!("No running core process found");
+        match self.core.take() {
+            Some(SupervisedCore::Spawned(mut child)) => {
+                tokio::spawn(async move {
+                    if let Err(e) = child.kill().await {
+                        warn!("Failed to kill child ({:?}): {e}", child.id());
+                    } else {
+                        info!("Successfully killed child ({:?})", child.id());
+                    }
+                });
+            }
+            #[cfg(target_os = "linux")]
+            Some(SupervisedCore::Adopted(core)) => match core.kill() {
+                Ok(()) => info!("Successfully killed adopted core ({})", core.pid()),
+                Err(e) => warn!("Failed to kill adopted core ({}): {e}", core.pid()),
+            },
+            None => info!("No running core process found"),
         }
     }
 }
@@ -190,14 +353,14 @@ fn core_args(config: &ClashConfig) -> Vec<String> {
     ]
 }
 
-fn log_core_exit(status: &std::process::ExitStatus, uptime: Duration) -> String {
+fn log_core_exit(status: Option<&ExitStatus>, uptime: Duration) -> String {
     let exit_info = CoreExitInfo {
-        exit_code: status.code(),
+        exit_code: status.and_then(ExitStatus::code),
         #[cfg(unix)]
-        signal: {
+        signal: status.and_then(|status| {
             use std::os::unix::process::ExitStatusExt;
             status.signal()
-        },
+        }),
         uptime,
     };
 
@@ -247,6 +410,11 @@ async fn write_runtime_record_for_config(
         pid,
         ipc_path: config.core_config.core_ipc_path.clone(),
         identity,
+        #[cfg(target_os = "linux")]
+        output_fds: Some(HANDOFF_OUTPUT_FDS),
+        #[cfg(not(target_os = "linux"))]
+        output_fds: None,
+        supervision: None,
     })
     .await
     .with_context(|| format!("failed to write core runtime record {context}"))
@@ -258,10 +426,14 @@ pub struct CoreManager {
     core_start_time: Arc<Mutex<Option<Instant>>>,
     core_started_at: Arc<AtomicU64>,
     last_core_exit_reason: Arc<Mutex<Option<String>>>,
+    last_crash_report: Arc<Mutex<Option<String>>>,
     restart_count: Arc<AtomicU32>,
     last_recovery_at: Arc<AtomicU64>,
-    watchdog_shutdown: Mutex<Option<oneshot::Sender<()>>>,
+    adopted: Arc<AtomicBool>,
+    watchdog_shutdown: Mutex<Option<oneshot::Sender<WatchdogExit>>>,
     watchdog_handle: Mutex<Option<JoinHandle<Result<()>>>>,
+    resource_history: Arc<StdMutex<ResourceHistory>>,
+    resource_sampler: Mutex<Option<JoinHandle<()>>>,
     failed_child: Arc<Mutex<Option<ChildGuard>>>,
 }
 
@@ -270,8 +442,12 @@ pub(super) struct CoreStatusSnapshot {
     pub(super) core_pid: Option<u32>,
     pub(super) core_started_at: Option<u64>,
     pub(super) last_core_exit_reason: Option<String>,
+    pub(super) last_crash_report: Option<String>,
     pub(super) restart_count: u32,
     pub(super) last_recovery_at: Option<u64>,
+    pub(super) adopted: bool,
+    pub(super) resources: Option<CoreResourceSample>,
+    pub(super) resource_history: Vec<CoreResourceSample>,
 }
 
 impl CoreManager {
@@ -282,10 +458,14 @@ impl CoreManager {
             core_start_time: Arc::new(Mutex::new(None)),
             core_started_at: Arc::new(AtomicU64::new(0)),
             last_core_exit_reason: Arc::new(Mutex::new(None)),
+            last_crash_report: Arc::new(Mutex::new(None)),
             restart_count: Arc::new(AtomicU32::new(0)),
             last_recovery_at: Arc::new(AtomicU64::new(0)),
+            adopted: Arc::new(AtomicBool::new(false)),
             watchdog_shutdown: Mutex::new(None),
             watchdog_handle: Mutex::new(None),
+            resource_history: Arc::new(StdMutex::new(ResourceHistory::default())),
+            resource_sampler: Mutex::new(None),
             failed_child: Arc::new(Mutex::new(None)),
         }
     }
@@ -381,9 +561,76 @@ impl CoreManager {
         self.running_pid
             .store(child_pid.unwrap_or_default(), Ordering::Release);
         *self.running_config.lock().await = Some(config.clone());
+        self.adopted.store(false, Ordering::Relaxed);
+
+        self.start_watchdog(child_guard, config, owner).await;
+        set_core_lifecycle_state(ServiceLifecycleState::Running);
+
+        Ok(())
+    }
+
+    /// Resumes supervising a core left running by a previous service instance.
+    /// The caller has verified the record's identity and that the active owner still wants
+    /// `config` running; the core keeps its PID, socket and runtime record.
+    #[cfg(target_os = "linux")]
+    pub(super) async fn adopt_core(
+        &self,
+        record: &CoreRuntimeRecord,
+        config: ClashConfig,
+        owner: OwnerIdentity,
+    ) -> Result<()> {
+        let output_fds = record
+            .output_fds
+            .context("core runtime record has no reattachable output")?;
+        let core = AdoptedCore::open(record)?;
+        let (stdout, stderr) = reopen_core_output(record.pid, output_fds)?;
+        set_or_update_writer(&config.log_config).await?;
+
+        let mut child_guard = ChildGuard::new(SupervisedCore::Adopted(core));
+        child_guard.attach_output(stdout, stderr, &owner);
+
+        let now_secs = unix_timestamp_secs();
+        let started_at = started_at_unix_secs(&record.identity).unwrap_or(now_secs);
+        let uptime = Duration::from_secs(now_secs.saturating_sub(started_at));
+        *self.core_start_time.lock().await = Some(
+            Instant::now()
+                .checked_sub(uptime)
+                .unwrap_or_else(Instant::now),
+        );
+        self.core_started_at.store(started_at, Ordering::Relaxed);
+        if let Some(supervision) = record.supervision.clone() {
+            self.restart_count
+                .store(supervision.restart_count, Ordering::Relaxed);
+            self.last_recovery_at.store(
+                supervision.last_recovery_at.unwrap_or_default(),
+                Ordering::Relaxed,
+            );
+            *self.last_core_exit_reason.lock().await = supervision.last_core_exit_reason;
+            *self.last_crash_report.lock().await = supervision.last_crash_report;
+            self.resource_history
+                .lock()
+                .unwrap()
+                .restore(supervision.resource_history);
+        }
+        self.running_pid.store(record.pid, Ordering::Release);
+        *self.running_config.lock().await = Some(config.clone());
+        self.adopted.store(true, Ordering::Relaxed);
+        if record.supervision.is_some() {
+            let record = CoreRuntimeRecord {
+                supervision: None,
+                ..record.clone()
+            };
+            if let Err(error) = write_core_runtime_record(&record).await {
+                warn!("Failed to clear handed-over supervision state: {error:#}");
+            }
+        }
 
         self.start_watchdog(child_guard, config, owner).await;
         set_core_lifecycle_state(ServiceLifecycleState::Running);
+        info!(
+            "Adopted core {} from the previous service instance",
+            record.pid
+        );
 
         Ok(())
     }
@@ -392,7 +639,7 @@ impl CoreManager {
         info!("Stopping core");
         LOGGER_MANAGER.clear_logs().await;
 
-        let watchdog_result = self.stop_watchdog().await;
+        let watchdog_result = self.stop_watchdog(WatchdogExit::Stop).await;
         let mut recovered_failed_child = false;
         if let Some(mut child_guard) = self.failed_child.lock().await.take() {
             if let Err(error) = child_guard.kill_now().await {
@@ -408,6 +655,8 @@ impl CoreManager {
         self.running_pid.store(0, Ordering::Release);
         *self.core_start_time.lock().await = None;
         self.core_started_at.store(0, Ordering::Relaxed);
+        self.adopted.store(false, Ordering::Relaxed);
+        self.resource_history.lock().unwrap().clear();
 
         let start_clash = self.running_config.lock().await.take();
         let core_ipc_path = start_clash
@@ -426,6 +675,60 @@ impl CoreManager {
         Ok(())
     }
 
+    /// Reports the exit a previous service instance recorded until this one sees a core exit.
+    pub(super) async fn restore_last_exit(&self) {
+        match read_core_exit_record().await {
+            Ok(exit) => {
+                *self.last_core_exit_reason.lock().await = exit.reason;
+                *self.last_crash_report.lock().await = exit.crash_report;
+            }
+            Err(error) => warn!("Failed to load the last core exit: {error:#}"),
+        }
+    }
+
+    /// Stops supervising the core but leaves it, its socket and its runtime record in place,
+    /// carrying the watchdog's bookkeeping in the record. Returns false when no core is running.
+    pub(super) async fn detach_core(&self) -> Result<bool> {
+        let running_pid = self.running_pid.load(Ordering::Acquire);
+        let adoptable = read_core_runtime_record()
+            .await?
+            .is_some_and(|record| record.pid == running_pid && record.output_fds.is_some());
+        if running_pid == 0 || !adoptable || self.failed_child.lock().await.is_some() {
+            return Ok(false);
+        }
+
+        self.stop_watchdog(WatchdogExit::Detach).await?;
+        let running_pid = self.running_pid.load(Ordering::Acquire);
+        if running_pid == 0 {
+            // The core exited while the watchdog was stopping; nothing is left to hand over.
+            return Ok(false);
+        }
+        let mut record = read_core_runtime_record()
+            .await?
+            .filter(|record| record.pid == running_pid)
+            .with_context(|| {
+                format!("detached core {running_pid} has no matching runtime record")
+            })?;
+        record.supervision = Some(CoreSupervisionState {
+            restart_count: self.restart_count.load(Ordering::Relaxed),
+            last_recovery_at: non_zero_u64(self.last_recovery_at.load(Ordering::Relaxed)),
+            last_core_exit_reason: self.last_core_exit_reason.lock().await.clone(),
+            last_crash_report: self.last_crash_report.lock().await.clone(),
+            resource_history: self.resource_history.lock().unwrap().samples(),
+        });
+        write_core_runtime_record(&record)
+            .await
+            .context("failed to record core supervision state for handoff")?;
+
+        self.running_pid.store(0, Ordering::Release);
+        *self.running_config.lock().await = None;
+        *self.core_start_time.lock().await = None;
+        self.core_started_at.store(0, Ordering::Relaxed);
+        self.adopted.store(false, Ordering::Relaxed);
+        info!("Detached core {} for handoff", record.pid);
+        Ok(true)
+    }
+
     async fn start_watchdog(
         &self,
         child_guard: ChildGuard,
@@ -436,8 +739,10 @@ impl CoreManager {
         let start_time_arc = Arc::clone(&self.core_start_time);
         let started_at_arc = Arc::clone(&self.core_started_at);
         let last_exit_reason_arc = Arc::clone(&self.last_core_exit_reason);
+        let last_crash_report_arc = Arc::clone(&self.last_crash_report);
         let restart_count_arc = Arc::clone(&self.restart_count);
         let last_recovery_at_arc = Arc::clone(&self.last_recovery_at);
+        let adopted_arc = Arc::clone(&self.adopted);
         let failed_child_arc = Arc::clone(&self.failed_child);
         let (shutdown_tx, shutdown_rx) = oneshot::channel();
         let watchdog_config = watchdog_config();
@@ -454,25 +759,27 @@ impl CoreManager {
                     break;
                 };
 
-                let wait_result = {
-                    let Some(child) = current_guard.inner() else {
-                        break;
-                    };
-
-                    tokio::select! {
-                        _ = &mut shutdown_rx => {
-                            info!("Core watchdog received shutdown signal");
-                            if let Err(error) = current_guard.kill_now().await {
-                                *failed_child_arc.lock().await = Some(current_guard);
-                                set_core_lifecycle_state(ServiceLifecycleState::Fatal);
-                                return Err(error.context(
-                                    "failed to terminate core during watchdog shutdown",
-                                ));
-                            }
-                            break 'watchdog;
+                if current_guard.core.is_none() {
+                    break;
+                }
+                let wait_result = tokio::select! {
+                    exit = &mut shutdown_rx => {
+                        if matches!(exit, Ok(WatchdogExit::Detach)) {
+                            info!("Core watchdog detaching from core {:?}", current_guard.id());
+                            current_guard.detach();
+                            return Ok(());
+                        }
+                        info!("Core watchdog received shutdown signal");
+                        if let Err(error) = current_guard.kill_now().await {
+                            *failed_child_arc.lock().await = Some(current_guard);
+                            set_core_lifecycle_state(ServiceLifecycleState::Fatal);
+                            return Err(error.context(
+                                "failed to terminate core during watchdog shutdown",
+                            ));
                         }
-                        wait_result = child.wait() => wait_result,
+                        break 'watchdog;
                     }
+                    wait_result = current_guard.wait() => wait_result,
                 };
 
                 let status = match wait_result {
@@ -489,13 +796,26 @@ impl CoreManager {
                     .await
                     .map(|t| t.elapsed())
                     .unwrap_or_default();
-                let exit_reason = log_core_exit(&status, uptime);
-                *last_exit_reason_arc.lock().await = Some(exit_reason);
+                let exit_reason = log_core_exit(status.as_ref(), uptime);
+                let crash_report = current_guard.drain_output().await;
+                if let Some(name) = crash_report.as_ref() {
+                    error!("Core left a crash trace, saved as {name}");
+                }
+                let exit = CoreExitRecord {
+                    reason: Some(exit_reason),
+                    crash_report,
+                };
+                if let Err(error) = write_core_exit_record(&exit).await {
+                    warn!("Failed to record core exit: {error:#}");
+                }
+                *last_exit_reason_arc.lock().await = exit.reason;
+                *last_crash_report_arc.lock().await = exit.crash_report;
                 set_core_lifecycle_state(ServiceLifecycleState::RecoveringCore);
 
                 let _ = current_guard.take();
                 running_pid_arc.store(0, Ordering::Release);
                 started_at_arc.store(0, Ordering::Relaxed);
+                adopted_arc.store(false, Ordering::Relaxed);
                 remove_core_runtime_record().await;
 
                 let now = Instant::now();
@@ -621,6 +941,7 @@ impl CoreManager {
                             let now_secs = unix_timestamp_secs();
                             started_at_arc.store(now_secs, Ordering::Relaxed);
                             restart_count_arc.fetch_add(1, Ordering::Relaxed);
+                            METRICS.record_core_restart();
                             last_recovery_at_arc.store(now_secs, Ordering::Relaxed);
                             consecutive_attempt += 1;
                             info!(
@@ -656,11 +977,21 @@ impl CoreManager {
 
         *self.watchdog_shutdown.lock().await = Some(shutdown_tx);
         *self.watchdog_handle.lock().await = Some(handle);
+        let sampler = spawn_resource_sampler(
+            Arc::clone(&self.running_pid),
+            Arc::clone(&self.resource_history),
+        );
+        if let Some(previous) = self.resource_sampler.lock().await.replace(sampler) {
+            previous.abort();
+        }
     }
 
-    async fn stop_watchdog(&self) -> Result<()> {
+    async fn stop_watchdog(&self, exit: WatchdogExit) -> Result<()> {
+        if let Some(sampler) = self.resource_sampler.lock().await.take() {
+            sampler.abort();
+        }
         if let Some(shutdown_tx) = self.watchdog_shutdown.lock().await.take() {
-            let _ = shutdown_tx.send(());
+            let _ = shutdown_tx.send(exit);
         }
 
         if let Some(handle) = self.watchdog_handle.lock().await.take() {
@@ -672,12 +1003,17 @@ impl CoreManager {
     }
 
     pub(super) async fn status(&self) -> CoreStatusSnapshot {
+        let core_pid = non_zero_u32(self.running_pid.load(Ordering::Relaxed));
         CoreStatusSnapshot {
-            core_pid: non_zero_u32(self.running_pid.load(Ordering::Relaxed)),
+            core_pid,
             core_started_at: non_zero_u64(self.core_started_at.load(Ordering::Relaxed)),
             last_core_exit_reason: self.last_core_exit_reason.lock().await.clone(),
+            last_crash_report: self.last_crash_report.lock().await.clone(),
             restart_count: self.restart_count.load(Ordering::Relaxed),
             last_recovery_at: non_zero_u64(self.last_recovery_at.load(Ordering::Relaxed)),
+            adopted: core_pid.is_some() && self.adopted.load(Ordering::Relaxed),
+            resources: core_pid.and_then(sample_core_resources),
+            resource_history: self.resource_history.lock().unwrap().samples(),
         }
     }
 
@@ -729,9 +1065,8 @@ pub async fn run_with_logging(
             .spawn()?
     };
 
-    #[cfg(unix)]
+    #[cfg(all(unix, not(target_os = "linux")))]
     let child = unsafe {
-        let _ = owner;
         Command::new(bin_path)
             .args(args)
             .stdout(Stdio::piped())
@@ -743,11 +1078,30 @@ pub async fn run_with_logging(
             .spawn()?
     };
 
-    let mut child_guard = ChildGuard {
-        child: Some(child),
-        readers: Vec::new(),
+    // The core keeps its own pipe read ends so it can outlive this service and be adopted.
+    #[cfg(target_os = "linux")]
+    let (child, stdout, stderr) = {
+        use std::os::fd::AsRawFd as _;
+
+        let pipes = create_output_pipes()?;
+        let (stdout_read, stderr_read) = (pipes.stdout.as_raw_fd(), pipes.stderr.as_raw_fd());
+        let child = unsafe {
+            Command::new(bin_path)
+                .args(args)
+                .stdout(Stdio::from(pipes.stdout_write))
+                .stderr(Stdio::from(pipes.stderr_write))
+                .pre_exec(move || {
+                    platform_lib::umask(0o007);
+                    expose_output_read_ends(stdout_read, stderr_read)
+                })
+                .spawn()?
+        };
+        (child, pipes.stdout, pipes.stderr)
     };
 
+    let mut child_guard = ChildGuard::new(SupervisedCore::Spawned(child));
+
+    #[cfg(not(target_os = "linux"))]
     let (Some(stdout), Some(stderr)) = (
         child_guard.inner().and_then(|c| c.stdout.take()),
         child_guard.inner().and_then(|c| c.stderr.take()),
@@ -755,50 +1109,7 @@ pub async fn run_with_logging(
         return Err(anyhow!("Failed to capture child output"));
     };
 
-    let stdout_handle = tokio::spawn(async move {
-        let mut stdout_reader = BufReader::new(stdout).lines();
-        while let Ok(Some(line)) = stdout_reader.next_line().await {
-            let message = CompactString::from(line.as_str());
-            {
-                if let Some(shared_writer) = get_writer() {
-                    let w = shared_writer.lock().await;
-                    let mut now = DeferredNow::default();
-                    let arg = format_args!("{}", line);
-                    let record = Record::builder()
-                        .args(arg)
-                        .level(log::Level::Info)
-                        .target("service")
-                        .build();
-                    let _ = w.write(&mut now, &record);
-                }
-            }
-            LOGGER_MANAGER.append_log(message).await;
-        }
-    });
-
-    let stderr_handle = tokio::spawn(async move {
-        let mut stderr_reader = BufReader::new(stderr).lines();
-        while let Ok(Some(line)) = stderr_reader.next_line().await {
-            let message = CompactString::from(line.as_str());
-            {
-                if let Some(shared_writer) = get_writer() {
-                    let w = shared_writer.lock().await;
-                    let mut now = DeferredNow::default();
-                    let arg = format_args!("{}", line);
-                    let record = Record::builder()
-                        .args(arg)
-                        .level(log::Level::Error)
-                        .target("service")
-                        .build();
-                    let _ = w.write(&mut now, &record);
-                }
-            }
-            LOGGER_MANAGER.append_log(message).await;
-        }
-    });
-
-    child_guard.readers.push(stdout_handle);
-    child_guard.readers.push(stderr_handle);
+    child_guard.attach_output(stdout, stderr, owner);
 
     Ok(child_guard)
 }
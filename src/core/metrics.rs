@@ -0,0 +1,306 @@
+//! In-process counters rendered in the Prometheus text exposition format.
+//! Everything is cumulative since service start; rates such as log lines per second are left
+//! to the scraper's `rate()` so the service never has to pick a window.
+
+use once_cell::sync::Lazy;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::sync::{
+    Mutex as StdMutex,
+    atomic::{AtomicU64, Ordering},
+};
+use std::time::Duration;
+
+const METRIC_PREFIX: &str = "clash_verge_service";
+/// Upper bounds in seconds; IPC handlers are capped at 25 s by the server write timeout.
+const LATENCY_BUCKETS: [f64; 10] = [0.001, 0.005, 0.01, 0.025, 0.05, 0.1, 0.5, 1.0, 5.0, 25.0];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum LogStream {
+    Stdout,
+    Stderr,
+}
+
+impl LogStream {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Stdout => "stdout",
+            Self::Stderr => "stderr",
+        }
+    }
+}
+
+/// Whether runtime files were written while the core was stopped or staged into a live one.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(super) enum RuntimeOperation {
+    Prepare,
+    Stage,
+}
+
+impl RuntimeOperation {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Prepare => "prepare",
+            Self::Stage => "stage",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Histogram {
+    buckets: [u64; LATENCY_BUCKETS.len()],
+    count: u64,
+    sum_seconds: f64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        for (bucket, bound) in self.buckets.iter_mut().zip(LATENCY_BUCKETS) {
+            if seconds <= bound {
+                *bucket += 1;
+            }
+        }
+        self.count += 1;
+        self.sum_seconds += seconds;
+    }
+
+    fn render(&self, output: &mut String, name: &str, labels: &str) {
+        let separator = if labels.is_empty() { "" } else { "," };
+        for (count, bound) in self.buckets.iter().zip(LATENCY_BUCKETS) {
+            let _ = writeln!(
+                output,
+                "{name}_bucket{{{labels}{separator}le=\"{bound}\"}} {count}"
+            );
+        }
+        let _ = writeln!(
+            output,
+            "{name}_bucket{{{labels}{separator}le=\"+Inf\"}} {}",
+            self.count
+        );
+        let braces = |labels: &str| {
+            if labels.is_empty() {
+                String::new()
+            } else {
+                format!("{{{labels}}}")
+            }
+        };
+        let _ = writeln!(output, "{name}_sum{} {}", braces(labels), self.sum_seconds);
+        let _ = writeln!(output, "{name}_count{} {}", braces(labels), self.count);
+    }
+}
+
+#[derive(Default)]
+pub(super) struct ServiceMetrics {
+    requests: StdMutex<BTreeMap<(String, String), u64>>,
+    handler_latency: StdMutex<BTreeMap<String, Histogram>>,
+    lifecycle_lock_wait: StdMutex<Histogram>,
+    core_restarts: AtomicU64,
+    ipc_listener_rebuilds: AtomicU64,
+    runtime_assets: StdMutex<BTreeMap<(RuntimeOperation, &'static str), u64>>,
+    core_log_lines: StdMutex<BTreeMap<LogStream, u64>>,
+}
+
+impl ServiceMetrics {
+    pub(super) fn record_request(&self, route: &str, code: String, elapsed: Duration) {
+        *self
+            .requests
+            .lock()
+            .unwrap()
+            .entry((route.to_owned(), code))
+            .or_default() += 1;
+        self.handler_latency
+            .lock()
+            .unwrap()
+            .entry(route.to_owned())
+            .or_default()
+            .observe(elapsed);
+    }
+
+    pub(super) fn record_lifecycle_lock_wait(&self, elapsed: Duration) {
+        self.lifecycle_lock_wait.lock().unwrap().observe(elapsed);
+    }
+
+    pub(super) fn record_core_restart(&self) {
+        self.core_restarts.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_ipc_listener_rebuild(&self) {
+        self.ipc_listener_rebuilds.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(super) fn record_runtime_assets(
+        &self,
+        operation: RuntimeOperation,
+        copied: usize,
+        skipped: usize,
+        discarded: usize,
+    ) {
+        let mut assets = self.runtime_assets.lock().unwrap();
+        for (result, count) in [
+            ("copied", copied),
+            ("skipped", skipped),
+            ("discarded", discarded),
+        ] {
+            *assets.entry((operation, result)).or_default() += count as u64;
+        }
+    }
+
+    pub(super) fn record_core_log_line(&self, stream: LogStream) {
+        *self
+            .core_log_lines
+            .lock()
+            .unwrap()
+            .entry(stream)
+            .or_default() += 1;
+    }
+
+    pub(super) fn render(&self) -> String {
+        let mut output = String::new();
+
+        header(
+            &mut output,
+            "ipc_requests_total",
+            "counter",
+            "IPC requests handled, by route and response code.",
+        );
+        for ((route, code), count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                output,
+                "{METRIC_PREFIX}_ipc_requests_total{{route=\"{route}\",code=\"{code}\"}} {count}"
+            );
+        }
+
+        header(
+            &mut output,
+            "ipc_handler_duration_seconds",
+            "histogram",
+            "Time spent in IPC route handlers.",
+        );
+        for (route, histogram) in self.handler_latency.lock().unwrap().iter() {
+            histogram.render(
+                &mut output,
+                &format!("{METRIC_PREFIX}_ipc_handler_duration_seconds"),
+                &format!("route=\"{route}\""),
+            );
+        }
+
+        header(
+            &mut output,
+            "owner_lifecycle_lock_wait_seconds",
+            "histogram",
+            "Time IPC handlers waited to acquire the owner lifecycle lock.",
+        );
+        self.lifecycle_lock_wait.lock().unwrap().render(
+            &mut output,
+            &format!("{METRIC_PREFIX}_owner_lifecycle_lock_wait_seconds"),
+            "",
+        );
+
+        header(
+            &mut output,
+            "core_restarts_total",
+            "counter",
+            "Core processes restarted by the watchdog.",
+        );
+        let _ = writeln!(
+            output,
+            "{METRIC_PREFIX}_core_restarts_total {}",
+            self.core_restarts.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut output,
+            "ipc_listener_rebuilds_total",
+            "counter",
+            "IPC listeners rebuilt by the supervisor after the previous one exited.",
+        );
+        let _ = writeln!(
+            output,
+            "{METRIC_PREFIX}_ipc_listener_rebuilds_total {}",
+            self.ipc_listener_rebuilds.load(Ordering::Relaxed)
+        );
+
+        header(
+            &mut output,
+            "runtime_assets_total",
+            "counter",
+            "Runtime files handled while preparing or staging a generation, by result.",
+        );
+        for ((operation, result), count) in self.runtime_assets.lock().unwrap().iter() {
+            let _ = writeln!(
+                output,
+                "{METRIC_PREFIX}_runtime_assets_total{{operation=\"{}\",result=\"{result}\"}} {count}",
+                operation.label()
+            );
+        }
+
+        header(
+            &mut output,
+            "core_log_lines_total",
+            "counter",
+            "Lines the core wrote to its output streams.",
+        );
+        for (stream, count) in self.core_log_lines.lock().unwrap().iter() {
+            let _ = writeln!(
+                output,
+                "{METRIC_PREFIX}_core_log_lines_total{{stream=\"{}\"}} {count}",
+                stream.label()
+            );
+        }
+
+        output
+    }
+}
+
+fn header(output: &mut String, name: &str, kind: &str, help: &str) {
+    let _ = writeln!(output, "# HELP {METRIC_PREFIX}_{name} {help}");
+    let _ = writeln!(output, "# TYPE {METRIC_PREFIX}_{name} {kind}");
+}
+
+pub(super) static METRICS: Lazy<ServiceMetrics> = Lazy::new(ServiceMetrics::default);
+
+#[cfg(test)]
+mod tests {
+    use super::{LogStream, RuntimeOperation, ServiceMetrics};
+    use std::time::Duration;
+
+    #[test]
+    fn renders_labelled_counters_and_cumulative_histograms() {
+        let metrics = ServiceMetrics::default();
+        metrics.record_request("/status", "0".to_owned(), Duration::from_millis(2));
+        metrics.record_request("/status", "0".to_owned(), Duration::from_millis(30));
+        metrics.record_request("/clash/stop", "1008".to_owned(), Duration::from_millis(1));
+        metrics.record_runtime_assets(RuntimeOperation::Stage, 1, 4, 0);
+        metrics.record_core_log_line(LogStream::Stderr);
+        metrics.record_core_restart();
+
+        let rendered = metrics.render();
+
+        assert!(
+            rendered.contains(
+                "clash_verge_service_ipc_requests_total{route=\"/status\",code=\"0\"} 2\n"
+            )
+        );
+        assert!(rendered.contains(
+            "clash_verge_service_ipc_requests_total{route=\"/clash/stop\",code=\"1008\"} 1\n"
+        ));
+        assert!(rendered.contains(
+            "clash_verge_service_ipc_handler_duration_seconds_bucket{route=\"/status\",le=\"0.005\"} 1\n"
+        ));
+        assert!(rendered.contains(
+            "clash_verge_service_ipc_handler_duration_seconds_bucket{route=\"/status\",le=\"+Inf\"} 2\n"
+        ));
+        assert!(
+            rendered.contains("clash_verge_service_owner_lifecycle_lock_wait_seconds_count 0\n")
+        );
+        assert!(rendered.contains(
+            "clash_verge_service_runtime_assets_total{operation=\"stage\",result=\"skipped\"} 4\n"
+        ));
+        assert!(
+            rendered.contains("clash_verge_service_core_log_lines_total{stream=\"stderr\"} 1\n")
+        );
+        assert!(rendered.contains("clash_verge_service_core_restarts_total 1\n"));
+        assert!(rendered.contains("# TYPE clash_verge_service_ipc_requests_total counter\n"));
+    }
+}
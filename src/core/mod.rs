@@ -2,27 +2,57 @@ pub mod command;
 pub use command::IpcCommand;
 
 pub mod structure;
+#[cfg(feature = "response")]
+pub use structure::Response;
 pub use structure::{
-    AuthenticatedRequest, AuthenticatedSessionRequest, ClashConfig, CoreConfig, MacosProxyConfig,
-    OWNER_TOKEN_FILE_NAME, OwnerCredentials, OwnerIdentity, OwnerSessionHandle, OwnerSessionProof,
-    ProtocolInfo, ProtocolVersion, ProxyApplyOutcome, RemoteProvider, RuntimeAsset, RuntimeBundle,
-    SERVICE_PROTOCOL_HEADER, SESSION_TOKEN_HEX_LEN, ServiceErrorCode, ServiceLifecycleState,
-    ServiceStatusSnapshot, StageRejection, StageRuntimeOutcome, StartClashRequest,
-    StartClashResult, WriterConfig, owner_key,
+    AuditQuery, AuditRecord, AuthenticatedRequest, AuthenticatedSessionRequest, ClashConfig,
+    CoreConfig, CoreResourceSample, CrashReport, CrashReportInfo, EvictOwnerRequest,
+    EvictOwnerResult, HANDSHAKE_NONCE_HEX_LEN, HandshakeProof, HandshakeRequest, LeaseExpiryPolicy,
+    MAX_SESSION_NAME_LEN, MacosProxyConfig, MintScopedSessionRequest, OWNER_TOKEN_FILE_NAME,
+    ObserverStatusSnapshot, OpenSessionRequest, OwnerCredentials, OwnerIdentity,
+    OwnerSessionHandle, OwnerSessionProof, PRIMARY_SESSION_NAME, ProtocolInfo, ProtocolVersion,
+    ProxyApplyOutcome, RemoteProvider, RuntimeAsset, RuntimeBundle, SERVICE_PROTOCOL_HEADER,
+    SESSION_TOKEN_HEX_LEN, ServiceCapability, ServiceErrorCode, ServiceFeature,
+    ServiceLifecycleState, ServiceMode, ServiceStatusSnapshot, SessionLease, SessionLeaseStatus,
+    SessionScope, StageRejection, StageRuntimeOutcome, StartClashRequest, StartClashResult,
+    WriterConfig, is_valid_session_name, owner_key,
 };
 
 pub mod paths;
+#[cfg(feature = "test")]
+pub use paths::SERVICE_ROOT_ENV;
 #[cfg(feature = "standalone")]
 pub use paths::prepare_service_install_directory;
-pub use paths::{OwnerPaths, ServicePaths, mihomo_ipc_path, service_paths};
+pub use paths::{
+    OwnerPaths, ServicePaths, mihomo_ipc_path, rooted_service_paths, service_paths,
+    service_paths_for, set_service_paths,
+};
 
+#[cfg(feature = "standalone")]
+mod access;
+#[cfg(all(feature = "standalone", target_os = "linux"))]
+mod adoption;
 #[cfg(feature = "standalone")]
 mod atomic_file;
 #[cfg(feature = "standalone")]
+mod audit;
+#[cfg(feature = "standalone")]
 mod auth;
 #[cfg(feature = "standalone")]
+mod crash;
+#[cfg(feature = "standalone")]
 mod desired;
 #[cfg(feature = "standalone")]
+mod doctor;
+#[cfg(feature = "standalone")]
+mod handoff;
+#[cfg(feature = "standalone")]
+mod handshake;
+#[cfg(feature = "standalone")]
+mod journal;
+#[cfg(feature = "standalone")]
+mod lease;
+#[cfg(feature = "standalone")]
 mod legacy_cleanup;
 #[cfg(feature = "standalone")]
 mod logger;
@@ -31,6 +61,10 @@ mod maintenance;
 #[cfg(feature = "standalone")]
 mod manager;
 #[cfg(feature = "standalone")]
+mod metrics;
+#[cfg(all(feature = "standalone", target_os = "linux"))]
+mod notify;
+#[cfg(feature = "standalone")]
 mod owner;
 #[cfg(feature = "standalone")]
 mod process;
@@ -41,10 +75,14 @@ mod reconcile;
 #[cfg(feature = "standalone")]
 mod repair;
 #[cfg(feature = "standalone")]
+mod resources;
+#[cfg(feature = "standalone")]
 mod runtime;
 #[cfg(feature = "standalone")]
 mod runtime_generation;
 #[cfg(feature = "standalone")]
+mod schema;
+#[cfg(feature = "standalone")]
 mod server;
 #[cfg(feature = "standalone")]
 mod state;
@@ -67,17 +105,23 @@ pub(in crate::core) use windows_security as platform_security;
 
 #[cfg(feature = "standalone")]
 pub use desired::{
-    ActiveOwnerState, DesiredState, load_active_owner, load_owner_desired_state,
-    restore_desired_state,
+    ActiveOwnerState, DesiredState, OwnerSessionRecord, load_active_owner,
+    load_owner_desired_state, restore_desired_state,
 };
 #[cfg(feature = "standalone")]
+pub use doctor::{DoctorFinding, DoctorReport, DoctorSeverity, diagnose_service};
+#[cfg(feature = "standalone")]
+pub use handoff::{cancel_core_handoff, request_core_handoff};
+#[cfg(feature = "standalone")]
+pub use handshake::withdraw_handshake_key;
+#[cfg(feature = "standalone")]
 pub use maintenance::cleanup_stale_owner_state;
 #[cfg(all(feature = "standalone", feature = "test"))]
 pub use manager::{CoreWatchdogTestConfig, set_core_watchdog_config_for_tests};
 #[cfg(feature = "standalone")]
 pub use owner::{ServiceOwnerGuard, acquire_service_owner};
 #[cfg(feature = "standalone")]
-pub use proxy::{apply_proxy, apply_proxy_or_direct, clear_proxy, validate_proxy_config};
+pub use proxy::validate_proxy_config;
 #[cfg(feature = "standalone")]
 pub use reconcile::reconcile_service_startup;
 #[cfg(feature = "standalone")]
@@ -0,0 +1,125 @@
+//! systemd readiness and watchdog notifications.
+//! Speaks the `sd_notify` datagram protocol on `NOTIFY_SOCKET` directly, so the service does not
+//! link libsystemd; outside a `Type=notify` unit the environment is unset and every call is a no-op.
+
+use crate::core::structure::ServiceLifecycleState;
+use std::io;
+use std::os::linux::net::SocketAddrExt as _;
+use std::os::unix::net::{SocketAddr, UnixDatagram};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tracing::{debug, warn};
+
+/// Reports a lifecycle transition; the first `Running` tells systemd startup has finished.
+pub(super) fn notify_lifecycle_state(state: ServiceLifecycleState) {
+    notify(lifecycle_message(state));
+}
+
+/// Tells systemd that shutdown has begun, so a slow core stop is not mistaken for a hang.
+pub(super) fn notify_stopping() {
+    notify("STOPPING=1\nSTATUS=Stopping");
+}
+
+/// Pings the unit watchdog at half its interval for as long as the runtime keeps scheduling us.
+pub(super) fn spawn_watchdog() -> Option<JoinHandle<()>> {
+    let interval = watchdog_interval(
+        std::env::var("WATCHDOG_USEC").ok().as_deref(),
+        std::env::var("WATCHDOG_PID").ok().as_deref(),
+        std::process::id(),
+    )?;
+    debug!("systemd watchdog enabled; pinging every {interval:?}");
+    Some(tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        loop {
+            ticker.tick().await;
+            notify("WATCHDOG=1");
+        }
+    }))
+}
+
+fn lifecycle_message(state: ServiceLifecycleState) -> &'static str {
+    match state {
+        ServiceLifecycleState::Starting => "STATUS=Starting IPC server",
+        ServiceLifecycleState::Running => "READY=1\nSTATUS=Running",
+        ServiceLifecycleState::RecoveringCore => "STATUS=Recovering core",
+        ServiceLifecycleState::RecoveringIpc => "STATUS=Rebuilding IPC listener",
+        ServiceLifecycleState::Fatal => "STATUS=Fatal error; see service log",
+    }
+}
+
+fn watchdog_interval(
+    watchdog_usec: Option<&str>,
+    watchdog_pid: Option<&str>,
+    own_pid: u32,
+) -> Option<Duration> {
+    if let Some(pid) = watchdog_pid
+        && pid.parse::<u32>().ok() != Some(own_pid)
+    {
+        return None;
+    }
+    let usec = watchdog_usec?
+        .parse::<u64>()
+        .ok()
+        .filter(|usec| *usec > 0)?;
+    Some(Duration::from_micros(usec / 2))
+}
+
+fn notify(message: &str) {
+    let Some(socket) = std::env::var_os("NOTIFY_SOCKET") else {
+        return;
+    };
+    if let Err(error) = send_notification(&socket.to_string_lossy(), message) {
+        warn!("Failed to notify systemd ({message:?}): {error}");
+    }
+}
+
+/// A leading `@` names a socket in the abstract namespace.
+fn send_notification(socket: &str, message: &str) -> io::Result<()> {
+    let address = match socket.strip_prefix('@') {
+        Some(name) => SocketAddr::from_abstract_name(name)?,
+        None => SocketAddr::from_pathname(socket)?,
+    };
+    UnixDatagram::unbound()?.send_to_addr(message.as_bytes(), &address)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{lifecycle_message, send_notification, watchdog_interval};
+    use crate::core::structure::ServiceLifecycleState;
+    use std::os::unix::net::UnixDatagram;
+    use std::time::Duration;
+
+    #[test]
+    fn notifications_reach_a_path_socket() {
+        let path = std::env::temp_dir().join(format!("service-notify-{}.sock", std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        let systemd = UnixDatagram::bind(&path).expect("bind notify socket");
+
+        send_notification(
+            path.to_str().expect("utf-8 temp path"),
+            lifecycle_message(ServiceLifecycleState::Running),
+        )
+        .expect("send notification");
+
+        let mut buffer = [0u8; 256];
+        let received = systemd.recv(&mut buffer).expect("receive notification");
+        assert_eq!(&buffer[..received], b"READY=1\nSTATUS=Running");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn watchdog_pings_at_half_the_interval_for_the_named_process() {
+        assert_eq!(
+            watchdog_interval(Some("30000000"), None, 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(
+            watchdog_interval(Some("30000000"), Some("42"), 42),
+            Some(Duration::from_secs(15))
+        );
+        assert_eq!(watchdog_interval(Some("30000000"), Some("41"), 42), None);
+        assert_eq!(watchdog_interval(Some("0"), None, 42), None);
+        assert_eq!(watchdog_interval(None, None, 42), None);
+    }
+}
@@ -45,7 +45,7 @@ pub async fn acquire_service_owner() -> Result<Option<ServiceOwnerGuard>> {
     let paths = service_paths();
     crate::core::paths::ensure_persistent_state_layout()?;
     #[cfg(unix)]
-    crate::core::unix_security::ensure_service_directory(paths.runtime_dir(), 0o755)?;
+    crate::core::unix_security::ensure_runtime_directory(paths.runtime_dir())?;
     #[cfg(windows)]
     crate::core::windows_security::ensure_private_service_directory(paths.runtime_dir())?;
 
@@ -174,7 +174,7 @@ fn write_owner_metadata(file: &mut File, paths: &ServicePaths, pid: u32) -> Resu
     Ok(())
 }
 
-fn read_owner_pid(paths: &ServicePaths) -> Option<u32> {
+pub(super) fn read_owner_pid(paths: &ServicePaths) -> Option<u32> {
     read_pid_file(paths.pid_file_path()).or_else(|| read_owner_lock_pid(paths.owner_lock_path()))
 }
 
@@ -1,8 +1,20 @@
-use crate::core::structure::{OwnerIdentity, owner_key};
+use crate::channel::{CHANNEL_IDENTITY, ChannelIdentity};
+use crate::core::structure::{OwnerIdentity, ServiceMode, owner_key};
 use std::path::{Path, PathBuf};
+use std::sync::RwLock;
+
+/// Relocates a test build's service below one directory, for helper processes that cannot be
+/// handed a path set. Release builds ignore it, so nobody can redirect a root service's state.
+#[cfg(feature = "test")]
+pub const SERVICE_ROOT_ENV: &str = "CLASH_VERGE_SERVICE_ROOT";
+
+static SELECTED_SERVICE_PATHS: RwLock<Option<ServicePaths>> = RwLock::new(None);
 
 #[derive(Debug, Clone)]
 pub struct ServicePaths {
+    channel: ChannelIdentity,
+    mode: ServiceMode,
+    root: Option<PathBuf>,
     runtime_dir: PathBuf,
     persistent_state_dir: PathBuf,
     ipc_path: PathBuf,
@@ -18,6 +30,43 @@ pub struct OwnerPaths {
 }
 
 impl ServicePaths {
+    fn new(
+        channel: ChannelIdentity,
+        mode: ServiceMode,
+        root: Option<PathBuf>,
+        runtime_dir: PathBuf,
+        persistent_state_dir: PathBuf,
+        ipc_path: PathBuf,
+    ) -> Self {
+        let slug = channel.service_slug;
+        Self {
+            channel,
+            mode,
+            root,
+            desired_state_path: persistent_state_dir.join("desired-state.json"),
+            persistent_state_dir,
+            ipc_path,
+            owner_lock_path: runtime_dir.join(format!("{slug}.owner.lock")),
+            pid_file_path: runtime_dir.join(format!("{slug}.pid")),
+            core_runtime_path: runtime_dir.join(format!("{slug}.core.json")),
+            runtime_dir,
+        }
+    }
+
+    pub fn channel(&self) -> ChannelIdentity {
+        self.channel
+    }
+
+    /// How the service at these paths was installed.
+    pub fn mode(&self) -> ServiceMode {
+        self.mode
+    }
+
+    /// The directory everything was relocated below, for paths from [`rooted_service_paths`].
+    pub fn root(&self) -> Option<&Path> {
+        self.root.as_deref()
+    }
+
     pub fn runtime_dir(&self) -> &Path {
         &self.runtime_dir
     }
@@ -46,6 +95,11 @@ impl ServicePaths {
         &self.desired_state_path
     }
 
+    pub fn core_handoff_path(&self) -> PathBuf {
+        self.runtime_dir
+            .join(format!("{}.handoff", self.channel.service_slug))
+    }
+
     pub fn install_dir(&self) -> PathBuf {
         self.persistent_state_dir.join("bin")
     }
@@ -58,6 +112,39 @@ impl ServicePaths {
         self.persistent_state_dir.join("owner-generation.json")
     }
 
+    pub fn owner_transition_path(&self) -> PathBuf {
+        self.persistent_state_dir.join("owner-transition.json")
+    }
+
+    /// How the last supervised core ended; unlike the core runtime record it outlives the core.
+    pub fn core_exit_path(&self) -> PathBuf {
+        self.persistent_state_dir.join("core-exit.json")
+    }
+
+    /// Written by an administrator; the service only reads it.
+    pub fn observer_policy_path(&self) -> PathBuf {
+        self.persistent_state_dir.join("observer-policy.json")
+    }
+
+    /// Written by an administrator like the observer policy.
+    pub fn admin_policy_path(&self) -> PathBuf {
+        self.persistent_state_dir.join("admin-policy.json")
+    }
+
+    pub fn audit_log_path(&self) -> PathBuf {
+        self.persistent_state_dir.join("audit.jsonl")
+    }
+
+    /// Per-install signing key the service answers handshakes with; never leaves the service.
+    pub fn handshake_key_path(&self) -> PathBuf {
+        self.persistent_state_dir.join("handshake.key")
+    }
+
+    /// Public half of the handshake key, published next to the control socket for clients.
+    pub fn handshake_public_key_path(&self) -> PathBuf {
+        self.runtime_dir.join("handshake.pub")
+    }
+
     pub fn for_owner(&self, identity: &OwnerIdentity) -> OwnerPaths {
         self.for_owner_key(&owner_key(identity))
     }
@@ -87,26 +174,177 @@ impl OwnerPaths {
     }
 }
 
+/// Makes `paths` the ones this process serves and maintains, until another set replaces them.
+/// [`crate::run_ipc_server`] selects the set it is given; service-side calls made before it, such
+/// as startup reconciliation, need the same set selected first.
+pub fn set_service_paths(paths: ServicePaths) {
+    *SELECTED_SERVICE_PATHS
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(paths);
+}
+
+/// Paths of the service this process runs or maintains: the selected set, or else the compiled-in
+/// channel's service at its usual place.
 pub fn service_paths() -> ServicePaths {
-    let runtime_dir = runtime_dir();
-    let persistent_state_dir = persistent_state_dir();
-    ServicePaths {
-        desired_state_path: persistent_state_dir.join("desired-state.json"),
-        persistent_state_dir,
-        ipc_path: PathBuf::from(crate::IPC_PATH),
-        owner_lock_path: runtime_dir.join(format!("{}.owner.lock", crate::SERVICE_SLUG)),
-        pid_file_path: runtime_dir.join(format!("{}.pid", crate::SERVICE_SLUG)),
-        core_runtime_path: runtime_dir.join(format!("{}.core.json", crate::SERVICE_SLUG)),
+    let selected = SELECTED_SERVICE_PATHS
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    selected.unwrap_or_else(|| service_paths_for(&CHANNEL_IDENTITY, ServiceMode::System))
+}
+
+/// Paths of `channel`'s service installed in `mode`, at its usual place. Rootless services only
+/// exist on Linux; elsewhere this is always the system service.
+pub fn service_paths_for(channel: &ChannelIdentity, mode: ServiceMode) -> ServicePaths {
+    #[cfg(feature = "test")]
+    if let Some(root) = std::env::var_os(SERVICE_ROOT_ENV)
+        .map(PathBuf::from)
+        .filter(|root| root.is_absolute())
+    {
+        return rooted_service_paths(channel, mode, &root);
+    }
+
+    #[cfg(target_os = "linux")]
+    if mode == ServiceMode::User {
+        return user_service_paths(channel, &user_runtime_root(), &user_state_root());
+    }
+
+    let persistent_state_dir = persistent_state_dir(channel);
+    let ipc_path = system_ipc_path(channel);
+    #[cfg(unix)]
+    let runtime_dir = ipc_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| PathBuf::from("/run").join(channel.service_slug));
+    #[cfg(windows)]
+    let runtime_dir = persistent_state_dir.join("runtime");
+    ServicePaths::new(
+        *channel,
+        ServiceMode::System,
+        None,
         runtime_dir,
+        persistent_state_dir,
+        ipc_path,
+    )
+}
+
+/// The control socket of a system service; for the compiled-in channel this is `IPC_PATH`.
+fn system_ipc_path(channel: &ChannelIdentity) -> PathBuf {
+    let slug = channel.service_slug;
+    if cfg!(windows) {
+        if cfg!(feature = "test") {
+            PathBuf::from(format!(r"\\.\pipe\{slug}-test"))
+        } else {
+            PathBuf::from(format!(r"\\.\pipe\{slug}"))
+        }
+    } else if cfg!(feature = "test") {
+        PathBuf::from(format!("/tmp/{slug}-ipc-test/service.sock"))
+    } else if cfg!(target_os = "macos") {
+        PathBuf::from(format!("/var/run/{slug}/service.sock"))
+    } else {
+        PathBuf::from(format!("/run/{slug}/service.sock"))
     }
 }
 
+/// Paths of `channel`'s service relocated below the absolute directory `root`, so isolated
+/// instances can run side by side. A root overrides both the system and the rootless layouts;
+/// the service still behaves as `mode` asks.
+pub fn rooted_service_paths(
+    channel: &ChannelIdentity,
+    mode: ServiceMode,
+    root: &Path,
+) -> ServicePaths {
+    let runtime_dir = root.join("run");
+    let ipc_path = rooted_ipc_path(channel, root, &runtime_dir);
+    ServicePaths::new(
+        *channel,
+        mode,
+        Some(root.to_path_buf()),
+        runtime_dir,
+        root.join("state"),
+        ipc_path,
+    )
+}
+
+#[cfg(unix)]
+fn rooted_ipc_path(_channel: &ChannelIdentity, _root: &Path, runtime_dir: &Path) -> PathBuf {
+    runtime_dir.join("service.sock")
+}
+
+/// Named pipes live outside the filesystem, so the root picks a pipe name instead.
+#[cfg(windows)]
+fn rooted_ipc_path(channel: &ChannelIdentity, root: &Path, _runtime_dir: &Path) -> PathBuf {
+    PathBuf::from(format!(
+        r"\\.\pipe\{}-{}",
+        channel.service_slug,
+        root_instance_id(root)
+    ))
+}
+
+/// A short, stable name for a relocated instance, used where a path cannot carry the root.
+fn root_instance_id(root: &Path) -> String {
+    use sha2::{Digest as _, Sha256};
+
+    Sha256::digest(root.as_os_str().as_encoded_bytes())[..8]
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Rootless layout: the socket lives in the user's runtime directory and state in
+/// `$XDG_STATE_HOME`, both named after the channel slug like their system counterparts.
+#[cfg(target_os = "linux")]
+fn user_service_paths(
+    channel: &ChannelIdentity,
+    runtime_root: &Path,
+    state_root: &Path,
+) -> ServicePaths {
+    let runtime_dir = runtime_root.join(channel.service_slug);
+    let ipc_path = runtime_dir.join("service.sock");
+    ServicePaths::new(
+        *channel,
+        ServiceMode::User,
+        None,
+        runtime_dir,
+        state_root.join(channel.service_slug),
+        ipc_path,
+    )
+}
+
+#[cfg(target_os = "linux")]
+fn user_runtime_root() -> PathBuf {
+    use std::os::unix::fs::MetadataExt as _;
+
+    absolute_env_path("XDG_RUNTIME_DIR").unwrap_or_else(|| {
+        let uid = std::fs::metadata("/proc/self").map_or(0, |metadata| metadata.uid());
+        PathBuf::from(format!("/run/user/{uid}"))
+    })
+}
+
+#[cfg(target_os = "linux")]
+fn user_state_root() -> PathBuf {
+    absolute_env_path("XDG_STATE_HOME").unwrap_or_else(|| {
+        absolute_env_path("HOME")
+            .unwrap_or_else(|| PathBuf::from("/"))
+            .join(".local/state")
+    })
+}
+
+/// The XDG base directory specification says relative values must be ignored.
+#[cfg(target_os = "linux")]
+fn absolute_env_path(key: &str) -> Option<PathBuf> {
+    std::env::var_os(key)
+        .map(PathBuf::from)
+        .filter(|path| path.is_absolute())
+}
+
 #[cfg(feature = "standalone")]
 pub(crate) fn ensure_persistent_state_layout() -> anyhow::Result<()> {
     let paths = service_paths();
     let root = paths.persistent_state_dir();
     use crate::core::platform_security;
 
+    create_user_state_home(&paths)?;
     platform_security::ensure_private_service_directory(root)?;
 
     let users = root.join("users");
@@ -115,21 +353,35 @@ pub(crate) fn ensure_persistent_state_layout() -> anyhow::Result<()> {
     platform_security::ensure_private_service_directory(&install)?;
     platform_security::secure_private_service_file_if_exists(&paths.active_owner_path())?;
     platform_security::secure_private_service_file_if_exists(&paths.owner_generation_path())?;
+    platform_security::secure_private_service_file_if_exists(&paths.owner_transition_path())?;
     Ok(())
 }
 
+/// Creates the private install directory of the service at `paths` and returns it.
 #[cfg(feature = "standalone")]
-pub fn prepare_service_install_directory() -> anyhow::Result<PathBuf> {
-    let paths = service_paths();
+pub fn prepare_service_install_directory(paths: &ServicePaths) -> anyhow::Result<PathBuf> {
     let root = paths.persistent_state_dir();
     let install = paths.install_dir();
     use crate::core::platform_security;
 
+    create_user_state_home(paths)?;
     platform_security::ensure_private_installer_directory(root)?;
     platform_security::ensure_private_installer_directory(&install)?;
     Ok(install)
 }
 
+/// A fresh account may not have `$XDG_STATE_HOME` yet; system state roots always have a parent.
+#[cfg(feature = "standalone")]
+fn create_user_state_home(paths: &ServicePaths) -> anyhow::Result<()> {
+    use anyhow::Context as _;
+
+    match (paths.mode(), paths.persistent_state_dir().parent()) {
+        (ServiceMode::User, Some(parent)) => std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed to create user state directory {parent:?}")),
+        _ => Ok(()),
+    }
+}
+
 #[cfg(feature = "standalone")]
 pub(crate) fn ensure_owner_state_directory(identity: &OwnerIdentity) -> anyhow::Result<OwnerPaths> {
     ensure_persistent_state_layout()?;
@@ -138,44 +390,29 @@ pub(crate) fn ensure_owner_state_directory(identity: &OwnerIdentity) -> anyhow::
     Ok(owner)
 }
 
-fn runtime_dir() -> PathBuf {
-    #[cfg(unix)]
-    {
-        Path::new(crate::IPC_PATH)
-            .parent()
-            .map(Path::to_path_buf)
-            .unwrap_or_else(|| PathBuf::from("/run/clash-verge-service"))
-    }
-
-    #[cfg(windows)]
-    {
-        persistent_state_dir().join("runtime")
-    }
-}
-
-fn persistent_state_dir() -> PathBuf {
+fn persistent_state_dir(channel: &ChannelIdentity) -> PathBuf {
     #[cfg(feature = "test")]
     {
-        std::env::temp_dir().join("clash-verge-service-ipc-test-state")
+        std::env::temp_dir().join(format!("{}-ipc-test-state", channel.service_slug))
     }
 
     // A root launchd daemon needs stable system state independent of unreliable HOME/XDG values
     // (issue #7333).
     #[cfg(all(target_os = "macos", not(feature = "test")))]
     {
-        PathBuf::from("/Library/Application Support").join(crate::SERVICE_SLUG)
+        PathBuf::from("/Library/Application Support").join(channel.service_slug)
     }
 
     #[cfg(all(unix, not(target_os = "macos"), not(feature = "test")))]
     {
-        PathBuf::from("/var/lib").join(crate::SERVICE_SLUG)
+        PathBuf::from("/var/lib").join(channel.service_slug)
     }
 
     #[cfg(all(windows, not(feature = "test")))]
     {
         windows_program_data()
             .unwrap_or_else(|| PathBuf::from(r"C:\ProgramData"))
-            .join(crate::SERVICE_SLUG)
+            .join(channel.service_slug)
     }
 }
 
@@ -211,44 +448,42 @@ pub(crate) fn unix_mihomo_ipc_path(runtime_root: &Path, uid: u32) -> PathBuf {
         .join("verge-mihomo.sock")
 }
 
+fn mihomo_pipe_namespace() -> String {
+    let paths = service_paths();
+    let channel_id = if cfg!(feature = "test") {
+        "test"
+    } else {
+        paths.channel().id
+    };
+    match paths.root() {
+        Some(root) => format!("{channel_id}-{}", root_instance_id(root)),
+        None => channel_id.to_owned(),
+    }
+}
+
 pub fn mihomo_ipc_path(identity: &OwnerIdentity) -> String {
     match identity {
         OwnerIdentity::Unix { uid: _uid, .. } => {
             #[cfg(windows)]
             {
-                let channel_id = if cfg!(feature = "test") {
-                    "test"
-                } else {
-                    crate::CHANNEL_IDENTITY.id
-                };
                 format!(
                     r"\\.\pipe\verge-mihomo-{}-{}",
-                    channel_id,
+                    mihomo_pipe_namespace(),
                     owner_key(identity)
                 )
             }
 
             #[cfg(unix)]
             {
-                #[cfg(feature = "test")]
-                let runtime_root = PathBuf::from("/tmp/clash-verge-service-ipc-test");
-                #[cfg(not(feature = "test"))]
-                let runtime_root = service_paths().runtime_dir().to_path_buf();
-
-                unix_mihomo_ipc_path(&runtime_root, *_uid)
+                unix_mihomo_ipc_path(service_paths().runtime_dir(), *_uid)
                     .to_string_lossy()
                     .into_owned()
             }
         }
         OwnerIdentity::Windows { .. } => {
-            let channel_id = if cfg!(feature = "test") {
-                "test"
-            } else {
-                crate::CHANNEL_IDENTITY.id
-            };
             format!(
                 r"\\.\pipe\verge-mihomo-{}-{}",
-                channel_id,
+                mihomo_pipe_namespace(),
                 owner_key(identity)
             )
         }
@@ -261,7 +496,7 @@ mod tests {
     #[cfg(unix)]
     use super::unix_mihomo_ipc_path;
     use crate::OwnerIdentity;
-    #[cfg(unix)]
+    use crate::ServiceMode;
     use std::path::Path;
 
     #[cfg(unix)]
@@ -276,6 +511,79 @@ mod tests {
         assert!(path.as_os_str().as_encoded_bytes().len() < 104);
     }
 
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn user_mode_keeps_the_socket_and_state_under_xdg_directories() {
+        let paths = super::user_service_paths(
+            &crate::CHANNEL_IDENTITY,
+            Path::new("/run/user/1000"),
+            Path::new("/home/someone/.local/state"),
+        );
+        let runtime = Path::new("/run/user/1000").join(crate::SERVICE_SLUG);
+        let state = Path::new("/home/someone/.local/state").join(crate::SERVICE_SLUG);
+
+        assert_eq!(paths.mode(), ServiceMode::User);
+        assert_eq!(paths.ipc_path(), runtime.join("service.sock"));
+        assert_eq!(paths.runtime_dir(), runtime);
+        assert_eq!(paths.persistent_state_dir(), state);
+        assert_eq!(paths.install_dir(), state.join("bin"));
+        assert!(paths.owner_lock_path().starts_with(&runtime));
+        assert!(paths.core_runtime_path().starts_with(&runtime));
+    }
+
+    #[test]
+    fn an_explicit_root_relocates_every_service_path() {
+        let root = std::env::temp_dir().join("service-paths-root");
+        let paths = super::rooted_service_paths(&crate::CHANNEL_IDENTITY, ServiceMode::User, &root);
+
+        assert_eq!(paths.root(), Some(root.as_path()));
+        assert_eq!(paths.mode(), ServiceMode::User);
+        for path in [
+            paths.runtime_dir(),
+            paths.persistent_state_dir(),
+            paths.owner_lock_path(),
+            paths.pid_file_path(),
+            paths.core_runtime_path(),
+            paths.desired_state_path(),
+        ] {
+            assert!(path.starts_with(&root), "{path:?} escapes {root:?}");
+        }
+        #[cfg(unix)]
+        assert!(paths.ipc_path().starts_with(&root));
+        #[cfg(windows)]
+        assert_ne!(
+            paths.ipc_path(),
+            super::rooted_service_paths(
+                &crate::CHANNEL_IDENTITY,
+                ServiceMode::System,
+                &root.join("other")
+            )
+            .ipc_path()
+        );
+    }
+
+    #[test]
+    fn system_paths_of_the_compiled_channel_match_ipc_path() {
+        assert_eq!(
+            super::system_ipc_path(&crate::CHANNEL_IDENTITY),
+            Path::new(crate::IPC_PATH)
+        );
+    }
+
+    #[test]
+    fn channels_never_share_system_paths() {
+        let production = super::system_ipc_path(&crate::PRODUCTION_CHANNEL);
+        let development = super::system_ipc_path(&crate::DEVELOPMENT_CHANNEL);
+        let qa = crate::ChannelIdentity::from_id("qa").expect("custom channel");
+
+        assert_ne!(production, development);
+        assert_ne!(production, super::system_ipc_path(&qa));
+        assert_ne!(
+            super::persistent_state_dir(&crate::PRODUCTION_CHANNEL),
+            super::persistent_state_dir(&qa)
+        );
+    }
+
     #[test]
     fn owner_paths_isolate_state_runtime_and_logs() {
         let paths = service_paths();
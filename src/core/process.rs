@@ -139,6 +139,184 @@ pub(super) fn process_identity(pid: u32) -> Result<Option<ProcessIdentity>> {
     }
 }
 
+/// Point-in-time resource consumption of a live process.
+/// Counters the platform cannot report cheaply are `None` rather than zero.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct ProcessUsage {
+    pub(super) rss_bytes: u64,
+    pub(super) cpu_time_ms: u64,
+    pub(super) threads: Option<u32>,
+    pub(super) open_fds: Option<u32>,
+}
+
+/// On Linux this reads only `/proc`, keeping periodic sampling free of `ps` subprocesses.
+pub(super) fn process_usage(pid: u32) -> Result<Option<ProcessUsage>> {
+    #[cfg(not(target_os = "linux"))]
+    if !is_process_alive(pid) {
+        return Ok(None);
+    }
+
+    #[cfg(target_os = "linux")]
+    {
+        let stat = match std::fs::read_to_string(format!("/proc/{pid}/stat")) {
+            Ok(stat) => stat,
+            Err(error) if error.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        let fields = stat
+            .rsplit_once(')')
+            .ok_or_else(|| anyhow::anyhow!("invalid /proc stat for process {pid}"))?
+            .1
+            .split_whitespace()
+            .collect::<Vec<_>>();
+        if fields.first() == Some(&"Z") {
+            return Ok(None);
+        }
+        // Indices are offset by the two fields before the closing parenthesis; see proc_pid_stat(5).
+        let field = |index: usize, name: &str| -> Result<u64> {
+            Ok(fields
+                .get(index)
+                .ok_or_else(|| anyhow::anyhow!("missing {name} for process {pid}"))?
+                .parse()?)
+        };
+        let cpu_ticks = field(11, "utime")?.saturating_add(field(12, "stime")?);
+        let threads = field(17, "thread count")?;
+        let rss_pages = field(21, "resident set size")?;
+        let ticks_per_second = unsafe { platform_lib::sysconf(platform_lib::_SC_CLK_TCK) }.max(1);
+        let page_size = unsafe { platform_lib::sysconf(platform_lib::_SC_PAGESIZE) }.max(1);
+        // Reading another user's fd table can fail even for a live process; report it as unknown.
+        let open_fds = std::fs::read_dir(format!("/proc/{pid}/fd"))
+            .ok()
+            .map(|entries| entries.count() as u32);
+        Ok(Some(ProcessUsage {
+            rss_bytes: rss_pages.saturating_mul(page_size as u64),
+            cpu_time_ms: cpu_ticks.saturating_mul(1000) / ticks_per_second as u64,
+            threads: Some(threads as u32),
+            open_fds,
+        }))
+    }
+
+    #[cfg(target_os = "macos")]
+    {
+        let mut info = unsafe { std::mem::zeroed::<platform_lib::proc_taskinfo>() };
+        let info_len = unsafe {
+            platform_lib::proc_pidinfo(
+                pid as i32,
+                platform_lib::PROC_PIDTASKINFO,
+                0,
+                (&mut info as *mut platform_lib::proc_taskinfo).cast(),
+                std::mem::size_of::<platform_lib::proc_taskinfo>() as i32,
+            )
+        };
+        if info_len != std::mem::size_of::<platform_lib::proc_taskinfo>() as i32 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let fd_buffer_len = unsafe {
+            platform_lib::proc_pidinfo(
+                pid as i32,
+                platform_lib::PROC_PIDLISTFDS,
+                0,
+                std::ptr::null_mut(),
+                0,
+            )
+        };
+        let open_fds = (fd_buffer_len > 0).then(|| {
+            (fd_buffer_len as usize / std::mem::size_of::<platform_lib::proc_fdinfo>()) as u32
+        });
+        // Task CPU times are Mach absolute time units, which are not nanoseconds on Apple silicon.
+        let mut timebase = platform_lib::mach_timebase_info_data_t { numer: 1, denom: 1 };
+        unsafe { platform_lib::mach_timebase_info(&mut timebase) };
+        let cpu_time_ns = u128::from(info.pti_total_user.saturating_add(info.pti_total_system))
+            * u128::from(timebase.numer)
+            / u128::from(timebase.denom.max(1));
+        Ok(Some(ProcessUsage {
+            rss_bytes: info.pti_resident_size,
+            cpu_time_ms: (cpu_time_ns / 1_000_000) as u64,
+            threads: u32::try_from(info.pti_threadnum).ok(),
+            open_fds,
+        }))
+    }
+
+    #[cfg(windows)]
+    {
+        use windows_sys::Win32::Foundation::{CloseHandle, FILETIME};
+        use windows_sys::Win32::System::ProcessStatus::{
+            GetProcessMemoryInfo, PROCESS_MEMORY_COUNTERS,
+        };
+        use windows_sys::Win32::System::Threading::{
+            GetProcessHandleCount, GetProcessTimes, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+        };
+
+        let handle = unsafe { OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid) };
+        if handle.is_null() {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        struct ProcessHandle(windows_sys::Win32::Foundation::HANDLE);
+        impl Drop for ProcessHandle {
+            fn drop(&mut self) {
+                unsafe { CloseHandle(self.0) };
+            }
+        }
+        let handle = ProcessHandle(handle);
+        let mut creation = FILETIME::default();
+        let mut exit = FILETIME::default();
+        let mut kernel = FILETIME::default();
+        let mut user = FILETIME::default();
+        if unsafe { GetProcessTimes(handle.0, &mut creation, &mut exit, &mut kernel, &mut user) }
+            == 0
+        {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let filetime_100ns =
+            |time: FILETIME| (u64::from(time.dwHighDateTime) << 32) | u64::from(time.dwLowDateTime);
+        let mut memory = unsafe { std::mem::zeroed::<PROCESS_MEMORY_COUNTERS>() };
+        memory.cb = std::mem::size_of::<PROCESS_MEMORY_COUNTERS>() as u32;
+        if unsafe { GetProcessMemoryInfo(handle.0, &mut memory, memory.cb) } == 0 {
+            return Err(std::io::Error::last_os_error().into());
+        }
+        let mut handles = 0u32;
+        let open_fds =
+            (unsafe { GetProcessHandleCount(handle.0, &mut handles) } != 0).then_some(handles);
+        Ok(Some(ProcessUsage {
+            rss_bytes: memory.WorkingSetSize as u64,
+            cpu_time_ms: filetime_100ns(kernel).saturating_add(filetime_100ns(user)) / 10_000,
+            threads: None,
+            open_fds,
+        }))
+    }
+
+    #[cfg(all(unix, not(any(target_os = "linux", target_os = "macos"))))]
+    {
+        let output = std::process::Command::new("ps")
+            .args(["-o", "rss=", "-o", "time=", "-p", &pid.to_string()])
+            .output()?;
+        if !output.status.success() {
+            return Ok(None);
+        }
+        let output = String::from_utf8(output.stdout)?;
+        let mut columns = output.split_whitespace();
+        let rss_kib: u64 = columns
+            .next()
+            .unwrap_or_default()
+            .parse()
+            .unwrap_or_default();
+        let cpu_seconds = columns
+            .next()
+            .unwrap_or_default()
+            .split(['-', ':'])
+            .filter_map(|part| part.parse::<u64>().ok())
+            .fold(0u64, |total, part| {
+                total.saturating_mul(60).saturating_add(part)
+            });
+        Ok(Some(ProcessUsage {
+            rss_bytes: rss_kib.saturating_mul(1024),
+            cpu_time_ms: cpu_seconds.saturating_mul(1000),
+            threads: None,
+            open_fds: None,
+        }))
+    }
+}
+
 pub(super) fn is_process_alive(pid: u32) -> bool {
     #[cfg(unix)]
     {
@@ -299,6 +299,44 @@ pub async fn apply_proxy_or_direct(
     }
 }
 
+/// Whether proxy routes affect real system settings in this build.
+const SERVICE_PROXY_IS_LIVE: bool = cfg!(all(target_os = "macos", not(feature = "test")));
+
+pub(super) async fn clear_service_proxy() -> anyhow::Result<()> {
+    if SERVICE_PROXY_IS_LIVE {
+        clear_proxy().await
+    } else {
+        Ok(())
+    }
+}
+
+pub(super) async fn compensate_service_proxy() -> anyhow::Result<()> {
+    if SERVICE_PROXY_IS_LIVE {
+        apply_proxy(&MacosProxyConfig::Disabled).await
+    } else {
+        Ok(())
+    }
+}
+
+#[cfg(not(feature = "test"))]
+pub(super) async fn apply_service_proxy_or_direct(
+    config: Option<&MacosProxyConfig>,
+) -> anyhow::Result<ProxyApplyOutcome> {
+    apply_proxy_or_direct(config).await
+}
+
+#[cfg(feature = "test")]
+pub(super) async fn apply_service_proxy_or_direct(
+    config: Option<&MacosProxyConfig>,
+) -> anyhow::Result<ProxyApplyOutcome> {
+    let _ = apply_proxy_or_direct;
+    Ok(if config.is_some() {
+        ProxyApplyOutcome::Applied
+    } else {
+        ProxyApplyOutcome::NotRequested
+    })
+}
+
 #[cfg(test)]
 mod tests {
     #[cfg(target_os = "macos")]
@@ -1,6 +1,11 @@
+#[cfg(target_os = "linux")]
+use crate::core::adoption::adoption_candidate;
+use crate::core::handoff::cancel_core_handoff;
+use crate::core::journal::recover_owner_transition;
+use crate::core::manager::CORE_MANAGER;
 use crate::core::process::{process_identity, terminate_process};
 use crate::core::runtime::{
-    cleanup_core_socket, is_core_socket_reachable, read_core_runtime_record,
+    CoreRuntimeRecord, cleanup_core_socket, is_core_socket_reachable, read_core_runtime_record,
     remove_core_runtime_record,
 };
 use anyhow::Result;
@@ -20,6 +25,12 @@ pub(super) fn ensure_startup_reconciled() -> Result<()> {
 pub async fn reconcile_service_startup() -> Result<()> {
     STARTUP_RECONCILED.store(false, Ordering::Release);
     info!("Running service startup reconciliation");
+    // A request the previous instance did not consume must not apply to this one's shutdown.
+    cancel_core_handoff();
+    // Ownership must be settled first: adoption asks the active owner whether to keep the core.
+    recover_owner_transition().await?;
+    // Adopting a handed-over core replaces this with the previous instance's own bookkeeping.
+    CORE_MANAGER.lock().await.restore_last_exit().await;
 
     let Some(record) = read_core_runtime_record().await? else {
         STARTUP_RECONCILED.store(true, Ordering::Release);
@@ -30,6 +41,10 @@ pub async fn reconcile_service_startup() -> Result<()> {
     let socket_reachable = is_core_socket_reachable(&record.ipc_path).await;
 
     if current_identity.as_ref() == Some(&record.identity) {
+        if socket_reachable && adopt_previous_core(&record).await {
+            STARTUP_RECONCILED.store(true, Ordering::Release);
+            return Ok(());
+        }
         warn!(
             "Found verified previous core process {} during startup; stopping it before supervision resumes",
             record.pid
@@ -64,3 +79,106 @@ pub async fn reconcile_service_startup() -> Result<()> {
     STARTUP_RECONCILED.store(true, Ordering::Release);
     Ok(())
 }
+
+/// Hands a verified, reachable leftover core to the manager when the active owner still wants it.
+/// Any failure leaves the core to be terminated like before.
+#[cfg(target_os = "linux")]
+async fn adopt_previous_core(record: &CoreRuntimeRecord) -> bool {
+    let candidate = match adoption_candidate(record).await {
+        Ok(Some(candidate)) => candidate,
+        Ok(None) => return false,
+        Err(error) => {
+            warn!("Failed to load desired state for core adoption: {error:#}");
+            return false;
+        }
+    };
+    match CORE_MANAGER
+        .lock()
+        .await
+        .adopt_core(record, candidate.config, candidate.owner)
+        .await
+    {
+        Ok(()) => true,
+        Err(error) => {
+            warn!("Failed to adopt previous core {}: {error:#}", record.pid);
+            false
+        }
+    }
+}
+
+/// Only Linux can reattach to a leftover core's output, so other platforms always replace it.
+#[cfg(not(target_os = "linux"))]
+async fn adopt_previous_core(_record: &CoreRuntimeRecord) -> bool {
+    false
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::reconcile_service_startup;
+    use crate::core::adoption::HANDOFF_OUTPUT_FDS;
+    use crate::core::desired::clear_active_owner;
+    use crate::core::manager::CORE_MANAGER;
+    use crate::core::process::process_identity;
+    use crate::core::runtime::{
+        CoreExitRecord, CoreRuntimeRecord, read_core_runtime_record, write_core_exit_record,
+        write_core_runtime_record,
+    };
+    use anyhow::Context as _;
+    use serial_test::serial;
+    use std::time::Duration;
+
+    /// The unit signals only the service, so one killed after its stop timeout leaves its core
+    /// running with the runtime record in place. Without an owner to adopt it, the next start
+    /// stops that core instead of leaving it unsupervised.
+    #[tokio::test]
+    #[serial]
+    async fn a_core_orphaned_by_a_killed_service_is_stopped_on_the_next_start() -> anyhow::Result<()>
+    {
+        clear_active_owner().await?;
+        let mut orphan = tokio::process::Command::new("sleep")
+            .arg("30")
+            .kill_on_drop(true)
+            .spawn()?;
+        let pid = orphan.id().context("orphan has no PID")?;
+        let identity = process_identity(pid)?.context("orphan exited early")?;
+        write_core_runtime_record(&CoreRuntimeRecord {
+            pid,
+            ipc_path: std::env::temp_dir()
+                .join(format!("orphaned-core-{pid}.sock"))
+                .to_string_lossy()
+                .into_owned(),
+            identity,
+            output_fds: Some(HANDOFF_OUTPUT_FDS),
+            supervision: None,
+        })
+        .await?;
+
+        reconcile_service_startup().await?;
+
+        let status = tokio::time::timeout(Duration::from_secs(5), orphan.wait()).await??;
+        assert!(!status.success());
+        assert!(read_core_runtime_record().await?.is_none());
+        Ok(())
+    }
+
+    /// The watchdog records each exit, so the crash report it saved can still be found after
+    /// the service restarts.
+    #[tokio::test]
+    #[serial]
+    async fn the_last_core_exit_survives_a_service_restart() -> anyhow::Result<()> {
+        clear_active_owner().await?;
+        let exit = CoreExitRecord {
+            reason: Some("core terminated by signal 6".to_owned()),
+            crash_report: Some("core-1700000000000-4242.log".to_owned()),
+        };
+        write_core_exit_record(&exit).await?;
+
+        reconcile_service_startup().await?;
+
+        let status = CORE_MANAGER.lock().await.status().await;
+        assert_eq!(status.last_core_exit_reason, exit.reason);
+        assert_eq!(status.last_crash_report, exit.crash_report);
+        write_core_exit_record(&CoreExitRecord::default()).await?;
+        Ok(())
+    }
+}
@@ -8,7 +8,7 @@ pub struct ServiceRepairGate {
 }
 
 pub fn acquire_service_repair_gate() -> Result<Option<ServiceRepairGate>> {
-    let directory = crate::prepare_service_install_directory()?;
+    let directory = crate::prepare_service_install_directory(&crate::service_paths())?;
     let path = directory.join(".repair.lock");
     let file = OpenOptions::new()
         .read(true)
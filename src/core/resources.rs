@@ -0,0 +1,134 @@
+//! Periodic resource sampling of the supervised core.
+//! History survives watchdog restarts so a leak that ends in a crash stays visible; each sample
+//! carries its PID so callers can tell the processes apart.
+
+use crate::core::process::process_usage;
+use crate::core::structure::CoreResourceSample;
+use std::collections::VecDeque;
+use std::sync::{
+    Arc, Mutex as StdMutex,
+    atomic::{AtomicU32, Ordering},
+};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::task::JoinHandle;
+use tracing::debug;
+
+const SAMPLE_INTERVAL: Duration = Duration::from_secs(15);
+/// Ten minutes at `SAMPLE_INTERVAL`.
+const HISTORY_CAPACITY: usize = 40;
+
+#[derive(Debug, Default)]
+pub(super) struct ResourceHistory {
+    samples: VecDeque<CoreResourceSample>,
+}
+
+impl ResourceHistory {
+    fn record(&mut self, sample: CoreResourceSample) {
+        if self.samples.len() == HISTORY_CAPACITY {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(sample);
+    }
+
+    pub(super) fn samples(&self) -> Vec<CoreResourceSample> {
+        self.samples.iter().cloned().collect()
+    }
+
+    pub(super) fn clear(&mut self) {
+        self.samples.clear();
+    }
+
+    /// Replaces the history with samples carried over from a previous service instance.
+    pub(super) fn restore(&mut self, samples: Vec<CoreResourceSample>) {
+        self.samples.clear();
+        for sample in samples {
+            self.record(sample);
+        }
+    }
+}
+
+/// Samples `pid` now; failures are logged and reported as no sample.
+pub(super) fn sample_core_resources(pid: u32) -> Option<CoreResourceSample> {
+    let usage = match process_usage(pid) {
+        Ok(usage) => usage?,
+        Err(error) => {
+            debug!("Failed to sample core {pid} resources: {error:#}");
+            return None;
+        }
+    };
+    Some(CoreResourceSample {
+        pid,
+        sampled_at: SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs())
+            .unwrap_or_default(),
+        rss_bytes: usage.rss_bytes,
+        cpu_time_ms: usage.cpu_time_ms,
+        threads: usage.threads,
+        open_fds: usage.open_fds,
+    })
+}
+
+/// Records a sample of whichever core `running_pid` names until the task is aborted.
+pub(super) fn spawn_resource_sampler(
+    running_pid: Arc<AtomicU32>,
+    history: Arc<StdMutex<ResourceHistory>>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(SAMPLE_INTERVAL);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        loop {
+            interval.tick().await;
+            let pid = running_pid.load(Ordering::Acquire);
+            if pid == 0 {
+                continue;
+            }
+            if let Some(sample) = sample_core_resources(pid) {
+                history.lock().unwrap().record(sample);
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HISTORY_CAPACITY, ResourceHistory, sample_core_resources};
+    use crate::core::structure::CoreResourceSample;
+
+    fn sample(pid: u32) -> CoreResourceSample {
+        CoreResourceSample {
+            pid,
+            sampled_at: u64::from(pid),
+            rss_bytes: 1,
+            cpu_time_ms: 1,
+            threads: None,
+            open_fds: None,
+        }
+    }
+
+    #[test]
+    fn history_keeps_only_the_newest_samples() {
+        let mut history = ResourceHistory::default();
+        for pid in 0..(HISTORY_CAPACITY as u32 + 5) {
+            history.record(sample(pid));
+        }
+
+        let samples = history.samples();
+        assert_eq!(samples.len(), HISTORY_CAPACITY);
+        assert_eq!(samples.first().map(|sample| sample.pid), Some(5));
+        assert_eq!(
+            samples.last().map(|sample| sample.pid),
+            Some(HISTORY_CAPACITY as u32 + 4)
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn samples_a_live_process() {
+        let sample = sample_core_resources(std::process::id()).expect("own process is alive");
+
+        assert!(sample.rss_bytes > 0);
+        assert!(sample.threads.is_some_and(|threads| threads >= 1));
+        assert!(sample.open_fds.is_some_and(|fds| fds >= 1));
+    }
+}
@@ -1,5 +1,8 @@
+use crate::core::desired::{read_document_or_default, write_document_atomic};
 use crate::core::paths::service_paths;
 use crate::core::process::ProcessIdentity;
+use crate::core::schema;
+use crate::core::structure::CoreResourceSample;
 use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use std::time::Duration;
@@ -9,6 +12,36 @@ pub(super) struct CoreRuntimeRecord {
     pub(super) pid: u32,
     pub(super) ipc_path: String,
     pub(super) identity: ProcessIdentity,
+    /// Descriptors at which the core holds the read ends of its own output pipes, if it was
+    /// started in a way that lets a later service instance reattach to them.
+    #[serde(default)]
+    pub(super) output_fds: Option<CoreOutputFds>,
+    /// Watchdog bookkeeping left by a service that handed the core over during an upgrade.
+    #[serde(default)]
+    pub(super) supervision: Option<CoreSupervisionState>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub(super) struct CoreSupervisionState {
+    pub(super) restart_count: u32,
+    pub(super) last_recovery_at: Option<u64>,
+    pub(super) last_core_exit_reason: Option<String>,
+    pub(super) last_crash_report: Option<String>,
+    pub(super) resource_history: Vec<CoreResourceSample>,
+}
+
+/// How the last supervised core ended. It survives service restarts, so the crash report it
+/// names can still be found with `GetCrashReport` afterwards.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub(super) struct CoreExitRecord {
+    pub(super) reason: Option<String>,
+    pub(super) crash_report: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub(super) struct CoreOutputFds {
+    pub(super) stdout: i32,
+    pub(super) stderr: i32,
 }
 
 pub(super) async fn write_core_runtime_record(record: &CoreRuntimeRecord) -> Result<()> {
@@ -71,6 +104,19 @@ pub(super) async fn remove_core_runtime_record() {
     let _ = tokio::fs::remove_file(paths.core_runtime_path()).await;
 }
 
+pub(super) async fn write_core_exit_record(record: &CoreExitRecord) -> Result<()> {
+    write_document_atomic(
+        &schema::CORE_EXIT,
+        &service_paths().core_exit_path(),
+        record,
+    )
+    .await
+}
+
+pub(super) async fn read_core_exit_record() -> Result<CoreExitRecord> {
+    read_document_or_default(&schema::CORE_EXIT, &service_paths().core_exit_path()).await
+}
+
 pub(super) async fn is_core_socket_reachable(path: &str) -> bool {
     #[cfg(unix)]
     {
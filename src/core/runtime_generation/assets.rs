@@ -1,4 +1,5 @@
 use crate::core::auth::{AuthenticatedOwner, ServiceError};
+use crate::core::metrics::{METRICS, RuntimeOperation};
 use crate::core::paths::ensure_owner_state_directory;
 use crate::{
     ClashConfig, CoreConfig, RuntimeBundle, ServiceErrorCode, WriterConfig, mihomo_ipc_path,
@@ -364,6 +365,12 @@ async fn materialize_plan(
         discarded = plan.required_deletes.len(),
         "Prepared the runtime generation"
     );
+    METRICS.record_runtime_assets(
+        RuntimeOperation::Prepare,
+        plan.copies.len(),
+        plan.skipped.len(),
+        plan.required_deletes.len(),
+    );
     Ok(())
 }
 
@@ -8,6 +8,8 @@ use super::assets::{
 };
 use crate::core::auth::{AuthenticatedOwner, ServiceError};
 use crate::core::manager::CORE_MANAGER;
+use crate::core::metrics::{METRICS, RuntimeOperation};
+use crate::core::schema;
 use crate::{RemoteProvider, RuntimeAsset, RuntimeBundle, StageRejection, StageRuntimeOutcome};
 use serde::{Deserialize, Serialize};
 use std::collections::{BTreeMap, BTreeSet};
@@ -36,9 +38,7 @@ impl SourceIdentity {
 /// A missing manifest means no file ownership or cache provenance can be trusted.
 #[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub(super) struct RuntimeManifest {
-    #[serde(default)]
     pub assets: BTreeMap<String, SourceIdentity>,
-    #[serde(default)]
     pub remote_providers: BTreeMap<String, String>,
 }
 
@@ -288,6 +288,12 @@ pub(crate) async fn stage_runtime(
         discarded = plan.required_deletes.len(),
         "Staged a runtime generation in place"
     );
+    METRICS.record_runtime_assets(
+        RuntimeOperation::Stage,
+        plan.copies.len(),
+        plan.skipped.len(),
+        plan.required_deletes.len(),
+    );
     Ok(StageRuntimeOutcome::Staged {
         config_path: config_path.to_string_lossy().into_owned(),
     })
@@ -311,12 +317,15 @@ pub(super) async fn source_identity_changed(source: &str, recorded: &SourceIdent
     }
 }
 
-/// Reads the manifest; absence is empty state, while malformed contents require a clean restart.
+/// Reads the manifest; absence is empty state, while malformed contents or a manifest from a
+/// newer service require a clean restart. The manifest is rewritten on every commit, so an old
+/// one is migrated without keeping a backup.
 pub(super) async fn read_manifest(generation: &Path) -> Result<RuntimeManifest, String> {
     let path = generation.join(MANIFEST_FILE_NAME);
     match tokio::fs::read(&path).await {
-        Ok(bytes) => serde_json::from_slice(&bytes)
-            .map_err(|error| format!("runtime manifest {path:?} is unreadable: {error}")),
+        Ok(bytes) => schema::decode(&schema::RUNTIME_MANIFEST, &bytes)
+            .map(|decoded| decoded.value)
+            .map_err(|error| format!("runtime manifest {path:?} is unreadable: {error:#}")),
         Err(error) if error.kind() == std::io::ErrorKind::NotFound => {
             Ok(RuntimeManifest::default())
         }
@@ -385,7 +394,7 @@ pub(super) async fn commit_staged_config(
 ) -> std::io::Result<()> {
     // Record completed file changes first; the caller removes it if config commit fails.
     let manifest_path = generation.join(MANIFEST_FILE_NAME);
-    let encoded = serde_json::to_vec(manifest)
+    let encoded = schema::encode(&schema::RUNTIME_MANIFEST, manifest)
         .map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidData, error))?;
     write_atomically(&manifest_path, &encoded).await?;
     write_atomically(config_path, yaml.as_bytes()).await
@@ -0,0 +1,393 @@
+//! Schema versions of the documents the service persists, and the migrations between them.
+//! Every document carries a top-level `schema_version`; documents written before versioning have
+//! none and count as version 0. Loading upgrades an old document one version at a time, after
+//! backing up the stored copy. A document from a newer service is refused and never rewritten,
+//! because this service would drop the fields it does not know.
+
+use crate::PRIMARY_SESSION_NAME;
+use anyhow::{Context as _, Result, bail};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+use std::fmt;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+pub(super) const SCHEMA_VERSION_FIELD: &str = "schema_version";
+
+/// Upgrades a document's fields from one schema version to the next.
+type Migration = fn(&mut Map<String, Value>);
+
+pub(super) struct DocumentSchema {
+    name: &'static str,
+    /// `migrations[n]` upgrades version `n` to `n + 1`, so the length is the current version.
+    migrations: &'static [Migration],
+}
+
+pub(super) const DESIRED_STATE: DocumentSchema = DocumentSchema {
+    name: "desired state",
+    migrations: &[unversioned, desired_state_v1_proxy],
+};
+
+pub(super) const ACTIVE_OWNER: DocumentSchema = DocumentSchema {
+    name: "active owner",
+    migrations: &[active_owner_v0_session, active_owner_v1_sessions],
+};
+
+pub(super) const OWNER_GENERATION: DocumentSchema = DocumentSchema {
+    name: "owner generation",
+    migrations: &[unversioned],
+};
+
+pub(super) const OWNER_IDENTITY: DocumentSchema = DocumentSchema {
+    name: "owner identity",
+    migrations: &[unversioned],
+};
+
+pub(super) const OWNER_TRANSITION: DocumentSchema = DocumentSchema {
+    name: "owner transition journal",
+    migrations: &[unversioned],
+};
+
+pub(super) const CORE_EXIT: DocumentSchema = DocumentSchema {
+    name: "core exit record",
+    migrations: &[unversioned],
+};
+
+/// Written by hand, so it is decoded but never migrated in place or backed up.
+pub(super) const OBSERVER_POLICY: DocumentSchema = DocumentSchema {
+    name: "observer policy",
+    migrations: &[unversioned],
+};
+
+/// Written by hand like the observer policy.
+pub(super) const ADMIN_POLICY: DocumentSchema = DocumentSchema {
+    name: "admin policy",
+    migrations: &[unversioned],
+};
+
+pub(super) const RUNTIME_MANIFEST: DocumentSchema = DocumentSchema {
+    name: "runtime manifest",
+    migrations: &[runtime_manifest_v0_tables],
+};
+
+/// Version 1 only added `schema_version`; an unversioned copy already has the version 1 layout.
+fn unversioned(_fields: &mut Map<String, Value>) {}
+
+/// Version 2 records the owner's system proxy, which earlier services never restored.
+fn desired_state_v1_proxy(fields: &mut Map<String, Value>) {
+    fields.entry("last_proxy_config").or_insert(Value::Null);
+    fields.entry("last_proxy_outcome").or_insert(Value::Null);
+}
+
+/// Owners recorded before sessions existed have neither a generation nor a session.
+fn active_owner_v0_session(fields: &mut Map<String, Value>) {
+    fields.entry("generation").or_insert(Value::from(0));
+    fields
+        .entry("session_token_hash")
+        .or_insert(Value::from(""));
+}
+
+/// Owners used to hold one session that never expired; it becomes the primary, unscoped
+/// session without a lease. An empty hash never matched a token, so it becomes no session at all.
+fn active_owner_v1_sessions(fields: &mut Map<String, Value>) {
+    fields.entry("lease").or_insert(Value::Null);
+    let sessions = match fields.remove("session_token_hash") {
+        Some(Value::String(token_hash)) if !token_hash.is_empty() => {
+            let mut session = Map::new();
+            session.insert("name".to_owned(), Value::from(PRIMARY_SESSION_NAME));
+            session.insert("token_hash".to_owned(), Value::String(token_hash));
+            session.insert("opened_at".to_owned(), Value::from(0));
+            session.insert("scopes".to_owned(), Value::Null);
+            vec![Value::Object(session)]
+        }
+        _ => Vec::new(),
+    };
+    fields.entry("sessions").or_insert(Value::Array(sessions));
+}
+
+/// The first manifests could omit either table when it was empty.
+fn runtime_manifest_v0_tables(fields: &mut Map<String, Value>) {
+    for table in ["assets", "remote_providers"] {
+        fields
+            .entry(table)
+            .or_insert_with(|| Value::Object(Map::new()));
+    }
+}
+
+impl DocumentSchema {
+    pub(super) const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    pub(super) const fn current_version(&self) -> u32 {
+        self.migrations.len() as u32
+    }
+}
+
+/// A document written by a service that knows a later schema than this one.
+#[derive(Debug)]
+pub(super) struct NewerSchemaError {
+    document: &'static str,
+    found: u64,
+    supported: u32,
+}
+
+impl fmt::Display for NewerSchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} uses schema version {}, but this service only understands up to version {}; \
+             it was written by a newer service and is left untouched",
+            self.document, self.found, self.supported
+        )
+    }
+}
+
+impl std::error::Error for NewerSchemaError {}
+
+pub(super) fn is_newer_schema(error: &anyhow::Error) -> bool {
+    error.chain().any(|cause| cause.is::<NewerSchemaError>())
+}
+
+pub(super) struct Decoded<T> {
+    pub(super) value: T,
+    /// The stored version, when the document had to be migrated to load.
+    pub(super) migrated_from: Option<u32>,
+}
+
+pub(super) fn decode<T: DeserializeOwned>(
+    schema: &DocumentSchema,
+    content: &[u8],
+) -> Result<Decoded<T>> {
+    let mut fields = document_fields(schema, content)?;
+    let current = schema.current_version();
+    let stored = match fields.remove(SCHEMA_VERSION_FIELD) {
+        None => 0,
+        Some(version) => checked_version(schema, &version)?,
+    };
+    for migration in &schema.migrations[stored as usize..] {
+        migration(&mut fields);
+    }
+    let value = serde_json::from_value(Value::Object(fields))
+        .with_context(|| format!("{} does not match schema version {current}", schema.name))?;
+    Ok(Decoded {
+        value,
+        migrated_from: (stored < current).then_some(stored),
+    })
+}
+
+pub(super) fn encode<T: Serialize>(schema: &DocumentSchema, value: &T) -> Result<Vec<u8>> {
+    let Value::Object(mut fields) = serde_json::to_value(value)? else {
+        bail!("{} does not serialize to a JSON object", schema.name);
+    };
+    fields.insert(
+        SCHEMA_VERSION_FIELD.to_owned(),
+        Value::from(schema.current_version()),
+    );
+    Ok(serde_json::to_vec_pretty(&Value::Object(fields))?)
+}
+
+/// Decodes a document read from `path`, backing up the stored copy before its first migration.
+pub(super) fn decode_file<T: DeserializeOwned>(
+    schema: &DocumentSchema,
+    path: &Path,
+    content: &[u8],
+) -> Result<T> {
+    let decoded = decode(schema, content).with_context(|| format!("failed to load {path:?}"))?;
+    if let Some(version) = decoded.migrated_from {
+        backup_before_migration(path, content, version)?;
+    }
+    Ok(decoded.value)
+}
+
+/// Refuses to replace `path` when it holds a document from a newer service. A missing or
+/// unreadable document may be replaced.
+pub(super) async fn ensure_replaceable(schema: &DocumentSchema, path: &Path) -> Result<()> {
+    let Ok(content) = tokio::fs::read(path).await else {
+        return Ok(());
+    };
+    let Ok(fields) = document_fields(schema, &content) else {
+        return Ok(());
+    };
+    match fields.get(SCHEMA_VERSION_FIELD) {
+        Some(version) => checked_version(schema, version)
+            .map(drop)
+            .with_context(|| format!("refusing to overwrite {path:?}")),
+        None => Ok(()),
+    }
+}
+
+/// Keeps the stored copy beside the document so a downgraded service can be pointed back at
+/// it. An existing backup is older still, so it is never replaced.
+pub(super) fn backup_before_migration(
+    path: &Path,
+    content: &[u8],
+    version: u32,
+) -> Result<Option<PathBuf>> {
+    let backup = pre_migration_backup_path(path, version);
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = match options.open(&backup) {
+        Ok(file) => file,
+        Err(error) if error.kind() == std::io::ErrorKind::AlreadyExists => return Ok(None),
+        Err(error) => {
+            return Err(error).with_context(|| format!("failed to create backup {backup:?}"));
+        }
+    };
+    file.write_all(content)
+        .and_then(|()| file.sync_all())
+        .with_context(|| format!("failed to write backup {backup:?}"))?;
+    Ok(Some(backup))
+}
+
+pub(super) fn pre_migration_backup_path(path: &Path, version: u32) -> PathBuf {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{file_name}.schema-v{version}.bak"))
+}
+
+fn document_fields(schema: &DocumentSchema, content: &[u8]) -> Result<Map<String, Value>> {
+    match serde_json::from_slice(content)
+        .with_context(|| format!("{} is not valid JSON", schema.name))?
+    {
+        Value::Object(fields) => Ok(fields),
+        _ => bail!("{} is not a JSON object", schema.name),
+    }
+}
+
+fn checked_version(schema: &DocumentSchema, version: &Value) -> Result<u32> {
+    let found = version
+        .as_u64()
+        .with_context(|| format!("{} has an invalid schema version", schema.name))?;
+    let supported = schema.current_version();
+    if found > u64::from(supported) {
+        return Err(NewerSchemaError {
+            document: schema.name,
+            found,
+            supported,
+        }
+        .into());
+    }
+    Ok(found as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        ACTIVE_OWNER, DocumentSchema, backup_before_migration, decode, encode, ensure_replaceable,
+        is_newer_schema, pre_migration_backup_path,
+    };
+    use serde::{Deserialize, Serialize};
+    use serde_json::{Map, Value};
+
+    #[derive(Debug, PartialEq, Serialize, Deserialize)]
+    struct Document {
+        name: String,
+        size: u64,
+        unit: String,
+    }
+
+    fn v0_add_size(fields: &mut Map<String, Value>) {
+        fields.entry("size").or_insert(Value::from(0));
+    }
+
+    fn v1_rename_label(fields: &mut Map<String, Value>) {
+        if let Some(label) = fields.remove("label") {
+            fields.insert("name".to_owned(), label);
+        }
+        fields.entry("unit").or_insert(Value::from("bytes"));
+    }
+
+    const DOCUMENT: DocumentSchema = DocumentSchema {
+        name: "test document",
+        migrations: &[v0_add_size, v1_rename_label],
+    };
+
+    #[test]
+    fn old_documents_migrate_one_version_at_a_time() -> anyhow::Result<()> {
+        let decoded = decode::<Document>(&DOCUMENT, br#"{"label":"a"}"#)?;
+        assert_eq!(decoded.migrated_from, Some(0));
+        assert_eq!(
+            decoded.value,
+            Document {
+                name: "a".to_owned(),
+                size: 0,
+                unit: "bytes".to_owned(),
+            }
+        );
+
+        let decoded =
+            decode::<Document>(&DOCUMENT, br#"{"schema_version":1,"label":"b","size":7}"#)?;
+        assert_eq!(decoded.migrated_from, Some(1));
+        assert_eq!(decoded.value.size, 7);
+
+        let current = encode(&DOCUMENT, &decoded.value)?;
+        let decoded = decode::<Document>(&DOCUMENT, &current)?;
+        assert_eq!(decoded.migrated_from, None);
+        assert_eq!(decoded.value.name, "b");
+        Ok(())
+    }
+
+    #[test]
+    fn a_single_session_owner_keeps_its_session_as_the_primary_one() -> anyhow::Result<()> {
+        let decoded = decode::<crate::core::desired::ActiveOwnerState>(
+            &ACTIVE_OWNER,
+            br#"{"schema_version":1,"owner_key":"501","identity":{"Unix":{"uid":501,"gid":20}},
+                "app_data_root":"/tmp","generation":4,"session_token_hash":"abc"}"#,
+        )?;
+        assert_eq!(decoded.migrated_from, Some(1));
+        assert_eq!(decoded.value.lease, None);
+        let sessions = decoded.value.sessions;
+        assert_eq!(sessions.len(), 1);
+        assert_eq!(sessions[0].name, crate::PRIMARY_SESSION_NAME);
+        assert_eq!(sessions[0].token_hash, "abc");
+        assert_eq!(sessions[0].scopes, None);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn documents_from_a_newer_service_are_refused_and_kept() -> anyhow::Result<()> {
+        let root = std::env::temp_dir().join(format!("service-schema-{}", std::process::id()));
+        std::fs::create_dir_all(&root)?;
+        let path = root.join("active-owner.json");
+        let newer = br#"{"schema_version":9,"owner_key":"501","future":true}"#;
+        std::fs::write(&path, newer)?;
+
+        let error = decode::<Document>(&ACTIVE_OWNER, newer)
+            .err()
+            .expect("a newer document must not load");
+        assert!(is_newer_schema(&error));
+        let error = ensure_replaceable(&ACTIVE_OWNER, &path)
+            .await
+            .err()
+            .expect("a newer document must not be overwritten");
+        assert!(is_newer_schema(&error));
+        assert_eq!(std::fs::read(&path)?, newer);
+
+        std::fs::write(&path, b"{ corrupt")?;
+        ensure_replaceable(&ACTIVE_OWNER, &path).await?;
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+
+    #[test]
+    fn the_first_pre_migration_backup_is_never_replaced() -> anyhow::Result<()> {
+        let root =
+            std::env::temp_dir().join(format!("service-schema-backup-{}", std::process::id()));
+        std::fs::create_dir_all(&root)?;
+        let path = root.join("desired-state.json");
+        let backup = pre_migration_backup_path(&path, 0);
+        let _ = std::fs::remove_file(&backup);
+
+        assert_eq!(
+            backup_before_migration(&path, b"first", 0)?,
+            Some(backup.clone())
+        );
+        assert_eq!(backup_before_migration(&path, b"second", 0)?, None);
+        assert_eq!(std::fs::read(&backup)?, b"first");
+        std::fs::remove_dir_all(root)?;
+        Ok(())
+    }
+}
@@ -1,32 +1,57 @@
+use crate::core::access::{authorize_admin, authorize_observer};
+use crate::core::audit::{
+    AuditDetails, append_audit_record, bundle_hash, is_audited, peer_pid, query_audit_log,
+};
 use crate::core::auth::{
     AuthenticatedOwner, ServiceError, authenticate_owner, hash_session_token,
     ipc_request_context_to_auth_context,
 };
+use crate::core::crash::{list_crash_reports, read_crash_report};
 use crate::core::desired::{
-    ActiveOwnerState, clear_active_owner, commit_active_owner_session, load_active_owner,
-    persist_owner_core_started, persist_owner_core_stopped, persist_owner_core_stopped_by_key,
-    persist_owner_writer_config,
+    ActiveOwnerState, clear_active_owner, commit_active_owner_session, is_owner_key,
+    load_active_owner, open_owner_session, persist_owner_core_started, persist_owner_core_stopped,
+    persist_owner_core_stopped_by_key, persist_owner_proxy, persist_owner_writer_config,
+    purge_owner_state, revoke_owner_session,
+};
+use crate::core::handoff::take_core_handoff_request;
+use crate::core::handshake::{
+    is_handshake_nonce, prepare_handshake_key, sign_handshake, withdraw_published_key,
+};
+use crate::core::journal::{OwnerTransitionJournal, OwnerTransitionStep};
+use crate::core::lease::{
+    ExpiredLease, arm_lease, disarm_lease, is_still_expired, renew_lease, spawn_lease_watcher,
+    validate_lease,
 };
 use crate::core::legacy_cleanup::cleanup_legacy_owner_files;
 use crate::core::logger::set_or_update_writer;
 use crate::core::manager::{CORE_MANAGER, LOGGER_MANAGER};
-use crate::core::paths::service_paths;
+use crate::core::metrics::METRICS;
+use crate::core::paths::{ServicePaths, service_paths, set_service_paths};
+use crate::core::proxy::{
+    apply_service_proxy_or_direct, clear_service_proxy, compensate_service_proxy,
+};
 use crate::core::runtime_generation::{PreparedRuntime, prepare_runtime, stage_runtime};
 use crate::core::state::{set_core_lifecycle_state, set_service_lifecycle_state};
-use crate::core::status::service_status_snapshot;
+use crate::core::status::{observer_status_snapshot, service_status_snapshot};
+#[cfg(unix)]
+use crate::core::structure::ServiceMode;
 use crate::core::structure::{OwnerSessionProof, Response, ServiceLifecycleState};
-use crate::core::{apply_proxy, apply_proxy_or_direct, clear_proxy, validate_proxy_config};
+use crate::core::validate_proxy_config;
 use crate::{
-    AuthenticatedRequest, AuthenticatedSessionRequest, IpcCommand, MIN_SUPPORTED_CLIENT_REVISION,
-    MacosProxyConfig, OwnerSessionHandle, ProtocolInfo, ProtocolVersion, ProxyApplyOutcome,
-    RuntimeBundle, SERVICE_PROTOCOL_HEADER, StartClashRequest, StartClashResult, WriterConfig,
+    AuditQuery, AuthenticatedRequest, AuthenticatedSessionRequest, EvictOwnerRequest,
+    EvictOwnerResult, HandshakeRequest, IpcCommand, LeaseExpiryPolicy,
+    MIN_SUPPORTED_CLIENT_REVISION, MacosProxyConfig, MintScopedSessionRequest, OpenSessionRequest,
+    OwnerSessionHandle, PRIMARY_SESSION_NAME, ProtocolInfo, ProtocolVersion, ProxyApplyOutcome,
+    RuntimeBundle, SERVICE_PROTOCOL_HEADER, SessionLease, SessionScope, StartClashRequest,
+    StartClashResult, WriterConfig,
 };
 use anyhow::{Context as _, Result as AnyResult, anyhow};
 use http::StatusCode;
 use kode_bridge::{IpcHttpServer, Result, Router, ServerConfig, ipc_http_server::HttpResponse};
 use once_cell::sync::Lazy;
-use serde::{Serialize, de::DeserializeOwned};
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
 use std::{
+    cell::{Cell, RefCell},
     future::Future,
     ops::ControlFlow,
     time::{Duration, Instant},
@@ -39,6 +64,7 @@ const IPC_MAX_RESTARTS: u32 = 10;
 const IPC_RESTART_WINDOW: Duration = Duration::from_secs(10);
 const IPC_MAX_BACKOFF: Duration = Duration::from_millis(500);
 const IPC_HANDLER_TIMEOUT: Duration = Duration::from_secs(25);
+const PROMETHEUS_CONTENT_TYPE: &str = "text/plain; version=0.0.4";
 #[cfg(any(test, all(windows, not(feature = "test"))))]
 const WINDOWS_CONTROL_PIPE_SDDL: &str = "D:P(A;;GA;;;SY)(A;;GA;;;BA)(A;;0x0012019b;;;AU)";
 #[cfg(all(windows, feature = "test"))]
@@ -51,10 +77,22 @@ trait OwnerProxyTransition {
     async fn start_new_core(&mut self) -> AnyResult<()>;
     async fn commit_new_owner(&mut self) -> AnyResult<ActiveOwnerState>;
     async fn apply_new_proxy(&mut self) -> AnyResult<crate::ProxyApplyOutcome>;
+    /// Journals a completed step so a crash after it is recovered at the next start.
+    async fn record_step(&mut self, step: OwnerTransitionStep);
+    async fn close_journal(&mut self);
 }
 
 async fn owner_proxy_transition(
     transition: &mut impl OwnerProxyTransition,
+) -> std::result::Result<(ActiveOwnerState, crate::ProxyApplyOutcome), ServiceError> {
+    // A transition that returns has compensated in process; only a crash leaves the journal.
+    let result = run_owner_proxy_transition(transition).await;
+    transition.close_journal().await;
+    result
+}
+
+async fn run_owner_proxy_transition(
+    transition: &mut impl OwnerProxyTransition,
 ) -> std::result::Result<(ActiveOwnerState, crate::ProxyApplyOutcome), ServiceError> {
     if let Err(clear_error) = transition.clear_previous_proxy().await {
         let compensation = transition.compensate_direct().await;
@@ -66,18 +104,30 @@ async fn owner_proxy_transition(
         };
         return Err(ServiceError::proxy_clear_failed(message));
     }
+    transition
+        .record_step(OwnerTransitionStep::PreviousProxyCleared)
+        .await;
 
     if let Err(stop_error) = transition.stop_previous_core().await {
         return Err(ServiceError::owner_switch_failed(format!(
             "Failed to stop the previous owner core: {stop_error:#}"
         )));
     }
+    transition
+        .record_step(OwnerTransitionStep::PreviousCoreStopped)
+        .await;
     transition.start_new_core().await.map_err(|error| {
         ServiceError::owner_switch_failed(format!("Failed to start owner core: {error:#}"))
     })?;
+    transition
+        .record_step(OwnerTransitionStep::NewCoreStarted)
+        .await;
     let active = transition.commit_new_owner().await.map_err(|error| {
         ServiceError::owner_switch_failed(format!("Failed to commit owner state: {error:#}"))
     })?;
+    transition
+        .record_step(OwnerTransitionStep::NewOwnerCommitted)
+        .await;
     let proxy_outcome = transition.apply_new_proxy().await.map_err(|error| {
         ServiceError::proxy_apply_failed(format!("Failed to apply owner proxy: {error:#}"))
     })?;
@@ -90,6 +140,8 @@ struct StartOwnerTransition<'a> {
     prepared_runtime: Option<PreparedRuntime>,
     proposed_session_token: &'a str,
     macos_proxy: Option<&'a MacosProxyConfig>,
+    lease: Option<&'a SessionLease>,
+    journal: Option<OwnerTransitionJournal>,
 }
 
 impl OwnerProxyTransition for StartOwnerTransition<'_> {
@@ -149,10 +201,13 @@ impl OwnerProxyTransition for StartOwnerTransition<'_> {
             .as_ref()
             .context("prepared runtime is unavailable during owner commit")?
             .clash_config();
-        if let Err(error) = persist_owner_core_started(self.owner, clash_config).await {
+        if let Err(error) =
+            persist_owner_core_started(self.owner, clash_config, self.macos_proxy).await
+        {
             return self.rollback_commit_failure(error).await;
         }
-        match commit_active_owner_session(self.owner, self.proposed_session_token).await {
+        match commit_active_owner_session(self.owner, self.proposed_session_token, self.lease).await
+        {
             Ok(active) => {
                 self.prepared_runtime
                     .take()
@@ -165,7 +220,23 @@ impl OwnerProxyTransition for StartOwnerTransition<'_> {
     }
 
     async fn apply_new_proxy(&mut self) -> AnyResult<ProxyApplyOutcome> {
-        apply_service_proxy_or_direct(self.macos_proxy).await
+        let outcome = apply_service_proxy_or_direct(self.macos_proxy).await?;
+        if let Err(error) = persist_owner_proxy(&self.owner.key, self.macos_proxy, &outcome).await {
+            warn!("Owner proxy applied; recording its outcome failed: {error:#}");
+        }
+        Ok(outcome)
+    }
+
+    async fn record_step(&mut self, step: OwnerTransitionStep) {
+        if let Some(journal) = self.journal.as_mut() {
+            journal.record(step).await;
+        }
+    }
+
+    async fn close_journal(&mut self) {
+        if let Some(journal) = self.journal.take() {
+            journal.close().await;
+        }
     }
 }
 
@@ -182,44 +253,6 @@ impl StartOwnerTransition<'_> {
     }
 }
 
-/// Whether proxy routes affect real system settings in this build.
-const SERVICE_PROXY_IS_LIVE: bool = cfg!(all(target_os = "macos", not(feature = "test")));
-
-async fn clear_service_proxy() -> AnyResult<()> {
-    if SERVICE_PROXY_IS_LIVE {
-        clear_proxy().await
-    } else {
-        Ok(())
-    }
-}
-
-async fn compensate_service_proxy() -> AnyResult<()> {
-    if SERVICE_PROXY_IS_LIVE {
-        apply_proxy(&MacosProxyConfig::Disabled).await
-    } else {
-        Ok(())
-    }
-}
-
-#[cfg(not(feature = "test"))]
-async fn apply_service_proxy_or_direct(
-    config: Option<&MacosProxyConfig>,
-) -> AnyResult<ProxyApplyOutcome> {
-    apply_proxy_or_direct(config).await
-}
-
-#[cfg(feature = "test")]
-async fn apply_service_proxy_or_direct(
-    config: Option<&MacosProxyConfig>,
-) -> AnyResult<ProxyApplyOutcome> {
-    let _ = apply_proxy_or_direct;
-    Ok(if config.is_some() {
-        ProxyApplyOutcome::Applied
-    } else {
-        ProxyApplyOutcome::NotRequested
-    })
-}
-
 async fn clear_proxy_with_direct_compensation() -> std::result::Result<(), ServiceError> {
     let Err(clear_error) = clear_service_proxy().await else {
         return Ok(());
@@ -234,6 +267,102 @@ async fn clear_proxy_with_direct_compensation() -> std::result::Result<(), Servi
     Err(ServiceError::proxy_clear_failed(message))
 }
 
+enum StopOwnerError {
+    Proxy(ServiceError),
+    Unavailable(String),
+}
+
+/// Clears the proxy, stops the core and releases ownership, as `StopClash` does. The caller
+/// holds `OWNER_LIFECYCLE_LOCK` and has checked that `owner_key` is active.
+async fn stop_active_owner(owner_key: &str) -> std::result::Result<(), StopOwnerError> {
+    clear_proxy_with_direct_compensation()
+        .await
+        .map_err(StopOwnerError::Proxy)?;
+    match CORE_MANAGER.lock().await.stop_core().await {
+        Ok(_) => info!("Core stopped successfully"),
+        Err(e) => {
+            return Err(StopOwnerError::Unavailable(format!(
+                "Failed to stop core: {e}"
+            )));
+        }
+    }
+    if let Err(e) = persist_owner_core_stopped_by_key(owner_key).await {
+        set_core_lifecycle_state(ServiceLifecycleState::Fatal);
+        return Err(StopOwnerError::Unavailable(format!(
+            "Failed to persist desired state: {e}"
+        )));
+    }
+    if let Err(e) = clear_active_owner().await {
+        set_core_lifecycle_state(ServiceLifecycleState::Fatal);
+        return Err(StopOwnerError::Unavailable(format!(
+            "Failed to clear active owner: {e}"
+        )));
+    }
+    disarm_lease();
+    Ok(())
+}
+
+/// Applies the policy of a lease the watcher found expired, unless the session was replaced,
+/// stopped or renewed while this waited for the lifecycle lock.
+pub(super) async fn expire_owner_lease(expired: ExpiredLease) {
+    let _lifecycle_guard = OWNER_LIFECYCLE_LOCK.lock().await;
+    let still_active = load_active_owner()
+        .await
+        .ok()
+        .flatten()
+        .is_some_and(|active| {
+            active.owner_key == expired.owner_key && active.generation == expired.generation
+        });
+    if !still_active || !is_still_expired(&expired) {
+        return;
+    }
+    let owner_key = &expired.owner_key;
+    match expired.on_expiry {
+        LeaseExpiryPolicy::KeepRunning => {
+            warn!("Session lease of owner {owner_key} expired; keeping its core and proxy");
+        }
+        LeaseExpiryPolicy::ClearProxy => {
+            warn!("Session lease of owner {owner_key} expired; clearing its system proxy");
+            if let Err(error) = clear_proxy_with_direct_compensation().await {
+                warn!("{error}");
+            } else if let Err(error) =
+                persist_owner_proxy(owner_key, None, &ProxyApplyOutcome::NotRequested).await
+            {
+                warn!("System proxy cleared; persisting it for restore failed: {error:#}");
+            }
+        }
+        LeaseExpiryPolicy::StopCore => {
+            warn!("Session lease of owner {owner_key} expired; stopping its core");
+            match stop_active_owner(owner_key).await {
+                Ok(()) => {}
+                Err(StopOwnerError::Proxy(error)) => warn!("{error}"),
+                Err(StopOwnerError::Unavailable(message)) => warn!("{message}"),
+            }
+        }
+    }
+}
+
+/// Stops `request.owner_key` through the `StopClash` path if it is active, then purges its state
+/// when asked. The caller holds `OWNER_LIFECYCLE_LOCK`.
+async fn evict_owner(
+    request: &EvictOwnerRequest,
+) -> std::result::Result<EvictOwnerResult, StopOwnerError> {
+    let owner_key = &request.owner_key;
+    let active = load_active_owner().await.map_err(|error| {
+        StopOwnerError::Unavailable(format!("Failed to load the active owner: {error:#}"))
+    })?;
+    let evicted = active.is_some_and(|active| &active.owner_key == owner_key);
+    if evicted {
+        warn!("Evicting owner {owner_key} at an admin's request");
+        stop_active_owner(owner_key).await?;
+    }
+    let purged = request.purge_state
+        && purge_owner_state(owner_key).await.map_err(|error| {
+            StopOwnerError::Unavailable(format!("Failed to purge owner state: {error:#}"))
+        })?;
+    Ok(EvictOwnerResult { evicted, purged })
+}
+
 async fn rollback_started_owner(owner: &AuthenticatedOwner) -> AnyResult<()> {
     if let Err(stop_error) = CORE_MANAGER.lock().await.stop_core().await {
         set_core_lifecycle_state(ServiceLifecycleState::Fatal);
@@ -276,10 +405,15 @@ async fn shutdown_ipc_server() {
     *guard = None;
 }
 
-pub async fn run_ipc_server() -> Result<JoinHandle<Result<()>>> {
+/// Serves the service installed at `paths`, which become this process's selected paths.
+pub async fn run_ipc_server(paths: &ServicePaths) -> Result<JoinHandle<Result<()>>> {
     let _lifecycle_guard = IPC_LIFECYCLE_LOCK.lock().await;
+    set_service_paths(paths.clone());
 
     make_ipc_dir().await?;
+    prepare_handshake_key()
+        .await
+        .map_err(|error| kode_bridge::KodeBridgeError::custom(format!("{error:#}")))?;
     cleanup_stale_ipc_socket().await?;
     init_ipc_state().await?;
 
@@ -310,12 +444,19 @@ pub async fn run_ipc_server() -> Result<JoinHandle<Result<()>>> {
 pub async fn stop_ipc_server() -> Result<()> {
     let _lifecycle_guard = IPC_LIFECYCLE_LOCK.lock().await;
 
-    CORE_MANAGER
-        .lock()
-        .await
-        .stop_core()
-        .await
-        .map_err(|error| kode_bridge::KodeBridgeError::custom(error.to_string()))?;
+    let core_manager = CORE_MANAGER.lock().await;
+    let handed_over = take_core_handoff_request().await
+        && core_manager
+            .detach_core()
+            .await
+            .map_err(|error| kode_bridge::KodeBridgeError::custom(format!("{error:#}")))?;
+    if !handed_over {
+        core_manager
+            .stop_core()
+            .await
+            .map_err(|error| kode_bridge::KodeBridgeError::custom(error.to_string()))?;
+    }
+    drop(core_manager);
 
     if let Some(sender) = IPC_SHUTDOWN_SENDER.lock().await.take() {
         let _ = sender.send(());
@@ -328,6 +469,9 @@ pub async fn stop_ipc_server() -> Result<()> {
     shutdown_ipc_server().await;
 
     cleanup_ipc_path().await?;
+    withdraw_published_key()
+        .await
+        .map_err(|error| kode_bridge::KodeBridgeError::custom(format!("{error:#}")))?;
     #[cfg(windows)]
     tokio::time::sleep(std::time::Duration::from_millis(70)).await;
 
@@ -335,12 +479,13 @@ pub async fn stop_ipc_server() -> Result<()> {
 }
 
 pub async fn run_ipc_supervisor_until_shutdown(
+    paths: &ServicePaths,
     shutdown: impl Future<Output = ()>,
 ) -> AnyResult<()> {
     set_service_lifecycle_state(ServiceLifecycleState::Starting);
     info!("Starting IPC server...");
 
-    let mut server_handle = match run_ipc_server().await {
+    let mut server_handle = match run_ipc_server(paths).await {
         Ok(handle) => handle,
         Err(error) => {
             set_service_lifecycle_state(ServiceLifecycleState::Fatal);
@@ -349,6 +494,9 @@ pub async fn run_ipc_supervisor_until_shutdown(
     };
     set_service_lifecycle_state(ServiceLifecycleState::Running);
     info!("IPC server started successfully. Waiting for shutdown signal...");
+    #[cfg(target_os = "linux")]
+    let watchdog = crate::core::notify::spawn_watchdog();
+    let lease_watcher = spawn_lease_watcher().await;
 
     let mut restart_timestamps: Vec<Instant> = Vec::new();
     let mut consecutive_attempt = 0u32;
@@ -358,6 +506,8 @@ pub async fn run_ipc_supervisor_until_shutdown(
         tokio::select! {
             _ = &mut shutdown => {
                 info!("Shutdown signal received. Stopping IPC server...");
+                #[cfg(target_os = "linux")]
+                crate::core::notify::notify_stopping();
                 break;
             }
             join_result = &mut server_handle => {
@@ -367,6 +517,7 @@ pub async fn run_ipc_supervisor_until_shutdown(
                     Err(error) => format!("IPC server task failed: {error}"),
                 };
                 warn!("{reason}; rebuilding IPC listener in-process");
+                METRICS.record_ipc_listener_rebuild();
                 set_service_lifecycle_state(ServiceLifecycleState::RecoveringIpc);
 
                 let now = Instant::now();
@@ -391,7 +542,7 @@ pub async fn run_ipc_supervisor_until_shutdown(
                     tokio::time::sleep(delay).await;
                 }
 
-                server_handle = match run_ipc_server().await {
+                server_handle = match run_ipc_server(paths).await {
                     Ok(handle) => handle,
                     Err(error) => {
                         set_service_lifecycle_state(ServiceLifecycleState::Fatal);
@@ -406,6 +557,11 @@ pub async fn run_ipc_supervisor_until_shutdown(
 
     stop_ipc_server().await?;
     server_handle.abort();
+    lease_watcher.abort();
+    #[cfg(target_os = "linux")]
+    if let Some(watchdog) = watchdog {
+        watchdog.abort();
+    }
     Ok(())
 }
 
@@ -432,7 +588,7 @@ async fn make_ipc_dir() -> Result<()> {
 
 #[cfg(unix)]
 fn ensure_control_runtime_dir(dir: &std::path::Path) -> std::io::Result<()> {
-    crate::core::unix_security::ensure_service_directory(dir, 0o755)
+    crate::core::unix_security::ensure_runtime_directory(dir)
         .map_err(|error| std::io::Error::other(error.to_string()))
 }
 
@@ -504,8 +660,12 @@ fn create_ipc_server() -> Result<IpcHttpServer> {
     {
         use platform_lib::{S_IRGRP, S_IROTH, S_IRUSR, S_IWGRP, S_IWOTH, S_IWUSR, mode_t};
 
-        let mode: mode_t =
-            platform_lib::mode_t::from(S_IRUSR | S_IWUSR | S_IRGRP | S_IWGRP | S_IROTH | S_IWOTH);
+        let mode: mode_t = match service_paths().mode() {
+            ServiceMode::System => platform_lib::mode_t::from(
+                S_IRUSR | S_IWUSR | S_IRGRP | S_IWGRP | S_IROTH | S_IWOTH,
+            ),
+            ServiceMode::User => platform_lib::mode_t::from(S_IRUSR | S_IWUSR),
+        };
         let server = server.with_listener_mode(mode);
         Ok(server)
     }
@@ -555,6 +715,36 @@ impl<T> OwnerRequestEnvelope for AuthenticatedSessionRequest<T> {
     }
 }
 
+/// Envelope of read routes, which authenticate the owner alone or also a session proof. A proof
+/// sent along must be current and hold the route's read scope, so a tool handed a scoped token
+/// reads exactly what it was granted.
+#[derive(Deserialize)]
+struct ReadRequest<T> {
+    credentials: crate::OwnerCredentials,
+    #[serde(default)]
+    session: Option<OwnerSessionProof>,
+    payload: T,
+}
+
+impl<T> OwnerRequestEnvelope for ReadRequest<T> {
+    fn credentials(&self) -> &crate::OwnerCredentials {
+        &self.credentials
+    }
+}
+
+impl<T> ReadRequest<T> {
+    fn gate<'a>(
+        &'a self,
+        owner_only: OwnerLifecycleGate<'a>,
+        scope: SessionScope,
+    ) -> OwnerLifecycleGate<'a> {
+        match self.session.as_ref() {
+            Some(proof) => OwnerLifecycleGate::ActiveSession(proof, Some(scope)),
+            None => owner_only,
+        }
+    }
+}
+
 /// Validates protocol, parses the envelope, then authenticates its owner.
 /// Lifecycle locking remains separate so `StartClash` can validate before waiting on the lock.
 fn authenticate_request<E>(
@@ -563,6 +753,10 @@ fn authenticate_request<E>(
 where
     E: DeserializeOwned + OwnerRequestEnvelope,
 {
+    note_audit(|details| {
+        details.peer_uid = ctx.client_info.peer_credentials.uid;
+        details.peer_pid = peer_pid(&ctx.client_info);
+    });
     if let Err(error) = require_protocol_version(ctx) {
         return ControlFlow::Break(service_error(error));
     }
@@ -574,6 +768,10 @@ where
         Ok(owner) => owner,
         Err(error) => return ControlFlow::Break(service_error(error)),
     };
+    note_audit(|details| {
+        details.caller = Some(owner.key.clone());
+        details.owner_key = Some(owner.key.clone());
+    });
     ControlFlow::Continue((request, owner))
 }
 
@@ -583,8 +781,8 @@ enum OwnerLifecycleGate<'a> {
     Unchecked,
     /// The authenticated owner must be active.
     ActiveOwner,
-    /// The request must prove the current active session.
-    ActiveSession(&'a OwnerSessionProof),
+    /// The request must prove a current session holding the scope; `None` needs a full session.
+    ActiveSession(&'a OwnerSessionProof, Option<SessionScope>),
 }
 
 /// Acquires `OWNER_LIFECYCLE_LOCK`, applies the gate, and returns the guard to the caller.
@@ -592,12 +790,16 @@ async fn enter_owner_lifecycle(
     owner: &AuthenticatedOwner,
     gate: OwnerLifecycleGate<'_>,
 ) -> ControlFlow<Result<HttpResponse>, MutexGuard<'static, ()>> {
+    let waiting_since = Instant::now();
     let lifecycle_guard = OWNER_LIFECYCLE_LOCK.lock().await;
+    METRICS.record_lifecycle_lock_wait(waiting_since.elapsed());
     let gated = match gate {
         OwnerLifecycleGate::Unchecked => Ok(()),
         OwnerLifecycleGate::ActiveOwner => require_active_owner(owner).await,
-        OwnerLifecycleGate::ActiveSession(proof) => {
-            require_active_session(owner, proof).await.map(|_| ())
+        OwnerLifecycleGate::ActiveSession(proof, scope) => {
+            require_active_session(owner, proof, scope)
+                .await
+                .map(|_| ())
         }
     };
     match gated {
@@ -606,241 +808,695 @@ async fn enter_owner_lifecycle(
     }
 }
 
+/// Runs a route handler, records its latency and response code, and audits state-changing
+/// routes. Handlers that fail before building an envelope are counted under the `error` code.
+async fn instrumented(
+    command: IpcCommand,
+    handler: impl Future<Output = Result<HttpResponse>>,
+) -> Result<HttpResponse> {
+    let started = Instant::now();
+    let (result, code, details) = RESPONSE_CODE
+        .scope(
+            Cell::new(None),
+            REQUEST_AUDIT.scope(RefCell::new(AuditDetails::default()), async {
+                let result = handler.await;
+                (
+                    result,
+                    RESPONSE_CODE.with(Cell::get),
+                    REQUEST_AUDIT.with(RefCell::take),
+                )
+            }),
+        )
+        .await;
+    if is_audited(&command, &details) {
+        append_audit_record(&details.into_record(&command, code)).await;
+    }
+    let code = match (&result, code) {
+        (_, Some(code)) => code.to_string(),
+        (Ok(_), None) => "0".to_owned(),
+        (Err(_), None) => "error".to_owned(),
+    };
+    METRICS.record_request(command.as_ref(), code, started.elapsed());
+    result
+}
+
 fn create_ipc_router() -> Result<Router> {
     let router = Router::new()
-        .get(IpcCommand::Magic.as_ref(), |ctx| async move {
-            trace!("Received Magic command");
-            ipc_request_context_to_auth_context(&ctx)?;
-            Ok(HttpResponse::builder().text("Tunglies!").build())
+        .get(IpcCommand::Magic.as_ref(), |ctx| {
+            instrumented(IpcCommand::Magic, async move {
+                trace!("Received Magic command");
+                // Kept for clients that predate `/handshake`; see `IPC_AUTH_EXPECT`.
+                ipc_request_context_to_auth_context(&ctx)?;
+                Ok(HttpResponse::builder().text("Tunglies!").build())
+            })
         })
-        .get(IpcCommand::GetVersion.as_ref(), |ctx| async move {
-            ipc_request_context_to_auth_context(&ctx)?;
-            ok_json(ProtocolInfo::current())
+        .get(IpcCommand::Handshake.as_ref(), |ctx| {
+            instrumented(IpcCommand::Handshake, async move {
+                trace!("Received Handshake command");
+                // Open to every caller: clients run it before they send any credentials, and the
+                // proof only reveals what `/version` already does.
+                let request = match ctx.json::<HandshakeRequest>() {
+                    Ok(request) => request,
+                    Err(error) => return bad_request(format!("Invalid JSON: {error}")),
+                };
+                if !is_handshake_nonce(&request.nonce) {
+                    return bad_request(
+                        "Handshake nonce must be 64 lowercase hexadecimal characters",
+                    );
+                }
+                let info = ProtocolInfo::current();
+                let Some(negotiated) = info.negotiate(request.protocol) else {
+                    return service_error(ServiceError::protocol_mismatch());
+                };
+                match sign_handshake(&request.nonce, info, negotiated) {
+                    Some(proof) => ok_json(proof),
+                    None => service_unavailable("Handshake key is not loaded yet"),
+                }
+            })
         })
-        .get(IpcCommand::Status.as_ref(), |ctx| async move {
-            trace!("Received Status command");
-            let (_request, owner) = match authenticate_request::<AuthenticatedRequest<()>>(&ctx) {
-                ControlFlow::Continue(authenticated) => authenticated,
-                ControlFlow::Break(response) => return response,
-            };
-            let _lifecycle_guard =
-                match enter_owner_lifecycle(&owner, OwnerLifecycleGate::Unchecked).await {
+        .get(IpcCommand::GetVersion.as_ref(), |ctx| {
+            instrumented(IpcCommand::GetVersion, async move {
+                ipc_request_context_to_auth_context(&ctx)?;
+                ok_json(ProtocolInfo::current())
+            })
+        })
+        .get(IpcCommand::Metrics.as_ref(), |ctx| {
+            instrumented(IpcCommand::Metrics, async move {
+                trace!("Received Metrics command");
+                // Any authenticated local user may scrape; metrics carry no owner-specific data and
+                // must not queue behind the lifecycle lock they measure.
+                if let ControlFlow::Break(response) =
+                    authenticate_request::<AuthenticatedRequest<()>>(&ctx)
+                {
+                    return response;
+                }
+                // Scrapers expect the exposition format itself rather than the JSON envelope.
+                let _ = RESPONSE_CODE.try_with(|recorded| recorded.set(Some(0)));
+                Ok(HttpResponse::builder()
+                    .status(StatusCode::OK)
+                    .text(METRICS.render())
+                    .header(http::header::CONTENT_TYPE, PROMETHEUS_CONTENT_TYPE)
+                    .build())
+            })
+        })
+        .get(IpcCommand::Status.as_ref(), |ctx| {
+            instrumented(IpcCommand::Status, async move {
+                trace!("Received Status command");
+                let (request, owner) = match authenticate_request::<ReadRequest<()>>(&ctx) {
+                    ControlFlow::Continue(authenticated) => authenticated,
+                    ControlFlow::Break(response) => return response,
+                };
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    request.gate(OwnerLifecycleGate::Unchecked, SessionScope::ReadStatus),
+                )
+                .await
+                {
                     ControlFlow::Continue(guard) => guard,
                     ControlFlow::Break(response) => return response,
                 };
-            match service_status_snapshot(&owner).await {
-                Ok(status) => ok_json(status),
-                Err(error) => {
-                    service_unavailable(format!("Failed to collect service status: {}", error))
+                match service_status_snapshot(&owner).await {
+                    Ok(status) => ok_json(status),
+                    Err(error) => {
+                        service_unavailable(format!("Failed to collect service status: {}", error))
+                    }
                 }
-            }
+            })
         })
-        .post(IpcCommand::StartClash.as_ref(), |ctx| async move {
-            trace!("Received StartClash command");
-            let (request, owner) =
-                match authenticate_request::<AuthenticatedRequest<StartClashRequest>>(&ctx) {
+        .get(IpcCommand::ObserveStatus.as_ref(), |ctx| {
+            instrumented(IpcCommand::ObserveStatus, async move {
+                trace!("Received ObserveStatus command");
+                // Observers are never owners of anything here, so the route takes no session and
+                // only ever answers with the redacted snapshot.
+                let (_, caller) = match authenticate_request::<AuthenticatedRequest<()>>(&ctx) {
                     ControlFlow::Continue(authenticated) => authenticated,
                     ControlFlow::Break(response) => return response,
                 };
-            let start_request = request.payload;
-            if hash_session_token(&start_request.proposed_session_token).is_err() {
-                return bad_request("Invalid proposed owner session token");
-            }
-            if let Some(proxy) = start_request.macos_proxy.as_ref()
-                && let Err(error) = validate_proxy_config(proxy)
-            {
-                return service_error(ServiceError::invalid_proxy_config(error.to_string()));
-            }
-            let _lifecycle_guard =
-                match enter_owner_lifecycle(&owner, OwnerLifecycleGate::Unchecked).await {
+                if let Err(error) = authorize_observer(&caller).await {
+                    return service_error(error);
+                }
+                let _lifecycle_guard =
+                    match enter_owner_lifecycle(&caller, OwnerLifecycleGate::Unchecked).await {
+                        ControlFlow::Continue(guard) => guard,
+                        ControlFlow::Break(response) => return response,
+                    };
+                match observer_status_snapshot().await {
+                    Ok(status) => ok_json(status),
+                    Err(error) => {
+                        service_unavailable(format!("Failed to collect service status: {}", error))
+                    }
+                }
+            })
+        })
+        .post(IpcCommand::StartClash.as_ref(), |ctx| {
+            instrumented(IpcCommand::StartClash, async move {
+                trace!("Received StartClash command");
+                let (request, owner) =
+                    match authenticate_request::<AuthenticatedRequest<StartClashRequest>>(&ctx) {
+                        ControlFlow::Continue(authenticated) => authenticated,
+                        ControlFlow::Break(response) => return response,
+                    };
+                let start_request = request.payload;
+                note_audit(|details| {
+                    details.bundle_hash = Some(bundle_hash(&start_request.runtime));
+                    details.proxy_mode = start_request
+                        .macos_proxy
+                        .as_ref()
+                        .map(|proxy| proxy.as_ref().to_owned());
+                });
+                if hash_session_token(&start_request.proposed_session_token).is_err() {
+                    return bad_request("Invalid proposed owner session token");
+                }
+                if let Some(proxy) = start_request.macos_proxy.as_ref()
+                    && let Err(error) = validate_proxy_config(proxy)
+                {
+                    return service_error(ServiceError::invalid_proxy_config(error.to_string()));
+                }
+                if let Some(lease) = start_request.lease.as_ref()
+                    && let Err(error) = validate_lease(lease)
+                {
+                    return bad_request(format!("Invalid session lease: {error}"));
+                }
+                let _lifecycle_guard =
+                    match enter_owner_lifecycle(&owner, OwnerLifecycleGate::Unchecked).await {
+                        ControlFlow::Continue(guard) => guard,
+                        ControlFlow::Break(response) => return response,
+                    };
+                let previous_owner = match load_active_owner().await {
+                    Ok(owner) => owner,
+                    Err(error) => {
+                        return service_unavailable(format!(
+                            "Failed to load active owner: {error}"
+                        ));
+                    }
+                };
+                let prepared_runtime = match prepare_runtime(&owner, &start_request.runtime).await {
+                    Ok(prepared) => prepared,
+                    Err(error) => return service_error(error),
+                };
+                let journal = match OwnerTransitionJournal::begin(
+                    previous_owner.as_ref(),
+                    &owner,
+                    &start_request.proposed_session_token,
+                )
+                .await
+                {
+                    Ok(journal) => journal,
+                    Err(error) => {
+                        return service_unavailable(format!(
+                            "Failed to journal the owner transition: {error:#}"
+                        ));
+                    }
+                };
+                let mut transition = StartOwnerTransition {
+                    previous_owner,
+                    owner: &owner,
+                    prepared_runtime: Some(prepared_runtime),
+                    proposed_session_token: &start_request.proposed_session_token,
+                    macos_proxy: start_request.macos_proxy.as_ref(),
+                    lease: start_request.lease.as_ref(),
+                    journal: Some(journal),
+                };
+                let (active, proxy_outcome) = match owner_proxy_transition(&mut transition).await {
+                    Ok(result) => result,
+                    Err(error) => return service_error(error),
+                };
+                arm_lease(&active);
+                note_audit(|details| details.generation = Some(active.generation));
+                if let Err(error) = cleanup_legacy_owner_files(&owner).await {
+                    warn!(
+                        "Core start committed, but legacy owner cleanup will be retried later: {error}"
+                    );
+                }
+                info!("Core started successfully");
+                ok_json(StartClashResult {
+                    session: OwnerSessionHandle {
+                        generation: active.generation,
+                    },
+                    proxy_outcome,
+                })
+            })
+        })
+        .get(IpcCommand::GetClashLogs.as_ref(), |ctx| {
+            instrumented(IpcCommand::GetClashLogs, async move {
+                trace!("Received GetClashLogs command");
+                let (request, owner) = match authenticate_request::<ReadRequest<()>>(&ctx) {
+                    ControlFlow::Continue(authenticated) => authenticated,
+                    ControlFlow::Break(response) => return response,
+                };
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    request.gate(OwnerLifecycleGate::ActiveOwner, SessionScope::ReadLogs),
+                )
+                .await
+                {
+                    ControlFlow::Continue(guard) => guard,
+                    ControlFlow::Break(response) => return response,
+                };
+                ok_json(LOGGER_MANAGER.get_logs().await)
+            })
+        })
+        .get(IpcCommand::GetClashLogSnapshot.as_ref(), |ctx| {
+            instrumented(IpcCommand::GetClashLogSnapshot, async move {
+                trace!("Received GetClashLogSnapshot command");
+                let (request, owner) = match authenticate_request::<ReadRequest<()>>(&ctx) {
+                    ControlFlow::Continue(authenticated) => authenticated,
+                    ControlFlow::Break(response) => return response,
+                };
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    request.gate(OwnerLifecycleGate::ActiveOwner, SessionScope::ReadLogs),
+                )
+                .await
+                {
                     ControlFlow::Continue(guard) => guard,
                     ControlFlow::Break(response) => return response,
                 };
-            let previous_owner = match load_active_owner().await {
-                Ok(owner) => owner,
-                Err(error) => {
-                    return service_unavailable(format!("Failed to load active owner: {error}"));
+                let path = service_paths()
+                    .for_owner(&owner.identity)
+                    .logs_dir()
+                    .join("service_latest.log");
+                match read_log_snapshot(&path).await {
+                    Ok(snapshot) => ok_json(snapshot),
+                    Err(error) => {
+                        service_unavailable(format!("Failed to read core log snapshot: {error}"))
+                    }
                 }
-            };
-            let prepared_runtime = match prepare_runtime(&owner, &start_request.runtime).await {
-                Ok(prepared) => prepared,
-                Err(error) => return service_error(error),
-            };
-            let mut transition = StartOwnerTransition {
-                previous_owner,
-                owner: &owner,
-                prepared_runtime: Some(prepared_runtime),
-                proposed_session_token: &start_request.proposed_session_token,
-                macos_proxy: start_request.macos_proxy.as_ref(),
-            };
-            let (active, proxy_outcome) = match owner_proxy_transition(&mut transition).await {
-                Ok(result) => result,
-                Err(error) => return service_error(error),
-            };
-            if let Err(error) = cleanup_legacy_owner_files(&owner).await {
-                warn!(
-                    "Core start committed, but legacy owner cleanup will be retried later: {error}"
-                );
-            }
-            info!("Core started successfully");
-            ok_json(StartClashResult {
-                session: OwnerSessionHandle {
-                    generation: active.generation,
-                },
-                proxy_outcome,
             })
         })
-        .get(IpcCommand::GetClashLogs.as_ref(), |ctx| async move {
-            trace!("Received GetClashLogs command");
-            let (_request, owner) = match authenticate_request::<AuthenticatedRequest<()>>(&ctx) {
-                ControlFlow::Continue(authenticated) => authenticated,
-                ControlFlow::Break(response) => return response,
-            };
-            let _lifecycle_guard =
-                match enter_owner_lifecycle(&owner, OwnerLifecycleGate::ActiveOwner).await {
+        .get(IpcCommand::ListCrashReports.as_ref(), |ctx| {
+            instrumented(IpcCommand::ListCrashReports, async move {
+                trace!("Received ListCrashReports command");
+                let (request, owner) = match authenticate_request::<ReadRequest<()>>(&ctx) {
+                    ControlFlow::Continue(authenticated) => authenticated,
+                    ControlFlow::Break(response) => return response,
+                };
+                // Reports stay readable after the core stops or another owner takes over; each
+                // owner only ever sees its own log directory.
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    request.gate(OwnerLifecycleGate::Unchecked, SessionScope::ReadLogs),
+                )
+                .await
+                {
                     ControlFlow::Continue(guard) => guard,
                     ControlFlow::Break(response) => return response,
                 };
-            ok_json(LOGGER_MANAGER.get_logs().await)
+                let logs_dir = service_paths().for_owner(&owner.identity).logs_dir();
+                match list_crash_reports(&logs_dir).await {
+                    Ok(reports) => ok_json(reports),
+                    Err(error) => {
+                        service_unavailable(format!("Failed to list crash reports: {error:#}"))
+                    }
+                }
+            })
         })
-        .get(IpcCommand::GetClashLogSnapshot.as_ref(), |ctx| async move {
-            trace!("Received GetClashLogSnapshot command");
-            let (_request, owner) = match authenticate_request::<AuthenticatedRequest<()>>(&ctx) {
-                ControlFlow::Continue(authenticated) => authenticated,
-                ControlFlow::Break(response) => return response,
-            };
-            let _lifecycle_guard =
-                match enter_owner_lifecycle(&owner, OwnerLifecycleGate::ActiveOwner).await {
+        .get(IpcCommand::GetCrashReport.as_ref(), |ctx| {
+            instrumented(IpcCommand::GetCrashReport, async move {
+                trace!("Received GetCrashReport command");
+                let (request, owner) = match authenticate_request::<ReadRequest<String>>(&ctx) {
+                    ControlFlow::Continue(authenticated) => authenticated,
+                    ControlFlow::Break(response) => return response,
+                };
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    request.gate(OwnerLifecycleGate::Unchecked, SessionScope::ReadLogs),
+                )
+                .await
+                {
                     ControlFlow::Continue(guard) => guard,
                     ControlFlow::Break(response) => return response,
                 };
-            let path = service_paths()
-                .for_owner(&owner.identity)
-                .logs_dir()
-                .join("service_latest.log");
-            match read_log_snapshot(&path).await {
-                Ok(snapshot) => ok_json(snapshot),
-                Err(error) => {
-                    service_unavailable(format!("Failed to read core log snapshot: {error}"))
+                let logs_dir = service_paths().for_owner(&owner.identity).logs_dir();
+                match read_crash_report(&logs_dir, &request.payload).await {
+                    Ok(report) => ok_json(report),
+                    Err(error) => bad_request(format!("Failed to read crash report: {error:#}")),
                 }
-            }
+            })
         })
-        .delete(IpcCommand::StopClash.as_ref(), |ctx| async move {
-            trace!("Received StopClash command");
-            let (request, owner) =
-                match authenticate_request::<AuthenticatedSessionRequest<()>>(&ctx) {
+        .delete(IpcCommand::StopClash.as_ref(), |ctx| {
+            instrumented(IpcCommand::StopClash, async move {
+                trace!("Received StopClash command");
+                let (request, owner) =
+                    match authenticate_request::<AuthenticatedSessionRequest<()>>(&ctx) {
+                        ControlFlow::Continue(authenticated) => authenticated,
+                        ControlFlow::Break(response) => return response,
+                    };
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    OwnerLifecycleGate::ActiveSession(
+                        &request.session,
+                        Some(SessionScope::StopCore),
+                    ),
+                )
+                .await
+                {
+                    ControlFlow::Continue(guard) => guard,
+                    ControlFlow::Break(response) => return response,
+                };
+                match stop_active_owner(&owner.key).await {
+                    Ok(()) => ok_empty("Core stopped successfully"),
+                    Err(StopOwnerError::Proxy(error)) => service_error(error),
+                    Err(StopOwnerError::Unavailable(message)) => service_unavailable(message),
+                }
+            })
+        })
+        .delete(IpcCommand::EvictOwner.as_ref(), |ctx| {
+            instrumented(IpcCommand::EvictOwner, async move {
+                trace!("Received EvictOwner command");
+                let (request, caller) =
+                    match authenticate_request::<AuthenticatedRequest<EvictOwnerRequest>>(&ctx) {
+                        ControlFlow::Continue(authenticated) => authenticated,
+                        ControlFlow::Break(response) => return response,
+                    };
+                let owner_key = request.payload.owner_key.as_str();
+                note_audit(|details| details.owner_key = Some(owner_key.to_owned()));
+                if let Err(error) = authorize_admin(&caller).await {
+                    return service_error(error);
+                }
+                if !is_owner_key(owner_key) {
+                    return bad_request(format!("Invalid owner key {owner_key:?}"));
+                }
+                let _lifecycle_guard =
+                    match enter_owner_lifecycle(&caller, OwnerLifecycleGate::Unchecked).await {
+                        ControlFlow::Continue(guard) => guard,
+                        ControlFlow::Break(response) => return response,
+                    };
+                match evict_owner(&request.payload).await {
+                    Ok(result) => {
+                        let message = format!(
+                            "Owner {owner_key} {}, state {}",
+                            if result.evicted {
+                                "evicted"
+                            } else {
+                                "was not active"
+                            },
+                            if result.purged { "purged" } else { "kept" }
+                        );
+                        json_response(StatusCode::OK, 0, message, Some(result))
+                    }
+                    Err(StopOwnerError::Proxy(error)) => service_error(error),
+                    Err(StopOwnerError::Unavailable(message)) => service_unavailable(message),
+                }
+            })
+        })
+        .get(IpcCommand::QueryAuditLog.as_ref(), |ctx| {
+            instrumented(IpcCommand::QueryAuditLog, async move {
+                trace!("Received QueryAuditLog command");
+                let (request, caller) =
+                    match authenticate_request::<AuthenticatedRequest<AuditQuery>>(&ctx) {
+                        ControlFlow::Continue(authenticated) => authenticated,
+                        ControlFlow::Break(response) => return response,
+                    };
+                if let Err(error) = authorize_admin(&caller).await {
+                    return service_error(error);
+                }
+                match query_audit_log(&request.payload).await {
+                    Ok(records) => ok_json(records),
+                    Err(error) => {
+                        service_unavailable(format!("Failed to read the audit log: {error:#}"))
+                    }
+                }
+            })
+        })
+        .put(IpcCommand::StageRuntime.as_ref(), |ctx| {
+            instrumented(IpcCommand::StageRuntime, async move {
+                trace!("Received StageRuntime command");
+                let (request, owner) = match authenticate_request::<
+                    AuthenticatedSessionRequest<RuntimeBundle>,
+                >(&ctx)
+                {
                     ControlFlow::Continue(authenticated) => authenticated,
                     ControlFlow::Break(response) => return response,
                 };
-            let _lifecycle_guard = match enter_owner_lifecycle(
-                &owner,
-                OwnerLifecycleGate::ActiveSession(&request.session),
-            )
-            .await
-            {
-                ControlFlow::Continue(guard) => guard,
-                ControlFlow::Break(response) => return response,
-            };
-            if let Err(error) = clear_proxy_with_direct_compensation().await {
-                return service_error(error);
-            }
-            match CORE_MANAGER.lock().await.stop_core().await {
-                Ok(_) => info!("Core stopped successfully"),
-                Err(e) => {
-                    return service_unavailable(format!("Failed to stop core: {}", e));
+                note_audit(|details| details.bundle_hash = Some(bundle_hash(&request.payload)));
+                // Staging rewrites the live generation, so hold the lifecycle lock and require its
+                // current session for the whole operation.
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    OwnerLifecycleGate::ActiveSession(
+                        &request.session,
+                        Some(SessionScope::StageRuntime),
+                    ),
+                )
+                .await
+                {
+                    ControlFlow::Continue(guard) => guard,
+                    ControlFlow::Break(response) => return response,
+                };
+                match stage_runtime(&owner, &request.payload).await {
+                    Ok(outcome) => ok_json(outcome),
+                    Err(error) => service_error(error),
                 }
-            }
-            if let Err(e) = persist_owner_core_stopped(&owner).await {
-                set_core_lifecycle_state(ServiceLifecycleState::Fatal);
-                return service_unavailable(format!("Failed to persist desired state: {}", e));
-            }
-            if let Err(e) = clear_active_owner().await {
-                set_core_lifecycle_state(ServiceLifecycleState::Fatal);
-                return service_unavailable(format!("Failed to clear active owner: {}", e));
-            }
-            ok_empty("Core stopped successfully")
+            })
         })
-        .put(IpcCommand::StageRuntime.as_ref(), |ctx| async move {
-            trace!("Received StageRuntime command");
-            let (request, owner) =
-                match authenticate_request::<AuthenticatedSessionRequest<RuntimeBundle>>(&ctx) {
+        .put(IpcCommand::UpdateWriter.as_ref(), |ctx| {
+            instrumented(IpcCommand::UpdateWriter, async move {
+                trace!("Received UpdateWriter command");
+                let (request, owner) =
+                    match authenticate_request::<AuthenticatedSessionRequest<WriterConfig>>(&ctx) {
+                        ControlFlow::Continue(authenticated) => authenticated,
+                        ControlFlow::Break(response) => return response,
+                    };
+                let mut writer_config = request.payload;
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    OwnerLifecycleGate::ActiveSession(
+                        &request.session,
+                        Some(SessionScope::UpdateWriter),
+                    ),
+                )
+                .await
+                {
+                    ControlFlow::Continue(guard) => guard,
+                    ControlFlow::Break(response) => return response,
+                };
+                // Never let the client choose a service-owned log destination.
+                writer_config.directory = service_paths()
+                    .for_owner(&owner.identity)
+                    .logs_dir()
+                    .to_string_lossy()
+                    .into_owned();
+                match set_or_update_writer(&writer_config).await {
+                    Ok(_) => info!("Update writer successfully"),
+                    Err(e) => {
+                        return service_unavailable(format!("Failed to update writer: {}", e));
+                    }
+                };
+                if let Err(e) = persist_owner_writer_config(&owner, &writer_config).await {
+                    return service_unavailable(format!("Failed to persist writer config: {}", e));
+                }
+                ok_empty("Update Writer successfully")
+            })
+        })
+        .put(IpcCommand::SetSystemProxy.as_ref(), |ctx| {
+            instrumented(IpcCommand::SetSystemProxy, async move {
+                trace!("Received SetSystemProxy command");
+                let (request, owner) = match authenticate_request::<
+                    AuthenticatedSessionRequest<MacosProxyConfig>,
+                >(&ctx)
+                {
                     ControlFlow::Continue(authenticated) => authenticated,
                     ControlFlow::Break(response) => return response,
                 };
-            // Staging rewrites the live generation, so hold the lifecycle lock and require its
-            // current session for the whole operation.
-            let _lifecycle_guard = match enter_owner_lifecycle(
-                &owner,
-                OwnerLifecycleGate::ActiveSession(&request.session),
-            )
-            .await
-            {
-                ControlFlow::Continue(guard) => guard,
-                ControlFlow::Break(response) => return response,
-            };
-            match stage_runtime(&owner, &request.payload).await {
-                Ok(outcome) => ok_json(outcome),
-                Err(error) => service_error(error),
-            }
+                note_audit(|details| {
+                    details.proxy_mode = Some(request.payload.as_ref().to_owned())
+                });
+                // Reject a stale session before reporting payload validation errors.
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    OwnerLifecycleGate::ActiveSession(
+                        &request.session,
+                        Some(SessionScope::SetSystemProxy),
+                    ),
+                )
+                .await
+                {
+                    ControlFlow::Continue(guard) => guard,
+                    ControlFlow::Break(response) => return response,
+                };
+                if let Err(error) = validate_proxy_config(&request.payload) {
+                    return service_error(ServiceError::invalid_proxy_config(error.to_string()));
+                }
+                match apply_service_proxy_or_direct(Some(&request.payload)).await {
+                    Ok(outcome) => {
+                        if let Err(error) =
+                            persist_owner_proxy(&owner.key, Some(&request.payload), &outcome).await
+                        {
+                            warn!(
+                                "System proxy applied; persisting it for restore failed: {error:#}"
+                            );
+                        }
+                        ok_json(outcome)
+                    }
+                    Err(error) => {
+                        service_error(ServiceError::proxy_apply_failed(error.to_string()))
+                    }
+                }
+            })
+        })
+        .put(IpcCommand::RenewSessionLease.as_ref(), |ctx| {
+            instrumented(IpcCommand::RenewSessionLease, async move {
+                trace!("Received RenewSessionLease command");
+                let (request, owner) =
+                    match authenticate_request::<AuthenticatedSessionRequest<()>>(&ctx) {
+                        ControlFlow::Continue(authenticated) => authenticated,
+                        ControlFlow::Break(response) => return response,
+                    };
+                // Heartbeats stay off the lifecycle lock; the clock is keyed by generation, so a
+                // renewal racing a new session cannot extend the new session's lease.
+                // Only a full session keeps the lease alive, so a status widget cannot keep an
+                // abandoned core running.
+                let active = match require_active_session(&owner, &request.session, None).await {
+                    Ok(active) => active,
+                    Err(error) => return service_error(error),
+                };
+                ok_json(renew_lease(&active))
+            })
         })
-        .put(IpcCommand::UpdateWriter.as_ref(), |ctx| async move {
-            trace!("Received UpdateWriter command");
-            let (request, owner) =
-                match authenticate_request::<AuthenticatedSessionRequest<WriterConfig>>(&ctx) {
+        .post(IpcCommand::OpenSession.as_ref(), |ctx| {
+            instrumented(IpcCommand::OpenSession, async move {
+                trace!("Received OpenSession command");
+                let (request, owner) = match authenticate_request::<
+                    AuthenticatedSessionRequest<OpenSessionRequest>,
+                >(&ctx)
+                {
                     ControlFlow::Continue(authenticated) => authenticated,
                     ControlFlow::Break(response) => return response,
                 };
-            let mut writer_config = request.payload;
-            let _lifecycle_guard = match enter_owner_lifecycle(
-                &owner,
-                OwnerLifecycleGate::ActiveSession(&request.session),
-            )
-            .await
-            {
-                ControlFlow::Continue(guard) => guard,
-                ControlFlow::Break(response) => return response,
-            };
-            // Never let the client choose a service-owned log destination.
-            writer_config.directory = service_paths()
-                .for_owner(&owner.identity)
-                .logs_dir()
-                .to_string_lossy()
-                .into_owned();
-            match set_or_update_writer(&writer_config).await {
-                Ok(_) => info!("Update writer successfully"),
-                Err(e) => {
-                    return service_unavailable(format!("Failed to update writer: {}", e));
+                // Only a full session may open another one; owner credentials alone would let
+                // any process running as the owner mint itself full rights.
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    OwnerLifecycleGate::ActiveSession(&request.session, None),
+                )
+                .await
+                {
+                    ControlFlow::Continue(guard) => guard,
+                    ControlFlow::Break(response) => return response,
+                };
+                let open = request.payload;
+                // Reopening the primary session would take it from whoever started the core.
+                if open.name == PRIMARY_SESSION_NAME {
+                    return service_error(ServiceError::invalid_owner_session(
+                        "the primary session is opened only by StartClash",
+                    ));
                 }
-            };
-            if let Err(e) = persist_owner_writer_config(&owner, &writer_config).await {
-                return service_unavailable(format!("Failed to persist writer config: {}", e));
-            }
-            ok_empty("Update Writer successfully")
+                match open_owner_session(&owner, &open.name, &open.proposed_session_token, None)
+                    .await
+                {
+                    Ok(active) => {
+                        info!("Opened session {:?} for owner {}", open.name, owner.key);
+                        ok_json(OwnerSessionHandle {
+                            generation: active.generation,
+                        })
+                    }
+                    Err(error) => session_update_error(error),
+                }
+            })
         })
-        .put(IpcCommand::SetSystemProxy.as_ref(), |ctx| async move {
-            trace!("Received SetSystemProxy command");
-            let (request, owner) =
-                match authenticate_request::<AuthenticatedSessionRequest<MacosProxyConfig>>(&ctx) {
+        .delete(IpcCommand::RevokeSession.as_ref(), |ctx| {
+            instrumented(IpcCommand::RevokeSession, async move {
+                trace!("Received RevokeSession command");
+                let (request, owner) =
+                    match authenticate_request::<AuthenticatedRequest<String>>(&ctx) {
+                        ControlFlow::Continue(authenticated) => authenticated,
+                        ControlFlow::Break(response) => return response,
+                    };
+                // Owner credentials suffice, so a lost helper's session can be revoked from
+                // anywhere the owner can authenticate. They do not prove the primary session,
+                // which ends only with its generation.
+                if request.payload == PRIMARY_SESSION_NAME {
+                    return service_error(ServiceError::invalid_owner_session(
+                        "the primary session ends only through StopClash",
+                    ));
+                }
+                let _lifecycle_guard =
+                    match enter_owner_lifecycle(&owner, OwnerLifecycleGate::ActiveOwner).await {
+                        ControlFlow::Continue(guard) => guard,
+                        ControlFlow::Break(response) => return response,
+                    };
+                match revoke_owner_session(&owner, &request.payload, false).await {
+                    Ok(_) => {
+                        info!(
+                            "Revoked session {:?} of owner {}",
+                            request.payload, owner.key
+                        );
+                        ok_empty("Session revoked")
+                    }
+                    Err(error) => session_update_error(error),
+                }
+            })
+        })
+        .post(IpcCommand::MintScopedSession.as_ref(), |ctx| {
+            instrumented(IpcCommand::MintScopedSession, async move {
+                trace!("Received MintScopedSession command");
+                let (request, owner) = match authenticate_request::<
+                    AuthenticatedSessionRequest<MintScopedSessionRequest>,
+                >(&ctx)
+                {
                     ControlFlow::Continue(authenticated) => authenticated,
                     ControlFlow::Break(response) => return response,
                 };
-            // Reject a stale session before reporting payload validation errors.
-            let _lifecycle_guard = match enter_owner_lifecycle(
-                &owner,
-                OwnerLifecycleGate::ActiveSession(&request.session),
-            )
-            .await
-            {
-                ControlFlow::Continue(guard) => guard,
-                ControlFlow::Break(response) => return response,
-            };
-            if let Err(error) = validate_proxy_config(&request.payload) {
-                return service_error(ServiceError::invalid_proxy_config(error.to_string()));
-            }
-            match apply_service_proxy_or_direct(Some(&request.payload)).await {
-                Ok(outcome) => ok_json(outcome),
-                Err(error) => service_error(ServiceError::proxy_apply_failed(error.to_string())),
-            }
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    OwnerLifecycleGate::ActiveSession(&request.session, None),
+                )
+                .await
+                {
+                    ControlFlow::Continue(guard) => guard,
+                    ControlFlow::Break(response) => return response,
+                };
+                let mint = request.payload;
+                match open_owner_session(
+                    &owner,
+                    &mint.name,
+                    &mint.proposed_session_token,
+                    Some(mint.scopes),
+                )
+                .await
+                {
+                    Ok(active) => {
+                        info!(
+                            "Minted scoped session {:?} for owner {}",
+                            mint.name, owner.key
+                        );
+                        ok_json(OwnerSessionHandle {
+                            generation: active.generation,
+                        })
+                    }
+                    Err(error) => session_update_error(error),
+                }
+            })
+        })
+        .delete(IpcCommand::RevokeScopedSession.as_ref(), |ctx| {
+            instrumented(IpcCommand::RevokeScopedSession, async move {
+                trace!("Received RevokeScopedSession command");
+                let (request, owner) =
+                    match authenticate_request::<AuthenticatedSessionRequest<String>>(&ctx) {
+                        ControlFlow::Continue(authenticated) => authenticated,
+                        ControlFlow::Break(response) => return response,
+                    };
+                let _lifecycle_guard = match enter_owner_lifecycle(
+                    &owner,
+                    OwnerLifecycleGate::ActiveSession(&request.session, None),
+                )
+                .await
+                {
+                    ControlFlow::Continue(guard) => guard,
+                    ControlFlow::Break(response) => return response,
+                };
+                match revoke_owner_session(&owner, &request.payload, true).await {
+                    Ok(_) => {
+                        info!(
+                            "Revoked scoped session {:?} of owner {}",
+                            request.payload, owner.key
+                        );
+                        ok_empty("Scoped session revoked")
+                    }
+                    Err(error) => session_update_error(error),
+                }
+            })
         });
     Ok(router)
 }
@@ -896,6 +1552,9 @@ fn service_error(error: ServiceError) -> Result<HttpResponse> {
     let status = match error.code {
         crate::ServiceErrorCode::UnauthorizedOwner => StatusCode::UNAUTHORIZED,
         crate::ServiceErrorCode::NotActive => StatusCode::CONFLICT,
+        crate::ServiceErrorCode::SessionScopeDenied
+        | crate::ServiceErrorCode::ObserverDenied
+        | crate::ServiceErrorCode::AdminDenied => StatusCode::FORBIDDEN,
         _ => StatusCode::UNPROCESSABLE_ENTITY,
     };
     json_response::<()>(status, error.code as u16, error.message, None)
@@ -925,9 +1584,12 @@ fn constant_time_eq(left: &[u8], right: &[u8]) -> bool {
         == 0
 }
 
+/// Checks that `proof` belongs to the owner's current generation and holds `scope`; `None`
+/// requires a full session.
 pub async fn require_active_session(
     owner: &AuthenticatedOwner,
     proof: &OwnerSessionProof,
+    scope: Option<SessionScope>,
 ) -> std::result::Result<ActiveOwnerState, ServiceError> {
     let active = load_active_owner()
         .await
@@ -935,27 +1597,49 @@ pub async fn require_active_session(
         .ok_or_else(ServiceError::stale_owner_session)?;
     let supplied_hash =
         hash_session_token(&proof.token).map_err(|_| ServiceError::stale_owner_session())?;
-    if active.owner_key != owner.key
-        || active.generation != proof.generation
-        || !constant_time_eq(
-            active.session_token_hash.as_bytes(),
-            supplied_hash.as_bytes(),
-        )
-    {
+    if active.owner_key != owner.key || active.generation != proof.generation {
         return Err(ServiceError::stale_owner_session());
     }
+    // Compare against every session so the timing does not tell which one matched.
+    let mut matched = None;
+    for session in &active.sessions {
+        if constant_time_eq(session.token_hash.as_bytes(), supplied_hash.as_bytes()) {
+            matched = Some(session);
+        }
+    }
+    let Some(session) = matched else {
+        return Err(ServiceError::stale_owner_session());
+    };
+    if !session.allows(scope) {
+        return Err(ServiceError::session_scope_denied(scope));
+    }
+    note_audit(|details| {
+        details.session = Some(session.name.clone());
+        details.generation = Some(active.generation);
+    });
     Ok(active)
 }
 
+/// Reports a rejected session change as its [`ServiceError`] and anything else as unavailable.
+fn session_update_error(error: anyhow::Error) -> Result<HttpResponse> {
+    match error.downcast::<ServiceError>() {
+        Ok(error) => service_error(error),
+        Err(error) => service_unavailable(format!("Failed to update owner sessions: {error:#}")),
+    }
+}
+
 fn json_response<T: Serialize>(
     status: StatusCode,
     code: u16,
     message: impl Into<String>,
     data: Option<T>,
 ) -> Result<HttpResponse> {
+    let _ = RESPONSE_CODE.try_with(|recorded| recorded.set(Some(code)));
+    let message = message.into();
+    note_audit(|details| details.message = Some(message.clone()));
     let json_value = Response {
         code,
-        message: message.into(),
+        message,
         data,
     };
     Ok(HttpResponse::builder()
@@ -966,6 +1650,18 @@ fn json_response<T: Serialize>(
 
 static OWNER_LIFECYCLE_LOCK: Lazy<Mutex<()>> = Lazy::new(|| Mutex::new(()));
 
+tokio::task_local! {
+    /// Envelope code of the response being built, recorded for request metrics.
+    static RESPONSE_CODE: Cell<Option<u16>>;
+    /// What the request's handler has learned about it so far, audited when it finishes.
+    static REQUEST_AUDIT: RefCell<AuditDetails>;
+}
+
+/// Adds to the audit details of the request being handled, if any.
+fn note_audit(update: impl FnOnce(&mut AuditDetails)) {
+    let _ = REQUEST_AUDIT.try_with(|details| update(&mut details.borrow_mut()));
+}
+
 #[cfg(test)]
 mod owner_lifecycle_tests {
     use super::{
@@ -975,10 +1671,17 @@ mod owner_lifecycle_tests {
     use crate::ServiceErrorCode;
     use crate::core::auth::AuthenticatedOwner;
     use crate::core::desired::{
-        ActiveOwnerState, clear_active_owner, commit_active_owner_session, persist_active_owner,
+        ActiveOwnerState, clear_active_owner, commit_active_owner_session, load_active_owner,
+        load_owner_desired_state, open_owner_session, persist_active_owner,
+        persist_owner_core_stopped_by_key, restore_owner_core_intent, revoke_owner_session,
+    };
+    use crate::core::journal::{
+        OwnerTransitionJournal, OwnerTransitionRecovery, OwnerTransitionStep,
+        recover_owner_transition,
     };
-    use crate::{OwnerIdentity, OwnerSessionProof, ProxyApplyOutcome};
+    use crate::{MacosProxyConfig, OwnerIdentity, OwnerSessionProof, ProxyApplyOutcome};
     use serial_test::serial;
+    use std::time::Duration;
 
     fn owner(uid: u32) -> AuthenticatedOwner {
         AuthenticatedOwner {
@@ -990,6 +1693,8 @@ mod owner_lifecycle_tests {
 
     struct RecordingTransition {
         events: Vec<&'static str>,
+        steps: Vec<OwnerTransitionStep>,
+        journal_closed: bool,
         active_owner: ActiveOwnerState,
         running_pid: u32,
         next_owner: ActiveOwnerState,
@@ -1043,11 +1748,21 @@ mod owner_lifecycle_tests {
             }
             Ok(ProxyApplyOutcome::Applied)
         }
+
+        async fn record_step(&mut self, step: OwnerTransitionStep) {
+            self.steps.push(step);
+        }
+
+        async fn close_journal(&mut self) {
+            self.journal_closed = true;
+        }
     }
 
     fn recording_transition() -> RecordingTransition {
         RecordingTransition {
             events: Vec::new(),
+            steps: Vec::new(),
+            journal_closed: false,
             active_owner: ActiveOwnerState::from(&owner(96_001)),
             running_pid: 101,
             next_owner: ActiveOwnerState::from(&owner(96_002)),
@@ -1067,6 +1782,16 @@ mod owner_lifecycle_tests {
             transition.events,
             ["clear_proxy", "stop_a", "start_b", "commit_b", "apply_b"]
         );
+        assert_eq!(
+            transition.steps,
+            [
+                OwnerTransitionStep::PreviousProxyCleared,
+                OwnerTransitionStep::PreviousCoreStopped,
+                OwnerTransitionStep::NewCoreStarted,
+                OwnerTransitionStep::NewOwnerCommitted,
+            ]
+        );
+        assert!(transition.journal_closed);
         assert_eq!(transition.active_owner.owner_key, "96002");
         assert_eq!(transition.running_pid, 202);
         assert_eq!(outcome, ProxyApplyOutcome::Applied);
@@ -1101,6 +1826,175 @@ mod owner_lifecycle_tests {
         assert_eq!(transition.events, ["clear_proxy", "stop_a"]);
         assert_eq!(transition.active_owner.owner_key, "96001");
         assert_eq!(transition.running_pid, 101);
+        assert!(
+            transition.journal_closed,
+            "a failure compensated in process must not be replayed at startup"
+        );
+    }
+
+    /// Performs the durable half of each step and never returns from `crash_at`, the way a
+    /// service killed there would leave disk.
+    struct CrashingTransition {
+        previous_key: String,
+        next: AuthenticatedOwner,
+        next_token: String,
+        journal: Option<OwnerTransitionJournal>,
+        crash_at: &'static str,
+    }
+
+    impl CrashingTransition {
+        async fn crash_point(&self, point: &'static str) {
+            if self.crash_at == point {
+                std::future::pending::<()>().await;
+            }
+        }
+    }
+
+    impl OwnerProxyTransition for CrashingTransition {
+        async fn clear_previous_proxy(&mut self) -> anyhow::Result<()> {
+            self.crash_point("clear_proxy").await;
+            Ok(())
+        }
+
+        async fn compensate_direct(&mut self) -> anyhow::Result<()> {
+            Ok(())
+        }
+
+        async fn stop_previous_core(&mut self) -> anyhow::Result<()> {
+            persist_owner_core_stopped_by_key(&self.previous_key).await?;
+            self.crash_point("stop_persisted").await;
+            clear_active_owner().await?;
+            self.crash_point("stop").await;
+            Ok(())
+        }
+
+        async fn start_new_core(&mut self) -> anyhow::Result<()> {
+            self.crash_point("start").await;
+            Ok(())
+        }
+
+        async fn commit_new_owner(&mut self) -> anyhow::Result<ActiveOwnerState> {
+            restore_owner_core_intent(&self.next.key, true, None).await?;
+            self.crash_point("commit_desired").await;
+            let active = commit_active_owner_session(&self.next, &self.next_token, None).await?;
+            self.crash_point("commit").await;
+            Ok(active)
+        }
+
+        async fn apply_new_proxy(&mut self) -> anyhow::Result<ProxyApplyOutcome> {
+            self.crash_point("apply").await;
+            Ok(ProxyApplyOutcome::Applied)
+        }
+
+        async fn record_step(&mut self, step: OwnerTransitionStep) {
+            if let Some(journal) = self.journal.as_mut() {
+                journal.record(step).await;
+            }
+        }
+
+        async fn close_journal(&mut self) {
+            if let Some(journal) = self.journal.take() {
+                journal.close().await;
+            }
+        }
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn interrupted_owner_transitions_recover_deterministically() -> anyhow::Result<()> {
+        let previous = owner(96_101);
+        let next = owner(96_102);
+        let previous_proxy = MacosProxyConfig::Global {
+            host: "127.0.0.1".to_owned(),
+            port: 7890,
+            bypass: String::new(),
+        };
+        for (crash_at, expected) in [
+            ("clear_proxy", OwnerTransitionRecovery::RolledBack),
+            ("stop_persisted", OwnerTransitionRecovery::RolledBack),
+            ("stop", OwnerTransitionRecovery::RolledBack),
+            ("start", OwnerTransitionRecovery::RolledBack),
+            ("commit_desired", OwnerTransitionRecovery::RolledBack),
+            ("commit", OwnerTransitionRecovery::Completed),
+            ("apply", OwnerTransitionRecovery::Completed),
+        ] {
+            let before = commit_active_owner_session(&previous, &"ab".repeat(32), None).await?;
+            restore_owner_core_intent(&previous.key, true, Some(&previous_proxy)).await?;
+            restore_owner_core_intent(&next.key, false, None).await?;
+            let mut transition = CrashingTransition {
+                previous_key: previous.key.clone(),
+                next: next.clone(),
+                next_token: "cd".repeat(32),
+                journal: Some(
+                    OwnerTransitionJournal::begin(Some(&before), &next, &"cd".repeat(32)).await?,
+                ),
+                crash_at,
+            };
+
+            let interrupted = tokio::time::timeout(
+                Duration::from_millis(200),
+                owner_proxy_transition(&mut transition),
+            )
+            .await;
+            assert!(
+                interrupted.is_err(),
+                "{crash_at}: the transition must crash"
+            );
+            drop(transition);
+
+            assert_eq!(
+                recover_owner_transition().await?,
+                Some(expected),
+                "{crash_at}"
+            );
+            let active = load_active_owner().await?.expect("an owner must remain");
+            let previous_desired = load_owner_desired_state(&previous.key).await?;
+            let previous_running = previous_desired.core_should_be_running;
+            let next_running = load_owner_desired_state(&next.key)
+                .await?
+                .core_should_be_running;
+            match expected {
+                OwnerTransitionRecovery::RolledBack => {
+                    assert_eq!(active, before, "{crash_at}");
+                    assert!(previous_running && !next_running, "{crash_at}");
+                    assert_eq!(
+                        previous_desired.last_proxy_config.as_ref(),
+                        Some(&previous_proxy),
+                        "{crash_at}: the previous owner's proxy comes back with its core"
+                    );
+                }
+                OwnerTransitionRecovery::Completed => {
+                    assert_eq!(active.owner_key, next.key, "{crash_at}");
+                    assert!(!previous_running && next_running, "{crash_at}");
+                    assert_eq!(previous_desired.last_proxy_config, None, "{crash_at}");
+                }
+            }
+            assert_eq!(
+                recover_owner_transition().await?,
+                None,
+                "{crash_at}: recovery must consume the journal"
+            );
+        }
+        clear_active_owner().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn an_unclosed_journal_does_not_block_the_next_transition() -> anyhow::Result<()> {
+        let previous = owner(96_111);
+        let next = owner(96_112);
+        let before = commit_active_owner_session(&previous, &"ab".repeat(32), None).await?;
+        // A transition that returned without managing to remove its journal.
+        let unclosed =
+            OwnerTransitionJournal::begin(Some(&before), &next, &"cd".repeat(32)).await?;
+        drop(unclosed);
+
+        let journal = OwnerTransitionJournal::begin(Some(&before), &next, &"ef".repeat(32)).await?;
+        journal.close().await;
+        assert_eq!(recover_owner_transition().await?, None);
+        clear_active_owner().await?;
+        Ok(())
     }
 
     #[tokio::test]
@@ -1136,7 +2030,7 @@ mod owner_lifecycle_tests {
     async fn non_active_owner_receives_stable_error() -> anyhow::Result<()> {
         let active = owner(92_001);
         let inactive = owner(92_002);
-        commit_active_owner_session(&active, &"10".repeat(32)).await?;
+        commit_active_owner_session(&active, &"10".repeat(32), None).await?;
 
         let error = require_active_owner(&inactive)
             .await
@@ -1152,16 +2046,16 @@ mod owner_lifecycle_tests {
     async fn same_owner_new_session_invalidates_old_proof() -> anyhow::Result<()> {
         clear_active_owner().await?;
         let owner = owner(95_001);
-        let first = commit_active_owner_session(&owner, &"11".repeat(32)).await?;
+        let first = commit_active_owner_session(&owner, &"11".repeat(32), None).await?;
         let first_proof = OwnerSessionProof {
             generation: first.generation,
             token: "11".repeat(32),
         };
-        require_active_session(&owner, &first_proof).await?;
-        let second = commit_active_owner_session(&owner, &"22".repeat(32)).await?;
+        require_active_session(&owner, &first_proof, None).await?;
+        let second = commit_active_owner_session(&owner, &"22".repeat(32), None).await?;
         assert!(second.generation > first.generation);
         assert_eq!(
-            require_active_session(&owner, &first_proof)
+            require_active_session(&owner, &first_proof, None)
                 .await
                 .expect_err("old proof must be stale")
                 .code,
@@ -1171,6 +2065,103 @@ mod owner_lifecycle_tests {
         Ok(())
     }
 
+    #[tokio::test]
+    #[serial]
+    async fn named_sessions_coexist_and_are_revoked_individually() -> anyhow::Result<()> {
+        clear_active_owner().await?;
+        let owner = owner(95_003);
+        let active = commit_active_owner_session(&owner, &"11".repeat(32), None).await?;
+        let proof = |token: &str| OwnerSessionProof {
+            generation: active.generation,
+            token: token.repeat(32),
+        };
+        open_owner_session(&owner, "tray", &"33".repeat(32), None).await?;
+        require_active_session(&owner, &proof("11"), None).await?;
+        require_active_session(&owner, &proof("33"), None).await?;
+
+        let rejected = open_owner_session(&owner, "Tray Helper", &"44".repeat(32), None)
+            .await
+            .expect_err("an invalid name must be rejected")
+            .downcast::<crate::core::auth::ServiceError>()?;
+        assert_eq!(rejected.code, ServiceErrorCode::InvalidOwnerSession);
+
+        revoke_owner_session(&owner, crate::PRIMARY_SESSION_NAME, false).await?;
+        assert_eq!(
+            require_active_session(&owner, &proof("11"), None)
+                .await
+                .expect_err("a revoked session must be stale")
+                .code,
+            ServiceErrorCode::StaleOwnerSession,
+        );
+        require_active_session(&owner, &proof("33"), None).await?;
+        assert!(
+            revoke_owner_session(&owner, crate::PRIMARY_SESSION_NAME, false)
+                .await
+                .is_err()
+        );
+        clear_active_owner().await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    #[serial]
+    async fn scoped_sessions_hold_only_their_scopes() -> anyhow::Result<()> {
+        use crate::SessionScope;
+
+        clear_active_owner().await?;
+        let owner = owner(95_004);
+        let active = commit_active_owner_session(&owner, &"11".repeat(32), None).await?;
+        let widget = OwnerSessionProof {
+            generation: active.generation,
+            token: "77".repeat(32),
+        };
+        open_owner_session(
+            &owner,
+            "widget",
+            &widget.token,
+            Some(vec![SessionScope::ReadStatus]),
+        )
+        .await?;
+
+        require_active_session(&owner, &widget, Some(SessionScope::ReadStatus)).await?;
+        for denied in [Some(SessionScope::StopCore), None] {
+            assert_eq!(
+                require_active_session(&owner, &widget, denied)
+                    .await
+                    .expect_err("a scoped session must stay within its scopes")
+                    .code,
+                ServiceErrorCode::SessionScopeDenied,
+            );
+        }
+
+        let over_full = open_owner_session(
+            &owner,
+            crate::PRIMARY_SESSION_NAME,
+            &"88".repeat(32),
+            Some(vec![SessionScope::ReadLogs]),
+        )
+        .await
+        .expect_err("a scoped session must not replace a full one")
+        .downcast::<crate::core::auth::ServiceError>()?;
+        assert_eq!(over_full.code, ServiceErrorCode::InvalidOwnerSession);
+        assert!(
+            revoke_owner_session(&owner, crate::PRIMARY_SESSION_NAME, true)
+                .await
+                .is_err()
+        );
+
+        revoke_owner_session(&owner, "widget", true).await?;
+        assert_eq!(
+            require_active_session(&owner, &widget, Some(SessionScope::ReadStatus))
+                .await
+                .expect_err("a revoked scoped session must be stale")
+                .code,
+            ServiceErrorCode::StaleOwnerSession,
+        );
+        clear_active_owner().await?;
+        Ok(())
+    }
+
     #[tokio::test]
     #[serial]
     async fn legacy_active_owner_session_fails_closed() -> anyhow::Result<()> {
@@ -1183,7 +2174,7 @@ mod owner_lifecycle_tests {
         };
 
         assert_eq!(
-            require_active_session(&owner, &proof)
+            require_active_session(&owner, &proof, None)
                 .await
                 .expect_err("legacy owner must not authenticate a session")
                 .code,
@@ -4,6 +4,8 @@ use std::sync::atomic::{AtomicU8, Ordering};
 
 pub fn set_service_lifecycle_state(state: ServiceLifecycleState) {
     service_lifecycle_state_cell().store(state as u8, Ordering::Relaxed);
+    #[cfg(target_os = "linux")]
+    crate::core::notify::notify_lifecycle_state(state);
 }
 
 pub fn service_lifecycle_state() -> ServiceLifecycleState {
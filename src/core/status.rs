@@ -1,9 +1,13 @@
 use crate::core::auth::AuthenticatedOwner;
 use crate::core::desired::{load_active_owner, load_owner_desired_state};
+use crate::core::lease::lease_status;
 use crate::core::manager::CORE_MANAGER;
 use crate::core::state::{core_lifecycle_state, service_lifecycle_state};
-use crate::core::structure::{ServiceLifecycleState, ServiceStatusSnapshot};
+use crate::core::structure::{
+    ObserverStatusSnapshot, OwnerIdentity, ServiceLifecycleState, ServiceStatusSnapshot,
+};
 use anyhow::Result;
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub async fn service_status_snapshot(owner: &AuthenticatedOwner) -> Result<ServiceStatusSnapshot> {
     let reported_service_state = service_lifecycle_state();
@@ -12,10 +16,10 @@ pub async fn service_status_snapshot(owner: &AuthenticatedOwner) -> Result<Servi
         .await
         .unwrap_or_default();
     let active_owner = load_active_owner().await?;
-    let active_generation = active_owner
+    let caller_active = active_owner
         .as_ref()
-        .filter(|active| active.owner_key == owner.key)
-        .map(|active| active.generation);
+        .filter(|active| active.owner_key == owner.key);
+    let active_generation = caller_active.map(|active| active.generation);
     let is_active = active_generation.is_some();
     let core = if is_active {
         Some(CORE_MANAGER.lock().await.status().await)
@@ -38,17 +42,80 @@ pub async fn service_status_snapshot(owner: &AuthenticatedOwner) -> Result<Servi
         service_state,
         core_pid,
         core_started_at: core.as_ref().and_then(|core| core.core_started_at),
+        core_adopted: core.as_ref().is_some_and(|core| core.adopted),
         last_core_exit_reason: core
             .as_ref()
             .and_then(|core| core.last_core_exit_reason.clone()),
+        last_crash_report: core
+            .as_ref()
+            .and_then(|core| core.last_crash_report.clone()),
         restart_count: core.as_ref().map_or(0, |core| core.restart_count),
         last_recovery_at: core.as_ref().and_then(|core| core.last_recovery_at),
+        core_resources: core.as_ref().and_then(|core| core.resources.clone()),
+        core_resource_history: core.map(|core| core.resource_history).unwrap_or_default(),
         desired_core_should_be_running: desired.core_should_be_running,
         desired_generation: desired.generation,
         desired_updated_at: desired.updated_at,
+        proxy_outcome: desired.last_proxy_outcome,
+        session_lease: caller_active.and_then(lease_status),
+        sessions: caller_active
+            .map(|active| {
+                active
+                    .sessions
+                    .iter()
+                    .map(|session| session.name.clone())
+                    .collect()
+            })
+            .unwrap_or_default(),
+    })
+}
+
+/// Status for an observer, taken from the active owner's side whoever the caller is.
+pub async fn observer_status_snapshot() -> Result<ObserverStatusSnapshot> {
+    let active_owner = load_active_owner().await?;
+    let Some(active) = active_owner else {
+        return Ok(ObserverStatusSnapshot {
+            service_state: service_lifecycle_state(),
+            owner_uid: None,
+            core_running: false,
+            core_started_at: None,
+            core_uptime_secs: None,
+            restart_count: 0,
+        });
+    };
+    let desired = load_owner_desired_state(&active.owner_key)
+        .await
+        .unwrap_or_default();
+    let core = CORE_MANAGER.lock().await.status().await;
+    let core_started_at = core.core_pid.and(core.core_started_at);
+
+    Ok(ObserverStatusSnapshot {
+        service_state: effective_service_state(
+            service_lifecycle_state(),
+            core_lifecycle_state(),
+            true,
+            desired.core_should_be_running,
+            core.core_pid,
+        ),
+        owner_uid: match active.identity {
+            OwnerIdentity::Unix { uid, .. } => Some(uid),
+            OwnerIdentity::Windows { .. } => None,
+        },
+        core_running: core.core_pid.is_some(),
+        core_started_at,
+        core_uptime_secs: core_started_at
+            .map(|started_at| unix_timestamp_secs().saturating_sub(started_at)),
+        restart_count: core.restart_count,
     })
 }
 
+fn unix_timestamp_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
 fn effective_service_state(
     reported: ServiceLifecycleState,
     core_reported: ServiceLifecycleState,
@@ -78,7 +145,7 @@ fn effective_service_state(
 
 #[cfg(test)]
 mod tests {
-    use super::{effective_service_state, service_status_snapshot};
+    use super::{effective_service_state, observer_status_snapshot, service_status_snapshot};
     use crate::core::auth::AuthenticatedOwner;
     use crate::core::desired::{clear_active_owner, commit_active_owner_session};
     use crate::{OwnerIdentity, ServiceLifecycleState};
@@ -131,7 +198,7 @@ mod tests {
     async fn inactive_owner_status_hides_active_core_details() -> anyhow::Result<()> {
         let active = owner(91_001);
         let inactive = owner(91_002);
-        let active_session = commit_active_owner_session(&active, &"66".repeat(32)).await?;
+        let active_session = commit_active_owner_session(&active, &"66".repeat(32), None).await?;
 
         let status = service_status_snapshot(&inactive).await?;
 
@@ -139,9 +206,13 @@ mod tests {
         assert_eq!(status.active_generation, None);
         assert_eq!(status.core_pid, None);
         assert_eq!(status.core_started_at, None);
+        assert!(!status.core_adopted);
         assert_eq!(status.last_core_exit_reason, None);
+        assert_eq!(status.last_crash_report, None);
         assert_eq!(status.restart_count, 0);
         assert_eq!(status.last_recovery_at, None);
+        assert_eq!(status.core_resources, None);
+        assert!(status.core_resource_history.is_empty());
         assert_eq!(
             service_status_snapshot(&active).await?.active_generation,
             Some(active_session.generation)
@@ -149,4 +220,20 @@ mod tests {
         clear_active_owner().await?;
         Ok(())
     }
+
+    #[tokio::test]
+    #[serial]
+    async fn observers_see_who_owns_the_service() -> anyhow::Result<()> {
+        clear_active_owner().await?;
+        let idle = observer_status_snapshot().await?;
+        assert_eq!(idle.owner_uid, None);
+        assert!(!idle.core_running);
+
+        commit_active_owner_session(&owner(91_003), &"67".repeat(32), None).await?;
+        let observed = observer_status_snapshot().await?;
+        assert_eq!(observed.owner_uid, Some(91_003));
+        assert_eq!(observed.core_uptime_secs.is_some(), observed.core_running);
+        clear_active_owner().await?;
+        Ok(())
+    }
 }
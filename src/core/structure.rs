@@ -2,10 +2,24 @@ use serde::{Deserialize, Serialize};
 #[cfg(feature = "client")]
 use serde_json::Value;
 use sha2::{Digest as _, Sha256};
+use strum_macros::AsRefStr;
 
 pub const OWNER_TOKEN_FILE_NAME: &str = ".clash-verge-service-owner-token";
 pub const SERVICE_PROTOCOL_HEADER: &str = "X-Clash-Verge-Service-Protocol";
 pub const SESSION_TOKEN_HEX_LEN: usize = 64;
+pub const HANDSHAKE_NONCE_HEX_LEN: usize = 64;
+/// Name of the session `StartClash` opens; other sessions are opened through `/session/open`.
+pub const PRIMARY_SESSION_NAME: &str = "primary";
+pub const MAX_SESSION_NAME_LEN: usize = 32;
+
+/// Session names are short lowercase labels such as `gui` or `tray-helper`.
+pub fn is_valid_session_name(name: &str) -> bool {
+    !name.is_empty()
+        && name.len() <= MAX_SESSION_NAME_LEN
+        && name
+            .bytes()
+            .all(|byte| matches!(byte, b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_'))
+}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ProtocolVersion {
@@ -34,22 +48,123 @@ impl ProtocolVersion {
     }
 }
 
+/// How the service was installed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceMode {
+    /// A privileged system service shared by every local user.
+    #[default]
+    System,
+    /// A rootless `systemd --user` service that only serves the user running it.
+    User,
+}
+
+/// A capability that depends on how the service was installed rather than on its revision.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ServiceFeature {
+    /// The core may create TUN devices, which needs the root privileges it inherits.
+    Tun,
+    /// A feature introduced by a newer service.
+    #[serde(other)]
+    Unknown,
+}
+
+/// A route or behaviour a service gains with a protocol revision; see [`ProtocolInfo::supports`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ServiceCapability {
+    /// `/clash/stage-runtime`.
+    RuntimeStaging,
+    /// `/clash/crash-reports` and `/clash/crash-report`.
+    CrashReports,
+    /// `/metrics`.
+    Metrics,
+    /// A shutdown that leaves the core to the replacement an installer asked for.
+    CoreHandoff,
+    /// `StartClashRequest::lease` and `/session/heartbeat`.
+    SessionLeases,
+    /// Several named sessions per owner, `/session/open` and `/session/revoke`.
+    NamedSessions,
+    /// Scoped sessions and session proofs on read routes.
+    ScopedSessions,
+    /// The observer policy and `/status/observe`.
+    ObserverStatus,
+    /// The admin policy and `/admin/evict`.
+    OwnerEviction,
+    /// The audit log and `/admin/audit`.
+    AuditLog,
+    /// The signed `/handshake`.
+    Handshake,
+}
+
+impl ServiceCapability {
+    pub const ALL: [Self; 11] = [
+        Self::RuntimeStaging,
+        Self::CrashReports,
+        Self::Metrics,
+        Self::CoreHandoff,
+        Self::SessionLeases,
+        Self::NamedSessions,
+        Self::ScopedSessions,
+        Self::ObserverStatus,
+        Self::OwnerEviction,
+        Self::AuditLog,
+        Self::Handshake,
+    ];
+
+    /// Revision that introduced the capability.
+    pub const fn min_service_revision(self) -> u16 {
+        match self {
+            Self::RuntimeStaging => crate::MIN_SERVICE_REVISION_FOR_RUNTIME_STAGING,
+            Self::CrashReports => crate::MIN_SERVICE_REVISION_FOR_CRASH_REPORTS,
+            Self::Metrics => crate::MIN_SERVICE_REVISION_FOR_METRICS,
+            Self::CoreHandoff => crate::MIN_SERVICE_REVISION_FOR_CORE_HANDOFF,
+            Self::SessionLeases => crate::MIN_SERVICE_REVISION_FOR_SESSION_LEASES,
+            Self::NamedSessions => crate::MIN_SERVICE_REVISION_FOR_NAMED_SESSIONS,
+            Self::ScopedSessions => crate::MIN_SERVICE_REVISION_FOR_SCOPED_SESSIONS,
+            Self::ObserverStatus => crate::MIN_SERVICE_REVISION_FOR_OBSERVER_STATUS,
+            Self::OwnerEviction => crate::MIN_SERVICE_REVISION_FOR_OWNER_EVICTION,
+            Self::AuditLog => crate::MIN_SERVICE_REVISION_FOR_AUDIT_LOG,
+            Self::Handshake => crate::MIN_SERVICE_REVISION_FOR_HANDSHAKE,
+        }
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct ProtocolInfo {
     pub build_version: String,
     pub protocol: ProtocolVersion,
     pub min_client_revision: u16,
+    #[serde(default)]
+    pub mode: ServiceMode,
+    /// Features this installation cannot provide; absent from services that predate the list.
+    #[serde(default)]
+    pub unavailable_features: Vec<ServiceFeature>,
 }
 
 impl ProtocolInfo {
     pub fn current() -> Self {
+        Self::for_mode(crate::core::paths::service_paths().mode())
+    }
+
+    pub fn for_mode(mode: ServiceMode) -> Self {
+        let unavailable_features = match mode {
+            ServiceMode::System => Vec::new(),
+            ServiceMode::User => vec![ServiceFeature::Tun],
+        };
         Self {
             build_version: crate::VERSION.to_owned(),
             protocol: ProtocolVersion::current(),
             min_client_revision: crate::MIN_SUPPORTED_CLIENT_REVISION,
+            mode,
+            unavailable_features,
         }
     }
 
+    pub fn supports_feature(&self, feature: ServiceFeature) -> bool {
+        !self.unavailable_features.contains(&feature)
+    }
+
     pub const fn supports_client(
         &self,
         client: ProtocolVersion,
@@ -60,14 +175,94 @@ impl ProtocolInfo {
             && client.revision >= self.min_client_revision
     }
 
+    /// The newest revision both this service and `client` speak, if the service accepts it.
+    pub fn negotiate(&self, client: ProtocolVersion) -> Option<ProtocolVersion> {
+        (self.protocol.epoch == client.epoch && client.revision >= self.min_client_revision).then(
+            || ProtocolVersion {
+                epoch: client.epoch,
+                revision: client.revision.min(self.protocol.revision),
+            },
+        )
+    }
+
+    /// Whether this service provides `capability`. Unlike `supports_client`, this is a
+    /// capability gate rather than a compatibility gate.
+    pub const fn supports(&self, capability: ServiceCapability) -> bool {
+        self.protocol.epoch == ProtocolVersion::current().epoch
+            && self.protocol.revision >= capability.min_service_revision()
+    }
+
     /// Whether this service supports in-place runtime staging.
-    /// Unlike `supports_client`, this is a capability gate rather than a compatibility gate.
     pub const fn supports_runtime_staging(&self) -> bool {
-        self.protocol.epoch == ProtocolVersion::current().epoch
-            && self.protocol.revision >= crate::MIN_SERVICE_REVISION_FOR_RUNTIME_STAGING
+        self.supports(ServiceCapability::RuntimeStaging)
     }
 }
 
+/// Opens a connection with the client's protocol and a fresh nonce the service must sign.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandshakeRequest {
+    /// [`HANDSHAKE_NONCE_HEX_LEN`] lowercase hexadecimal characters, never reused.
+    pub nonce: String,
+    pub protocol: ProtocolVersion,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HandshakeProof {
+    pub info: ProtocolInfo,
+    pub negotiated: ProtocolVersion,
+    /// Hex-encoded Ed25519 signature of [`handshake_transcript`] by the per-install key.
+    pub signature: String,
+}
+
+/// What the service signs to answer `nonce`. It binds the channel and both protocol versions, so a
+/// proof can be neither replayed to another client nor altered to downgrade the protocol.
+#[cfg(any(feature = "standalone", feature = "client"))]
+pub(crate) fn handshake_transcript(
+    service_slug: &str,
+    nonce: &str,
+    info: &ProtocolInfo,
+    negotiated: ProtocolVersion,
+) -> Vec<u8> {
+    let mut transcript = b"clash-verge-service handshake v1\0".to_vec();
+    for field in [service_slug, nonce, info.build_version.as_str()] {
+        transcript.extend_from_slice(field.as_bytes());
+        transcript.push(0);
+    }
+    for number in [
+        info.protocol.epoch,
+        info.protocol.revision,
+        info.min_client_revision,
+        negotiated.epoch,
+        negotiated.revision,
+    ] {
+        transcript.extend_from_slice(&number.to_be_bytes());
+    }
+    transcript.push(match info.mode {
+        ServiceMode::System => 0,
+        ServiceMode::User => 1,
+    });
+    transcript
+}
+
+/// Decodes exactly `N` bytes from lowercase hexadecimal, as handshake keys and proofs carry them.
+#[cfg(any(feature = "standalone", feature = "client"))]
+pub(crate) fn decode_hex<const N: usize>(text: &str) -> Option<[u8; N]> {
+    let digits = text.as_bytes();
+    if digits.len() != N * 2 {
+        return None;
+    }
+    let nibble = |digit: u8| match digit {
+        b'0'..=b'9' => Some(digit - b'0'),
+        b'a'..=b'f' => Some(digit - b'a' + 10),
+        _ => None,
+    };
+    let mut bytes = [0_u8; N];
+    for (byte, pair) in bytes.iter_mut().zip(digits.chunks_exact(2)) {
+        *byte = (nibble(pair[0])? << 4) | nibble(pair[1])?;
+    }
+    Some(bytes)
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum OwnerIdentity {
     Unix { uid: u32, gid: u32 },
@@ -125,8 +320,9 @@ pub struct RuntimeBundle {
     pub core_path: String,
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, AsRefStr)]
 #[serde(tag = "mode", rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
 pub enum MacosProxyConfig {
     Disabled,
     Global {
@@ -147,11 +343,45 @@ pub enum ProxyApplyOutcome {
     DirectFallback { message: String },
 }
 
+/// What the service does once an owner stops renewing its session lease.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LeaseExpiryPolicy {
+    /// Leave the core and proxy as they are; status still reports the lease as expired.
+    KeepRunning,
+    /// Put the system proxy back to direct and keep the core running.
+    ClearProxy,
+    /// Clear the proxy, stop the core and release ownership, as `StopClash` does.
+    StopCore,
+}
+
+/// A lease on the session `StartClash` opens. The client renews it through
+/// `/session/heartbeat` more often than every `ttl_secs`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionLease {
+    pub ttl_secs: u64,
+    pub on_expiry: LeaseExpiryPolicy,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionLeaseStatus {
+    pub ttl_secs: u64,
+    pub on_expiry: LeaseExpiryPolicy,
+    /// Unix time in seconds at which the lease runs out unless renewed.
+    pub expires_at: u64,
+    /// The lease ran out and `on_expiry` was applied; the next renewal starts it again.
+    pub expired: bool,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StartClashRequest {
     pub runtime: RuntimeBundle,
     pub proposed_session_token: String,
     pub macos_proxy: Option<MacosProxyConfig>,
+    /// Send only to services that support [`ServiceCapability::SessionLeases`]; older ones ignore
+    /// it and keep the session until it is replaced.
+    #[serde(default)]
+    pub lease: Option<SessionLease>,
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -159,6 +389,36 @@ pub struct OwnerSessionHandle {
     pub generation: u64,
 }
 
+/// Opens another session for the active owner's current generation. Reopening a name replaces
+/// that session's token.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OpenSessionRequest {
+    pub name: String,
+    pub proposed_session_token: String,
+}
+
+/// One right a scoped session may hold. A full session, opened by `StartClash` or
+/// `/session/open`, holds all of them and may also mint and revoke scoped sessions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, AsRefStr)]
+#[serde(rename_all = "snake_case")]
+#[strum(serialize_all = "snake_case")]
+pub enum SessionScope {
+    ReadStatus,
+    ReadLogs,
+    StageRuntime,
+    UpdateWriter,
+    SetSystemProxy,
+    StopCore,
+}
+
+/// Mints a session limited to `scopes` for the active generation, through a full session.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MintScopedSessionRequest {
+    pub name: String,
+    pub proposed_session_token: String,
+    pub scopes: Vec<SessionScope>,
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub struct StartClashResult {
     pub session: OwnerSessionHandle,
@@ -194,6 +454,34 @@ pub enum StageRejection {
     CoreRestarted,
 }
 
+/// A Go panic or fatal-error trace the service extracted from the core's stderr.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashReportInfo {
+    pub name: String,
+    pub size: u64,
+    pub created_at: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub name: String,
+    pub content: String,
+}
+
+/// Resource consumption of the core process at `sampled_at`.
+/// `cpu_time_ms` is cumulative; divide the delta between samples by their interval for a rate.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CoreResourceSample {
+    pub pid: u32,
+    pub sampled_at: u64,
+    pub rss_bytes: u64,
+    pub cpu_time_ms: u64,
+    /// `None` where the platform does not report the count cheaply.
+    pub threads: Option<u32>,
+    /// Open file descriptors, or open handles on Windows.
+    pub open_fds: Option<u32>,
+}
+
 #[repr(u16)]
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ServiceErrorCode {
@@ -208,6 +496,10 @@ pub enum ServiceErrorCode {
     InvalidProxyConfig = 1009,
     ProxyClearFailed = 1010,
     ProxyApplyFailed = 1011,
+    InvalidOwnerSession = 1012,
+    SessionScopeDenied = 1013,
+    ObserverDenied = 1014,
+    AdminDenied = 1015,
 }
 
 pub fn owner_key(identity: &OwnerIdentity) -> String {
@@ -270,12 +562,101 @@ pub struct ServiceStatusSnapshot {
     pub service_state: ServiceLifecycleState,
     pub core_pid: Option<u32>,
     pub core_started_at: Option<u64>,
+    /// The running core was started by a previous service instance and adopted on startup.
+    #[serde(default)]
+    pub core_adopted: bool,
     pub last_core_exit_reason: Option<String>,
+    /// Name of the crash report captured for the last exit, if the core left a trace.
+    #[serde(default)]
+    pub last_crash_report: Option<String>,
     pub restart_count: u32,
     pub last_recovery_at: Option<u64>,
+    #[serde(default)]
+    pub core_resources: Option<CoreResourceSample>,
+    /// Samples taken every 15 seconds over the last 10 minutes, oldest first.
+    #[serde(default)]
+    pub core_resource_history: Vec<CoreResourceSample>,
     pub desired_core_should_be_running: bool,
     pub desired_generation: u64,
     pub desired_updated_at: u64,
+    /// Outcome of the last proxy apply for this owner, including the one after a restore.
+    #[serde(default)]
+    pub proxy_outcome: Option<ProxyApplyOutcome>,
+    /// Lease of the caller's active session, when it has one.
+    #[serde(default)]
+    pub session_lease: Option<SessionLeaseStatus>,
+    /// Names of the sessions open for the caller's active generation.
+    #[serde(default)]
+    pub sessions: Vec<String>,
+}
+
+/// The status an observer may read: who holds the service and whether its core is healthy,
+/// without pids, paths, logs, resources or proxy settings.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ObserverStatusSnapshot {
+    pub service_state: ServiceLifecycleState,
+    /// Unix uid of the active owner; `None` while nobody owns the service.
+    pub owner_uid: Option<u32>,
+    pub core_running: bool,
+    pub core_started_at: Option<u64>,
+    pub core_uptime_secs: Option<u64>,
+    pub restart_count: u32,
+}
+
+/// Asks an admin route to take over for an owner whose app is gone: stop its core, clear its
+/// proxy and release ownership if it is active, then optionally forget everything kept for it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvictOwnerRequest {
+    /// `owner_key` of the owner to evict; on Unix, its uid in decimal.
+    pub owner_key: String,
+    /// Also remove the owner's desired state, runtime and logs.
+    pub purge_state: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EvictOwnerResult {
+    /// The owner was active and has been stopped.
+    pub evicted: bool,
+    /// The owner had persisted state and it has been removed.
+    pub purged: bool,
+}
+
+/// One request to a state-changing route, as the service's audit log recorded it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// Unix time in seconds.
+    pub at: u64,
+    pub route: String,
+    /// Kernel-reported uid of the connecting process.
+    pub peer_uid: Option<u32>,
+    /// Kernel-reported pid of the connecting process, where the IPC transport reports one.
+    pub peer_pid: Option<u32>,
+    /// Owner key of the authenticated caller; `None` when authentication failed.
+    pub caller: Option<String>,
+    /// Owner the request acted on, which is not the caller for admin routes.
+    pub owner_key: Option<String>,
+    /// Name of the session that authorized the request.
+    pub session: Option<String>,
+    /// Owner generation the request ran under, or the one it started.
+    pub generation: Option<u64>,
+    /// Envelope code of the response, `0` on success; `None` when no envelope was sent.
+    pub code: Option<u16>,
+    pub message: Option<String>,
+    /// SHA-256 of the runtime bundle a start or stage carried.
+    pub bundle_hash: Option<String>,
+    /// `mode` of the proxy configuration the request carried.
+    pub proxy_mode: Option<String>,
+}
+
+/// Selects audit records; every bound is optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditQuery {
+    /// Earliest `at`, inclusive.
+    pub since: Option<u64>,
+    /// Latest `at`, inclusive.
+    pub until: Option<u64>,
+    /// Only records whose caller or target is this owner.
+    pub owner_key: Option<String>,
 }
 
 #[cfg(feature = "response")]
@@ -286,17 +667,16 @@ pub struct Response<T> {
     pub data: Option<T>,
 }
 
+/// Resolved on each call from the paths this process uses, so it never fixes them early.
 impl Default for CoreConfig {
     fn default() -> Self {
-        let core_ipc_path = if cfg!(windows) {
-            format!(r"\\.\pipe\verge-mihomo-{}", crate::CHANNEL_IDENTITY.id)
-        } else if cfg!(feature = "test") {
-            "/tmp/clash-verge-service-ipc-test/mihomo.sock".to_string()
-        } else if cfg!(target_os = "macos") {
-            format!("/var/run/{}/users/0/verge-mihomo.sock", crate::SERVICE_SLUG)
-        } else {
-            format!("/run/{}/users/0/verge-mihomo.sock", crate::SERVICE_SLUG)
-        };
+        let paths = crate::service_paths();
+        #[cfg(windows)]
+        let core_ipc_path = format!(r"\\.\pipe\verge-mihomo-{}", paths.channel().id);
+        #[cfg(unix)]
+        let core_ipc_path = crate::core::paths::unix_mihomo_ipc_path(paths.runtime_dir(), 0)
+            .to_string_lossy()
+            .into_owned();
         Self {
             core_path: "./clash".to_string(),
             core_ipc_path,
@@ -328,8 +708,9 @@ impl<T> JsonConvert for T where T: Serialize + for<'de> Deserialize<'de> {}
 #[cfg(test)]
 mod tests {
     use super::{
-        MacosProxyConfig, OwnerIdentity, ProtocolInfo, ProtocolVersion, RuntimeBundle,
-        ServiceErrorCode, StartClashRequest, owner_key,
+        LeaseExpiryPolicy, MacosProxyConfig, OwnerIdentity, PRIMARY_SESSION_NAME, ProtocolInfo,
+        ProtocolVersion, RuntimeBundle, ServiceCapability, ServiceErrorCode, ServiceFeature,
+        ServiceMode, SessionLease, StartClashRequest, is_valid_session_name, owner_key,
     };
 
     #[test]
@@ -347,6 +728,7 @@ mod tests {
                 port: 7897,
                 bypass: "localhost".to_owned(),
             }),
+            lease: None,
         };
         let encoded = serde_json::to_vec(&request).expect("request should serialize");
         let decoded: StartClashRequest =
@@ -361,6 +743,10 @@ mod tests {
         assert_eq!(ServiceErrorCode::InvalidProxyConfig as u16, 1009);
         assert_eq!(ServiceErrorCode::ProxyClearFailed as u16, 1010);
         assert_eq!(ServiceErrorCode::ProxyApplyFailed as u16, 1011);
+        assert_eq!(ServiceErrorCode::InvalidOwnerSession as u16, 1012);
+        assert_eq!(ServiceErrorCode::SessionScopeDenied as u16, 1013);
+        assert_eq!(ServiceErrorCode::ObserverDenied as u16, 1014);
+        assert_eq!(ServiceErrorCode::AdminDenied as u16, 1015);
     }
 
     #[test]
@@ -404,6 +790,110 @@ mod tests {
         );
     }
 
+    #[test]
+    fn capabilities_are_gated_by_the_revision_that_introduced_them() {
+        for capability in ServiceCapability::ALL {
+            let mut older = ProtocolInfo::current();
+            older.protocol.revision = capability.min_service_revision() - 1;
+            assert!(!older.supports(capability), "{capability:?}");
+
+            older.protocol.revision += 1;
+            assert!(older.supports(capability), "{capability:?}");
+            assert!(
+                ProtocolInfo::current().supports(capability),
+                "{capability:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn negotiation_settles_on_the_older_revision_of_one_epoch() {
+        let service = ProtocolInfo::current();
+        let current = ProtocolVersion::current();
+        let older = ProtocolVersion {
+            revision: current.revision - 1,
+            ..current
+        };
+        let newer = ProtocolVersion {
+            revision: current.revision + 1,
+            ..current
+        };
+
+        assert_eq!(service.negotiate(older), Some(older));
+        assert_eq!(service.negotiate(newer), Some(current));
+        let other_epoch = ProtocolVersion {
+            epoch: current.epoch + 1,
+            ..current
+        };
+        assert_eq!(service.negotiate(other_epoch), None);
+    }
+
+    #[test]
+    fn session_names_are_short_lowercase_labels() {
+        for valid in [PRIMARY_SESSION_NAME, "gui", "tray-helper", "cli_2"] {
+            assert!(is_valid_session_name(valid), "{valid}");
+        }
+        let too_long = "a".repeat(33);
+        for invalid in ["", "GUI", "tray helper", "../gui", too_long.as_str()] {
+            assert!(!is_valid_session_name(invalid), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn requests_without_a_lease_still_decode() {
+        let mut request = serde_json::json!({
+            "runtime": {
+                "yaml": "mode: rule\n",
+                "assets": [],
+                "core_path": "/tmp/mihomo",
+            },
+            "proposed_session_token": "11".repeat(32),
+            "macos_proxy": null,
+        });
+        let decoded: StartClashRequest =
+            serde_json::from_value(request.clone()).expect("a revision 5 request should decode");
+        assert_eq!(decoded.lease, None);
+
+        request["lease"] = serde_json::json!({"ttl_secs": 30, "on_expiry": "clear_proxy"});
+        let decoded: StartClashRequest =
+            serde_json::from_value(request).expect("a leased request should decode");
+        assert_eq!(
+            decoded.lease,
+            Some(SessionLease {
+                ttl_secs: 30,
+                on_expiry: LeaseExpiryPolicy::ClearProxy,
+            })
+        );
+    }
+
+    #[test]
+    fn rootless_services_report_root_only_features_as_unavailable() {
+        assert!(ProtocolInfo::for_mode(ServiceMode::System).supports_feature(ServiceFeature::Tun));
+        assert!(!ProtocolInfo::for_mode(ServiceMode::User).supports_feature(ServiceFeature::Tun));
+
+        let mut legacy = serde_json::to_value(ProtocolInfo::for_mode(ServiceMode::User))
+            .expect("protocol info should serialize");
+        let fields = legacy.as_object_mut().expect("protocol info is an object");
+        fields.remove("mode");
+        fields.remove("unavailable_features");
+        let legacy: ProtocolInfo =
+            serde_json::from_value(legacy).expect("older services omit the mode");
+        assert_eq!(legacy.mode, ServiceMode::System);
+        assert!(legacy.supports_feature(ServiceFeature::Tun));
+
+        let newer: ProtocolInfo = serde_json::from_value(serde_json::json!({
+            "build_version": "9.9.9",
+            "protocol": { "epoch": 2, "revision": 99 },
+            "min_client_revision": 1,
+            "unavailable_features": ["tun", "something_new"],
+        }))
+        .expect("unknown features must not break older clients");
+        assert_eq!(
+            newer.unavailable_features,
+            [ServiceFeature::Tun, ServiceFeature::Unknown]
+        );
+    }
+
     #[test]
     fn protocol_compatibility_is_epoch_and_revision_based() {
         let info = ProtocolInfo::current();
@@ -11,6 +11,19 @@ pub(crate) fn ensure_private_service_directory(path: &Path) -> Result<()> {
     ensure_service_directory(path, 0o700)
 }
 
+/// The runtime directory holds the control socket. Every local user may reach a system service,
+/// while a rootless one has a single client and keeps the directory private.
+pub(crate) fn ensure_runtime_directory(path: &Path) -> Result<()> {
+    ensure_service_directory(path, runtime_directory_mode())
+}
+
+pub(crate) fn runtime_directory_mode() -> platform_lib::mode_t {
+    match crate::service_paths().mode() {
+        crate::ServiceMode::System => 0o755,
+        crate::ServiceMode::User => 0o700,
+    }
+}
+
 pub(crate) fn ensure_service_directory(path: &Path, mode: platform_lib::mode_t) -> Result<()> {
     match std::fs::create_dir(path) {
         Ok(()) => {}
@@ -39,6 +52,15 @@ pub(crate) fn ensure_service_directory(path: &Path, mode: platform_lib::mode_t)
 }
 
 pub(crate) fn secure_private_service_file_if_exists(path: &Path) -> Result<()> {
+    secure_service_file_if_exists(path, 0o600)
+}
+
+/// For files every client must read but only the service may write.
+pub(crate) fn secure_public_service_file_if_exists(path: &Path) -> Result<()> {
+    secure_service_file_if_exists(path, 0o644)
+}
+
+fn secure_service_file_if_exists(path: &Path, mode: platform_lib::mode_t) -> Result<()> {
     let path_c = std::ffi::CString::new(path.as_os_str().as_bytes())
         .map_err(|_| anyhow::anyhow!("service file path contains NUL"))?;
     let fd = unsafe {
@@ -55,11 +77,7 @@ pub(crate) fn secure_private_service_file_if_exists(path: &Path) -> Result<()> {
         return Err(error).with_context(|| format!("failed to open service file {path:?}"));
     }
     let mut stat = unsafe { std::mem::zeroed::<platform_lib::stat>() };
-    let expected_uid = if unsafe { platform_lib::geteuid() } == 0 {
-        0
-    } else {
-        unsafe { platform_lib::geteuid() }
-    };
+    let expected_uid = expected_owner_uid();
     let valid = unsafe { platform_lib::fstat(fd, &mut stat) } == 0
         && stat.st_uid == expected_uid
         && stat.st_mode & platform_lib::S_IFMT == platform_lib::S_IFREG;
@@ -68,7 +86,7 @@ pub(crate) fn secure_private_service_file_if_exists(path: &Path) -> Result<()> {
         bail!("service file {path:?} has an unexpected owner or file type");
     }
     let chown_ok = expected_uid != 0 || unsafe { platform_lib::fchown(fd, 0, 0) } == 0;
-    let chmod_ok = unsafe { platform_lib::fchmod(fd, 0o600 as platform_lib::mode_t) } == 0;
+    let chmod_ok = unsafe { platform_lib::fchmod(fd, mode) } == 0;
     let error = (!chown_ok || !chmod_ok).then(std::io::Error::last_os_error);
     unsafe { platform_lib::close(fd) };
     if let Some(error) = error {
@@ -77,6 +95,11 @@ pub(crate) fn secure_private_service_file_if_exists(path: &Path) -> Result<()> {
     Ok(())
 }
 
+/// Service files and directories belong to root, or to the service user when running rootless.
+pub(crate) fn expected_owner_uid() -> u32 {
+    unsafe { platform_lib::geteuid() }
+}
+
 fn secure_open_directory(
     fd: std::os::fd::RawFd,
     path: &Path,
@@ -87,11 +110,7 @@ fn secure_open_directory(
         return Err(std::io::Error::last_os_error())
             .with_context(|| format!("failed to inspect service directory {path:?}"));
     }
-    let expected_uid = if unsafe { platform_lib::geteuid() } == 0 {
-        0
-    } else {
-        unsafe { platform_lib::geteuid() }
-    };
+    let expected_uid = expected_owner_uid();
     if stat.st_uid != expected_uid || stat.st_mode & platform_lib::S_IFMT != platform_lib::S_IFDIR {
         bail!("service directory {path:?} has an unexpected owner or file type");
     }
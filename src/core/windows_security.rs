@@ -36,6 +36,10 @@ const PRIVATE_INSTALLER_DIRECTORY_SDDL: &str = "O:BAD:P(A;OICI;FA;;;SY)(A;OICI;F
 const PRIVATE_SERVICE_FILE_SDDL: &str = "O:SYD:P(A;;FA;;;SY)(A;;FA;;;BA)";
 #[cfg(feature = "test")]
 const PRIVATE_SERVICE_FILE_SDDL: &str = "D:P(A;;FA;;;OW)(A;;FA;;;SY)(A;;FA;;;BA)";
+#[cfg(not(feature = "test"))]
+const PUBLIC_SERVICE_FILE_SDDL: &str = "O:SYD:P(A;;FA;;;SY)(A;;FA;;;BA)(A;;FR;;;BU)";
+#[cfg(feature = "test")]
+const PUBLIC_SERVICE_FILE_SDDL: &str = "D:P(A;;FA;;;OW)(A;;FA;;;SY)(A;;FA;;;BA)(A;;FR;;;BU)";
 
 pub(crate) fn ensure_private_service_directory(path: &Path) -> Result<()> {
     ensure_private_directory(path, PRIVATE_SERVICE_DIRECTORY_SDDL, true)
@@ -120,7 +124,17 @@ pub(crate) fn secure_private_directory(path: &Path) -> Result<()> {
 }
 
 pub(crate) fn secure_private_service_file_if_exists(path: &Path) -> Result<()> {
-    let descriptor = LocalDescriptor::from_sddl(PRIVATE_SERVICE_FILE_SDDL)?;
+    secure_service_file_if_exists(path, PRIVATE_SERVICE_FILE_SDDL)
+}
+
+/// Lets local users read a file by its full path; they still cannot list or change the private
+/// directory holding it.
+pub(crate) fn secure_public_service_file_if_exists(path: &Path) -> Result<()> {
+    secure_service_file_if_exists(path, PUBLIC_SERVICE_FILE_SDDL)
+}
+
+fn secure_service_file_if_exists(path: &Path, sddl: &str) -> Result<()> {
+    let descriptor = LocalDescriptor::from_sddl(sddl)?;
     let wide = wide_path(path)?;
     let handle = unsafe {
         CreateFileW(
@@ -137,7 +151,7 @@ pub(crate) fn secure_private_service_file_if_exists(path: &Path) -> Result<()> {
         return match unsafe { GetLastError() } {
             ERROR_FILE_NOT_FOUND | ERROR_PATH_NOT_FOUND => Ok(()),
             _ => Err(std::io::Error::last_os_error())
-                .with_context(|| format!("failed to open private service file {path:?}")),
+                .with_context(|| format!("failed to open service file {path:?}")),
         };
     }
     let handle = OwnedHandle(handle);
@@ -5,27 +5,40 @@ mod core;
 mod client;
 
 pub use channel::{
-    CHANNEL_IDENTITY, ChannelIdentity, MACOS_APP_BUNDLE_ID, MACOS_SERVICE_ID, SERVICE_DISPLAY_NAME,
-    SERVICE_SLUG, WINDOWS_SERVICE_NAME,
+    CHANNEL_IDENTITY, ChannelIdentity, DEVELOPMENT_CHANNEL, MACOS_APP_BUNDLE_ID, MACOS_SERVICE_ID,
+    PRODUCTION_CHANNEL, SERVICE_DISPLAY_NAME, SERVICE_SLUG, WINDOWS_SERVICE_NAME,
+};
+#[cfg(feature = "response")]
+pub use core::Response;
+#[cfg(feature = "test")]
+pub use core::SERVICE_ROOT_ENV;
+pub use core::{
+    AuditQuery, AuditRecord, AuthenticatedRequest, AuthenticatedSessionRequest, ClashConfig,
+    CoreConfig, CoreResourceSample, CrashReport, CrashReportInfo, EvictOwnerRequest,
+    EvictOwnerResult, HANDSHAKE_NONCE_HEX_LEN, HandshakeProof, HandshakeRequest, IpcCommand,
+    LeaseExpiryPolicy, MAX_SESSION_NAME_LEN, MacosProxyConfig, MintScopedSessionRequest,
+    OWNER_TOKEN_FILE_NAME, ObserverStatusSnapshot, OpenSessionRequest, OwnerCredentials,
+    OwnerIdentity, OwnerSessionHandle, OwnerSessionProof, PRIMARY_SESSION_NAME, ProtocolInfo,
+    ProtocolVersion, ProxyApplyOutcome, RemoteProvider, RuntimeAsset, RuntimeBundle,
+    SERVICE_PROTOCOL_HEADER, SESSION_TOKEN_HEX_LEN, ServiceCapability, ServiceErrorCode,
+    ServiceFeature, ServiceLifecycleState, ServiceMode, ServiceStatusSnapshot, SessionLease,
+    SessionLeaseStatus, SessionScope, StageRejection, StageRuntimeOutcome, StartClashRequest,
+    StartClashResult, WriterConfig, is_valid_session_name, mihomo_ipc_path, owner_key,
 };
 pub use core::{
-    AuthenticatedRequest, AuthenticatedSessionRequest, ClashConfig, CoreConfig, IpcCommand,
-    MacosProxyConfig, OWNER_TOKEN_FILE_NAME, OwnerCredentials, OwnerIdentity, OwnerSessionHandle,
-    OwnerSessionProof, ProtocolInfo, ProtocolVersion, ProxyApplyOutcome, RemoteProvider,
-    RuntimeAsset, RuntimeBundle, SERVICE_PROTOCOL_HEADER, SESSION_TOKEN_HEX_LEN, ServiceErrorCode,
-    ServiceLifecycleState, ServiceStatusSnapshot, StageRejection, StageRuntimeOutcome,
-    StartClashRequest, StartClashResult, WriterConfig, mihomo_ipc_path, owner_key,
+    OwnerPaths, ServicePaths, rooted_service_paths, service_paths, service_paths_for,
+    set_service_paths,
 };
-pub use core::{OwnerPaths, ServicePaths, service_paths};
 
 #[cfg(feature = "standalone")]
 pub use core::{
-    ActiveOwnerState, DesiredState, REPAIR_IN_PROGRESS_EXIT_CODE, ServiceOwnerGuard,
-    ServiceRepairGate, acquire_service_owner, acquire_service_repair_gate,
-    cleanup_stale_owner_state, load_active_owner, load_owner_desired_state,
-    prepare_service_install_directory, reconcile_service_startup, restore_desired_state,
-    run_ipc_server, run_ipc_supervisor_until_shutdown, service_lifecycle_state,
-    set_service_lifecycle_state, stop_ipc_server,
+    ActiveOwnerState, DesiredState, DoctorFinding, DoctorReport, DoctorSeverity,
+    OwnerSessionRecord, REPAIR_IN_PROGRESS_EXIT_CODE, ServiceOwnerGuard, ServiceRepairGate,
+    acquire_service_owner, acquire_service_repair_gate, cancel_core_handoff,
+    cleanup_stale_owner_state, diagnose_service, load_active_owner, load_owner_desired_state,
+    prepare_service_install_directory, reconcile_service_startup, request_core_handoff,
+    restore_desired_state, run_ipc_server, run_ipc_supervisor_until_shutdown,
+    service_lifecycle_state, set_service_lifecycle_state, stop_ipc_server, withdraw_handshake_key,
 };
 
 #[cfg(feature = "test")]
@@ -38,6 +51,8 @@ pub use core::{CoreWatchdogTestConfig, set_core_watchdog_config_for_tests};
 #[cfg(feature = "client")]
 pub use client::*;
 
+/// Control socket of the compiled-in channel's system service.
+/// `service_paths()` follows the selected paths, which carry their mode, instead.
 #[cfg(all(
     target_os = "macos",
     not(feature = "test"),
@@ -74,14 +89,37 @@ pub static IPC_PATH: &str = "/tmp/clash-verge-service-ipc-test/service.sock";
 #[cfg(all(feature = "test", windows))]
 pub static IPC_PATH: &str = r"\\.\pipe\clash-verge-service-test";
 
+/// Legacy `/magic` header value, which proves nothing since every build embeds it. Clients only send
+/// it to services that predate the handshake, and services still accept it from older clients;
+/// both sides can drop it once `MIN_SUPPORTED_CLIENT_REVISION` reaches the handshake revision.
 #[cfg(any(feature = "standalone", feature = "client"))]
 pub static IPC_AUTH_EXPECT: &str = r#"A thing of beauty is a joy for ever. Its loveliness increases; it will never pass into nothingness."#;
 
 pub static VERSION: &str = env!("CARGO_PKG_VERSION");
 pub const PROTOCOL_EPOCH: u16 = 2;
-pub const PROTOCOL_REVISION: u16 = 2;
+pub const PROTOCOL_REVISION: u16 = 12;
 pub const MIN_SUPPORTED_CLIENT_REVISION: u16 = 1;
 pub const MIN_REQUIRED_SERVICE_REVISION: u16 = 1;
 /// Revision that introduced `/clash/stage-runtime`.
 /// This is a capability gate, not the minimum compatible service revision.
 pub const MIN_SERVICE_REVISION_FOR_RUNTIME_STAGING: u16 = 2;
+/// Revision that introduced `/clash/crash-reports` and `/clash/crash-report`.
+pub const MIN_SERVICE_REVISION_FOR_CRASH_REPORTS: u16 = 3;
+/// Revision that introduced `/metrics`.
+pub const MIN_SERVICE_REVISION_FOR_METRICS: u16 = 4;
+/// Revision whose shutdown honours an installer's core handoff request.
+pub const MIN_SERVICE_REVISION_FOR_CORE_HANDOFF: u16 = 5;
+/// Revision that introduced session leases and `/session/heartbeat`.
+pub const MIN_SERVICE_REVISION_FOR_SESSION_LEASES: u16 = 6;
+/// Revision that introduced named sessions, `/session/open` and `/session/revoke`.
+pub const MIN_SERVICE_REVISION_FOR_NAMED_SESSIONS: u16 = 7;
+/// Revision that introduced scoped sessions and session proofs on read routes.
+pub const MIN_SERVICE_REVISION_FOR_SCOPED_SESSIONS: u16 = 8;
+/// Revision that introduced the observer policy and `/status/observe`.
+pub const MIN_SERVICE_REVISION_FOR_OBSERVER_STATUS: u16 = 9;
+/// Revision that introduced the admin policy and `/admin/evict`.
+pub const MIN_SERVICE_REVISION_FOR_OWNER_EVICTION: u16 = 10;
+/// Revision that introduced the audit log and `/admin/audit`.
+pub const MIN_SERVICE_REVISION_FOR_AUDIT_LOG: u16 = 11;
+/// Revision that introduced the signed `/handshake`.
+pub const MIN_SERVICE_REVISION_FOR_HANDSHAKE: u16 = 12;
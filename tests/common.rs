@@ -1,4 +1,6 @@
-use clash_verge_service_ipc::{OwnerCredentials, test_owner_credentials};
+use clash_verge_service_ipc::{OwnerCredentials, ServicePaths, test_owner_credentials};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 // Each integration-test binary uses a different subset of these helpers.
 #[allow(dead_code)]
@@ -19,12 +21,60 @@ pub fn test_bin_path(name: &str) -> std::path::PathBuf {
     path
 }
 
-/// Waits for the asynchronously started IPC listener to become ready.
+/// A service root owned by one test. Dropping it removes the directory with everything the
+/// service left there.
 #[allow(dead_code)]
-pub async fn wait_for_ipc() -> anyhow::Result<()> {
+pub struct ServiceRoot {
+    pub paths: ServicePaths,
+    dir: PathBuf,
+}
+
+#[allow(dead_code)]
+impl ServiceRoot {
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl Drop for ServiceRoot {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Gives one test its own service root, so it shares no socket or state with other tests or runs,
+/// and selects it for the service side of this process. `/tmp` keeps Unix socket paths short
+/// where the platform temp directory is deeply nested.
+#[allow(dead_code)]
+pub fn isolate_service_root() -> ServiceRoot {
+    static NEXT_ROOT: AtomicUsize = AtomicUsize::new(0);
+    let base = if cfg!(unix) {
+        PathBuf::from("/tmp")
+    } else {
+        std::env::temp_dir()
+    };
+    let dir = base.join(format!(
+        "clash-verge-service-root-{}-{}",
+        std::process::id(),
+        NEXT_ROOT.fetch_add(1, Ordering::Relaxed)
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).expect("test service root should be creatable");
+    let paths = clash_verge_service_ipc::rooted_service_paths(
+        &clash_verge_service_ipc::CHANNEL_IDENTITY,
+        clash_verge_service_ipc::ServiceMode::System,
+        &dir,
+    );
+    clash_verge_service_ipc::set_service_paths(paths.clone());
+    ServiceRoot { paths, dir }
+}
+
+/// Waits for the asynchronously started IPC listener at `paths` to become ready.
+#[allow(dead_code)]
+pub async fn wait_for_ipc(paths: &ServicePaths) -> anyhow::Result<()> {
     let deadline = std::time::Instant::now() + std::time::Duration::from_secs(5);
     while std::time::Instant::now() < deadline {
-        if clash_verge_service_ipc::connect().await.is_ok() {
+        if clash_verge_service_ipc::connect_at(paths).await.is_ok() {
             return Ok(());
         }
         tokio::time::sleep(std::time::Duration::from_millis(25)).await;
@@ -4,9 +4,11 @@ mod common;
 
 use anyhow::{Context as _, Result};
 use clash_verge_service_ipc::{
-    IpcCommand, OwnerCredentials, OwnerSessionProof, RuntimeBundle, ServiceErrorCode,
-    StartClashRequest, StartClashResult, connect, get_status, run_ipc_server, start_clash,
-    stop_clash, stop_ipc_server,
+    EvictOwnerRequest, IpcCommand, MintScopedSessionRequest, OpenSessionRequest, OwnerCredentials,
+    OwnerSessionProof, PRIMARY_SESSION_NAME, RuntimeBundle, ServiceErrorCode, ServicePaths,
+    SessionScope, StartClashRequest, StartClashResult, connect_at, evict_owner_at, get_status_at,
+    mint_scoped_session_at, open_session_at, revoke_session_at, run_ipc_server, start_clash_at,
+    stop_clash_at, stop_ipc_server,
 };
 use serde::Deserialize;
 use serial_test::serial;
@@ -28,15 +30,18 @@ fn runtime_bundle() -> RuntimeBundle {
 }
 
 async fn start(
+    paths: &ServicePaths,
     credentials: &OwnerCredentials,
     token: &str,
 ) -> Result<(StartClashResult, OwnerSessionProof)> {
-    let response = start_clash(
+    let response = start_clash_at(
+        paths,
         credentials,
         &StartClashRequest {
             runtime: runtime_bundle(),
             proposed_session_token: token.to_owned(),
             macos_proxy: None,
+            lease: None,
         },
     )
     .await?;
@@ -49,11 +54,15 @@ async fn start(
     Ok((result, session))
 }
 
-async fn start_server() -> Result<tokio::task::JoinHandle<kode_bridge::Result<()>>> {
+async fn start_server() -> Result<(
+    common::ServiceRoot,
+    tokio::task::JoinHandle<kode_bridge::Result<()>>,
+)> {
+    let root = common::isolate_service_root();
     let _ = stop_ipc_server().await;
-    let server = run_ipc_server().await?;
-    common::wait_for_ipc().await?;
-    Ok(server)
+    let server = run_ipc_server(&root.paths).await?;
+    common::wait_for_ipc(&root.paths).await?;
+    Ok((root, server))
 }
 
 async fn stop_server(server: tokio::task::JoinHandle<kode_bridge::Result<()>>) -> Result<()> {
@@ -65,8 +74,9 @@ async fn stop_server(server: tokio::task::JoinHandle<kode_bridge::Result<()>>) -
 #[tokio::test]
 #[serial]
 async fn protocol_mismatch_is_rejected_before_payload_deserialization() -> Result<()> {
-    let server = start_server().await?;
-    let response = connect()
+    let (root, server) = start_server().await?;
+    let paths = &root.paths;
+    let response = connect_at(paths)
         .await?
         .post(IpcCommand::StartClash.as_ref())
         .json_body(&serde_json::Value::String("invalid request".to_owned()))
@@ -81,24 +91,106 @@ async fn protocol_mismatch_is_rejected_before_payload_deserialization() -> Resul
 #[tokio::test]
 #[serial]
 async fn restarting_an_owner_invalidates_the_previous_session() -> Result<()> {
-    let server = start_server().await?;
+    let (root, server) = start_server().await?;
+    let paths = &root.paths;
     let credentials = common::owner_credentials();
 
-    let (first, first_session) = start(&credentials, &"11".repeat(32)).await?;
-    let (second, second_session) = start(&credentials, &"22".repeat(32)).await?;
+    let (first, first_session) = start(paths, &credentials, &"11".repeat(32)).await?;
+    let (second, second_session) = start(paths, &credentials, &"22".repeat(32)).await?;
 
     assert!(second.session.generation > first.session.generation);
     assert_eq!(
-        stop_clash(&credentials, &first_session).await?.code,
+        stop_clash_at(paths, &credentials, &first_session)
+            .await?
+            .code,
         ServiceErrorCode::StaleOwnerSession as u16
     );
-    let status = get_status(&credentials)
+    let status = get_status_at(paths, &credentials)
         .await?
         .data
         .context("status omitted data")?;
     assert!(status.is_active);
     assert!(status.core_pid.is_some());
-    assert_eq!(stop_clash(&credentials, &second_session).await?.code, 0);
+    assert_eq!(
+        stop_clash_at(paths, &credentials, &second_session)
+            .await?
+            .code,
+        0
+    );
+
+    stop_server(server).await
+}
+
+#[tokio::test]
+#[serial]
+async fn only_a_full_session_opens_another_session() -> Result<()> {
+    let (root, server) = start_server().await?;
+    let paths = &root.paths;
+    let credentials = common::owner_credentials();
+    let (_, full) = start(paths, &credentials, &"55".repeat(32)).await?;
+    let scoped = OwnerSessionProof {
+        generation: full.generation,
+        token: "66".repeat(32),
+    };
+    let minted = mint_scoped_session_at(
+        paths,
+        &credentials,
+        &full,
+        &MintScopedSessionRequest {
+            name: "widget".to_owned(),
+            proposed_session_token: scoped.token.clone(),
+            scopes: vec![SessionScope::ReadStatus],
+        },
+    )
+    .await?;
+    assert_eq!(minted.code, 0, "{}", minted.message);
+    let open = |token: &str| OpenSessionRequest {
+        name: "tray".to_owned(),
+        proposed_session_token: token.repeat(32),
+    };
+
+    assert_eq!(
+        open_session_at(paths, &credentials, &scoped, &open("77"))
+            .await?
+            .code,
+        ServiceErrorCode::SessionScopeDenied as u16
+    );
+    assert_eq!(
+        open_session_at(paths, &credentials, &full, &open("88"))
+            .await?
+            .code,
+        0
+    );
+    assert_eq!(stop_clash_at(paths, &credentials, &full).await?.code, 0);
+
+    stop_server(server).await
+}
+
+#[tokio::test]
+#[serial]
+async fn owner_credentials_do_not_end_the_primary_session() -> Result<()> {
+    let (root, server) = start_server().await?;
+    let paths = &root.paths;
+    let credentials = common::owner_credentials();
+    let (_, primary) = start(paths, &credentials, &"55".repeat(32)).await?;
+
+    assert_eq!(
+        revoke_session_at(paths, &credentials, PRIMARY_SESSION_NAME)
+            .await?
+            .code,
+        ServiceErrorCode::InvalidOwnerSession as u16
+    );
+    let reopen = OpenSessionRequest {
+        name: PRIMARY_SESSION_NAME.to_owned(),
+        proposed_session_token: "99".repeat(32),
+    };
+    assert_eq!(
+        open_session_at(paths, &credentials, &primary, &reopen)
+            .await?
+            .code,
+        ServiceErrorCode::InvalidOwnerSession as u16
+    );
+    assert_eq!(stop_clash_at(paths, &credentials, &primary).await?.code, 0);
 
     stop_server(server).await
 }
@@ -107,39 +199,79 @@ async fn restarting_an_owner_invalidates_the_previous_session() -> Result<()> {
 #[tokio::test]
 #[serial]
 async fn a_new_owner_takes_over_and_the_previous_owner_becomes_inactive() -> Result<()> {
-    let server = start_server().await?;
-    let root = std::env::temp_dir();
+    let (root, server) = start_server().await?;
+    let paths = &root.paths;
+    let app_data_root = std::env::temp_dir();
     let owner_a = clash_verge_service_ipc::test_owner_credentials_for_uid(
-        &root.join(format!("service-ipc-owner-a-{}", std::process::id())),
+        &app_data_root.join(format!("service-ipc-owner-a-{}", std::process::id())),
         91_001,
     )?;
     let owner_b = clash_verge_service_ipc::test_owner_credentials_for_uid(
-        &root.join(format!("service-ipc-owner-b-{}", std::process::id())),
+        &app_data_root.join(format!("service-ipc-owner-b-{}", std::process::id())),
         91_002,
     )?;
 
-    let (_, session_a) = start(&owner_a, &"33".repeat(32)).await?;
-    let (_, session_b) = start(&owner_b, &"44".repeat(32)).await?;
+    let (_, session_a) = start(paths, &owner_a, &"33".repeat(32)).await?;
+    let (_, session_b) = start(paths, &owner_b, &"44".repeat(32)).await?;
 
     assert!(
-        !get_status(&owner_a)
+        !get_status_at(paths, &owner_a)
             .await?
             .data
             .context("no status")?
             .is_active
     );
     assert!(
-        get_status(&owner_b)
+        get_status_at(paths, &owner_b)
             .await?
             .data
             .context("no status")?
             .is_active
     );
     assert_eq!(
-        stop_clash(&owner_a, &session_a).await?.code,
+        stop_clash_at(paths, &owner_a, &session_a).await?.code,
         ServiceErrorCode::StaleOwnerSession as u16
     );
-    assert_eq!(stop_clash(&owner_b, &session_b).await?.code, 0);
+    assert_eq!(stop_clash_at(paths, &owner_b, &session_b).await?.code, 0);
+
+    stop_server(server).await
+}
+
+#[cfg(unix)]
+#[tokio::test]
+#[serial]
+async fn an_owner_outside_the_admin_policy_cannot_evict() -> Result<()> {
+    let (root, server) = start_server().await?;
+    let paths = &root.paths;
+    let app_data_root = std::env::temp_dir();
+    let intruder = clash_verge_service_ipc::test_owner_credentials_for_uid(
+        &app_data_root.join(format!("service-ipc-intruder-{}", std::process::id())),
+        91_003,
+    )?;
+    let owner = clash_verge_service_ipc::test_owner_credentials_for_uid(
+        &app_data_root.join(format!("service-ipc-evicted-{}", std::process::id())),
+        91_004,
+    )?;
+    let (_, session) = start(paths, &owner, &"66".repeat(32)).await?;
+
+    let refused = evict_owner_at(
+        paths,
+        &intruder,
+        &EvictOwnerRequest {
+            owner_key: "91004".to_owned(),
+            purge_state: true,
+        },
+    )
+    .await?;
+    assert_eq!(refused.code, ServiceErrorCode::AdminDenied as u16);
+    assert!(
+        get_status_at(paths, &owner)
+            .await?
+            .data
+            .context("no status")?
+            .is_active
+    );
+    assert_eq!(stop_clash_at(paths, &owner, &session).await?.code, 0);
 
     stop_server(server).await
 }
@@ -5,9 +5,13 @@ mod common;
 use anyhow::{Context as _, Result};
 use clash_verge_service_ipc::{
     CoreWatchdogTestConfig, OwnerSessionProof, RuntimeBundle, ServiceLifecycleState,
-    StartClashRequest, connect, get_status, run_ipc_server, run_ipc_supervisor_until_shutdown,
-    service_lifecycle_state, set_core_watchdog_config_for_tests, start_clash, stop_clash,
-    stop_ipc_server,
+    StartClashRequest, connect_at, get_status_at, run_ipc_server,
+    run_ipc_supervisor_until_shutdown, service_lifecycle_state, set_core_watchdog_config_for_tests,
+    start_clash_at, stop_clash_at, stop_ipc_server,
+};
+#[cfg(target_os = "linux")]
+use clash_verge_service_ipc::{
+    ServiceStatusSnapshot, reconcile_service_startup, request_core_handoff,
 };
 use serial_test::serial;
 use std::time::{Duration, Instant};
@@ -27,18 +31,27 @@ async fn wait_until(label: &str, mut condition: impl AsyncFnMut() -> bool) -> Re
 #[tokio::test]
 #[serial]
 async fn ipc_supervisor_restarts_a_stopped_listener() -> Result<()> {
+    let root = common::isolate_service_root();
     let _ = stop_ipc_server().await;
     let (shutdown_tx, shutdown_rx) = oneshot::channel();
-    let supervisor = tokio::spawn(run_ipc_supervisor_until_shutdown(async {
-        let _ = shutdown_rx.await;
-    }));
+    let paths = root.paths.clone();
+    let supervisor = tokio::spawn(async move {
+        run_ipc_supervisor_until_shutdown(&paths, async {
+            let _ = shutdown_rx.await;
+        })
+        .await
+    });
 
     wait_until("IPC startup", async || {
-        connect().await.is_ok() && service_lifecycle_state() == ServiceLifecycleState::Running
+        connect_at(&root.paths).await.is_ok()
+            && service_lifecycle_state() == ServiceLifecycleState::Running
     })
     .await?;
     stop_ipc_server().await?;
-    wait_until("IPC restart", async || connect().await.is_ok()).await?;
+    wait_until("IPC restart", async || {
+        connect_at(&root.paths).await.is_ok()
+    })
+    .await?;
 
     let _ = shutdown_tx.send(());
     supervisor.await??;
@@ -49,21 +62,24 @@ async fn ipc_supervisor_restarts_a_stopped_listener() -> Result<()> {
 #[tokio::test]
 #[serial]
 async fn a_healthy_service_owner_prevents_a_second_instance() -> Result<()> {
+    let root = common::isolate_service_root();
     let _ = stop_ipc_server().await;
     let owner = clash_verge_service_ipc::acquire_service_owner()
         .await?
         .context("current process did not acquire the service owner lock")?;
-    let server = run_ipc_server().await?;
-    common::wait_for_ipc().await?;
+    let server = run_ipc_server(&root.paths).await?;
+    common::wait_for_ipc(&root.paths).await?;
 
     let status = tokio::time::timeout(
         Duration::from_secs(5),
-        tokio::process::Command::new(common::test_bin_path("owner_lock_holder")).status(),
+        tokio::process::Command::new(common::test_bin_path("owner_lock_holder"))
+            .env(clash_verge_service_ipc::SERVICE_ROOT_ENV, root.dir())
+            .status(),
     )
     .await
     .context("second owner did not exit")??;
     assert_eq!(status.code(), Some(2));
-    assert!(connect().await.is_ok());
+    assert!(connect_at(&root.paths).await.is_ok());
 
     stop_ipc_server().await?;
     server.await??;
@@ -71,6 +87,80 @@ async fn a_healthy_service_owner_prevents_a_second_instance() -> Result<()> {
     Ok(())
 }
 
+#[cfg(target_os = "linux")]
+#[tokio::test]
+#[serial]
+async fn a_requested_handoff_leaves_the_core_to_the_next_service() -> Result<()> {
+    let root = common::isolate_service_root();
+    let _ = stop_ipc_server().await;
+    let server = run_ipc_server(&root.paths).await?;
+    common::wait_for_ipc(&root.paths).await?;
+    let credentials = common::owner_credentials();
+    let token = "51".repeat(32);
+    let response = start_clash_at(
+        &root.paths,
+        &credentials,
+        &StartClashRequest {
+            runtime: RuntimeBundle {
+                yaml: "mode: rule\n".to_owned(),
+                assets: Vec::new(),
+                remote_providers: Vec::new(),
+                core_path: common::test_bin_path("mock_binary")
+                    .to_string_lossy()
+                    .into_owned(),
+            },
+            proposed_session_token: token.clone(),
+            macos_proxy: None,
+            lease: None,
+        },
+    )
+    .await?;
+    anyhow::ensure!(response.code == 0, "{}", response.message);
+    let session = OwnerSessionProof {
+        generation: response
+            .data
+            .context("start omitted its result")?
+            .session
+            .generation,
+        token,
+    };
+    let core_pid =
+        |status: Option<ServiceStatusSnapshot>| status.and_then(|status| status.core_pid);
+    let pid = core_pid(get_status_at(&root.paths, &credentials).await?.data)
+        .context("core is not running")?;
+
+    request_core_handoff()?;
+    stop_ipc_server().await?;
+    server.await??;
+    assert!(
+        !root.paths.core_handoff_path().exists(),
+        "shutdown must consume the request"
+    );
+
+    reconcile_service_startup().await?;
+    let server = run_ipc_server(&root.paths).await?;
+    common::wait_for_ipc(&root.paths).await?;
+    assert_eq!(
+        core_pid(get_status_at(&root.paths, &credentials).await?.data),
+        Some(pid)
+    );
+
+    // The adopted core answers to the owner's session like one this instance started.
+    assert_eq!(
+        stop_clash_at(&root.paths, &credentials, &session)
+            .await?
+            .code,
+        0
+    );
+    assert_eq!(
+        core_pid(get_status_at(&root.paths, &credentials).await?.data),
+        None
+    );
+    stop_ipc_server().await?;
+    server.await??;
+    Ok(())
+}
+
 #[tokio::test]
 #[serial]
 async fn core_watchdog_stops_a_bounded_crash_loop() -> Result<()> {
@@ -81,6 +171,7 @@ async fn core_watchdog_stops_a_bounded_crash_loop() -> Result<()> {
         }
     }
 
+    let root = common::isolate_service_root();
     let _reset = ResetWatchdog;
     set_core_watchdog_config_for_tests(Some(CoreWatchdogTestConfig {
         max_restarts: 2,
@@ -88,11 +179,12 @@ async fn core_watchdog_stops_a_bounded_crash_loop() -> Result<()> {
         max_backoff: Duration::ZERO,
     }));
     let _ = stop_ipc_server().await;
-    let server = run_ipc_server().await?;
-    common::wait_for_ipc().await?;
+    let server = run_ipc_server(&root.paths).await?;
+    common::wait_for_ipc(&root.paths).await?;
     let credentials = common::owner_credentials();
     let token = "41".repeat(32);
-    let response = start_clash(
+    let response = start_clash_at(
+        &root.paths,
         &credentials,
         &StartClashRequest {
             runtime: RuntimeBundle {
@@ -105,6 +197,7 @@ async fn core_watchdog_stops_a_bounded_crash_loop() -> Result<()> {
             },
             proposed_session_token: token.clone(),
             macos_proxy: None,
+            lease: None,
         },
     )
     .await?;
@@ -119,7 +212,7 @@ async fn core_watchdog_stops_a_bounded_crash_loop() -> Result<()> {
     };
 
     wait_until("watchdog limit", async || {
-        get_status(&credentials)
+        get_status_at(&root.paths, &credentials)
             .await
             .ok()
             .and_then(|response| response.data)
@@ -127,7 +220,7 @@ async fn core_watchdog_stops_a_bounded_crash_loop() -> Result<()> {
     })
     .await?;
     assert!(
-        get_status(&credentials)
+        get_status_at(&root.paths, &credentials)
             .await?
             .data
             .context("status omitted data")?
@@ -135,7 +228,12 @@ async fn core_watchdog_stops_a_bounded_crash_loop() -> Result<()> {
             .is_some()
     );
 
-    assert_eq!(stop_clash(&credentials, &session).await?.code, 0);
+    assert_eq!(
+        stop_clash_at(&root.paths, &credentials, &session)
+            .await?
+            .code,
+        0
+    );
     stop_ipc_server().await?;
     server.await??;
     Ok(())
@@ -4,9 +4,9 @@ mod common;
 
 use anyhow::{Context as _, Result};
 use clash_verge_service_ipc::{
-    OwnerCredentials, OwnerSessionProof, RuntimeAsset, RuntimeBundle, StageRejection,
-    StageRuntimeOutcome, StartClashRequest, get_status, run_ipc_server, service_paths,
-    stage_runtime, start_clash, stop_clash, stop_ipc_server, test_owner_credentials,
+    OwnerCredentials, OwnerSessionProof, RuntimeAsset, RuntimeBundle, ServicePaths, StageRejection,
+    StageRuntimeOutcome, StartClashRequest, get_status_at, run_ipc_server, stage_runtime_at,
+    start_clash_at, stop_clash_at, stop_ipc_server, test_owner_credentials,
 };
 use serial_test::serial;
 use std::path::{Path, PathBuf};
@@ -24,6 +24,7 @@ fn bundle(app_root: &Path, yaml: &str) -> RuntimeBundle {
 }
 
 struct RunningCore {
+    paths: ServicePaths,
     credentials: OwnerCredentials,
     session: OwnerSessionProof,
     app_root: PathBuf,
@@ -32,7 +33,7 @@ struct RunningCore {
 }
 
 impl RunningCore {
-    async fn start(label: &str) -> Result<Self> {
+    async fn start(paths: &ServicePaths, label: &str) -> Result<Self> {
         let app_root =
             std::env::temp_dir().join(format!("service-ipc-stage-{label}-{}", std::process::id()));
         let _ = std::fs::remove_dir_all(&app_root);
@@ -44,12 +45,14 @@ impl RunningCore {
         )?;
         let credentials = test_owner_credentials(&app_root)?;
         let token = "ab".repeat(32);
-        let response = start_clash(
+        let response = start_clash_at(
+            paths,
             &credentials,
             &StartClashRequest {
                 runtime: bundle(&app_root, "mode: rule\n"),
                 proposed_session_token: token.clone(),
                 macos_proxy: None,
+                lease: None,
             },
         )
         .await?;
@@ -59,17 +62,16 @@ impl RunningCore {
             .context("start omitted its result")?
             .session
             .generation;
-        let pid = get_status(&credentials)
+        let pid = get_status_at(paths, &credentials)
             .await?
             .data
             .context("status omitted data")?
             .core_pid
             .context("started core has no pid")?;
-        let runtime_dir = service_paths()
-            .for_owner(&credentials.identity)
-            .runtime_dir();
+        let runtime_dir = paths.for_owner(&credentials.identity).runtime_dir();
 
         Ok(Self {
+            paths: paths.clone(),
             credentials,
             session: OwnerSessionProof { generation, token },
             app_root,
@@ -79,13 +81,14 @@ impl RunningCore {
     }
 
     async fn stage(&self, runtime: &RuntimeBundle) -> Result<StageRuntimeOutcome> {
-        let response = stage_runtime(&self.credentials, &self.session, runtime).await?;
+        let response =
+            stage_runtime_at(&self.paths, &self.credentials, &self.session, runtime).await?;
         anyhow::ensure!(response.code == 0, "{}", response.message);
         response.data.context("staging omitted its outcome")
     }
 
     async fn shut_down(self) -> Result<()> {
-        let response = stop_clash(&self.credentials, &self.session).await?;
+        let response = stop_clash_at(&self.paths, &self.credentials, &self.session).await?;
         anyhow::ensure!(response.code == 0, "{}", response.message);
         std::fs::remove_dir_all(self.generation)?;
         std::fs::remove_dir_all(self.app_root)?;
@@ -95,13 +98,14 @@ impl RunningCore {
 
 async fn with_server<F, Fut>(test: F) -> Result<()>
 where
-    F: FnOnce() -> Fut,
+    F: FnOnce(ServicePaths) -> Fut,
     Fut: std::future::Future<Output = Result<()>>,
 {
+    let root = common::isolate_service_root();
     let _ = stop_ipc_server().await;
-    let server = run_ipc_server().await?;
-    common::wait_for_ipc().await?;
-    let result = test().await;
+    let server = run_ipc_server(&root.paths).await?;
+    common::wait_for_ipc(&root.paths).await?;
+    let result = test(root.paths.clone()).await;
     stop_ipc_server().await?;
     server.await??;
     result
@@ -110,8 +114,8 @@ where
 #[tokio::test]
 #[serial]
 async fn staging_updates_configuration_without_restarting_the_core() -> Result<()> {
-    with_server(|| async {
-        let core = RunningCore::start("config").await?;
+    with_server(|paths| async move {
+        let core = RunningCore::start(&paths, "config").await?;
         let outcome = core
             .stage(&bundle(&core.app_root, "mode: global\n"))
             .await?;
@@ -121,7 +125,7 @@ async fn staging_updates_configuration_without_restarting_the_core() -> Result<(
             std::fs::read_to_string(core.generation.join("config.yaml"))?,
             "mode: global\n"
         );
-        let status = get_status(&core.credentials)
+        let status = get_status_at(&core.paths, &core.credentials)
             .await?
             .data
             .context("status omitted data")?;
@@ -136,8 +140,8 @@ async fn staging_updates_configuration_without_restarting_the_core() -> Result<(
 #[tokio::test]
 #[serial]
 async fn staging_updates_declared_assets_but_preserves_core_owned_files() -> Result<()> {
-    with_server(|| async {
-        let core = RunningCore::start("assets").await?;
+    with_server(|paths| async move {
+        let core = RunningCore::start(&paths, "assets").await?;
         let source = core.app_root.join("provider.yaml");
         std::fs::write(&source, b"proxies: []\n")?;
         let core_state = core.generation.join("cache.db");
@@ -167,8 +171,8 @@ async fn staging_updates_declared_assets_but_preserves_core_owned_files() -> Res
 #[tokio::test]
 #[serial]
 async fn staging_declines_a_core_binary_change_without_touching_configuration() -> Result<()> {
-    with_server(|| async {
-        let core = RunningCore::start("core-path").await?;
+    with_server(|paths| async move {
+        let core = RunningCore::start(&paths, "core-path").await?;
         let other_core = core.app_root.join("other-core");
         std::fs::copy(common::test_bin_path("mock_binary"), &other_core)?;
         let mut changed = bundle(&core.app_root, "mode: global\n");
@@ -4,7 +4,7 @@ mod common;
 
 use anyhow::{Context as _, Result};
 use clash_verge_service_ipc::{
-    PROTOCOL_EPOCH, PROTOCOL_REVISION, VERSION, get_status, get_version, run_ipc_server,
+    PROTOCOL_EPOCH, PROTOCOL_REVISION, VERSION, get_status_at, get_version_at, run_ipc_server,
     stop_ipc_server,
 };
 use serial_test::serial;
@@ -13,12 +13,13 @@ use serial_test::serial;
 #[tokio::test]
 #[serial]
 async fn a_stale_ipc_path_requires_service_reinstallation() -> Result<()> {
+    let root = common::isolate_service_root();
     let _ = stop_ipc_server().await;
-    let ipc_path = std::path::Path::new(clash_verge_service_ipc::IPC_PATH);
+    let ipc_path = root.paths.ipc_path();
     std::fs::create_dir_all(ipc_path.parent().context("IPC path has no parent")?)?;
     std::fs::write(ipc_path, b"")?;
 
-    assert!(clash_verge_service_ipc::is_reinstall_service_needed().await);
+    assert!(clash_verge_service_ipc::is_reinstall_service_needed_at(&root.paths).await);
 
     std::fs::remove_file(ipc_path)?;
     Ok(())
@@ -27,16 +28,20 @@ async fn a_stale_ipc_path_requires_service_reinstallation() -> Result<()> {
 #[tokio::test]
 #[serial]
 async fn running_server_reports_its_protocol_and_status() -> Result<()> {
+    let root = common::isolate_service_root();
     let _ = stop_ipc_server().await;
-    let server = run_ipc_server().await?;
-    common::wait_for_ipc().await?;
+    let server = run_ipc_server(&root.paths).await?;
+    common::wait_for_ipc(&root.paths).await?;
 
-    let version = get_version().await?.data.context("version omitted data")?;
+    let version = get_version_at(&root.paths)
+        .await?
+        .data
+        .context("version omitted data")?;
     assert_eq!(version.build_version, VERSION);
     assert_eq!(version.protocol.epoch, PROTOCOL_EPOCH);
     assert_eq!(version.protocol.revision, PROTOCOL_REVISION);
     assert!(
-        get_status(&common::owner_credentials())
+        get_status_at(&root.paths, &common::owner_credentials())
             .await?
             .data
             .is_some()
@@ -45,7 +50,7 @@ async fn running_server_reports_its_protocol_and_status() -> Result<()> {
     #[cfg(unix)]
     {
         use std::os::unix::fs::PermissionsExt as _;
-        let socket = std::path::Path::new(clash_verge_service_ipc::IPC_PATH);
+        let socket = root.paths.ipc_path();
         assert_eq!(
             std::fs::metadata(socket)?.permissions().mode() & 0o777,
             0o666